@@ -0,0 +1,24 @@
+/* C interface of circmark, built with `cargo rustc --lib --release --features ffi --crate-type cdylib` */
+#ifndef CIRCMARK_H
+#define CIRCMARK_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Renders circmark source to an SVG document, to be freed with circmark_free_string.
+ * Returns NULL on failure, circmark_last_error then telling why. */
+char *circmark_render_svg(const char *source);
+
+/* The error of the last call on this thread, or NULL if it succeeded.
+ * Owned by circmark and valid until the next call on this thread. */
+const char *circmark_last_error(void);
+
+/* Frees a string returned by circmark, doing nothing for NULL. */
+void circmark_free_string(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
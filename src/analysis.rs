@@ -0,0 +1,553 @@
+//! Symbolic circuit analysis.
+
+use std::collections::HashMap;
+pub use num_complex::Complex64;
+use crate::circuit::{Document, Element, Impedance, SubCircuit, SubCircuitGroup, Twoport, TwoportLink};
+
+pub mod dc;
+pub mod differential;
+pub mod fixtures;
+pub mod response;
+
+pub use dc::{dc_solve, DcError, DcSolution};
+
+/// Values of elements, by label
+pub type Values = HashMap<String, f64>;
+
+/// The impedance of open circuits, as [`Expr::eval`] gives it for e.g. capacitors of zero capacitance
+pub const INFINITE: Complex64 = Complex64::new(f64::INFINITY, 0.0);
+
+/// Collects the values given in the document, e.g. `R1=4.7k`
+pub fn values(document: &Document) -> Values {
+    fn collect(circuit: &SubCircuit, values: &mut Values) {
+        match circuit {
+            SubCircuit::Element(element, Some(value)) => {
+                values.insert(element.label(), value.number);
+            }
+            SubCircuit::Element(_, None) => {}
+            SubCircuit::Group(group) => match group.as_ref() {
+                SubCircuitGroup::Single(circuit) => collect(circuit, values),
+                SubCircuitGroup::Series(a, b) | SubCircuitGroup::Parallel(a, b) => {
+                    collect(a, values);
+                    collect(b, values);
+                }
+            },
+        }
+    }
+    let mut values = Values::new();
+    match document {
+        Document::Circuit(circuit) => collect(circuit, &mut values),
+        Document::Twoport(twoport) => for link in &twoport.links {
+            match link {
+                TwoportLink::Series(circuit) | TwoportLink::Shunt(circuit) => collect(circuit, &mut values),
+                TwoportLink::Transformer { ratio: Some(ratio), .. } => {
+                    values.insert(link.label().unwrap(), ratio.number);
+                }
+                TwoportLink::Transformer { ratio: None, .. } | TwoportLink::Node(_) | TwoportLink::Annotation { .. } => {}
+            }
+        },
+    }
+    values
+}
+
+/// Symbolic expression in the complex frequency `s`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Constant
+    Num(f64),
+    /// Named quantity, e.g. the resistance `R1`
+    Sym(String),
+    /// The complex frequency `s`
+    S,
+    /// Sum of all terms
+    Add(Vec<Expr>),
+    /// Product of all factors
+    Mul(Vec<Expr>),
+    /// Numerator divided by denominator
+    Div(Box<Expr>, Box<Expr>),
+    /// Parallel combination of impedances, `a || b` being `1/(1/a + 1/b)`
+    Parallel(Vec<Expr>),
+}
+
+impl Expr {
+    /// Sum of two expressions (the series combination of two impedances), flattening nested sums and dropping zeros
+    pub fn plus(self, other: Expr) -> Expr {
+        let mut terms = vec![];
+        for expr in [self, other] {
+            match expr {
+                Expr::Add(inner) => terms.extend(inner),
+                Expr::Num(0.0) => {}
+                expr => terms.push(expr),
+            }
+        }
+        // ascending powers of s
+        terms.sort_by_key(Expr::degree);
+        match terms.len() {
+            0 => Expr::Num(0.0),
+            1 => terms.pop().unwrap(),
+            _ => Expr::Add(terms),
+        }
+    }
+
+    /// Parallel combination of two impedances, flattening nested combinations.
+    ///
+    /// Anything in parallel to a short circuit (zero) is shorted as well.
+    pub fn parallel(self, other: Expr) -> Expr {
+        let mut branches = vec![];
+        for expr in [self, other] {
+            match expr {
+                Expr::Parallel(inner) => branches.extend(inner),
+                Expr::Num(0.0) => return Expr::Num(0.0),
+                expr => branches.push(expr),
+            }
+        }
+        Expr::Parallel(branches)
+    }
+
+    /// Product of two expressions, flattening nested products and expanding sums.
+    ///
+    /// Constant factors go first, followed by `s`, so that `R1 * s * C1` becomes `sR1C1`.
+    pub fn times(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Add(terms), other) | (other, Expr::Add(terms)) => terms.into_iter()
+                .map(|term| term.times(other.clone()))
+                .fold(Expr::Num(0.0), Expr::plus),
+            (a, b) => a.product(b),
+        }
+    }
+
+    fn product(self, other: Expr) -> Expr {
+        let mut factors = vec![];
+        let mut constant = 1.0;
+        let mut denominator = None;
+        for expr in [self, other] {
+            match expr {
+                Expr::Num(n) => constant *= n,
+                Expr::Mul(inner) => factors.extend(inner),
+                // keep products of fractions as a single fraction
+                Expr::Div(numerator, d) => {
+                    factors.push(*numerator);
+                    denominator = Some(match denominator {
+                        Some(denominator) => Expr::times(denominator, *d),
+                        None => *d,
+                    });
+                }
+                expr => factors.push(expr),
+            }
+        }
+        if constant == 0.0 {
+            return Expr::Num(0.0);
+        }
+        // numerators of fractions may be products or constants as well
+        let mut flat = vec![];
+        for factor in factors {
+            match factor {
+                Expr::Num(n) => constant *= n,
+                Expr::Mul(inner) => flat.extend(inner),
+                factor => flat.push(factor),
+            }
+        }
+        flat.sort_by_key(|factor| !matches!(factor, Expr::S));
+        if constant != 1.0 || flat.is_empty() {
+            flat.insert(0, Expr::Num(constant));
+        }
+        let product = if flat.len() == 1 { flat.pop().unwrap() } else { Expr::Mul(flat) };
+        match denominator {
+            Some(denominator) => Expr::Div(Box::new(product), Box::new(denominator)),
+            None => product,
+        }
+    }
+
+    /// One divided by the expression, e.g. the admittance of an impedance
+    pub fn reciprocal(self) -> Expr {
+        match self {
+            Expr::Num(n) => Expr::Num(1.0 / n),
+            Expr::Div(numerator, denominator) => match *numerator {
+                Expr::Num(1.0) => *denominator,
+                numerator => Expr::Div(denominator, Box::new(numerator)),
+            },
+            Expr::Parallel(branches) => branches.into_iter()
+                .map(Expr::reciprocal)
+                .reduce(Expr::plus)
+                .unwrap_or(Expr::Num(0.0)),
+            expr => Expr::Div(Box::new(Expr::Num(1.0)), Box::new(expr)),
+        }
+    }
+
+    /// Evaluates the expression at the complex frequency `s`, `None` if a symbol has no value.
+    ///
+    /// Values of zero make impedances zero (e.g. of `R1=0`) or infinite (of `C1=0`), see [`INFINITE`]: anything
+    /// parallel to a short circuit is shorted, and anything in series with an open circuit is open. Expressions
+    /// that are neither, like zero times infinity, are `None` as well.
+    pub fn eval(&self, s: Complex64, values: &Values) -> Option<Complex64> {
+        let zero = Complex64::new(0.0, 0.0);
+        let eval_all = |exprs: &[Expr]| exprs.iter().map(|expr| expr.eval(s, values)).collect::<Option<Vec<_>>>();
+        Some(match self {
+            Expr::Num(n) => Complex64::new(*n, 0.0),
+            Expr::Sym(name) => Complex64::new(*values.get(name)?, 0.0),
+            Expr::S => s,
+            Expr::Add(terms) => {
+                let terms = eval_all(terms)?;
+                if terms.iter().any(|term| term.is_infinite()) { INFINITE } else { terms.into_iter().sum() }
+            }
+            Expr::Mul(factors) => {
+                let factors = eval_all(factors)?;
+                match (factors.iter().any(|factor| factor.is_infinite()), factors.contains(&zero)) {
+                    (true, true) => return None,
+                    (true, false) => INFINITE,
+                    (false, _) => factors.into_iter().product(),
+                }
+            }
+            Expr::Div(numerator, denominator) => {
+                let (numerator, denominator) = (numerator.eval(s, values)?, denominator.eval(s, values)?);
+                match (numerator.is_infinite(), denominator.is_infinite(), numerator == zero, denominator == zero) {
+                    (true, true, _, _) | (_, _, true, true) => return None,
+                    (false, true, _, _) => zero,
+                    (true, false, _, _) | (_, _, false, true) => INFINITE,
+                    _ => numerator / denominator,
+                }
+            }
+            Expr::Parallel(branches) => {
+                let branches = eval_all(branches)?;
+                if branches.contains(&zero) {
+                    return Some(zero);
+                }
+                let admittance: Complex64 = branches.iter().filter(|z| !z.is_infinite()).map(Complex64::inv).sum();
+                if admittance == zero { INFINITE } else { admittance.inv() }
+            }
+        })
+    }
+
+    /// Formats the expression as LaTeX math, e.g. `R_{1} + \frac{1}{s C_{1}}`
+    pub fn latex(&self) -> String {
+        match self {
+            Expr::Num(n) => n.to_string(),
+            Expr::Sym(name) => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.as_str()) {
+                    (Some(prefix), id) if !id.is_empty() => format!("{prefix}_{{{id}}}"),
+                    _ => name.clone(),
+                }
+            }
+            Expr::S => "s".to_string(),
+            Expr::Add(terms) => join(terms, " + ", |term| matches!(term, Expr::Parallel(_)), Expr::latex, latex_parens),
+            Expr::Mul(factors) => powers_of_s(factors, |k| format!("s^{{{k}}}"), |factors| join(factors, " ", Expr::is_compound, Expr::latex, latex_parens), " "),
+            Expr::Div(numerator, denominator) => format!("\\frac{{{}}}{{{}}}", numerator.latex(), denominator.latex()),
+            Expr::Parallel(branches) => join(branches, " \\parallel ", |branch| matches!(branch, Expr::Add(_)), Expr::latex, latex_parens),
+        }
+    }
+
+    /// Number of factors `s` of a term
+    fn degree(&self) -> usize {
+        match self {
+            Expr::S => 1,
+            Expr::Mul(factors) => factors.iter().filter(|factor| **factor == Expr::S).count(),
+            _ => 0,
+        }
+    }
+
+    /// Whether the expression needs parentheses when used as an operand
+    fn is_compound(&self) -> bool {
+        matches!(self, Expr::Add(_) | Expr::Parallel(_) | Expr::Div(..))
+    }
+}
+
+fn latex_parens(operand: String) -> String {
+    format!("\\left({operand}\\right)")
+}
+
+fn text_parens(operand: String) -> String {
+    format!("({operand})")
+}
+
+/// Formats the factors of a product, writing repeated factors `s` as a power
+fn powers_of_s(factors: &[Expr], power: fn(usize) -> String, format: impl Fn(&[Expr]) -> String, separator: &str) -> String {
+    let count = factors.iter().filter(|factor| **factor == Expr::S).count();
+    if count < 2 {
+        return format(factors);
+    }
+    let others: Vec<Expr> = factors.iter().filter(|factor| **factor != Expr::S).cloned().collect();
+    let mut result = String::new();
+    if let [Expr::Num(n), ..] = others[..] {
+        result.push_str(&format!("{n}{separator}"));
+    }
+    result.push_str(&power(count));
+    let rest = others.iter().skip_while(|factor| matches!(factor, Expr::Num(_))).cloned().collect::<Vec<_>>();
+    if !rest.is_empty() {
+        result.push_str(separator);
+        result.push_str(&format(&rest));
+    }
+    result
+}
+
+/// Formats the operands, wrapping those for which `needs_parens` returns true
+fn join(operands: &[Expr], separator: &str, needs_parens: fn(&Expr) -> bool, format: fn(&Expr) -> String, parens: fn(String) -> String) -> String {
+    operands.iter()
+        .map(|operand| if needs_parens(operand) { parens(format(operand)) } else { format(operand) })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Formats the expression as plain text, e.g. `R1 + 1/(sC1)`
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = |expr: &Expr| expr.to_string();
+        match self {
+            Expr::Num(n) => write!(f, "{n}"),
+            Expr::Sym(name) => f.write_str(name),
+            Expr::S => f.write_str("s"),
+            Expr::Add(terms) => f.write_str(&join(terms, " + ", |term| matches!(term, Expr::Parallel(_)), text, text_parens)),
+            Expr::Mul(factors) => f.write_str(&powers_of_s(factors, |k| format!("s^{k}"), |factors| join(factors, "", Expr::is_compound, text, text_parens), "")),
+            Expr::Div(numerator, denominator) => {
+                let numerator = if numerator.is_compound() { text_parens(numerator.to_string()) } else { numerator.to_string() };
+                let denominator = match denominator.as_ref() {
+                    Expr::Num(_) | Expr::Sym(_) | Expr::S => denominator.to_string(),
+                    _ => text_parens(denominator.to_string()),
+                };
+                write!(f, "{numerator}/{denominator}")
+            }
+            Expr::Parallel(branches) => f.write_str(&join(branches, " || ", |branch| matches!(branch, Expr::Add(_)), text, text_parens)),
+        }
+    }
+}
+
+impl Element<'_> {
+    /// The impedance of the element, `None` for open circuits.
+    ///
+    /// Sources are ideal: voltage sources are a short circuit, current sources are open.
+    /// Diodes are represented by their (small signal) resistance, named after their label.
+    pub fn impedance(&self) -> Option<Expr> {
+        match self.kind().impedance {
+            Impedance::Resistive => Some(Expr::Sym(self.label())),
+            Impedance::Inductive => Some(Expr::Mul(vec![Expr::S, Expr::Sym(self.label())])),
+            Impedance::Capacitive => Some(Expr::Div(
+                Box::new(Expr::Num(1.0)),
+                Box::new(Expr::Mul(vec![Expr::S, Expr::Sym(self.label())])),
+            )),
+            Impedance::Short => Some(Expr::Num(0.0)),
+            Impedance::Open => None,
+        }
+    }
+}
+
+impl SubCircuit<'_> {
+    /// The equivalent impedance between both ends of the sub-circuit, `None` if they are not connected.
+    ///
+    /// ```
+    /// use circmark_parse::circuit;
+    /// let circuit = circuit::sub_circuit::<nom::error::VerboseError<&str>>("(R1+R2||C1)").unwrap().1;
+    /// assert_eq!(circuit.impedance().unwrap().to_string(), "R1 + (R2 || 1/(sC1))");
+    /// ```
+    pub fn impedance(&self) -> Option<Expr> {
+        match self {
+            SubCircuit::Element(element, _) => element.impedance(),
+            SubCircuit::Group(group) => match group.as_ref() {
+                SubCircuitGroup::Single(circuit) => circuit.impedance(),
+                SubCircuitGroup::Series(a, b) => Some(a.impedance()?.plus(b.impedance()?)),
+                SubCircuitGroup::Parallel(a, b) => match (a.impedance(), b.impedance()) {
+                    (Some(a), Some(b)) => Some(a.parallel(b)),
+                    (a, b) => a.or(b),
+                },
+            },
+        }
+    }
+}
+
+impl Twoport<'_> {
+    /// The voltage transfer function `Vout/Vin` of the unloaded twoport, `None` if the output is not connected to the input.
+    ///
+    /// The twoport is treated as a chain of ABCD matrices, with shunt links before the first series link being
+    /// parallel to the input (like a source driving it), so they don't affect the result. Transformers are ideal,
+    /// their turns ratio being named after their label.
+    ///
+    /// ```
+    /// use circmark_parse::circuit;
+    /// let twoport = circuit::twoport::<nom::error::VerboseError<&str>>("|V1-R1|C1").unwrap().1;
+    /// assert_eq!(twoport.transfer_function().unwrap().to_string(), "1/(1 + sR1C1)");
+    /// ```
+    pub fn transfer_function(&self) -> Option<Expr> {
+        // the chain's ABCD matrix, as `[[a, b], [c, d]]`
+        let mut matrix = [[Expr::Num(1.0), Expr::Num(0.0)], [Expr::Num(0.0), Expr::Num(1.0)]];
+        let mut at_input = true;
+        let mut shorted = false;
+        for link in &self.links {
+            let link_matrix = match link {
+                TwoportLink::Series(circuit) => {
+                    at_input = false;
+                    [[Expr::Num(1.0), circuit.impedance()?], [Expr::Num(0.0), Expr::Num(1.0)]]
+                }
+                TwoportLink::Shunt(circuit) => match circuit.impedance() {
+                    _ if at_input => continue,
+                    None => continue,
+                    // everything behind a short circuit is at zero volts, unless an open circuit behind it
+                    // disconnects the output
+                    Some(Expr::Num(0.0)) => {
+                        shorted = true;
+                        continue;
+                    }
+                    Some(impedance) => [[Expr::Num(1.0), Expr::Num(0.0)], [impedance.reciprocal(), Expr::Num(1.0)]],
+                },
+                TwoportLink::Transformer { .. } => {
+                    let ratio = Expr::Sym(link.label().unwrap());
+                    [[ratio.clone(), Expr::Num(0.0)], [Expr::Num(0.0), ratio.reciprocal()]]
+                }
+                TwoportLink::Node(_) | TwoportLink::Annotation { .. } => continue,
+            };
+            matrix = multiply(matrix, link_matrix);
+        }
+        if shorted {
+            return Some(Expr::Num(0.0));
+        }
+        let [[a, _], _] = matrix;
+        Some(a.reciprocal())
+    }
+}
+
+/// Computes the complex gain `Vout/Vin` of the unloaded twoport at each of the frequencies (in Hz).
+///
+/// Like [`Twoport::transfer_function`], but evaluated numerically link by link. Returns `None` if an element
+/// has no value or the output is not connected to the input. Frequencies should be positive, as capacitors
+/// have no finite impedance at DC.
+pub fn ac_sweep(twoport: &Twoport, values: &Values, freqs: &[f64]) -> Option<Vec<Complex64>> {
+    enum Link {
+        Series(Expr),
+        Shunt(Expr),
+        Transformer(String),
+    }
+    // all links that affect the gain
+    let mut links = vec![];
+    let mut at_input = true;
+    let mut shorted = false;
+    for link in &twoport.links {
+        match link {
+            TwoportLink::Series(circuit) => {
+                at_input = false;
+                links.push(Link::Series(circuit.impedance()?));
+            }
+            TwoportLink::Shunt(circuit) => match circuit.impedance() {
+                _ if at_input => {}
+                None => {}
+                Some(Expr::Num(0.0)) => shorted = true,
+                Some(impedance) => links.push(Link::Shunt(impedance)),
+            },
+            TwoportLink::Transformer { .. } => links.push(Link::Transformer(link.label().unwrap())),
+            TwoportLink::Node(_) | TwoportLink::Annotation { .. } => {}
+        }
+    }
+    if shorted {
+        return Some(vec![Complex64::new(0.0, 0.0); freqs.len()]);
+    }
+    freqs.iter().map(|f| {
+        let s = Complex64::new(0.0, 2.0 * std::f64::consts::PI * f);
+        let one = Complex64::new(1.0, 0.0);
+        let zero = Complex64::new(0.0, 0.0);
+        // only the first row of the chain's ABCD matrix is needed for the gain
+        let (mut a, mut b) = (one, zero);
+        for link in &links {
+            (a, b) = match link {
+                Link::Series(impedance) => match impedance.eval(s, values)? {
+                    impedance if impedance.is_infinite() => (a, INFINITE),
+                    impedance => (a, a * impedance + b),
+                },
+                Link::Shunt(impedance) => match impedance.eval(s, values)? {
+                    // everything behind a short circuit is at zero volts
+                    impedance if impedance == zero => return Some(zero),
+                    impedance if impedance.is_infinite() => (a, b),
+                    _ if b.is_infinite() => (INFINITE, b),
+                    impedance => (a + b / impedance, b),
+                },
+                Link::Transformer(label) => {
+                    let n = *values.get(label)?;
+                    (a * n, b / n)
+                }
+            };
+        }
+        Some(if a.is_infinite() { zero } else { a.inv() })
+    }).collect()
+}
+
+fn multiply([[a1, b1], [c1, d1]]: [[Expr; 2]; 2], [[a2, b2], [c2, d2]]: [[Expr; 2]; 2]) -> [[Expr; 2]; 2] {
+    [
+        [a1.clone().times(a2.clone()).plus(b1.clone().times(c2.clone())), a1.times(b2.clone()).plus(b1.times(d2.clone()))],
+        [c1.clone().times(a2).plus(d1.clone().times(c2)), c1.times(b2).plus(d1.times(d2))],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    fn impedance(input: &'static str) -> Option<Expr> {
+        circuit::sub_circuit::<E>(input).unwrap().1.impedance()
+    }
+
+    #[test]
+    fn test_impedance() {
+        assert_eq!(impedance("R1"), Some(Expr::Sym("R1".to_string())));
+        assert_eq!(impedance("(R1+R2+R3)"), Some(Expr::Add(vec![
+            Expr::Sym("R1".to_string()), Expr::Sym("R2".to_string()), Expr::Sym("R3".to_string()),
+        ])));
+        assert_eq!(impedance("(L1||O)").unwrap().to_string(), "sL1");
+        assert_eq!(impedance("(R1+O)"), None);
+        assert_eq!(impedance("(R1||V1)"), Some(Expr::Num(0.0)));
+        assert_eq!(impedance("(V1+R1)"), Some(Expr::Sym("R1".to_string())));
+        assert_eq!(impedance("((R1+L1)||C1||Rp)").unwrap().to_string(), "(R1 + sL1) || 1/(sC1) || Rp");
+        assert_eq!(impedance("(R1+(C1+L1)||Rp)").unwrap().to_string(), "R1 + ((1/(sC1) + sL1) || Rp)");
+    }
+
+    #[test]
+    fn test_transfer_function() {
+        let transfer_function = |input| circuit::twoport::<E>(input).unwrap().1.transfer_function();
+        assert_eq!(transfer_function("-R1").unwrap().to_string(), "1");
+        assert_eq!(transfer_function("|V1-R1|R2").unwrap().to_string(), "1/(1 + R1/R2)");
+        assert_eq!(transfer_function("|V1-C1|R1").unwrap().to_string(), "1/(1 + 1/(sC1R1))");
+        assert_eq!(transfer_function("|V1-R1|C1-R2|C2").unwrap().to_string(), "1/(1 + sR1C1 + sR2C2 + sR1C2 + s^2R1C1R2C2)");
+        assert_eq!(transfer_function("|V1-R1|V2-R2|C1").unwrap(), Expr::Num(0.0));
+        assert_eq!(transfer_function("|V1-O|C1"), None);
+        assert_eq!(transfer_function("|V1=T1|R1").unwrap().to_string(), "1/T1");
+    }
+
+    #[test]
+    fn test_eval() {
+        let document = circuit::document("|V1-R1=1k|C1=1u").unwrap();
+        let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+        let values = values(&document);
+        let transfer_function = twoport.transfer_function().unwrap();
+        // at the cutoff frequency, the gain is 1/(1 + j)
+        let gain = transfer_function.eval(Complex64::new(0.0, 1000.0), &values).unwrap();
+        assert!((gain - Complex64::new(0.5, -0.5)).norm() < 1e-9);
+        assert_eq!(transfer_function.eval(Complex64::new(0.0, 1.0), &Values::new()), None);
+        let impedance = circuit::sub_circuit::<E>("(R1||R2)").unwrap().1.impedance().unwrap();
+        let values = Values::from([("R1".to_string(), 100.0), ("R2".to_string(), 100.0)]);
+        assert_eq!(impedance.eval(Complex64::new(0.0, 0.0), &values), Some(Complex64::new(50.0, 0.0)));
+    }
+
+    #[test]
+    fn test_ac_sweep() {
+        let document = circuit::document("|V1-R1=1k|C1=1u-R2=1k|C2=1u").unwrap();
+        let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+        let values = values(&document);
+        let freqs = [10.0, 159.0, 10_000.0];
+        let gains = ac_sweep(twoport, &values, &freqs).unwrap();
+        let transfer_function = twoport.transfer_function().unwrap();
+        for (f, gain) in freqs.iter().zip(gains) {
+            let expected = transfer_function.eval(Complex64::new(0.0, 2.0 * std::f64::consts::PI * f), &values).unwrap();
+            assert!((gain - expected).norm() < 1e-9);
+        }
+        assert!(ac_sweep(twoport, &Values::new(), &freqs).is_none());
+        let shorted = circuit::twoport::<E>("|V1-R1=1|V2").unwrap().1;
+        assert_eq!(ac_sweep(&shorted, &Values::new(), &[1.0]), Some(vec![Complex64::new(0.0, 0.0)]));
+        let document = circuit::document("|V1=T1(n=2)-R1=1k|R2=1k").unwrap();
+        let circuit::Document::Twoport(transformed) = &document else { unreachable!() };
+        assert_eq!(ac_sweep(transformed, &super::values(&document), &[1.0]), Some(vec![Complex64::new(0.25, 0.0)]));
+    }
+
+    #[test]
+    fn test_latex() {
+        assert_eq!(impedance("(R1+R2||C1)").unwrap().latex(), "R_{1} + \\left(R_{2} \\parallel \\frac{1}{s C_{1}}\\right)");
+        assert_eq!(impedance("(Lx+Zth)").unwrap().latex(), "Z_{th} + s L_{x}");
+    }
+}
@@ -0,0 +1,354 @@
+//! DC operating point by modified nodal analysis, e.g. for checking the node voltages of a homework circuit.
+//!
+//! Capacitors are open and inductors short at DC. The transformers of twoports are coupled inductors like in
+//! [`crate::netlist::to_spice`], so their windings short their nodes to ground. Currents are positive when they
+//! flow through an element from its first node to its second (see [`CircuitGraph::edges`]), so sources delivering
+//! power have negative currents, as in SPICE.
+//!
+//! ```
+//! use circmark_parse::{analysis::{self, dc_solve}, circuit, graph::NodeId};
+//! let document = circuit::document("|V1=10-R1=1k|R2=4k").unwrap();
+//! let solution = dc_solve(&document.graph(), &analysis::Values::new()).unwrap();
+//! assert_eq!(solution.voltage(NodeId(2)), 8.0);
+//! assert_eq!(solution.currents["R1"], 2e-3);
+//! assert_eq!(solution.currents["V1"], -2e-3);
+//! ```
+
+use std::collections::HashMap;
+use crate::{
+    circuit::{Element, Impedance, Polarity, Spice},
+    graph::{CircuitGraph, ElementRef, NodeId},
+};
+use super::Values;
+
+/// Result of [`dc_solve`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DcSolution {
+    /// The voltage of each node against ground, by [`NodeId`]
+    pub voltages: Vec<f64>,
+    /// The current through each element (but potentiometers) from its first node to its second, by label
+    pub currents: HashMap<String, f64>,
+}
+
+impl DcSolution {
+    /// The voltage of the node against ground
+    pub fn voltage(&self, node: NodeId) -> f64 {
+        self.voltages[node.0]
+    }
+}
+
+/// Error returned by [`dc_solve`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DcError {
+    /// The element has no value, neither in the circuit nor in the values passed
+    MissingValue(String),
+    /// The element has no linear model, e.g. a diode
+    Unsupported(String),
+    /// The controlling element of the controlled source doesn't exist, or carries no current of its own
+    /// for current controlled sources (e.g. a resistor)
+    Control(String),
+    /// The circuit has no unique solution, e.g. as a node is only connected through capacitors, or voltage sources
+    /// form a loop
+    Singular,
+}
+
+impl std::fmt::Display for DcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DcError::MissingValue(label) => write!(f, "{label} has no value"),
+            DcError::Unsupported(label) => write!(f, "{label} has no linear DC model"),
+            DcError::Control(label) => write!(f, "the controlling element of {label} doesn't exist or has no current of its own"),
+            DcError::Singular => write!(f, "the circuit has no unique DC solution, e.g. as a node is only connected through capacitors"),
+        }
+    }
+}
+
+impl std::error::Error for DcError {}
+
+/// How an edge enters the equations, between its nodes `p` and `q`
+enum Model {
+    Open,
+    Conductance(f64),
+    /// A current source driving this current from `p` through itself to `q`
+    Current(f64),
+    /// A voltage source `V(p) - V(q)`, which has a current of its own
+    Voltage(f64),
+    /// `V(p) - V(q)` being the gain times the voltage across the controlling element
+    VoltageControlledVoltage(f64, String),
+    /// The current from `p` to `q` being the gain times the voltage across the controlling element
+    VoltageControlledCurrent(f64, String),
+    /// `V(p) - V(q)` being the gain times the current through the controlling element
+    CurrentControlledVoltage(f64, String),
+    /// The current from `p` to `q` being the gain times the current through the controlling element
+    CurrentControlledCurrent(f64, String),
+}
+
+impl Model {
+    /// Whether the current through the edge is an unknown of its own
+    fn has_current(&self) -> bool {
+        matches!(self, Model::Voltage(_) | Model::VoltageControlledVoltage(..) | Model::CurrentControlledVoltage(..))
+    }
+}
+
+/// The model of an edge and its nodes `p` and `q`, which are swapped for sources pointing the other way
+fn model(a: NodeId, b: NodeId, edge: &ElementRef, values: &Values) -> Result<(Model, NodeId, NodeId), DcError> {
+    let (element, value) = match edge {
+        ElementRef::Element { element, value, .. } => (element, value),
+        // a winding is an inductor to ground
+        ElementRef::Winding { .. } => return Ok((Model::Voltage(0.0), a, b)),
+    };
+    let label = element.label();
+    let value = || values.get(&label).copied().or(value.map(|value| value.number)).ok_or_else(|| DcError::MissingValue(label.clone()));
+    let control = || element.control().unwrap_or_default().to_string();
+    let kind = element.kind();
+    let model = match kind.spice {
+        // the positive terminal is on the left (or top), and SPICE current sources drive current out of their
+        // second node, see `to_spice`
+        Spice::Source => {
+            let (model, swap) = match element {
+                Element::V { polarity, .. } => (Model::Voltage(value()?), *polarity == Polarity::Down),
+                _ => (Model::Current(value()?), element.polarity() == Polarity::Up),
+            };
+            return Ok(if swap { (model, b, a) } else { (model, a, b) });
+        }
+        Spice::Diode | Spice::ZenerDiode => return Err(DcError::Unsupported(label)),
+        Spice::Switch { closed: true } | Spice::Short => Model::Voltage(0.0),
+        Spice::Switch { closed: false } => Model::Open,
+        Spice::VoltageControlled if kind.impedance == Impedance::Open => Model::VoltageControlledCurrent(value()?, control()),
+        Spice::VoltageControlled => Model::VoltageControlledVoltage(value()?, control()),
+        Spice::CurrentControlled if kind.impedance == Impedance::Open => Model::CurrentControlledCurrent(value()?, control()),
+        Spice::CurrentControlled => Model::CurrentControlledVoltage(value()?, control()),
+        // each edge is one half
+        Spice::Potentiometer => resistance(value()? / 2.0),
+        Spice::Passive(_) | Spice::Omitted => match kind.impedance {
+            Impedance::Resistive => resistance(value()?),
+            Impedance::Inductive | Impedance::Short => Model::Voltage(0.0),
+            Impedance::Capacitive | Impedance::Open => Model::Open,
+        },
+    };
+    Ok((model, a, b))
+}
+
+/// Models a resistance, shorting for none rather than giving an infinite conductance
+fn resistance(resistance: f64) -> Model {
+    if resistance == 0.0 {
+        Model::Voltage(0.0)
+    } else {
+        Model::Conductance(1.0 / resistance)
+    }
+}
+
+/// Solves the DC operating point of the circuit: the voltage of each node and the current through each element.
+///
+/// Values are taken from `values`, falling back to the ones given in the circuit. Controlled sources are controlled
+/// by the voltage across (E, G) or the current through (H, F) their controlling element; currents can only control
+/// them when they are unknowns of their own, i.e. of sources, inductors and shorts.
+pub fn dc_solve(graph: &CircuitGraph, values: &Values) -> Result<DcSolution, DcError> {
+    let models = graph.edges.iter().map(|(a, b, edge)| model(*a, *b, edge, values)).collect::<Result<Vec<_>, _>>()?;
+    // the unknowns are the voltages of the nodes but ground, followed by the currents of some edges
+    let nodes = graph.nodes.len() - 1;
+    let mut branches = HashMap::new();
+    let mut branch_count = 0;
+    for (i, (model, _, _)) in models.iter().enumerate() {
+        if model.has_current() {
+            branches.insert(i, nodes + branch_count);
+            branch_count += 1;
+        }
+    }
+    let size = nodes + branch_count;
+    let mut matrix = vec![vec![0.0; size]; size];
+    let mut rhs = vec![0.0; size];
+    let index = |node: NodeId| node.0.checked_sub(1);
+    // the element's current unknown, for current controlled sources
+    let branch = |label: &str| graph.edges.iter().enumerate()
+        .find(|(i, (_, _, edge))| edge.label() == label && matches!(edge, ElementRef::Element { .. }) && branches.contains_key(i))
+        .map(|(i, _)| branches[&i]);
+    // adds `gain` times the unknown to the current leaving `p` and entering `q`
+    let current = |matrix: &mut Vec<Vec<f64>>, p: NodeId, q: NodeId, column: usize, gain: f64| {
+        if let Some(p) = index(p) {
+            matrix[p][column] += gain;
+        }
+        if let Some(q) = index(q) {
+            matrix[q][column] -= gain;
+        }
+    };
+    for (i, ((model, p, q), (_, _, edge))) in models.iter().zip(&graph.edges).enumerate() {
+        let (p, q) = (*p, *q);
+        let terminals = |label: &str| graph.terminals(label).ok_or_else(|| DcError::Control(edge.label()));
+        match model {
+            Model::Open => {}
+            Model::Conductance(g) => {
+                for (row, sign) in [(p, 1.0), (q, -1.0)] {
+                    let Some(row) = index(row) else { continue };
+                    for (column, node) in [(p, 1.0), (q, -1.0)] {
+                        if let Some(column) = index(column) {
+                            matrix[row][column] += sign * node * g;
+                        }
+                    }
+                }
+            }
+            Model::Current(value) => {
+                if let Some(p) = index(p) {
+                    rhs[p] -= value;
+                }
+                if let Some(q) = index(q) {
+                    rhs[q] += value;
+                }
+            }
+            Model::VoltageControlledCurrent(gain, control) => {
+                let (c, d) = terminals(control)?;
+                for (node, sign) in [(c, 1.0), (d, -1.0)] {
+                    if let Some(column) = index(node) {
+                        current(&mut matrix, p, q, column, sign * gain);
+                    }
+                }
+            }
+            Model::CurrentControlledCurrent(gain, control) => {
+                let column = branch(control).ok_or_else(|| DcError::Control(edge.label()))?;
+                current(&mut matrix, p, q, column, *gain);
+            }
+            Model::Voltage(_) | Model::VoltageControlledVoltage(..) | Model::CurrentControlledVoltage(..) => {
+                let row = branches[&i];
+                current(&mut matrix, p, q, row, 1.0);
+                // the row of the branch's voltage
+                if let Some(p) = index(p) {
+                    matrix[row][p] += 1.0;
+                }
+                if let Some(q) = index(q) {
+                    matrix[row][q] -= 1.0;
+                }
+                match model {
+                    Model::Voltage(value) => rhs[row] = *value,
+                    Model::VoltageControlledVoltage(gain, control) => {
+                        let (c, d) = terminals(control)?;
+                        for (node, sign) in [(c, 1.0), (d, -1.0)] {
+                            if let Some(column) = index(node) {
+                                matrix[row][column] -= sign * gain;
+                            }
+                        }
+                    }
+                    Model::CurrentControlledVoltage(gain, control) => {
+                        matrix[row][branch(control).ok_or_else(|| DcError::Control(edge.label()))?] -= gain;
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+    let solution = solve(matrix, rhs).ok_or(DcError::Singular)?;
+
+    let mut voltages = vec![0.0];
+    voltages.extend(&solution[..nodes]);
+    let voltage = |node: NodeId| voltages[node.0];
+    let mut currents = HashMap::new();
+    for (i, ((model, p, q), (a, _, edge))) in models.iter().zip(&graph.edges).enumerate() {
+        let ElementRef::Element { element, .. } = edge else { continue };
+        if let Element::P { .. } = element {
+            continue;
+        }
+        let through = match model {
+            Model::Open => 0.0,
+            Model::Conductance(g) => g * (voltage(*p) - voltage(*q)),
+            Model::Current(value) => *value,
+            Model::VoltageControlledCurrent(gain, control) => {
+                let (c, d) = graph.terminals(control).unwrap();
+                gain * (voltage(c) - voltage(d))
+            }
+            Model::CurrentControlledCurrent(gain, control) => gain * solution[branch(control).unwrap()],
+            Model::Voltage(_) | Model::VoltageControlledVoltage(..) | Model::CurrentControlledVoltage(..) => solution[branches[&i]],
+        };
+        // from the first node of the edge, which sources may have swapped
+        currents.insert(element.label(), if p == a { through } else { -through });
+    }
+    Ok(DcSolution { voltages, currents })
+}
+
+/// Solves the linear equations by Gaussian elimination with partial pivoting, `None` if the matrix is singular
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let size = rhs.len();
+    // pivots this much smaller than the largest entry are taken for zero, which rounding errors leave them at
+    let scale = matrix.iter().flatten().fold(0.0f64, |max, entry| max.max(entry.abs()));
+    let epsilon = scale * 1e-12;
+    for column in 0..size {
+        let pivot = (column..size).max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))?;
+        if matrix[pivot][column].abs() <= epsilon {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        let (above, below) = matrix.split_at_mut(column + 1);
+        let pivot_row = &above[column];
+        for (offset, row) in below.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            if factor == 0.0 {
+                continue;
+            }
+            for (entry, pivot_entry) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *entry -= factor * pivot_entry;
+            }
+            rhs[column + 1 + offset] -= factor * rhs[column];
+        }
+    }
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let sum: f64 = (row + 1..size).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    fn solve(source: &str) -> Result<DcSolution, DcError> {
+        dc_solve(&circuit::document(source).unwrap().graph(), &Values::new())
+    }
+
+    /// Rounds away the errors of elimination, for comparing values
+    fn round(value: f64) -> f64 {
+        crate::format::significant(value, 9)
+    }
+
+    #[test]
+    fn test_divider() {
+        // the inductor shorts, the capacitor is open
+        let solution = solve("|V1=12-R1=1k-L1|(R2=2k+C1)|R3=2k").unwrap();
+        assert_eq!(solution.voltages.iter().map(|v| round(*v)).collect::<Vec<_>>(), [0.0, 12.0, 8.0, 8.0, 8.0]);
+        assert_eq!(round(solution.currents["L1"]), 4e-3);
+        assert_eq!(solution.currents["C1"], 0.0);
+        assert_eq!(round(solution.currents["V1"]), -4e-3);
+
+        // a current source pointing up drives current out of its top
+        let solution = solve("|^I1=1m|R1=1k|P1>mid=2k").unwrap();
+        assert_eq!(round(solution.voltage(NodeId(1))), round(2.0 / 3.0));
+        assert_eq!(round(solution.currents["I1"]), -1e-3);
+
+        // resistors of no resistance short
+        let solution = solve("|V1=1-R1=0|R2=1k").unwrap();
+        assert_eq!(round(solution.voltage(NodeId(2))), 1.0);
+        assert_eq!(round(solution.currents["R1"]), 1e-3);
+        assert!(solve("|V1=1-P1=0|R2=1k").is_ok());
+    }
+
+    #[test]
+    fn test_controlled() {
+        // amplifying the voltage across R1 tenfold, and mirroring the current through V1 twice
+        let solution = solve("|V1=1-R1=1k|Egain(R1)=10|R2=1k").unwrap();
+        assert_eq!(round(solution.voltage(NodeId(2))), round(10.0 / 11.0));
+        let solution = solve("|V1=2|R1=1k|Fmirror(V1)=2|R2=1k").unwrap();
+        // V1 also supplies the mirrored current: 4mA + 3 I(V1) = 0
+        assert_eq!(round(solution.currents["V1"]), round(-4e-3 / 3.0));
+        assert_eq!(round(solution.currents["Fmirror"]), round(-8e-3 / 3.0));
+        assert_eq!(solve("|V1=2|R1=1k|Fmirror(R1)=2"), Err(DcError::Control("Fmirror".to_string())));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(solve("|V1=1-R1|C1"), Err(DcError::MissingValue("R1".to_string())));
+        assert_eq!(solve("|V1=1-D1|R1=1"), Err(DcError::Unsupported("D1".to_string())));
+        assert_eq!(solve("|V1=1-C1=1u-C2=1u|R1=1k"), Err(DcError::Singular));
+        assert_eq!(solve("|V1=1|V2=2"), Err(DcError::Singular));
+    }
+}
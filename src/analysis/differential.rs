@@ -0,0 +1,350 @@
+//! Randomized differential testing of the analysis backends.
+//!
+//! Random twoports are analyzed at random frequencies by the symbolic [`Twoport::transfer_function`],
+//! by [`ac_sweep`] and by a reference evaluation by modified nodal analysis of the [`crate::graph::CircuitGraph`]. All three should agree,
+//! so any divergence points at a bug in one of them.
+
+use super::{ac_sweep, values, Complex64, Values};
+use crate::{
+    circuit::{self, Document, Element, Impedance, Twoport, Value},
+    graph::{CircuitGraph, ElementRef, NodeId},
+};
+
+/// Relative difference above which results are considered to diverge
+const TOLERANCE: f64 = 1e-6;
+
+/// Results that don't agree for a circuit at a frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// The twoport in canonical form
+    pub source: String,
+    /// Frequency in Hz
+    pub freq: f64,
+    pub symbolic: Option<Complex64>,
+    pub swept: Option<Complex64>,
+    pub reference: Option<Complex64>,
+}
+
+/// Generates and compares `cases` random twoports, the same `seed` giving the same twoports
+pub fn run(seed: u64, cases: usize) -> Vec<Divergence> {
+    let mut random = Random(seed.max(1));
+    (0..cases).filter_map(|_| {
+        let source = random.twoport();
+        let freq = 10f64.powf(random.uniform(0.0, 6.0));
+        let Ok(Document::Twoport(twoport)) = circuit::document(&source) else {
+            unreachable!("generated twoports parse, unlike {source:?}");
+        };
+        compare(&twoport, freq)
+    }).collect()
+}
+
+/// Analyzes a twoport with all three backends, returning a divergence if they disagree
+///
+/// ```
+/// use circmark_parse::{analysis::differential::compare, circuit::{self, Document}};
+/// let Ok(Document::Twoport(twoport)) = circuit::document("|V1-R1=1k|C1=1u") else { unreachable!() };
+/// assert_eq!(compare(&twoport, 100.0), None);
+/// ```
+pub fn compare(twoport: &Twoport, freq: f64) -> Option<Divergence> {
+    let values = values(&Document::Twoport(twoport.clone()));
+    let s = Complex64::new(0.0, 2.0 * std::f64::consts::PI * freq);
+    let symbolic = twoport.transfer_function().and_then(|transfer_function| transfer_function.eval(s, &values));
+    let swept = ac_sweep(twoport, &values, &[freq]).map(|gains| gains[0]);
+    let reference = reference_gain(twoport, &values, s);
+    let agree = |a: Option<Complex64>, b: Option<Complex64>| match (a, b) {
+        (Some(a), Some(b)) => (a - b).norm() <= TOLERANCE * a.norm().max(b.norm()) + 1e-12,
+        (a, b) => a.is_none() && b.is_none(),
+    };
+    if agree(symbolic, reference) && agree(swept, reference) {
+        return None;
+    }
+    Some(Divergence { source: twoport.to_string(), freq, symbolic, swept, reference })
+}
+
+/// Gain of the unloaded twoport by modified nodal analysis of its graph at the complex frequency `s`, driving the
+/// input with 1V. Unlike the other backends, it only looks at which ends of the elements are joined, not at how the
+/// links and groups are nested.
+///
+/// Every element has a current of its own, related to its voltage by its impedance, rather than entering the
+/// equations of its nodes by its admittance. Chains of elements the unloaded output ends in then carry no current
+/// exactly, whereas the tiny admittance of a capacitor at low frequencies would make their voltages inexact.
+fn reference_gain(twoport: &Twoport, values: &Values, s: Complex64) -> Option<Complex64> {
+    let graph = twoport.graph();
+    if !reached(&graph, graph.input, false).contains(&graph.output?) {
+        return None;
+    }
+    // nodes only joined by open circuits have no voltage, e.g. the one of `(O+O)`
+    let connected = reached(&graph, NodeId::GROUND, true);
+    let zero = Complex64::new(0.0, 0.0);
+    // the equation of each edge, relating the voltage between its nodes to its current: the voltage it is fixed at
+    // (`None` for transformers, whose voltage is relative to the secondary winding) and its impedance
+    let mut branches = vec![];
+    for (a, b, edge) in &graph.edges {
+        if !connected.contains(a) {
+            continue;
+        }
+        let equation = match edge {
+            ElementRef::Winding { primary: false, .. } => continue,
+            ElementRef::Winding { label, .. } => (None, Complex64::new(*values.get(label)?, 0.0)),
+            ElementRef::Element { element: Element::V { .. }, .. } => (Some(1.0), zero),
+            ElementRef::Element { element, .. } => {
+                let value = || values.get(&element.label()).copied();
+                let impedance = match element.kind().impedance {
+                    Impedance::Resistive => Complex64::new(value()?, 0.0),
+                    Impedance::Inductive => s * value()?,
+                    // capacitors without capacitance are open, as their impedance is infinite
+                    Impedance::Capacitive if value()? == 0.0 => continue,
+                    Impedance::Capacitive => (s * value()?).inv(),
+                    Impedance::Short => zero,
+                    Impedance::Open => continue,
+                };
+                (Some(0.0), impedance)
+            }
+        };
+        branches.push((*a, *b, edge, equation));
+    }
+    let secondary = |label: &str| graph.edges.iter()
+        .find_map(|(c, _, edge)| matches!(edge, ElementRef::Winding { label: other, primary: false, .. } if other == label).then_some(*c));
+    // nodes joined by shorts are one node, as the currents of shorts in parallel are arbitrary. So are the ends of
+    // primaries whose secondary is shorted to ground, which may in turn short another transformer's secondary
+    let mut roots: Vec<NodeId> = (0..graph.nodes.len()).map(NodeId).collect();
+    let root = |roots: &[NodeId], mut node: NodeId| {
+        while roots[node.0] != node {
+            node = roots[node.0];
+        }
+        node
+    };
+    loop {
+        let count = branches.len();
+        branches.retain(|(a, b, edge, equation)| {
+            let short = match edge {
+                ElementRef::Winding { label, .. } => secondary(label).is_some_and(|node| root(&roots, node) == NodeId::GROUND),
+                ElementRef::Element { .. } => *equation == (Some(0.0), zero),
+            };
+            if short {
+                let (a, b) = (root(&roots, *a), root(&roots, *b));
+                roots[a.max(b).0] = a.min(b);
+            }
+            !short
+        });
+        if branches.len() == count {
+            break;
+        }
+    }
+    for (a, b, _, _) in &mut branches {
+        (*a, *b) = (root(&roots, *a), root(&roots, *b));
+    }
+    // the unknowns are the voltages of the nodes but ground, followed by the currents of the branches
+    let mut unknowns: Vec<NodeId> = connected.into_iter().map(|node| root(&roots, node)).filter(|node| *node != NodeId::GROUND).collect();
+    unknowns.sort();
+    unknowns.dedup();
+    let nodes = unknowns.len();
+    let size = nodes + branches.len();
+    let mut matrix = vec![vec![zero; size]; size];
+    let mut rhs = vec![zero; size];
+    let index = |node: NodeId| unknowns.iter().position(|unknown| *unknown == node);
+    for (i, (a, b, edge, (voltage, impedance))) in branches.iter().enumerate() {
+        let row = nodes + i;
+        // the current leaves the first node and enters the second
+        for (node, sign) in [(index(*a), 1.0), (index(*b), -1.0)] {
+            if let Some(node) = node {
+                matrix[node][row] += sign;
+                matrix[row][node] += sign;
+            }
+        }
+        match voltage {
+            Some(voltage) => {
+                matrix[row][row] -= impedance;
+                rhs[row] = Complex64::new(*voltage, 0.0);
+            }
+            // the primary's voltage is `n` times the secondary's, whose node the current `n` times as large enters
+            None => {
+                let ElementRef::Winding { label, .. } = edge else { unreachable!() };
+                let secondary = secondary(label).and_then(|node| index(root(&roots, node)))?;
+                matrix[secondary][row] -= impedance;
+                matrix[row][secondary] -= impedance;
+            }
+        }
+    }
+    let solution = solve(matrix, rhs)?;
+    // an output shorted to ground has no unknown of its own
+    Some(index(root(&roots, graph.output?)).map_or(zero, |output| solution[output]))
+}
+
+/// The nodes reached from `start` by elements that aren't open and across transformers, including `start`, and
+/// ground only if `through_ground`. The other backends have no gain for twoports whose output isn't reached from the
+/// input other than through ground, e.g. with an open circuit in series
+fn reached(graph: &CircuitGraph, start: NodeId, through_ground: bool) -> Vec<NodeId> {
+    let mut reached = vec![start];
+    let mut pending = vec![start];
+    while let Some(node) = pending.pop() {
+        for (a, b, edge) in &graph.edges {
+            let next = match edge {
+                ElementRef::Element { element, .. } if element.kind().impedance == Impedance::Open => continue,
+                // from the primary winding to the secondary's node
+                ElementRef::Winding { label, primary: true, .. } if *a == node && !through_ground => {
+                    let secondary = graph.edges.iter()
+                        .find_map(|(c, _, edge)| matches!(edge, ElementRef::Winding { label: other, primary: false, .. } if other == label).then_some(*c));
+                    let Some(secondary) = secondary else { continue };
+                    secondary
+                }
+                _ if *a == node => *b,
+                _ if *b == node => *a,
+                _ => continue,
+            };
+            if (through_ground || next != NodeId::GROUND) && !reached.contains(&next) {
+                reached.push(next);
+                pending.push(next);
+            }
+        }
+    }
+    reached
+}
+
+/// Solves the linear equations by Gaussian elimination with partial pivoting, `None` if a pivot is zero.
+///
+/// Unlike the solver of [`super::dc_solve`], pivots are only taken for zero when they are, as the admittances of
+/// small capacitors at low frequencies are many orders of magnitude below the others
+fn solve(mut matrix: Vec<Vec<Complex64>>, mut rhs: Vec<Complex64>) -> Option<Vec<Complex64>> {
+    let size = rhs.len();
+    // scaling each row to entries of at most 1 lets pivots be chosen by how much they matter in their row
+    for (row, rhs) in matrix.iter_mut().zip(&mut rhs) {
+        let scale = row.iter().fold(0.0f64, |max, entry| max.max(entry.norm()));
+        if scale > 0.0 {
+            row.iter_mut().for_each(|entry| *entry /= scale);
+            *rhs /= scale;
+        }
+    }
+    for column in 0..size {
+        let pivot = (column..size).max_by(|a, b| matrix[*a][column].norm().total_cmp(&matrix[*b][column].norm()))?;
+        if matrix[pivot][column].norm() == 0.0 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        let (above, below) = matrix.split_at_mut(column + 1);
+        let pivot_row = &above[column];
+        for (offset, row) in below.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (entry, pivot_entry) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *entry -= factor * pivot_entry;
+            }
+            let pivot_rhs = rhs[column];
+            rhs[column + 1 + offset] -= factor * pivot_rhs;
+        }
+    }
+    let mut solution = vec![Complex64::new(0.0, 0.0); size];
+    for row in (0..size).rev() {
+        let sum: Complex64 = (row + 1..size).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+/// xorshift64*, which is plenty for generating circuits
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn uniform(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Source of a twoport driven by a voltage source, with one to four more links.
+    ///
+    /// Short circuits are left out of shunt links across the source, which the other backends leave out as an
+    /// ideal source drives them, but which contradict the source. Nor are there short circuits or values of zero in
+    /// the first series link, which would put the source across the shunt links after it. Values of zero are left
+    /// out of shunt links: the
+    /// symbolic transfer function is expanded, so that e.g. `-L1=0|L2=0` has a term `sL1/(sL2)` of no value.
+    fn twoport(&mut self) -> String {
+        let mut ids = 0;
+        let mut source = String::from("|V1");
+        let mut across_source = true;
+        for _ in 0..1 + self.below(4) {
+            match self.below(5) {
+                0 => {
+                    ids += 1;
+                    let ratio = Value { number: self.uniform(10.0, 1000.0).round() / 100.0, unit: "" };
+                    source.push_str(&format!("=T{ids}(n={})", ratio.to_circmark()));
+                    continue;
+                }
+                1 | 2 => source.push('-'),
+                _ => source.push('|'),
+            }
+            let series = source.ends_with('-');
+            let circuit = self.sub_circuit(2, &mut ids, !across_source, series && !across_source);
+            across_source &= !series;
+            source.push_str(&circuit);
+        }
+        source
+    }
+
+    /// Source of a resistor, inductor or capacitor, or a group of up to `depth` levels. Some elements are open
+    /// circuits, and with `shorts` closed switches, or with `zeros` resistors and inductors with a value of zero
+    fn sub_circuit(&mut self, depth: u32, ids: &mut u32, shorts: bool, zeros: bool) -> String {
+        if depth == 0 || self.below(2) == 0 {
+            let kind = self.below(16);
+            if kind == 0 {
+                return "O".to_string();
+            }
+            *ids += 1;
+            if kind == 1 && shorts {
+                return format!("S{ids}*");
+            }
+            if kind == 2 && zeros {
+                return format!("{}{ids}=0", ["R", "L"][self.below(2) as usize]);
+            }
+            let (prefix, lowest_exponent, exponents) = [("R", 0, 6), ("L", -6, 5), ("C", -12, 8)][self.below(3) as usize];
+            // three significant digits, like real parts
+            let mantissa = (self.uniform(100.0, 1000.0).round()) as i32;
+            let exponent = lowest_exponent + self.below(exponents) as i32;
+            let number = format!("{mantissa}e{}", exponent - 2).parse().unwrap();
+            let value = Value { number, unit: "" };
+            return format!("{prefix}{ids}={}", value.to_circmark());
+        }
+        let operator = if self.below(2) == 0 { "+" } else { "||" };
+        let a = self.sub_circuit(depth - 1, ids, shorts, zeros);
+        let b = self.sub_circuit(depth - 1, ids, shorts, zeros);
+        format!("({a}{operator}{b})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_agree() {
+        let divergences = run(1, 500);
+        assert!(divergences.is_empty(), "{divergences:#?}");
+    }
+
+    fn twoport(source: &str) -> Twoport<'_> {
+        let Ok(Document::Twoport(twoport)) = circuit::document(source) else {
+            panic!("{source:?} is not a twoport");
+        };
+        twoport
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare(&twoport("|V1-R1=1k|C1=1u"), 100.0), None);
+        assert_eq!(compare(&twoport("|V1-R1=1k=T2(n=2)|C1=1u-L3=1m"), 1.0), None);
+        assert_eq!(compare(&twoport("|V1-R1=1k|O|(C1=0||R2=1k)"), 1.0), None);
+        assert_eq!(compare(&twoport("|V1-(R1=1k+L1=0)|(R2=1k||O)"), 1.0), None);
+        assert_eq!(compare(&twoport("|V1-R1=1k|(R2=0||S3*)"), 1.0), None);
+        assert_eq!(compare(&twoport("|V1-R1=1k|S2*=T3(n=2)|S4*"), 1.0), None);
+        assert_eq!(compare(&twoport("|V1-R1=1k|S2*-O"), 1.0), None);
+    }
+}
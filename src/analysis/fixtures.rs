@@ -0,0 +1,120 @@
+//! Canonical circuits with their known impedances and transfer functions.
+//!
+//! When adding a kind of element or changing the analysis, add fixtures using it
+//! and run `cargo test`, which checks all of them.
+
+use super::{ac_sweep, values, Complex64, Expr};
+use crate::circuit::{self, Document};
+
+/// A circuit whose analysis result is known
+pub struct Fixture {
+    /// circmark source, with values for all elements
+    pub source: &'static str,
+    /// Impedance of a circuit, or transfer function of a twoport, as formatted by [`Expr`]
+    pub expected: &'static str,
+    /// Angular frequencies ω (in rad/s) with the value expected at `s = jω`
+    pub samples: &'static [(f64, Complex64)],
+}
+
+pub static FIXTURES: &[Fixture] = &[
+    Fixture {
+        source: "(R1=100+R2=220)",
+        expected: "R1 + R2",
+        samples: &[(0.0, Complex64::new(320.0, 0.0)), (1e6, Complex64::new(320.0, 0.0))],
+    },
+    Fixture {
+        source: "(R1=1k||C1=1u)",
+        expected: "R1 || 1/(sC1)",
+        // the corner frequency, ω = 1/(R1 C1)
+        samples: &[(1000.0, Complex64::new(500.0, -500.0))],
+    },
+    Fixture {
+        source: "(L1=10m+C1=1u)",
+        expected: "1/(sC1) + sL1",
+        // resonance at ω = 1/sqrt(L1 C1)
+        samples: &[(1e4, Complex64::new(0.0, 0.0)), (1e3, Complex64::new(0.0, 10.0 - 1000.0))],
+    },
+    Fixture {
+        source: "(R1=1k+V1||I1)",
+        expected: "R1",
+        samples: &[(1.0, Complex64::new(1000.0, 0.0))],
+    },
+    Fixture {
+        source: "|V1-R1=1k|R2=1k",
+        expected: "1/(1 + R1/R2)",
+        samples: &[(1.0, Complex64::new(0.5, 0.0))],
+    },
+    Fixture {
+        source: "|V1-R1=1k|C1=1u",
+        expected: "1/(1 + sR1C1)",
+        samples: &[(1000.0, Complex64::new(0.5, -0.5))],
+    },
+    Fixture {
+        source: "|V1-C1=1u|R1=1k",
+        expected: "1/(1 + 1/(sC1R1))",
+        samples: &[(1000.0, Complex64::new(0.5, 0.5))],
+    },
+    Fixture {
+        source: "|V1-R1=1k|C1=1u-R2=1k|C2=1u",
+        expected: "1/(1 + sR1C1 + sR2C2 + sR1C2 + s^2R1C1R2C2)",
+        samples: &[(1000.0, Complex64::new(0.0, -1.0 / 3.0))],
+    },
+    Fixture {
+        source: "|V1-R1=1k|V2-R2=1k|C1=1u",
+        expected: "0",
+        samples: &[(1000.0, Complex64::new(0.0, 0.0))],
+    },
+    Fixture {
+        source: "|V1-R1=1k=T1(n=2)|R2=1k",
+        expected: "1/(T1 + R1/(T1R2))",
+        // R2 appears as n²·R2 = 4k on the primary side
+        samples: &[(1.0, Complex64::new(0.4, 0.0))],
+    },
+];
+
+impl Fixture {
+    /// Compares the symbolic result and its numeric evaluation (and, for twoports, the AC sweep)
+    /// with the expectation, describing the first mismatch
+    pub fn check(&self) -> Result<(), String> {
+        let document = circuit::document(self.source).map_err(|error| format!("{}: {error}", self.source))?;
+        let result = match &document {
+            Document::Circuit(circuit) => circuit.impedance(),
+            Document::Twoport(twoport) => twoport.transfer_function(),
+        };
+        let result: Expr = result.ok_or_else(|| format!("{}: no result", self.source))?;
+        if result.to_string() != self.expected {
+            return Err(format!("{}: expected {}, got {result}", self.source, self.expected));
+        }
+        let values = values(&document);
+        for &(omega, expected) in self.samples {
+            let actual = result.eval(Complex64::new(0.0, omega), &values)
+                .ok_or_else(|| format!("{}: can't evaluate at ω = {omega}", self.source))?;
+            compare(self.source, "evaluated", omega, actual, expected)?;
+            if let Document::Twoport(twoport) = &document {
+                let freq = omega / (2.0 * std::f64::consts::PI);
+                let actual = ac_sweep(twoport, &values, &[freq])
+                    .ok_or_else(|| format!("{}: can't sweep at ω = {omega}", self.source))?[0];
+                compare(self.source, "swept", omega, actual, expected)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn compare(source: &str, how: &str, omega: f64, actual: Complex64, expected: Complex64) -> Result<(), String> {
+    if (actual - expected).norm() > 1e-9 * expected.norm().max(1.0) {
+        return Err(format!("{source}: expected {expected} at ω = {omega}, {how} {actual}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures() {
+        let failures: Vec<String> = FIXTURES.iter().filter_map(|fixture| fixture.check().err()).collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
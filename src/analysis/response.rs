@@ -0,0 +1,122 @@
+//! Frequency response of twoports: magnitude, unwrapped phase and group delay over a logarithmic sweep,
+//! for plotting them (see [`crate::draw::svg::SvgDrawer::response_plot`]) or exporting them as CSV.
+//!
+//! ```
+//! use circmark_parse::{analysis::{self, response::{log_frequencies, Response}}, circuit};
+//! let document = circuit::document("|V1-R1=1k|C1=1u").unwrap();
+//! let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+//! let response = Response::sweep(twoport, &analysis::values(&document), &log_frequencies(1.0, 10e3, 10)).unwrap();
+//! // well below the cutoff frequency, the delay of an RC low pass is RC
+//! assert!((response.points[0].group_delay - 1e-3).abs() < 1e-5);
+//! assert!(response.to_csv().starts_with("frequency_hz,magnitude_db,phase_deg,group_delay_s\n1,"));
+//! ```
+
+use super::{ac_sweep, Complex64, Values};
+use crate::circuit::Twoport;
+
+/// The response at one frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponsePoint {
+    /// Frequency in Hz
+    pub frequency: f64,
+    /// Complex gain `Vout/Vin`
+    pub gain: Complex64,
+    /// Magnitude of the gain in dB
+    pub magnitude: f64,
+    /// Phase of the gain in degrees, unwrapped so it doesn't jump by 360° between neighbouring frequencies
+    pub phase: f64,
+    /// Group delay `-dφ/dω` in seconds
+    pub group_delay: f64,
+}
+
+/// Response of a twoport over a sweep of frequencies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Points in the order of the swept frequencies
+    pub points: Vec<ResponsePoint>,
+}
+
+/// `points_per_decade` logarithmically spaced frequencies from `start` to `stop` (in Hz), both included.
+///
+/// There are none if either bound is zero, negative or not finite, which have no logarithm to space them by
+pub fn log_frequencies(start: f64, stop: f64, points_per_decade: usize) -> Vec<f64> {
+    if ![start, stop].iter().all(|bound| bound.is_finite() && *bound > 0.0) {
+        return vec![];
+    }
+    let steps = ((stop / start).log10() * points_per_decade as f64).round().max(1.0) as usize;
+    (0..=steps).map(|i| start * (stop / start).powf(i as f64 / steps as f64)).collect()
+}
+
+impl Response {
+    /// Sweeps the twoport over the frequencies (see [`ac_sweep`]), `None` if it has no gain or there are
+    /// fewer than two frequencies to take the group delay from
+    pub fn sweep(twoport: &Twoport, values: &Values, freqs: &[f64]) -> Option<Response> {
+        if freqs.len() < 2 {
+            return None;
+        }
+        let gains = ac_sweep(twoport, values, freqs)?;
+        let mut phases: Vec<f64> = Vec::with_capacity(gains.len());
+        for gain in &gains {
+            let phase = match phases.last() {
+                // the multiple of 2π closest to the previous phase
+                Some(previous) => gain.arg() + (2.0 * std::f64::consts::PI) * ((previous - gain.arg()) / (2.0 * std::f64::consts::PI)).round(),
+                None => gain.arg(),
+            };
+            phases.push(phase);
+        }
+        let omega = |i: usize| 2.0 * std::f64::consts::PI * freqs[i];
+        let points = (0..freqs.len()).map(|i| {
+            // central differences, one-sided at both ends
+            let (before, after) = (i.saturating_sub(1), (i + 1).min(freqs.len() - 1));
+            ResponsePoint {
+                frequency: freqs[i],
+                gain: gains[i],
+                magnitude: 20.0 * gains[i].norm().log10(),
+                phase: phases[i].to_degrees(),
+                group_delay: -(phases[after] - phases[before]) / (omega(after) - omega(before)),
+            }
+        }).collect();
+        Some(Response { points })
+    }
+
+    /// The response as CSV, with a header line and a line for each frequency
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frequency_hz,magnitude_db,phase_deg,group_delay_s\n");
+        for point in &self.points {
+            csv.push_str(&format!("{},{},{},{}\n", point.frequency, point.magnitude, point.phase, point.group_delay));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis, circuit};
+
+    #[test]
+    fn test_log_frequencies() {
+        let freqs = log_frequencies(10.0, 1e3, 2);
+        assert_eq!(freqs.len(), 5);
+        assert_eq!((freqs[0], freqs[4]), (10.0, 1e3));
+        assert!((freqs[1] - 10f64.powf(1.5)).abs() < 1e-9);
+        for (start, stop) in [(0.0, 1e3), (-10.0, 1e3), (10.0, f64::INFINITY), (f64::NAN, 1e3)] {
+            assert!(log_frequencies(start, stop, 2).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_sweep() {
+        // three RC sections turn the phase by up to 270°, so it has to be unwrapped
+        let document = circuit::document("|V1-R1=1k|C1=1u-R2=1k|C2=1u-R3=1k|C3=1u").unwrap();
+        let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+        let response = Response::sweep(twoport, &analysis::values(&document), &log_frequencies(1.0, 1e6, 20)).unwrap();
+        let last = response.points.last().unwrap();
+        assert!(last.phase < -260.0 && last.phase > -270.0);
+        assert!(response.points.windows(2).all(|pair| pair[1].phase <= pair[0].phase));
+        assert!(response.points.iter().all(|point| point.group_delay > 0.0));
+        assert!(last.magnitude < -150.0);
+        assert_eq!(response.to_csv().lines().count(), response.points.len() + 1);
+        assert_eq!(Response::sweep(twoport, &analysis::values(&document), &[1.0]), None);
+    }
+}
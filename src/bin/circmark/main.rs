@@ -0,0 +1,700 @@
+//! The circmark command line tool, rendering, checking and formatting circmark files and sources.
+//!
+//! The arguments are parsed by hand rather than with clap. Inputs may be circmark source, which starts with `-` for a
+//! series link (e.g. `-R1|C1`) that clap would take for short flags; allowing hyphens in its values would make it
+//! take misspelled options for inputs as well, whereas [`input`] reports them. Inputs are rendered without a command
+//! (`circmark in.cm`), which clap's subcommands only allow with workarounds. And as the binary shares the library's
+//! dependencies, every user of the library would build clap, whereas the other binaries' dependencies are optional.
+
+use std::{io::{Read, Write}, path::{Path, PathBuf}};
+use circmark_parse::{
+    bom::{self, Bom},
+    circuit,
+    describe,
+    document,
+    dump,
+    draw,
+    format,
+    gallery,
+    legend,
+    netlist,
+    render::{self, RenderOptions},
+    search::{self, NodePath},
+    sweep,
+    validate,
+};
+
+mod watch;
+
+const USAGE: &str = "\
+usage: circmark [render] [OPTIONS] [INPUT...] [-o OUTPUT]
+       circmark check [--variant NAME] [--bom FILE] [INPUT...]
+       circmark fmt [INPUT...] [-o OUTPUT]
+       circmark convert [--to FORMAT] [OPTIONS] [INPUT...] [-o OUTPUT]
+       circmark steps [OPTIONS] [INPUT...] [-o OUTPUT]
+       circmark ast [--path PATH] [INPUT...] [-o OUTPUT]
+       circmark outline [INPUT...] [-o OUTPUT]
+       circmark gallery [OPTIONS] DIRECTORY -o OUTPUT
+
+render  draws the inputs, as SVG unless --format or the extension of OUTPUT says otherwise;
+        with --watch again whenever an input file changes, and with --serve ADDRESS also
+        serving the drawing to browsers, which reload it
+check   reports errors and warnings in the inputs, and with --bom the differences to a bill of
+        materials in CSV, exiting with status 1 if there are any but warnings
+fmt     writes the inputs in canonical form
+convert writes the inputs as spice, json, tikz, kicad (a KiCad netlist) or circmark, given by
+        --to or the extension of OUTPUT
+steps   draws how the circuit reduces to equivalent resistors, capacitors and inductors, one
+        step below the other, as SVG
+ast     writes the syntax tree of each section of the inputs as a line of JSON, with the byte
+        range in its input of each link and element, or with --path (e.g. sections[0]/links[1])
+        that of the node it addresses in the sections of all inputs
+outline lists the sections of the inputs and the links of each, with the line and column
+        they start at, e.g. for jumping to them in an editor
+gallery draws each circmark file (*.cm) of DIRECTORY into the directory OUTPUT, with a
+        thumbnail of --thumbnail SIZE pixels (160 by default) and an index.html of them, captioned
+        and tagged by the `# title:` and `# tags:` comments the files start with, or captioned by
+        the title of their first titled section
+
+An input naming a file is read from it, `-` (or --stdin) reads stdin, any other input is circmark
+source, and stdin is read when there is none. The output goes to stdout (or --stdout) without -o.
+The options of render are described in the README.";
+
+/// What to do with the inputs, given by the first argument
+#[derive(Copy, Clone, PartialEq)]
+enum Command {
+    Render,
+    Check,
+    Fmt,
+    Convert,
+    Steps,
+    Ast,
+    Outline,
+    Gallery,
+}
+
+/// Size in pixels of the thumbnails of `circmark gallery`, unless given by --thumbnail
+const GALLERY_THUMBNAIL: u32 = 160;
+
+/// The formats `circmark convert` writes, as named by `--to`
+const CONVERSIONS: &[&str] = &["spice", "json", "tikz", "kicad", "circmark"];
+
+/// A source to process, with the name to report errors under
+struct Input {
+    name: String,
+    source: String,
+    /// The file the source was read from
+    path: Option<PathBuf>,
+}
+
+/// Reports an error on stderr and exits; stdout only ever receives the output
+fn fail(message: &str) -> ! {
+    eprintln!("circmark: {message}");
+    std::process::exit(1);
+}
+
+/// Reads the inputs named by the arguments, which are files, circmark source or `-` for stdin, and stdin without any
+fn read_inputs(args: Vec<String>) -> Vec<Input> {
+    if args.is_empty() {
+        return vec![stdin()];
+    }
+    args.into_iter().map(|arg| if arg == "-" { stdin() } else { input(arg).unwrap_or_else(|error| fail(&error)) }).collect()
+}
+
+fn stdin() -> Input {
+    let mut source = String::new();
+    std::io::stdin().read_to_string(&mut source).unwrap_or_else(|error| fail(&format!("can't read stdin: {error}")));
+    Input { name: "stdin".to_string(), source, path: None }
+}
+
+/// The input an argument names: the file of that name, or else circmark source. Arguments that don't parse but look
+/// like a path (or an option) are reported as such, rather than with the parse error they'd give as source
+fn input(arg: String) -> Result<Input, String> {
+    let path = PathBuf::from(&arg);
+    if path.is_file() {
+        return Ok(Input { source: read_file(&path)?, name: arg, path: Some(path) });
+    }
+    if document::parse(&arg).is_err() {
+        let mut chars = arg.chars();
+        if chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_ascii_alphabetic()) && chars.next().is_none() {
+            return Err(format!("unknown option {arg}, see --help"));
+        }
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let has_extension = extension.is_some_and(|extension| !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphabetic()));
+        if !arg.contains(char::is_whitespace) && (arg.contains(['/', '\\']) || has_extension) {
+            return Err(format!("can't read {arg:?}: no such file"));
+        }
+    }
+    Ok(Input { name: format!("{arg:?}"), source: arg, path: None })
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|error| format!("can't read {path:?}: {error}"))
+}
+
+/// The line and column (in characters, both starting at 1) of the byte offset in the source
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    (before.matches('\n').count() + 1, before.chars().rev().take_while(|c| *c != '\n').count() + 1)
+}
+
+/// Writes the result to the output file, or stdout without one
+fn write_output(output: Option<&str>, bytes: &[u8]) {
+    try_write_output(output, bytes).unwrap_or_else(|error| fail(&error));
+}
+
+/// Like [`write_output`], but returning the error rather than exiting
+fn try_write_output(output: Option<&str>, bytes: &[u8]) -> Result<(), String> {
+    match output {
+        Some(path) => std::fs::write(path, bytes),
+        None => std::io::stdout().write_all(bytes),
+    }.map_err(|error| format!("can't write output: {error}"))
+}
+
+/// The format implied by the extension of the output file, SVG without one and None for unknown ones
+fn format_of(output: &str) -> Option<&'static str> {
+    let Some(extension) = Path::new(output).extension() else {
+        return Some("svg");
+    };
+    Some(match extension.to_str()?.to_ascii_lowercase().as_str() {
+        "svg" => "svg",
+        "png" => "png",
+        "drawio" => "drawio",
+        "dxf" => "dxf",
+        "hpgl" => "hpgl",
+        "gcode" => "gcode",
+        "tex" => "tikz",
+        "cir" | "sp" => "spice",
+        "csv" => "csv",
+        "json" => "json",
+        "net" => "kicad",
+        "cm" => "circmark",
+        "txt" => "text",
+        _ => return None,
+    })
+}
+
+/// Parses a frequency given as a value, e.g. `1kHz`, which must be positive (and not too large to be a number)
+fn parse_frequency(frequency: &str) -> f64 {
+    circuit::value::<nom::error::VerboseError<&str>>(frequency).ok()
+        .filter(|(rest, value)| rest.is_empty() && value.number.is_finite() && value.number > 0.0)
+        .unwrap_or_else(|| fail(&format!("invalid frequency {frequency:?}, expected a positive frequency, e.g. 1kHz"))).1.number
+}
+
+/// Parses an element's range of values for a sweep, e.g. `R1=1k:100k:5` for five values from 1kΩ to 100kΩ
+fn parse_axis(sweep: &str) -> sweep::Axis {
+    let invalid = format!("invalid sweep {sweep:?}, expected e.g. R1=1k:100k:5");
+    let (label, range) = sweep.split_once('=').unwrap_or_else(|| fail(&invalid));
+    let [start, stop, steps] = range.split(':').collect::<Vec<_>>()[..] else { fail(&invalid) };
+    let value = |value: &str| circuit::value::<nom::error::VerboseError<&str>>(value).ok()
+        .filter(|(rest, value)| rest.is_empty() && value.number.is_finite() && value.number > 0.0)
+        .unwrap_or_else(|| fail(&invalid)).1.number;
+    let steps = steps.parse().ok().filter(|steps| *steps > 0).unwrap_or_else(|| fail(&invalid));
+    sweep::Axis::logarithmic(label, value(start), value(stop), steps)
+}
+
+fn main() {
+    let mut options = RenderOptions::default();
+    let mut format = None;
+    let mut output = None;
+    let mut inputs = vec![];
+    let mut metric_given = None;
+    let mut watch = false;
+    let mut serve = None;
+    let mut bill = None;
+    let mut locale = None;
+    let mut path = None;
+    // parsed by hand, see the module documentation
+    let mut args = std::env::args().skip(1).peekable();
+    // without a command the inputs are rendered
+    let command = match args.peek().map(String::as_str) {
+        Some("check") => Command::Check,
+        Some("fmt") => Command::Fmt,
+        Some("convert") => Command::Convert,
+        Some("steps") => Command::Steps,
+        Some("ast") => Command::Ast,
+        Some("outline") => Command::Outline,
+        Some("gallery") => Command::Gallery,
+        _ => Command::Render,
+    };
+    args.next_if(|arg| ["render", "check", "fmt", "convert", "steps", "ast", "outline", "gallery"].contains(&arg.as_str()));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            "-o" | "--output" => output = Some(args.next().unwrap_or_else(|| fail(&format!("{arg} requires a file")))),
+            // the defaults without inputs and -o, given explicitly e.g. by editors running circmark on a block
+            "--stdin" => inputs.push("-".to_string()),
+            "--stdout" => output = None,
+            "--scale" => {
+                let scale = args.next().unwrap_or_else(|| fail("--scale requires a factor"));
+                options.scale = Some(scale.parse().ok().filter(|scale: &f64| *scale > 0.0 && scale.is_finite())
+                    .unwrap_or_else(|| fail(&format!("invalid scale {scale:?}, expected a positive number"))));
+            }
+            "--thumbnail" => {
+                let size = args.next().unwrap_or_else(|| fail("--thumbnail requires a size in pixels"));
+                options.thumbnail = Some(size.parse().ok().filter(|size| *size > 0)
+                    .unwrap_or_else(|| fail(&format!("invalid thumbnail size {size:?}, expected a positive number of pixels"))));
+            }
+            "--mirror" => options.mirror = true,
+            "--shorten" => options.shorten = true,
+            "--impedance-at" => {
+                let frequency = args.next().unwrap_or_else(|| fail("--impedance-at requires a frequency"));
+                options.impedance_at = Some(parse_frequency(&frequency));
+            }
+            "--response" => {
+                let range = args.next().unwrap_or_else(|| fail("--response requires a frequency range"));
+                let (start, stop) = range.split_once(':').unwrap_or_else(|| fail(&format!("invalid frequency range {range:?}, expected e.g. 10Hz:100kHz")));
+                let (start, stop) = (parse_frequency(start), parse_frequency(stop));
+                if !(0.0 < start && start < stop) {
+                    fail(&format!("invalid frequency range {range:?}, expected increasing positive frequencies"));
+                }
+                options.response = Some((start, stop));
+            }
+            "--theme" => options.theme = args.next().unwrap_or_else(|| fail("--theme requires a theme, light or dark"))
+                .parse().unwrap_or_else(|error: circmark_parse::error::UnknownName| fail(&error.to_string())),
+            "--sweep" => {
+                let sweep = args.next().unwrap_or_else(|| fail("--sweep requires an element and range, e.g. R1=1k:100k:5"));
+                let axis = parse_axis(&sweep);
+                let (_, rows, columns) = options.sweep.get_or_insert_with(|| (sweep::Metric::Cutoff, axis.clone(), None));
+                if rows != &axis {
+                    if columns.is_some() {
+                        fail("at most two elements can be swept");
+                    }
+                    *columns = Some(axis);
+                }
+            }
+            "--metric" => {
+                let metric = match args.next().as_deref() {
+                    Some("cutoff") => sweep::Metric::Cutoff,
+                    Some("ripple") => sweep::Metric::Ripple,
+                    Some(zin) if zin.starts_with("zin@") => sweep::Metric::InputImpedance(parse_frequency(&zin["zin@".len()..])),
+                    other => fail(&format!("unknown metric {other:?}, expected cutoff, ripple or zin@FREQUENCY")),
+                };
+                metric_given = Some(metric);
+            }
+            "--symbols" => options.symbols = args.next().unwrap_or_else(|| fail("--symbols requires a standard, iec or ansi"))
+                .parse().unwrap_or_else(|error: circmark_parse::error::UnknownName| fail(&error.to_string())),
+            "--inherit-style" => options.inherit_style = true,
+            "--keyboard" => options.keyboard_navigation = Some(false),
+            "--keyboard-announce" => options.keyboard_navigation = Some(true),
+            // applied after all options, so that --theme doesn't reset it
+            "--locale" => locale = Some(args.next().unwrap_or_else(|| fail("--locale requires a locale, en, de, si or ascii"))
+                .parse::<format::Locale>().unwrap_or_else(|error| fail(&error.to_string()))),
+            "--legend" => options.legend = Some(legend::names(&args.next().unwrap_or_else(|| fail("--legend requires a language, en or de")))
+                .unwrap_or_else(|error| fail(&error.to_string()))),
+            "--font-family" => options.font = Some(draw::svg::Font {
+                family: args.next().unwrap_or_else(|| fail("--font-family requires an argument")),
+                source: None,
+            }),
+            "--font-url" | "--font-file" => {
+                let value = args.next().unwrap_or_else(|| fail(&format!("{arg} requires an argument")));
+                let font = options.font.as_mut().unwrap_or_else(|| fail("--font-family must be given before the font source"));
+                font.source = Some(if arg == "--font-url" {
+                    draw::svg::FontSource::Url(value)
+                } else {
+                    let format = match value.rsplit('.').next().map(str::to_ascii_lowercase).as_deref() {
+                        Some("ttf") => "truetype",
+                        Some("otf") => "opentype",
+                        Some("woff") => "woff",
+                        _ => "woff2",
+                    };
+                    draw::svg::FontSource::Embedded {
+                        data: std::fs::read(&value).unwrap_or_else(|error| fail(&format!("can't read font file {value:?}: {error}"))),
+                        format: format.to_string(),
+                    }
+                });
+            }
+            "--font-dir" => options.font_dir = Some(args.next().unwrap_or_else(|| fail("--font-dir requires a directory")).into()),
+            "--title" => options.title = Some(args.next().unwrap_or_else(|| fail("--title requires a text"))),
+            "--desc" => options.description = Some(args.next().unwrap_or_else(|| fail("--desc requires a text"))),
+            "--attribute" => {
+                let attribute = args.next().unwrap_or_else(|| fail("--attribute requires NAME=VALUE"));
+                let (name, value) = attribute.split_once('=').unwrap_or_else(|| fail(&format!("invalid attribute {attribute:?}, expected NAME=VALUE")));
+                if !draw::svg::is_xml_name(name) {
+                    fail(&format!("invalid attribute name {name:?}"));
+                }
+                options.attributes.push((name.to_string(), value.to_string()));
+            }
+            "--path" => path = Some(args.next().unwrap_or_else(|| fail("--path requires a path, e.g. sections[0]/links[1]"))
+                .parse::<NodePath>().unwrap_or_else(|error| fail(&error.to_string()))),
+            "--bom" => bill = Some(args.next().unwrap_or_else(|| fail("--bom requires a file"))),
+            "--watch" => watch = true,
+            "--serve" => serve = Some(args.next().unwrap_or_else(|| fail("--serve requires an address, e.g. 127.0.0.1:8000"))),
+            "--parasitics" => options.parasitics = true,
+            "--variant" => options.variant = Some(args.next().unwrap_or_else(|| fail("--variant requires an argument"))),
+            "--format" | "--to" => format = Some(args.next().unwrap_or_else(|| fail(&format!("{arg} requires a format")))),
+            // circmark source never starts with two dashes
+            _ if arg.starts_with("--") => fail(&format!("unknown option {arg}, see --help")),
+            _ => inputs.push(arg),
+        }
+    }
+    if let Some(locale) = locale {
+        options.theme.locale = locale;
+    }
+    match (&mut options.sweep, metric_given) {
+        (Some((metric, _, _)), Some(given)) => *metric = given,
+        (None, Some(_)) => fail("--metric requires --sweep"),
+        _ => {}
+    }
+    if command == Command::Gallery {
+        let [directory] = &inputs[..] else {
+            fail("gallery requires a single directory");
+        };
+        let output = output.unwrap_or_else(|| fail("gallery requires an output directory, given by -o"));
+        gallery(Path::new(directory), Path::new(&output), &options);
+        return;
+    }
+    let inputs = read_inputs(inputs);
+    if let Some(path) = &path {
+        if command != Command::Ast {
+            fail("--path requires the ast command");
+        }
+        let json = node_json(&inputs, path).unwrap_or_else(|error| fail(&error));
+        write_output(output.as_deref(), format!("{json}\n").as_bytes());
+        return;
+    }
+    match command {
+        Command::Check => {
+            let mut failed = false;
+            let mut sections = vec![];
+            for input in &inputs {
+                for diagnostic in validate::validate(&input.source) {
+                    let (line, column) = line_column(&input.source, diagnostic.span.start);
+                    eprintln!("circmark: {}:{line}:{column}: {diagnostic}", input.name);
+                    failed |= diagnostic.severity == validate::Severity::Error;
+                }
+                sections.extend(render::sections(&input.source, &options).unwrap_or_default());
+            }
+            if let Some(bill) = &bill {
+                let csv = read_file(Path::new(bill)).unwrap_or_else(|error| fail(&error));
+                let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+                for mismatch in bom::cross_check(&documents, &csv).unwrap_or_else(|error| fail(&error.to_string())) {
+                    eprintln!("circmark: {mismatch}");
+                    failed = true;
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Command::Fmt => {
+            let formatted: String = inputs.iter().map(|input| document::format(&input.source)
+                .unwrap_or_else(|error| fail(&format!("can't parse {}:\n{error}", input.name)))).collect();
+            write_output(output.as_deref(), formatted.as_bytes());
+            return;
+        }
+        Command::Convert => {
+            let target = format.or_else(|| output.as_deref().and_then(format_of).filter(|format| CONVERSIONS.contains(format)).map(str::to_string))
+                .unwrap_or_else(|| fail("convert requires --to FORMAT, e.g. --to spice"));
+            if !CONVERSIONS.contains(&target.as_str()) {
+                fail(&format!("can't convert to {target:?}, expected spice, json, tikz, kicad or circmark"));
+            }
+            let result = render(&inputs, &options, &target).unwrap_or_else(|error| fail(&error));
+            write_output(output.as_deref(), &result);
+            return;
+        }
+        Command::Steps => {
+            let mut sections = vec![];
+            for input in &inputs {
+                sections.extend(render::sections(&input.source, &options)
+                    .unwrap_or_else(|error| fail(&format!("can't parse {}:\n{error}", input.name))));
+            }
+            let [section] = &sections[..] else {
+                fail(&format!("steps requires a single circuit, the inputs have {} sections", sections.len()));
+            };
+            let svg = render::render_steps(&section.document.borrow(), &options);
+            write_output(output.as_deref(), svg.as_bytes());
+            return;
+        }
+        Command::Ast => {
+            let mut json = String::new();
+            for input in &inputs {
+                let sections = document::parse(&input.source)
+                    .unwrap_or_else(|error| fail(&format!("can't parse {}:\n{error}", input.name)));
+                // the content as written, without the links of the twoport it extends
+                for section in sections {
+                    let content = &input.source[section.content.clone()];
+                    json.push_str(&dump::to_json_with_spans(content, section.content.start)
+                        .unwrap_or_else(|error| fail(&format!("can't parse {}:\n{error}", input.name))));
+                    json.push('\n');
+                }
+            }
+            write_output(output.as_deref(), json.as_bytes());
+            return;
+        }
+        Command::Outline => {
+            let mut outline = String::new();
+            for input in &inputs {
+                let sections = document::parse(&input.source)
+                    .unwrap_or_else(|error| fail(&format!("can't parse {}:\n{error}", input.name)));
+                for section in sections {
+                    let headed = section.span.start < section.content.start;
+                    if headed {
+                        let (line, column) = line_column(&input.source, section.span.start);
+                        let title = section.title.as_ref().or(section.name.as_ref()).map(|title| format!(" {title}")).unwrap_or_default();
+                        outline.push_str(&format!("{}:{line}:{column}: @{}{title}\n", input.name, section.kind.keyword()));
+                    }
+                    // the links as written, without those of the twoport it extends
+                    let content = &input.source[section.content.clone()];
+                    let items = circuit::document(content).ok().and_then(|document| document.outline(content)).unwrap_or_default();
+                    for item in items {
+                        let (line, column) = line_column(&input.source, section.content.start + item.span.start);
+                        let indent = if headed { "  " } else { "" };
+                        outline.push_str(&format!("{}:{line}:{column}: {indent}{} {}\n", input.name, item.kind.name(), item.caption));
+                    }
+                }
+            }
+            write_output(output.as_deref(), outline.as_bytes());
+            return;
+        }
+        Command::Render | Command::Gallery => {}
+    }
+    let format = format.unwrap_or_else(|| match output.as_deref() {
+        Some(output) => format_of(output).unwrap_or_else(|| fail(&format!("unknown format of {output:?}, give it with --format"))).to_string(),
+        None => "svg".to_string(),
+    });
+    if options.thumbnail.is_some() && format != "svg" {
+        fail("--thumbnail requires SVG output");
+    }
+    if watch || serve.is_some() {
+        let paths: Vec<_> = inputs.iter().filter_map(|input| input.path.clone()).collect();
+        if paths.is_empty() {
+            fail("--watch requires input files");
+        }
+        if serve.is_some() && format != "svg" {
+            fail("--serve requires SVG output");
+        }
+        let mut inputs = inputs;
+        watch::watch(&paths, serve.as_deref(), || {
+            for input in &mut inputs {
+                if let Some(path) = &input.path {
+                    input.source = read_file(path)?;
+                }
+            }
+            let result = render(&inputs, &options, &format)?;
+            // the drawing is still served, and written again on the next change
+            if let Err(error) = try_write_output(output.as_deref(), &result) {
+                eprintln!("circmark: {error}");
+            }
+            Ok(result)
+        });
+    }
+    let result = render(&inputs, &options, &format).unwrap_or_else(|error| fail(&error));
+    write_output(output.as_deref(), &result);
+}
+
+/// The syntax tree as JSON of the node the path addresses in the sections of all inputs, e.g. `sections[0]/links[1]`,
+/// or of the whole section for `sections[0]`
+fn node_json(inputs: &[Input], path: &NodePath) -> Result<String, String> {
+    let mut sections = vec![];
+    for input in inputs {
+        sections.extend(document::parse(&input.source).map_err(|error| format!("can't parse {}:\n{error}", input.name))?);
+    }
+    let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+    let (i, within) = path.section().ok_or_else(|| format!("the path {path} doesn't start with sections[n]"))?;
+    let document = documents.get(i).ok_or_else(|| format!("no section {i}, the inputs have {}", documents.len()))?;
+    if within.0.is_empty() {
+        return Ok(dump::to_json(document));
+    }
+    search::get(&documents, path).map(dump::sub_circuit_to_json).ok_or_else(|| format!("no sub-circuit at {path}"))
+}
+
+/// Draws each circmark file of the directory into the output directory as `NAME.svg` and `NAME.thumb.svg`, and
+/// writes their `index.html`, titled by --title or the directory's name. Files that fail are reported and skipped,
+/// exiting with status 1 after the index is written
+fn gallery(directory: &Path, output: &Path, options: &RenderOptions) {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)
+        .unwrap_or_else(|error| fail(&format!("can't read directory {directory:?}: {error}")))
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cm"))
+        .collect();
+    paths.sort();
+    std::fs::create_dir_all(output).unwrap_or_else(|error| fail(&format!("can't create directory {output:?}: {error}")));
+    // the title names the gallery rather than each drawing
+    let drawing_options = RenderOptions { thumbnail: None, title: None, ..options.clone() };
+    let size = options.thumbnail.unwrap_or(GALLERY_THUMBNAIL);
+    let mut entries = vec![];
+    let mut failed = false;
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let rendered = read_file(&path).and_then(|source| {
+            let sections = render::sections(&source, options).map_err(|error| error.to_string())?;
+            let drawing = render::render_sections(&sections, &drawing_options).map_err(nothing_drawn)?;
+            let thumbnail = render::render_thumbnail(&sections, size, &drawing_options).map_err(nothing_drawn)?;
+            Ok((source, drawing, thumbnail))
+        });
+        let (source, drawing, thumbnail) = match rendered {
+            Ok(rendered) => rendered,
+            Err(error) => {
+                eprintln!("circmark: skipping {}: {error}", path.display());
+                failed = true;
+                continue;
+            }
+        };
+        let entry = gallery::Entry {
+            metadata: gallery::Metadata::parse(&source),
+            thumbnail: format!("{name}.thumb.svg"),
+            link: format!("{name}.svg"),
+            name,
+        };
+        write_output(Some(&output.join(&entry.link).to_string_lossy()), drawing.as_bytes());
+        write_output(Some(&output.join(&entry.thumbnail).to_string_lossy()), thumbnail.as_bytes());
+        entries.push(entry);
+    }
+    let title = options.title.clone().unwrap_or_else(|| {
+        std::fs::canonicalize(directory).ok().and_then(|path| Some(path.file_name()?.to_string_lossy().to_string())).unwrap_or_else(|| "Circuits".to_string())
+    });
+    write_output(Some(&output.join("index.html").to_string_lossy()), gallery::index(&title, &entries).as_bytes());
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// The error of drawings without anything drawn, which the sections of other variants are left out of
+fn nothing_drawn(_: draw::svg::EmptyDrawing) -> String {
+    "nothing to draw, as no section is of the selected variant".to_string()
+}
+
+/// Renders the inputs in the format, e.g. `svg`
+fn render(inputs: &[Input], options: &RenderOptions, format: &str) -> Result<Vec<u8>, String> {
+    // each input may consist of several sections, which are drawn below each other
+    let mut sections = vec![];
+    for input in inputs {
+        sections.extend(render::sections(&input.source, &RenderOptions { variant: None, ..options.clone() })
+            .map_err(|error| format!("can't parse {}:\n{error}", input.name))?);
+    }
+    // sections of other variants are left out
+    let sections: Vec<_> = match &options.variant {
+        Some(variant) if !sections.iter().any(|section| section.variant.as_ref() == Some(variant)) => return Err(format!("no section of variant {variant:?}")),
+        Some(variant) => sections.into_iter().filter(|section| section.in_variant(variant)).collect(),
+        None => sections,
+    };
+    let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+    let ctx = options.context();
+    Ok(match format {
+        "svg" => render::render_sections(&sections, options).map_err(nothing_drawn)?.into_bytes(),
+        #[cfg(feature = "raster")]
+        "png" => {
+            let mut raster_drawer = draw::raster::RasterDrawer::new().theme(options.theme.clone()).geometry(options.geometry())
+                .scale(options.scale.unwrap_or(1.0) as f32);
+            if let Some(font) = &options.font {
+                raster_drawer = raster_drawer.font(font.clone());
+            }
+            if let Some(font_dir) = &options.font_dir {
+                raster_drawer = raster_drawer.font_dir(font_dir);
+            }
+            render::draw_sections(&sections, ctx, &mut raster_drawer);
+            for chart in render::smith_charts(&sections) {
+                raster_drawer.smith_chart(&chart);
+            }
+            for response in render::responses(&sections, options) {
+                raster_drawer.response_plot(&response);
+            }
+            for matrix in render::sweeps(&sections, options) {
+                raster_drawer.heatmap(&matrix);
+            }
+            raster_drawer.finalize().map_err(|error| match error {
+                draw::raster::RasterError::Empty(error) => nothing_drawn(error),
+                error => error.to_string(),
+            })?.encode_png().map_err(|error| format!("can't encode PNG: {error}"))?
+        }
+        "drawio" => {
+            let mut drawio_drawer = draw::drawio::DrawioDrawer::new();
+            render::draw_sections(&sections, ctx, &mut drawio_drawer);
+            drawio_drawer.finalize().into_bytes()
+        }
+        "dxf" => {
+            let mut display_list = draw::display_list::DisplayList::new().geometry(options.geometry());
+            render::draw_sections(&sections, ctx, &mut display_list);
+            draw::dxf::to_dxf(&display_list).into_bytes()
+        }
+        "hpgl" => {
+            let mut display_list = draw::display_list::DisplayList::new().geometry(options.geometry());
+            render::draw_sections(&sections, ctx, &mut display_list);
+            draw::hpgl::to_hpgl(&display_list, 0.25).into_bytes()
+        }
+        "gcode" => {
+            let mut display_list = draw::display_list::DisplayList::new().geometry(options.geometry());
+            render::draw_sections(&sections, ctx, &mut display_list);
+            draw::gcode::to_gcode(&display_list, &draw::gcode::GcodeOptions::default()).into_bytes()
+        }
+        "ascii" => {
+            let mut ascii_drawer = draw::ascii::AsciiDrawer::new();
+            render::draw_sections(&sections, ctx, &mut ascii_drawer);
+            ascii_drawer.finalize().into_bytes()
+        }
+        "tikz" => {
+            let mut tikz_drawer = draw::tikz::TikzDrawer::new();
+            render::draw_sections(&sections, ctx, &mut tikz_drawer);
+            tikz_drawer.finalize().into_bytes()
+        }
+        "spice" => documents.iter().map(netlist::to_spice).collect::<String>().into_bytes(),
+        "kicad" => documents.iter().map(netlist::to_kicad).collect::<String>().into_bytes(),
+        // the documents as drawn, e.g. resolved and with parasitics added
+        "circmark" => documents.iter().map(|document| format!("{document}\n")).collect::<String>().into_bytes(),
+        "bom" => Bom::new(&documents).to_csv().into_bytes(),
+        "kicad-bom" => Bom::new(&documents).to_kicad().into_bytes(),
+        "digikey-bom" => Bom::new(&documents).to_digikey().into_bytes(),
+        "csv" if options.sweep.is_some() => {
+            let matrices: Vec<_> = render::sweeps(&sections, options).iter().map(|matrix| matrix.to_csv()).collect();
+            if matrices.is_empty() {
+                return Err("no twoport to sweep".to_string());
+            }
+            matrices.join("\n").into_bytes()
+        }
+        "csv" => {
+            if options.response.is_none() {
+                return Err("--format csv requires --response FROM:TO or --sweep".to_string());
+            }
+            let responses = render::responses(&sections, options);
+            if responses.is_empty() {
+                return Err("no twoport with values for all of its elements to sweep".to_string());
+            }
+            // twoports are separated by an empty line
+            let csv: Vec<_> = responses.iter().map(|response| response.to_csv()).collect();
+            csv.join("\n").into_bytes()
+        }
+        "json" => documents.iter().map(|document| format!("{}\n", dump::to_json(document))).collect::<String>().into_bytes(),
+        "sexpr" => documents.iter().map(|document| format!("{}\n", dump::to_sexpr(document))).collect::<String>().into_bytes(),
+        "text" => documents.iter().map(|document| format!("{}\n", describe::describe(document))).collect::<String>().into_bytes(),
+        _ => return Err(format!("unknown format {format:?}, expected svg, png (with the raster feature), drawio, dxf, hpgl, gcode, tikz, ascii, spice, kicad, circmark, bom, kicad-bom, digikey-bom, csv, json, sexpr or text")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_of() {
+        assert_eq!(format_of("out.PNG"), Some("png"));
+        assert_eq!(format_of("out.net"), Some("kicad"));
+        assert_eq!(format_of("out"), Some("svg"));
+        assert_eq!(format_of("out.pdf"), None);
+    }
+
+    #[test]
+    fn test_input() {
+        assert_eq!(input("|V1-R1".to_string()).unwrap().source, "|V1-R1");
+        assert_eq!(input("-O".to_string()).unwrap().source, "-O");
+        assert_eq!(input("-f".to_string()).err().unwrap(), "unknown option -f, see --help");
+        assert_eq!(input("circuits/missing.cm".to_string()).err().unwrap(), "can't read \"circuits/missing.cm\": no such file");
+        assert_eq!(input("missing.cm".to_string()).err().unwrap(), "can't read \"missing.cm\": no such file");
+        assert!(input("|V1-R1(".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_node_json() {
+        let inputs = [input("@circuit\n(R1+C1)\n@twoport\n|V1-R2".to_string()).unwrap(), input("-L1".to_string()).unwrap()];
+        let json = |path: &str| node_json(&inputs, &path.parse().unwrap());
+        assert_eq!(json("sections[0]/series[1]").unwrap(), r#"{"type":"element","kind":"C","id":"1"}"#);
+        assert_eq!(json("sections[2]").unwrap(), r#"{"type":"twoport","links":[{"type":"series","circuit":{"type":"element","kind":"L","id":"1"}}]}"#);
+        assert_eq!(json("sections[1]/links[2]").unwrap_err(), "no sub-circuit at sections[1]/links[2]");
+        assert_eq!(json("sections[3]").unwrap_err(), "no section 3, the inputs have 3");
+        assert_eq!(json("links[0]").unwrap_err(), "the path links[0] doesn't start with sections[n]");
+    }
+}
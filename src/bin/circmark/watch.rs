@@ -0,0 +1,114 @@
+//! Watch mode, rendering again whenever an input file changes, optionally serving the drawing over HTTP to
+//! browsers, which reload it.
+//!
+//! Input files are polled for their modification time rather than watched with the notify crate: a check every
+//! [`POLL_INTERVAL`] is quick enough for a preview and costs next to nothing for a few files, and unlike file system
+//! events it works the same on every platform and on network file systems, and catches editors saving by writing a
+//! new file and renaming it over the old one (whose watch notify would have to set up again). It also keeps the
+//! command line tool free of dependencies.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// How often the input files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long a browser may take to send its request, after which the connection is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Page showing the drawing, which polls its version and reloads the drawing when it changes
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>circmark</title></head>
+<body style="margin: 2em; text-align: center">
+<img id="drawing" src="drawing.svg">
+<script>
+var version = null;
+setInterval(function () {
+  fetch('version').then(function (response) { return response.text(); }).then(function (latest) {
+    if (version !== null && latest !== version) {
+      document.getElementById('drawing').src = 'drawing.svg?' + latest;
+    }
+    version = latest;
+  });
+}, 500);
+</script>
+</body>
+</html>
+"#;
+
+/// The drawing last rendered, numbered to tell browsers it changed
+#[derive(Default)]
+struct Latest {
+    version: u64,
+    drawing: Vec<u8>,
+}
+
+/// Calls `update` now and whenever one of the files was modified, serving the drawing it returns at `address`
+/// if given. Errors are reported on stderr, keeping the drawing rendered before.
+pub fn watch(paths: &[PathBuf], address: Option<&str>, mut update: impl FnMut() -> Result<Vec<u8>, String>) -> ! {
+    let latest = Arc::new(Mutex::new(Latest::default()));
+    if let Some(address) = address {
+        let listener = TcpListener::bind(address).unwrap_or_else(|error| super::fail(&format!("can't listen on {address}: {error}")));
+        eprintln!("circmark: serving the drawing at http://{address}/");
+        let latest = latest.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // each on a thread of its own, so that a connection without a request doesn't hold up the others
+                let latest = latest.clone();
+                std::thread::spawn(move || {
+                    // a browser going away while being answered is of no concern
+                    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT)).and_then(|()| respond(stream, &latest));
+                });
+            }
+        });
+    }
+    let modified = || paths.iter().map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()).collect::<Vec<Option<SystemTime>>>();
+    let mut last_modified = None;
+    loop {
+        let now = modified();
+        if last_modified.as_ref() != Some(&now) {
+            last_modified = Some(now);
+            match update() {
+                Ok(drawing) => {
+                    let mut latest = latest.lock().unwrap();
+                    latest.version += 1;
+                    latest.drawing = drawing;
+                    eprintln!("circmark: rendered");
+                }
+                Err(error) => eprintln!("circmark: {error}"),
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Answers a request for the page, the drawing or its version
+fn respond(mut stream: TcpStream, latest: &Mutex<Latest>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers are of no interest
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap();
+    let (status, content_type, body) = {
+        let latest = latest.lock().unwrap();
+        match path {
+            "/" => ("200 OK", "text/html; charset=utf-8", PAGE.as_bytes().to_vec()),
+            "/drawing.svg" => ("200 OK", "image/svg+xml", latest.drawing.clone()),
+            "/version" => ("200 OK", "text/plain", latest.version.to_string().into_bytes()),
+            _ => ("404 Not Found", "text/plain", b"not found".to_vec()),
+        }
+    };
+    write!(stream, "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n", body.len())?;
+    stream.write_all(&body)
+}
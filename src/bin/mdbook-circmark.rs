@@ -0,0 +1,104 @@
+//! mdBook preprocessor, replacing ```` ```circmark ```` code blocks with drawings.
+//!
+//! Usage, in `book.toml`:
+//! ```toml
+//! [preprocessor.circmark]
+//! ```
+//!
+//! The SVG is inlined, taking its color and text size from the page so that it follows mdBook's themes. Code blocks
+//! can have sections, see [`circmark_parse::document`]. Those that fail to parse are left as they are.
+
+use std::io::Read;
+use serde_json::Value;
+use circmark_parse::render::{render_svg, RenderOptions};
+
+/// Renders circmark code blocks of a chapter's markdown, leaving everything else as it is
+fn preprocess(markdown: &str, chapter: &str, options: &RenderOptions) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut lines = markdown.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        let Some(fence) = opening_fence(line) else {
+            output.push_str(line);
+            continue;
+        };
+        let mut block = vec![line];
+        let mut closed = false;
+        for line in lines.by_ref() {
+            block.push(line);
+            if is_closing_fence(line, fence) {
+                closed = true;
+                break;
+            }
+        }
+        let source: String = block[1..block.len() - usize::from(closed)].concat();
+        match render_svg(&source, options) {
+            // markdown ends HTML blocks at blank lines
+            Ok(svg) => {
+                output.push_str("<div class=\"circmark\">\n");
+                for line in svg.lines().filter(|line| !line.trim().is_empty()) {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+                output.push_str("</div>\n");
+            }
+            Err(error) => {
+                eprintln!("mdbook-circmark: leaving code block in {chapter} as is, {error}");
+                output.push_str(&block.concat());
+            }
+        }
+    }
+    output
+}
+
+/// The fence opening a circmark code block, e.g. ```` ``` ```` for ```` ```circmark ````
+fn opening_fence(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let unindented = line.trim_start_matches(' ');
+    if line.len() - unindented.len() > 3 {
+        return None;
+    }
+    let marker = unindented.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = unindented.len() - unindented.trim_start_matches(marker).len();
+    let info = &unindented[length..];
+    (length >= 3 && info.split_whitespace().next() == Some("circmark")).then_some(&unindented[..length])
+}
+
+/// Whether the line closes the block opened by `fence`, being as long a fence of the same character or longer
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    let marker = fence.chars().next().expect("fences have characters");
+    line.len() >= fence.len() && line.chars().all(|c| c == marker)
+}
+
+/// Renders the code blocks of every chapter below `value`, which are the objects with `content` and `sub_items`
+fn walk(value: &mut Value, options: &RenderOptions) {
+    match value {
+        Value::Object(fields) => {
+            if let (Some(Value::String(content)), true) = (fields.get("content"), fields.contains_key("sub_items")) {
+                let chapter = fields.get("name").and_then(Value::as_str).unwrap_or_default();
+                let content = preprocess(content, chapter, options);
+                fields.insert("content".to_string(), Value::String(content));
+            }
+            fields.values_mut().for_each(|field| walk(field, options));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| walk(item, options)),
+        _ => {}
+    }
+}
+
+fn main() {
+    // mdBook asks whether a renderer is supported, which only HTML is as the drawings are inlined
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, renderer] = &args[..] {
+        if command == "supports" {
+            std::process::exit(if renderer == "html" { 0 } else { 1 });
+        }
+    }
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).expect("read");
+    // mdBook passes `[context, book]` and expects the book back
+    let [_, mut book]: [Value; 2] = serde_json::from_str(&input).expect("mdBook JSON");
+    let options = RenderOptions { inherit_style: true, ..RenderOptions::default() };
+    walk(&mut book, &options);
+    serde_json::to_writer(std::io::stdout(), &book).expect("write");
+}
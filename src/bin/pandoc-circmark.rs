@@ -0,0 +1,111 @@
+//! Pandoc JSON filter, replacing `circmark` code blocks with drawings.
+//!
+//! Usage: `pandoc --filter pandoc-circmark input.md -o output.docx`
+//!
+//! For HTML output the SVG is inlined. For all other formats it is written to
+//! `circmark-images/` and referenced as an image, with the circuit's description as alt text.
+//! Code blocks with an id can be extended by later ones, see [`circmark_parse::resolve`].
+
+use std::io::Read;
+use serde_json::{json, Value};
+use circmark_parse::{
+    circuit,
+    describe,
+    draw::{svg::SvgDrawer, Context, Draw},
+    layout::Layout,
+    resolve::Resolver,
+};
+
+const IMAGE_DIRECTORY: &str = "circmark-images";
+
+struct Filter {
+    /// Whether the output format can contain inline SVG
+    html: bool,
+    image_count: usize,
+    /// Code blocks seen so far, by their id
+    resolver: Resolver,
+    /// Drawer of all code blocks, each one being finalized into its own drawing
+    drawer: SvgDrawer,
+}
+
+impl Filter {
+    /// Replaces circmark code blocks anywhere below `value`
+    fn walk(&mut self, value: &mut Value) {
+        if let Some(replacement) = self.replace(value) {
+            *value = replacement;
+            return;
+        }
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.walk(item)),
+            Value::Object(fields) => fields.values_mut().for_each(|field| self.walk(field)),
+            _ => {}
+        }
+    }
+
+    /// Returns the replacement for a circmark code block, `None` for anything else
+    fn replace(&mut self, block: &Value) -> Option<Value> {
+        if block["t"] != "CodeBlock" {
+            return None;
+        }
+        // a code block is `[[id, classes, attributes], code]`
+        let [attr, code] = block["c"].as_array()?.as_slice() else {
+            return None;
+        };
+        if !attr[1].as_array()?.iter().any(|class| class == "circmark") {
+            return None;
+        }
+        let source = match self.resolver.resolve(code.as_str()?.trim()) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("pandoc-circmark: leaving code block as is, {error}");
+                return None;
+            }
+        };
+        let document = match circuit::document(&source) {
+            Ok(document) => document,
+            Err(error) => {
+                eprintln!("pandoc-circmark: leaving code block as is, {error}");
+                return None;
+            }
+        };
+        if let Some(id) = attr[0].as_str().filter(|id| !id.is_empty()) {
+            self.resolver.add(id, source.clone());
+        }
+
+        document.draw(document.layout_size(), Context::default(), &mut self.drawer);
+        let svg = self.drawer.finalize().expect("documents have elements").to_string();
+        if self.html {
+            return Some(json!({ "t": "RawBlock", "c": ["html", svg] }));
+        }
+
+        self.image_count += 1;
+        let path = format!("{IMAGE_DIRECTORY}/circmark-{}.svg", self.image_count);
+        std::fs::create_dir_all(IMAGE_DIRECTORY).expect("create image directory");
+        std::fs::write(&path, svg).expect("write image");
+        let description = describe::describe(&document);
+        Some(json!({
+            "t": "Para",
+            "c": [{
+                "t": "Image",
+                "c": [attr, [{ "t": "Str", "c": description }], [path, ""]],
+            }],
+        }))
+    }
+}
+
+fn main() {
+    // pandoc passes the output format as the first argument
+    let format = std::env::args().nth(1).unwrap_or_default();
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).expect("read");
+    let mut document: Value = serde_json::from_str(&input).expect("pandoc JSON");
+
+    let mut filter = Filter {
+        html: format.starts_with("html") || format.starts_with("epub"),
+        image_count: 0,
+        resolver: Resolver::new(),
+        drawer: SvgDrawer::new(),
+    };
+    filter.walk(&mut document["blocks"]);
+    serde_json::to_writer(std::io::stdout(), &document).expect("write");
+}
@@ -0,0 +1,332 @@
+//! Bills of materials, listing the parts needed to build circuits with identical parts counted together.
+//!
+//! Sources (including controlled ones) and generic impedances are idealizations rather than parts, so
+//! they are left out.
+//!
+//! A bill kept elsewhere, e.g. the one a board is built from, can be compared to the circuits with
+//! [`cross_check`], to keep figures in documentation in sync with the real build.
+//!
+//! ```
+//! use circmark_parse::{bom::Bom, circuit};
+//! let document = circuit::document("|V1-R1=1k|C1=100n-R2=1k|C2=100n-R3=2.2k").unwrap();
+//! let bom = Bom::new([&document]);
+//! assert_eq!(bom.to_csv(), "Quantity,Designators,Part,Value\n2,R1 R2,resistor,1kΩ\n2,C1 C2,capacitor,100nF\n1,R3,resistor,2.2kΩ\n");
+//! ```
+
+use nom::error::VerboseError;
+use crate::{
+    circuit::{self, Document, Element, Spice, SubCircuit, SubCircuitGroup, TwoportLink, Value},
+    format::{self, Locale},
+    legend,
+};
+
+/// Units of the values of kinds of elements, by designator prefix, for values written without one
+const UNITS: &[(&str, &str)] = &[
+    ("R", "Ω"),
+    ("P", "Ω"),
+    ("NTC", "Ω"),
+    ("C", "F"),
+    ("L", "H"),
+    ("Dz", "V"),
+    ("XTAL", "Hz"),
+    ("FUSE", "A"),
+];
+
+/// Names of the column listing designators in the bills of [`cross_check`], in lower case
+const DESIGNATOR_COLUMNS: &[&str] = &["designator", "designators", "reference", "references", "refs", "ref", "customer reference"];
+
+/// Relative difference below which values are the same, allowing for rounding
+const VALUE_TOLERANCE: f64 = 1e-9;
+
+/// A part of a circuit as collected for a bill
+struct Part {
+    designator: String,
+    part: String,
+    /// The value as written on the bill
+    value: Option<String>,
+    number: Option<f64>,
+}
+
+/// A line of the bill, i.e. one kind of part with one value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// Labels of the elements, in the order they appear in, e.g. `R1` and `R2`
+    pub designators: Vec<String>,
+    /// What the part is, e.g. `resistor`, named after [`legend::ENGLISH`]
+    pub part: String,
+    /// The value with its unit, e.g. `4.7kΩ`
+    pub value: Option<String>,
+}
+
+/// Bill of materials, with one item per distinct part in the order of their first appearance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bom {
+    pub items: Vec<Item>,
+}
+
+impl Bom {
+    /// Collects the parts of the documents. Elements with a label seen before, e.g. in a twoport extending
+    /// another one, are the same part and counted once.
+    pub fn new<'a, 'b: 'a>(documents: impl IntoIterator<Item = &'a Document<'b>>) -> Self {
+        let mut items: Vec<Item> = vec![];
+        for Part { designator, part, value, .. } in parts(documents) {
+            match items.iter_mut().find(|item| item.part == part && item.value == value) {
+                Some(item) => item.designators.push(designator),
+                None => items.push(Item { designators: vec![designator], part, value }),
+            }
+        }
+        Bom { items }
+    }
+
+    /// Writes the bill as CSV, with the quantity, designators (separated by spaces), part and value of each item
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("Quantity,Designators,Part,Value\n");
+        for item in &self.items {
+            csv.push_str(&format!("{},{},{},{}\n", item.designators.len(), field(&item.designators.join(" ")), field(&item.part), field(item.value.as_deref().unwrap_or_default())));
+        }
+        csv
+    }
+
+    /// Writes the bill in the CSV format of KiCad's BOM export, grouped by value
+    pub fn to_kicad(&self) -> String {
+        let mut csv = String::from("\"Refs\",\"Value\",\"Footprint\",\"Qty\",\"DNP\"\n");
+        for item in &self.items {
+            csv.push_str(&format!("{},{},\"\",\"{}\",\"\"\n", quote(&item.designators.join(",")), quote(item.value.as_deref().unwrap_or_default()), item.designators.len()));
+        }
+        csv
+    }
+
+    /// Writes the bill as CSV for uploading to Digi-Key's BOM manager, describing each part by its kind and
+    /// value in ASCII (e.g. `resistor 4.7kOhm`), with the designators as customer reference
+    pub fn to_digikey(&self) -> String {
+        let mut csv = String::from("Quantity,Description,Customer Reference\n");
+        for item in &self.items {
+            let description = match &item.value {
+                Some(value) => format!("{} {}", item.part, value.replace('µ', "u").replace('Ω', "Ohm")),
+                None => item.part.clone(),
+            };
+            csv.push_str(&format!("{},{},{}\n", item.designators.len(), field(&description), field(&item.designators.join(" "))));
+        }
+        csv
+    }
+}
+
+/// A field of CSV, quoted if it contains a separator, a quote or a line break, e.g. `1,000Ω` in German
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        quote(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A field of CSV in double quotes, doubling those it contains
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// A difference between circuits and a bill, found by [`cross_check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A part of the circuits is not on the bill
+    Missing { designator: String },
+    /// A designator on the bill is not part of the circuits
+    Unknown { designator: String },
+    /// The part has different values in the circuits and on the bill, as written there
+    Value { designator: String, circuit: String, bill: String },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::Missing { designator } => write!(f, "{designator} is not on the bill of materials"),
+            Mismatch::Unknown { designator } => write!(f, "{designator} is on the bill of materials but not in the circuit"),
+            Mismatch::Value { designator, circuit, bill } => write!(f, "{designator} is {circuit} in the circuit but {bill:?} on the bill of materials"),
+        }
+    }
+}
+
+/// Error returned by [`cross_check`] for a bill without a column of designators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoDesignatorColumn;
+
+impl std::fmt::Display for NoDesignatorColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the bill of materials has no column of designators, expected one named e.g. Designator or Refs")
+    }
+}
+
+impl std::error::Error for NoDesignatorColumn {}
+
+/// Compares the parts of the documents to a bill of materials in CSV, e.g. one exported by an EDA tool.
+///
+/// The bill needs a column of designators, named e.g. `Designator`, `Reference` or `Refs`, holding one or
+/// more designators separated by commas or spaces. Values are compared if it has a `Value` column, by number
+/// where the value parses (`4.7k` is `4.7kΩ`), and as written otherwise. Values missing on either side aren't compared.
+///
+/// ```
+/// use circmark_parse::{bom::{cross_check, Mismatch}, circuit};
+/// let document = circuit::document("|V1-R1=4.7k|C1=100n-R2").unwrap();
+/// let bill = "Refs,Value\n\"R1,R2\",4.7kΩ\nC1,10nF\nC2,1uF\n";
+/// assert_eq!(cross_check([&document], bill).unwrap(), [
+///     Mismatch::Value { designator: "C1".to_string(), circuit: "100nF".to_string(), bill: "10nF".to_string() },
+///     Mismatch::Unknown { designator: "C2".to_string() },
+/// ]);
+/// ```
+pub fn cross_check<'a, 'b: 'a>(documents: impl IntoIterator<Item = &'a Document<'b>>, csv: &str) -> Result<Vec<Mismatch>, NoDesignatorColumn> {
+    let mut rows = csv.lines().filter(|line| !line.trim().is_empty()).map(csv_fields);
+    let header = rows.next().ok_or(NoDesignatorColumn)?;
+    let column = |names: &[&str]| header.iter().position(|name| names.contains(&name.trim().to_lowercase().as_str()));
+    let designator_column = column(DESIGNATOR_COLUMNS).ok_or(NoDesignatorColumn)?;
+    let value_column = column(&["value"]);
+    // the designators of the bill with their values, in order
+    let mut bill: Vec<(String, Option<String>)> = vec![];
+    for row in rows {
+        let value = value_column.and_then(|column| row.get(column)).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        let designators = row.get(designator_column).map_or("", String::as_str);
+        for designator in designators.split([',', ' ', ';']).filter(|designator| !designator.is_empty()) {
+            bill.push((designator.to_string(), value.clone()));
+        }
+    }
+    let parts = parts(documents);
+    let mut mismatches = vec![];
+    for part in &parts {
+        match bill.iter().find(|(designator, _)| *designator == part.designator) {
+            None => mismatches.push(Mismatch::Missing { designator: part.designator.clone() }),
+            Some((_, Some(bill_value))) => if let Some(value) = &part.value {
+                let number = circuit::value::<VerboseError<&str>>(bill_value).ok()
+                    .filter(|(rest, _)| rest.is_empty())
+                    .map(|(_, value)| value.number);
+                let same = match (part.number, number) {
+                    (Some(a), Some(b)) => (a - b).abs() <= VALUE_TOLERANCE * a.abs().max(b.abs()),
+                    _ => value == bill_value,
+                };
+                if !same {
+                    mismatches.push(Mismatch::Value { designator: part.designator.clone(), circuit: value.clone(), bill: bill_value.clone() });
+                }
+            },
+            Some((_, None)) => {}
+        }
+    }
+    for (designator, _) in &bill {
+        if !parts.iter().any(|part| part.designator == *designator) {
+            mismatches.push(Mismatch::Unknown { designator: designator.clone() });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Splits a line of CSV into its fields, which may be quoted (with `""` for a quote)
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// The parts of the documents, each once
+fn parts<'a, 'b: 'a>(documents: impl IntoIterator<Item = &'a Document<'b>>) -> Vec<Part> {
+    let mut parts = vec![];
+    for document in documents {
+        match document {
+            Document::Circuit(circuit) => collect(circuit, &mut parts),
+            Document::Twoport(twoport) => for link in &twoport.links {
+                match link {
+                    TwoportLink::Series(circuit) | TwoportLink::Shunt(circuit) => collect(circuit, &mut parts),
+                    TwoportLink::Transformer { ratio, .. } => parts.push(Part {
+                        designator: link.label().unwrap(),
+                        part: "transformer".to_string(),
+                        value: ratio.as_ref().map(|ratio| format!("n={}", format::number(ratio.number, &Locale::ASCII))),
+                        number: None,
+                    }),
+                    TwoportLink::Node(_) | TwoportLink::Annotation { .. } => {}
+                }
+            },
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    parts.retain(|part| seen.insert(part.designator.clone()));
+    parts
+}
+
+/// Adds the parts of the sub-circuit
+fn collect(circuit: &SubCircuit, parts: &mut Vec<Part>) {
+    match circuit {
+        SubCircuit::Element(element, value) => {
+            let kind = element.kind();
+            let idealized = matches!(kind.spice, Spice::Source | Spice::VoltageControlled | Spice::CurrentControlled) || matches!(element, Element::Z(_));
+            let Some(prefix) = element.prefix().filter(|_| !idealized) else {
+                return;
+            };
+            let part = legend::ENGLISH.iter().find(|(name_prefix, _)| *name_prefix == prefix).map_or(prefix, |(_, name)| name);
+            let formatted = value.as_ref().map(|Value { number, unit }| {
+                let unit = if unit.is_empty() { UNITS.iter().find(|(unit_prefix, _)| *unit_prefix == prefix).map_or("", |(_, unit)| unit) } else { unit };
+                format::si(*number, unit, &Locale::default())
+            });
+            parts.push(Part { designator: element.label(), part: part.to_string(), value: formatted, number: value.as_ref().map(|value| value.number) });
+        }
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => collect(circuit, parts),
+            SubCircuitGroup::Series(a, b) | SubCircuitGroup::Parallel(a, b) => {
+                collect(a, parts);
+                collect(b, parts);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_parts() {
+        let documents = [circuit::document("|V1=5V-R1=4.7k|Egain(V1)=10-(S1+LED1)=T1(n=10)|Z1").unwrap(), circuit::document("|V1=5V-R1=4.7k|C1=1u").unwrap()];
+        let bom = Bom::new(&documents);
+        let summary: Vec<_> = bom.items.iter().map(|item| (item.designators.join(" "), item.part.as_str(), item.value.as_deref())).collect();
+        assert_eq!(summary, [
+            ("R1".to_string(), "resistor", Some("4.7kΩ")),
+            ("S1".to_string(), "switch", None),
+            ("LED1".to_string(), "light emitting diode", None),
+            ("T1".to_string(), "transformer", Some("n=10")),
+            ("C1".to_string(), "capacitor", Some("1µF")),
+        ]);
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let document = circuit::document("|V1-R1=4.7k|C1=100n-R2=1k=T1(n=2)|L1").unwrap();
+        let bill = "\"Qty\",\"Reference\",\"Value\"\n2,\"R1, R2\",\"4.7kOhm\"\n1,C1,100nF\n1,T1,n=3\n1,L1,\n";
+        assert_eq!(cross_check([&document], bill).unwrap(), [
+            Mismatch::Value { designator: "R2".to_string(), circuit: "1kΩ".to_string(), bill: "4.7kOhm".to_string() },
+            Mismatch::Value { designator: "T1".to_string(), circuit: "n=2".to_string(), bill: "n=3".to_string() },
+        ]);
+        assert_eq!(cross_check([&document], "Designator\nR1 R2 C1\n").unwrap(), [
+            Mismatch::Missing { designator: "T1".to_string() },
+            Mismatch::Missing { designator: "L1".to_string() },
+        ]);
+        assert_eq!(cross_check([&document], "Part,Value\nR1,1k\n"), Err(NoDesignatorColumn));
+    }
+
+    #[test]
+    fn test_formats() {
+        let bom = Bom::new([&circuit::document("(R1=4.7k+R2=4.7kΩ||C1=1u+D1)").unwrap()]);
+        assert_eq!(bom.to_kicad(), "\"Refs\",\"Value\",\"Footprint\",\"Qty\",\"DNP\"\n\"R1,R2\",\"4.7kΩ\",\"\",\"2\",\"\"\n\"C1\",\"1µF\",\"\",\"1\",\"\"\n\"D1\",\"\",\"\",\"1\",\"\"\n");
+        assert_eq!(bom.to_digikey(), "Quantity,Description,Customer Reference\n2,resistor 4.7kOhm,R1 R2\n1,capacitor 1uF,C1\n1,diode,D1\n");
+        let bom = Bom::new([&circuit::document("(R1=999.9995+R2=999.9995T)").unwrap()]);
+        assert_eq!(bom.to_csv(), "Quantity,Designators,Part,Value\n1,R1,resistor,1kΩ\n1,R2,resistor,\"1,000TΩ\"\n");
+        assert_eq!(quote("4\" disc"), "\"4\"\" disc\"");
+    }
+}
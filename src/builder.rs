@@ -0,0 +1,259 @@
+//! Fluent construction of circuits, as an alternative to nesting the syntax tree by hand.
+//!
+//! ```
+//! use circmark_parse::{builder::{c, open, r, v}, circuit::{self, Twoport}};
+//! let twoport = Twoport::builder()
+//!     .shunt(v("1").value(5.0, "V"))
+//!     .series(r("1").series(c("1")))
+//!     .shunt(open())
+//!     .build();
+//! assert_eq!(circuit::twoport::<nom::error::VerboseError<&str>>("|V1=5V-(R1+C1)|O").unwrap().1, twoport);
+//! ```
+
+use crate::circuit::{Element, ElementKind, Polarity, Quantity, SubCircuit, SubCircuitGroup, Twoport, TwoportLink, Value};
+
+/// Resistor, e.g. `r("1")` for `R1`
+pub fn r(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::R(id), None)
+}
+
+/// Capacitor, e.g. `c("1")` for `C1`
+pub fn c(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::C(id), None)
+}
+
+/// Voltage source without polarity marks, e.g. `v("1")` for `V1`, see [`SubCircuit::polarity`]
+pub fn v(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::V { id, polarity: Polarity::Unmarked }, None)
+}
+
+/// Inductor, e.g. `l("1")` for `L1`
+pub fn l(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::L(id), None)
+}
+
+/// Impedance, e.g. `z("1")` for `Z1`
+pub fn z(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Z(id), None)
+}
+
+/// Current source without an arrow, e.g. `i("1")` for `I1`, see [`SubCircuit::polarity`]
+pub fn i(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::I { id, polarity: Polarity::Unmarked }, None)
+}
+
+/// Diode conducting to its second end, e.g. `d("1")` for `D1`, see [`SubCircuit::polarity`]
+pub fn d(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::D { id, polarity: Polarity::Unmarked }, None)
+}
+
+/// Zener diode conducting to its second end, e.g. `dz("1")` for `Dz1`, see [`SubCircuit::polarity`]
+pub fn dz(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Dz { id, polarity: Polarity::Unmarked }, None)
+}
+
+/// Switch, e.g. `s("1", false)` for `S1` and `s("1", true)` for the closed `S1*`
+pub fn s(id: &str, closed: bool) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Switch { id, closed }, None)
+}
+
+/// Potentiometer, e.g. `p("1", Some("out"))` for `P1>out` with its wiper at the node `out`
+pub fn p<'a>(id: &'a str, wiper: Option<&'a str>) -> SubCircuit<'a> {
+    SubCircuit::Element(Element::P { id, wiper }, None)
+}
+
+/// Light emitting diode conducting to its second end, e.g. `led("1")` for `LED1`, see [`SubCircuit::polarity`]
+pub fn led(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Led { id, polarity: Polarity::Unmarked }, None)
+}
+
+/// Voltage controlled voltage source, e.g. `e("1", "R1")` for `E1(R1)`
+pub fn e<'a>(id: &'a str, control: &'a str) -> SubCircuit<'a> {
+    SubCircuit::Element(Element::E { id, control }, None)
+}
+
+/// Voltage controlled current source, e.g. `g("1", "R1")` for `G1(R1)`
+pub fn g<'a>(id: &'a str, control: &'a str) -> SubCircuit<'a> {
+    SubCircuit::Element(Element::G { id, control }, None)
+}
+
+/// Current controlled voltage source, e.g. `h("1", "V1")` for `H1(V1)`
+pub fn h<'a>(id: &'a str, control: &'a str) -> SubCircuit<'a> {
+    SubCircuit::Element(Element::H { id, control }, None)
+}
+
+/// Current controlled current source, e.g. `f("1", "V1")` for `F1(V1)`
+pub fn f<'a>(id: &'a str, control: &'a str) -> SubCircuit<'a> {
+    SubCircuit::Element(Element::F { id, control }, None)
+}
+
+/// Thermistor, e.g. `ntc("1")` for `NTC1`
+pub fn ntc(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Ntc(id), None)
+}
+
+/// Quartz crystal, e.g. `xtal("1")` for `XTAL1`
+pub fn xtal(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Xtal(id), None)
+}
+
+/// Fuse, e.g. `fuse("1")` for `FUSE1`
+pub fn fuse(id: &str) -> SubCircuit<'_> {
+    SubCircuit::Element(Element::Fuse(id), None)
+}
+
+/// Element of a kind, e.g. one returned by [`register`](crate::circuit::register), without a reference
+pub fn custom<'a>(kind: &ElementKind, id: &'a str) -> SubCircuit<'a> {
+    SubCircuit::Element((kind.make)(kind.prefix, id, ""), None)
+}
+
+/// Open circuit, written `O`
+pub fn open() -> SubCircuit<'static> {
+    SubCircuit::Element(Element::Open, None)
+}
+
+impl<'a> SubCircuit<'a> {
+    /// Sets the value of an element, e.g. `r("1").value(4.7e3, "Ω")` for `R1=4.7kΩ`.
+    ///
+    /// Only elements have values, so groups are returned as they are.
+    pub fn value(self, number: f64, unit: &'a str) -> Self {
+        match self {
+            SubCircuit::Element(element, _) => SubCircuit::Element(element, Some(Value { number, unit })),
+            group => group,
+        }
+    }
+
+    /// Marks which way a source or diode points, e.g. `v("1").polarity(Polarity::Up)` for `^V1`.
+    ///
+    /// Only sources and diodes (including LEDs) have a polarity, so other elements and groups are returned as they are.
+    pub fn polarity(self, polarity: Polarity) -> Self {
+        let SubCircuit::Element(element, value) = self else {
+            return self;
+        };
+        let element = match element {
+            Element::V { id, .. } => Element::V { id, polarity },
+            Element::I { id, .. } => Element::I { id, polarity },
+            Element::D { id, .. } => Element::D { id, polarity },
+            Element::Dz { id, .. } => Element::Dz { id, polarity },
+            Element::Led { id, .. } => Element::Led { id, polarity },
+            element => element,
+        };
+        SubCircuit::Element(element, value)
+    }
+
+    /// Connects `other` in series to the right.
+    ///
+    /// Chains like `a.series(b).series(c)` are grouped like `(a+b+c)` is parsed, i.e. as `a+(b+c)`.
+    pub fn series(self, other: SubCircuit<'a>) -> Self {
+        match self {
+            SubCircuit::Group(group) => match *group {
+                SubCircuitGroup::Series(left, right) => SubCircuitGroup::Series(left, right.series(other)).into(),
+                group => SubCircuitGroup::Series(group.into(), other).into(),
+            },
+            element => SubCircuitGroup::Series(element, other).into(),
+        }
+    }
+
+    /// Connects `other` in parallel below.
+    ///
+    /// Chains like `a.parallel(b).parallel(c)` are grouped like `(a||b||c)` is parsed, i.e. as `a||(b||c)`.
+    pub fn parallel(self, other: SubCircuit<'a>) -> Self {
+        match self {
+            SubCircuit::Group(group) => match *group {
+                SubCircuitGroup::Parallel(top, bottom) => SubCircuitGroup::Parallel(top, bottom.parallel(other)).into(),
+                group => SubCircuitGroup::Parallel(group.into(), other).into(),
+            },
+            element => SubCircuitGroup::Parallel(element, other).into(),
+        }
+    }
+}
+
+/// Builds a twoport link by link, see [`Twoport::builder`]
+#[derive(Default)]
+pub struct TwoportBuilder<'a> {
+    links: Vec<TwoportLink<'a>>,
+}
+
+impl<'a> TwoportBuilder<'a> {
+    pub fn series(mut self, circuit: SubCircuit<'a>) -> Self {
+        self.links.push(TwoportLink::Series(circuit));
+        self
+    }
+
+    pub fn shunt(mut self, circuit: SubCircuit<'a>) -> Self {
+        self.links.push(TwoportLink::Shunt(circuit));
+        self
+    }
+
+    /// Adds an ideal transformer with the turns ratio `n:1`, see [`TwoportLink::Transformer`]
+    pub fn transformer(mut self, id: &'a str, ratio: Option<f64>) -> Self {
+        self.links.push(TwoportLink::Transformer { id, ratio: ratio.map(|number| Value { number, unit: "" }) });
+        self
+    }
+
+    /// Names the node on the top line at this point, see [`TwoportLink::Node`]
+    pub fn node(mut self, name: &'a str) -> Self {
+        self.links.push(TwoportLink::Node(name));
+        self
+    }
+
+    /// Annotates the current or voltage at this point, see [`TwoportLink::Annotation`]
+    pub fn annotate(mut self, quantity: Quantity, name: &'a str) -> Self {
+        self.links.push(TwoportLink::Annotation { quantity, name });
+        self
+    }
+
+    pub fn build(self) -> Twoport<'a> {
+        Twoport { links: self.links }
+    }
+}
+
+impl<'a> Twoport<'a> {
+    pub fn builder() -> TwoportBuilder<'a> {
+        TwoportBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    fn parse(input: &'static str) -> SubCircuit<'static> {
+        circuit::sub_circuit::<E>(input).unwrap().1
+    }
+
+    #[test]
+    fn test_sub_circuits() {
+        assert_eq!(r("1").series(c("1")).series(l("1")), parse("(R1+C1+L1)"));
+        assert_eq!(r("1").parallel(c("1")).parallel(l("1")), parse("(R1||C1||L1)"));
+        assert_eq!(r("1").series(c("1").parallel(l("1"))), parse("(R1+C1||L1)"));
+        assert_eq!(r("1").parallel(c("1")).series(l("1")), parse("(R1||C1+L1)"));
+        assert_eq!(r("1").series(c("1")).parallel(l("1")), parse("((R1+C1)||L1)"));
+        assert_eq!(z("1").series(i("1")).series(d("1").parallel(dz("1").value(5.1, "V"))), parse("(Z1+I1+D1||Dz1=5.1V)"));
+    }
+
+    #[test]
+    fn test_elements() {
+        assert_eq!(s("1", true).series(s("2", false)).series(p("1", Some("out"))).series(p("2", None)), parse("(S1*+S2+P1>out+P2)"));
+        assert_eq!(e("1", "R1").parallel(g("1", "R1")).parallel(h("1", "V1")).parallel(f("1", "V1")), parse("(E1(R1)||G1(R1)||H1(V1)||F1(V1))"));
+        assert_eq!(led("1").series(ntc("1")).series(xtal("1")).series(fuse("1")), parse("(LED1+NTC1+XTAL1+FUSE1)"));
+        let kind = circuit::register(ElementKind::custom("TB", circuit::Symbol::Custom("test"), circuit::Impedance::Short, circuit::Spice::Omitted));
+        assert_eq!(custom(kind, "1").value(2.0, ""), parse("TB1=2"));
+    }
+
+    #[test]
+    fn test_polarity() {
+        assert_eq!(v("1").polarity(Polarity::Up).series(i("1").polarity(Polarity::Down)), parse("(^V1+vI1)"));
+        assert_eq!(d("1").value(0.7, "V").polarity(Polarity::Up).series(led("1").polarity(Polarity::Down)), parse("(^D1=0.7V+vLED1)"));
+        assert_eq!(dz("1").polarity(Polarity::Up).series(r("1").polarity(Polarity::Up)), parse("(^Dz1+R1)"));
+        assert_eq!(r("1").series(c("1")).polarity(Polarity::Up), parse("(R1+C1)"));
+    }
+
+    #[test]
+    fn test_value_of_group() {
+        assert_eq!(r("1").series(c("1")).value(1.0, "Ω"), parse("(R1+C1)"));
+    }
+}
@@ -1,44 +1,64 @@
 use nom::{
     IResult,
-    multi::many1,
+    multi::{many0_count, many1},
     branch::alt,
-    combinator::map,
-    sequence::{preceded, delimited, separated_pair},
-    bytes::complete::tag,
-    character::complete::alphanumeric1,
-    error::{context, ContextError, ParseError, VerboseError},
+    combinator::{map, opt, recognize},
+    sequence::{pair, preceded, delimited, separated_pair, terminated, tuple},
+    bytes::complete::{tag, take_until, take_while},
+    character::complete::{alphanumeric1, digit1, char, multispace1, not_line_ending, one_of},
+    error::{context, ContextError, ErrorKind, ParseError, VerboseError},
 };
 
+use std::sync::RwLock;
+
+pub mod owned;
+mod print;
+mod scan;
+
+pub use scan::{validate_only, Diagnostics};
+pub(crate) use scan::{element_spans, spans};
+
 /// A twoport is an arrangement of series and shunt elements in a signal path.
 ///
 /// For example a voltage divider would be a twoport with three links:
 /// - a shunt voltage source
 /// - series resistance R1
 /// - shunt resistance R2
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(bound(deserialize = "'de: 'a")))]
 pub struct Twoport<'a> {
     pub links: Vec<TwoportLink<'a>>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(bound(deserialize = "'de: 'a")))]
 pub enum TwoportLink<'a> {
     Series(SubCircuit<'a>),
     Shunt(SubCircuit<'a>),
+    /// Ideal transformer with the turns ratio `n:1`, e.g. `=T1(n=10)`, dividing the voltage by `n`
+    Transformer { id: &'a str, ratio: Option<Value<'a>> },
+    /// Name of the node on the top line where it appears, e.g. `.vout`
+    Node(&'a str),
+    /// Arrow annotating the quantity at the point where it appears, e.g. `{i:i1}` for the current through
+    /// the top line or `{u:u2}` for the voltage between the lines
+    Annotation { quantity: Quantity, name: &'a str },
 }
 
 /// A sub-circuit consists of either an element, or any series/parallel arrangement of elements.
 ///
 /// Sub-circuits have two legs, just like an element.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(bound(deserialize = "'de: 'a")))]
 pub enum SubCircuit<'a> {
-    /// Single element, e.g. `R1`
-    Element(Element<'a>),
+    /// Single element, e.g. `R1`, with an optional value, e.g. `R1=4.7k`
+    Element(Element<'a>, Option<Value<'a>>),
     /// Multiple elements, e.g. `(R1||R2)`
     Group(Box<SubCircuitGroup<'a>>),
 }
 
 /// Represents an arrangement of a group of sub-circuits
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(bound(deserialize = "'de: 'a")))]
 pub enum SubCircuitGroup<'a> {
     /// A single subcircuit
     Single(SubCircuit<'a>),
@@ -49,105 +69,682 @@ pub enum SubCircuitGroup<'a> {
 }
 
 /// A single circuit element
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Element<'a> {
     /// Resistance
     R(&'a str),
     /// Capacitance
     C(&'a str),
-    /// Voltage source
-    V(&'a str),
+    /// Voltage source, e.g. `V1`, or `^V1` with its positive terminal marked at its first end
+    V { id: &'a str, polarity: Polarity },
     /// Inductance
     L(&'a str),
     /// Impedance
     Z(&'a str),
-    /// Current source
-    I(&'a str),
+    /// Current source, e.g. `I1`, or `^I1` with an arrow towards its first end
+    I { id: &'a str, polarity: Polarity },
+    /// Diode, conducting from left to right (or top to bottom), or the other way for `^D1`
+    D { id: &'a str, polarity: Polarity },
+    /// Zener diode, conducting from left to right (or top to bottom), or the other way for `^Dz1`
+    Dz { id: &'a str, polarity: Polarity },
+    /// Ideal switch, written `S1` when open and `S1*` when closed
+    Switch { id: &'a str, closed: bool },
+    /// Voltage controlled voltage source, e.g. `Egain(R1)` for the voltage across `R1` times the gain
+    E { id: &'a str, control: &'a str },
+    /// Voltage controlled current source
+    G { id: &'a str, control: &'a str },
+    /// Current controlled voltage source, controlled by the current through a voltage source, e.g. `H1(V1)`
+    H { id: &'a str, control: &'a str },
+    /// Current controlled current source, controlled by the current through a voltage source
+    F { id: &'a str, control: &'a str },
+    /// Potentiometer, whose wiper can be connected to a named node, e.g. `P1>out`
+    P { id: &'a str, wiper: Option<&'a str> },
+    /// Light emitting diode, conducting from left to right (or top to bottom), e.g. `LED1`, or the other way for
+    /// `^LED1`
+    Led { id: &'a str, polarity: Polarity },
+    /// Thermistor with a negative temperature coefficient, e.g. `NTC1`
+    Ntc(&'a str),
+    /// Quartz crystal, e.g. `XTAL1`
+    Xtal(&'a str),
+    /// Fuse, e.g. `FUSE1`
+    Fuse(&'a str),
+    /// Element of a kind added with [`register`]
+    Custom { prefix: &'a str, id: &'a str },
     /// Open circuit
     Open,
 }
 
+/// Value of an element, e.g. `4.7k` or `100nF`
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Value<'a> {
+    /// The value in base units, with the SI prefix applied (`100n` is `1e-7`)
+    pub number: f64,
+    /// Unit following the SI prefix, as written (may be empty)
+    pub unit: &'a str,
+}
+
+impl std::fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&crate::format::si(self.number, self.unit, &crate::format::Locale::default()))
+    }
+}
+
 /// A circmark document.
 ///
 /// Currently either two-ended circuit (parallel/series arrangement), or a twoport network.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(bound(deserialize = "'de: 'a")))]
 pub enum Document<'a> {
     Circuit(SubCircuit<'a>),
     Twoport(Twoport<'a>),
 }
 
-impl Element<'_> {
+/// How elements of a kind are drawn, each symbol having a [`crate::draw::Drawer`] method
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    Resistor,
+    Capacitor,
+    Inductor,
+    VoltageSource,
+    CurrentSource,
+    Diode,
+    ZenerDiode,
+    Switch { closed: bool },
+    /// Diamond with a `+` and `-` (voltage) or an arrow (current)
+    ControlledSource { current: bool },
+    /// Box with an arrow for the wiper
+    Potentiometer,
+    /// Diode with two arrows pointing away from it
+    Led,
+    /// Box crossed by a diagonal line
+    Thermistor,
+    /// Box between two plates
+    Crystal,
+    /// Box with the wire running through it
+    Fuse,
+    /// A symbol of kinds added with [`register`], drawn by [`crate::draw::Drawer::custom`] with the given name
+    Custom(&'static str),
+    Open,
+}
+
+/// Impedance of elements of a kind, as used by [`crate::analysis`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Impedance {
+    /// Frequency independent, named after the element's label
+    Resistive,
+    /// `sL`
+    Inductive,
+    /// `1/(sC)`
+    Capacitive,
+    /// Zero, e.g. for ideal voltage sources
+    Short,
+    /// Infinite, e.g. for ideal current sources
+    Open,
+}
+
+/// What an annotation arrow stands for, see [`TwoportLink::Annotation`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Quantity {
+    /// Current flowing to the right along the top line, written `i`
+    Current,
+    /// Voltage from the top line to the bottom line, written `u`
+    Voltage,
+}
+
+impl Quantity {
+    /// The letter the quantity is written with, e.g. `i` in `{i:i1}`
+    pub fn letter(self) -> char {
+        match self {
+            Quantity::Current => 'i',
+            Quantity::Voltage => 'u',
+        }
+    }
+}
+
+/// Which way a source or diode is marked to point, written before it as `^` or `v`.
+///
+/// Elements run from their first end (the top of shunt links and the left of series links) to their
+/// second end. Voltage sources are marked with `+` and `−` signs, the `+` at the end they point to,
+/// and current sources with an arrow pointing in the direction of the current. Diodes point (and conduct) to
+/// their second end unless marked otherwise.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Polarity {
+    /// Drawn without marks
+    #[default]
+    Unmarked,
+    /// Pointing to the first end, e.g. `^V1`
+    Up,
+    /// Pointing to the second end, e.g. `vV1`
+    Down,
+}
+
+/// How elements of a kind appear in SPICE netlists, see [`crate::netlist`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Spice {
+    /// Element with a value, the name being the label with the given prefix prepended
+    Passive(&'static str),
+    /// Independent DC source
+    Source,
+    /// Diode with the default model
+    Diode,
+    /// Diode with the value as breakdown voltage
+    ZenerDiode,
+    /// Voltage controlled switch, held in place by a DC control source
+    Switch { closed: bool },
+    /// Source controlled by the voltage across the controlling element, the value being the gain
+    VoltageControlled,
+    /// Source controlled by the current through the controlling voltage source, the value being the gain
+    CurrentControlled,
+    /// Two resistors of half the value, joined at the wiper
+    Potentiometer,
+    /// Voltage source of zero volts, i.e. a short circuit
+    Short,
+    /// Not part of the netlist
+    Omitted,
+}
+
+/// Another element or a node an element refers to, see [`ElementKind::reference`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reference {
+    None,
+    /// The controlling element in parentheses, e.g. `Egain(V1)`
+    Control,
+    /// Optionally the node connected to the wiper, e.g. `P1>out`
+    Wiper,
+}
+
+/// Describes a kind of element: how it is written, drawn, analyzed and exported
+#[derive(Debug, Copy, Clone)]
+pub struct ElementKind {
+    /// Written before the prefix, e.g. `^` for sources pointing to their first end
+    pub marker: &'static str,
+    /// Letter(s) the element starts with, e.g. `R`
+    pub prefix: &'static str,
+    /// Whether the prefix is followed by an id (all elements except the open circuit)
+    pub has_id: bool,
+    /// Written after the id, e.g. `*` for closed switches
+    pub suffix: &'static str,
+    /// What is written after the id (and suffix)
+    pub reference: Reference,
+    pub symbol: Symbol,
+    pub impedance: Impedance,
+    pub spice: Spice,
+    /// Builds an element of this kind from its prefix, its id and what it refers to (empty if there is no reference)
+    pub make: for<'a> fn(&'static str, &'a str, &'a str) -> Element<'a>,
+}
+
+impl ElementKind {
+    /// Describes a kind of element for [`register`], written as its prefix followed by an id
+    pub const fn custom(prefix: &'static str, symbol: Symbol, impedance: Impedance, spice: Spice) -> Self {
+        ElementKind { marker: "", prefix, has_id: true, suffix: "", reference: Reference::None, symbol, impedance, spice, make: |prefix, id, _| Element::Custom { prefix, id } }
+    }
+}
+
+/// All built-in kinds of elements.
+///
+/// Adding a kind of element means adding an [`Element`] variant and an entry here,
+/// plus a drawer method if it needs a new [`Symbol`]. Kinds of elements that are not built in can be
+/// added with [`register`] instead.
+pub static ELEMENT_KINDS: &[ElementKind] = &[
+    ElementKind { marker: "", prefix: "R", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Resistor, impedance: Impedance::Resistive, spice: Spice::Passive(""), make: |_, id, _| Element::R(id) },
+    ElementKind { marker: "", prefix: "C", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Capacitor, impedance: Impedance::Capacitive, spice: Spice::Passive(""), make: |_, id, _| Element::C(id) },
+    ElementKind { marker: "", prefix: "V", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::VoltageSource, impedance: Impedance::Short, spice: Spice::Source, make: |_, id, _| Element::V { id, polarity: Polarity::Unmarked } },
+    ElementKind { marker: "^", prefix: "V", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::VoltageSource, impedance: Impedance::Short, spice: Spice::Source, make: |_, id, _| Element::V { id, polarity: Polarity::Up } },
+    ElementKind { marker: "v", prefix: "V", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::VoltageSource, impedance: Impedance::Short, spice: Spice::Source, make: |_, id, _| Element::V { id, polarity: Polarity::Down } },
+    ElementKind { marker: "", prefix: "L", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Inductor, impedance: Impedance::Inductive, spice: Spice::Passive(""), make: |_, id, _| Element::L(id) },
+    // there is no generic impedance in SPICE, so it is simulated as a resistance, like it is drawn
+    ElementKind { marker: "", prefix: "Z", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Resistor, impedance: Impedance::Resistive, spice: Spice::Passive("R"), make: |_, id, _| Element::Z(id) },
+    ElementKind { marker: "", prefix: "I", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::CurrentSource, impedance: Impedance::Open, spice: Spice::Source, make: |_, id, _| Element::I { id, polarity: Polarity::Unmarked } },
+    ElementKind { marker: "^", prefix: "I", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::CurrentSource, impedance: Impedance::Open, spice: Spice::Source, make: |_, id, _| Element::I { id, polarity: Polarity::Up } },
+    ElementKind { marker: "v", prefix: "I", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::CurrentSource, impedance: Impedance::Open, spice: Spice::Source, make: |_, id, _| Element::I { id, polarity: Polarity::Down } },
+    // diodes are analyzed with their small signal resistance
+    ElementKind { marker: "", prefix: "D", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Diode, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::D { id, polarity: Polarity::Unmarked } },
+    ElementKind { marker: "^", prefix: "D", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Diode, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::D { id, polarity: Polarity::Up } },
+    ElementKind { marker: "v", prefix: "D", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Diode, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::D { id, polarity: Polarity::Down } },
+    ElementKind { marker: "", prefix: "Dz", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::ZenerDiode, impedance: Impedance::Resistive, spice: Spice::ZenerDiode, make: |_, id, _| Element::Dz { id, polarity: Polarity::Unmarked } },
+    ElementKind { marker: "^", prefix: "Dz", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::ZenerDiode, impedance: Impedance::Resistive, spice: Spice::ZenerDiode, make: |_, id, _| Element::Dz { id, polarity: Polarity::Up } },
+    ElementKind { marker: "v", prefix: "Dz", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::ZenerDiode, impedance: Impedance::Resistive, spice: Spice::ZenerDiode, make: |_, id, _| Element::Dz { id, polarity: Polarity::Down } },
+    ElementKind { marker: "", prefix: "S", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Switch { closed: false }, impedance: Impedance::Open, spice: Spice::Switch { closed: false }, make: |_, id, _| Element::Switch { id, closed: false } },
+    ElementKind { marker: "", prefix: "S", has_id: true, suffix: "*", reference: Reference::None, symbol: Symbol::Switch { closed: true }, impedance: Impedance::Short, spice: Spice::Switch { closed: true }, make: |_, id, _| Element::Switch { id, closed: true } },
+    // the series/parallel analysis has no notion of controlling quantities, so controlled sources are analyzed like independent ones
+    ElementKind { marker: "", prefix: "E", has_id: true, suffix: "", reference: Reference::Control, symbol: Symbol::ControlledSource { current: false }, impedance: Impedance::Short, spice: Spice::VoltageControlled, make: |_, id, control| Element::E { id, control } },
+    ElementKind { marker: "", prefix: "G", has_id: true, suffix: "", reference: Reference::Control, symbol: Symbol::ControlledSource { current: true }, impedance: Impedance::Open, spice: Spice::VoltageControlled, make: |_, id, control| Element::G { id, control } },
+    ElementKind { marker: "", prefix: "H", has_id: true, suffix: "", reference: Reference::Control, symbol: Symbol::ControlledSource { current: false }, impedance: Impedance::Short, spice: Spice::CurrentControlled, make: |_, id, control| Element::H { id, control } },
+    ElementKind { marker: "", prefix: "F", has_id: true, suffix: "", reference: Reference::Control, symbol: Symbol::ControlledSource { current: true }, impedance: Impedance::Open, spice: Spice::CurrentControlled, make: |_, id, control| Element::F { id, control } },
+    // the wiper is a third terminal, which the analysis of two-ended circuits leaves out
+    ElementKind { marker: "", prefix: "P", has_id: true, suffix: "", reference: Reference::Wiper, symbol: Symbol::Potentiometer, impedance: Impedance::Resistive, spice: Spice::Potentiometer, make: |_, id, wiper| Element::P { id, wiper: (!wiper.is_empty()).then_some(wiper) } },
+    ElementKind { marker: "", prefix: "LED", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Led, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::Led { id, polarity: Polarity::Unmarked } },
+    ElementKind { marker: "^", prefix: "LED", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Led, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::Led { id, polarity: Polarity::Up } },
+    ElementKind { marker: "v", prefix: "LED", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Led, impedance: Impedance::Resistive, spice: Spice::Diode, make: |_, id, _| Element::Led { id, polarity: Polarity::Down } },
+    ElementKind { marker: "", prefix: "NTC", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Thermistor, impedance: Impedance::Resistive, spice: Spice::Passive("R"), make: |_, id, _| Element::Ntc(id) },
+    // the value of a crystal is its frequency, away from which (and at DC) it hardly conducts
+    ElementKind { marker: "", prefix: "XTAL", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Crystal, impedance: Impedance::Open, spice: Spice::Omitted, make: |_, id, _| Element::Xtal(id) },
+    // the value of a fuse is its rated current
+    ElementKind { marker: "", prefix: "FUSE", has_id: true, suffix: "", reference: Reference::None, symbol: Symbol::Fuse, impedance: Impedance::Short, spice: Spice::Short, make: |_, id, _| Element::Fuse(id) },
+    ElementKind { marker: "", prefix: "O", has_id: false, suffix: "", reference: Reference::None, symbol: Symbol::Open, impedance: Impedance::Open, spice: Spice::Omitted, make: |_, _, _| Element::Open },
+];
+
+/// The kinds of elements the parser knows, built-in and registered, indexed by the character they start with
+struct Kinds {
+    all: Vec<&'static ElementKind>,
+    /// The kinds starting with each byte (kinds written without marker and prefix start with any), longest prefix
+    /// first, so that zener diodes (`Dz1`) are not taken for diodes with an id starting with `z`, and kinds with a
+    /// suffix before those without (`S1*` is not an open switch followed by `*`)
+    by_first_byte: Vec<Vec<&'static ElementKind>>,
+}
+
+impl Kinds {
+    /// Indexes the kinds, leaking them so that parsing reads them without holding a lock or copying them
+    fn leak(all: Vec<&'static ElementKind>) -> &'static Kinds {
+        let mut by_first_byte = vec![vec![]; 256];
+        for &kind in &all {
+            match kind.marker.bytes().chain(kind.prefix.bytes()).next() {
+                Some(byte) => by_first_byte[usize::from(byte)].push(kind),
+                None => by_first_byte.iter_mut().for_each(|kinds| kinds.push(kind)),
+            }
+        }
+        for kinds in &mut by_first_byte {
+            // stable, so that kinds written alike keep their order
+            kinds.sort_by_key(|kind| std::cmp::Reverse((kind.marker.len() + kind.prefix.len(), kind.suffix.len())));
+        }
+        Box::leak(Box::new(Kinds { all, by_first_byte }))
+    }
+}
+
+/// The built-in kinds and the ones added with [`register`], indexed on first use
+static KINDS: RwLock<Option<&'static Kinds>> = RwLock::new(None);
+
+fn kinds() -> &'static Kinds {
+    if let Some(kinds) = *KINDS.read().unwrap() {
+        return kinds;
+    }
+    KINDS.write().unwrap().get_or_insert_with(|| Kinds::leak(ELEMENT_KINDS.iter().collect()))
+}
+
+/// Adds a kind of element to the ones the parser knows, e.g. to draw thermocouples (`TC1`) as
+/// [`Symbol::Custom`] symbols.
+///
+/// Like the built-in kinds, the longest prefix matching an element wins, so registered kinds can
+/// extend the prefix of a built-in one. Registered kinds apply to all documents parsed afterwards, in the whole
+/// process: they can't be removed, and each registration keeps the kinds known so far alive for good, so kinds are
+/// best registered once, at startup.
+///
+/// ```
+/// use circmark_parse::circuit::{self, Element, ElementKind, Impedance, Spice, Symbol};
+/// circuit::register(ElementKind::custom("TC", Symbol::Custom("thermocouple"), Impedance::Short, Spice::Omitted));
+/// let document = circuit::document("|TC1-R1").unwrap();
+/// assert_eq!(document.elements()[0], &Element::Custom { prefix: "TC", id: "1" });
+/// assert_eq!(document.elements()[0].kind().symbol, Symbol::Custom("thermocouple"));
+/// ```
+pub fn register(kind: ElementKind) -> &'static ElementKind {
+    let kind = Box::leak(Box::new(kind));
+    let mut kinds = KINDS.write().unwrap();
+    // registering is rare, so the index is rebuilt (and the old one leaked) rather than shared mutably
+    let mut all = kinds.map_or_else(|| ELEMENT_KINDS.iter().collect(), |kinds| kinds.all.clone());
+    all.push(kind);
+    *kinds = Some(Kinds::leak(all));
+    kind
+}
+
+/// Describes the elements of [`Element::Custom`] kinds that were never registered, e.g. deserialized ones: an open
+/// circuit, left out of netlists and drawn as a custom symbol named `unregistered`
+pub static UNREGISTERED: ElementKind = ElementKind::custom("", Symbol::Custom("unregistered"), Impedance::Open, Spice::Omitted);
+
+/// Error of [`Element::try_kind`], for an element of a custom kind that was never registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnregisteredKind {
+    pub prefix: String,
+}
+
+impl std::fmt::Display for UnregisteredKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no kind of element with prefix {} is registered", self.prefix)
+    }
+}
+
+impl std::error::Error for UnregisteredKind {}
+
+/// The built-in kinds of elements, followed by the registered ones
+pub fn element_kinds() -> impl Iterator<Item = &'static ElementKind> {
+    kinds().all.iter().copied()
+}
+
+/// The kinds whose marker and prefix the input starts with, longest prefix (and then suffix) first
+pub(crate) fn candidate_kinds(input: &str) -> impl Iterator<Item = &'static ElementKind> + '_ {
+    let kinds = input.bytes().next().map_or(&[][..], |byte| &kinds().by_first_byte[usize::from(byte)][..]);
+    kinds.iter().copied().filter(move |kind| input.strip_prefix(kind.marker).is_some_and(|rest| rest.starts_with(kind.prefix)))
+}
+
+impl<'a> Element<'a> {
+    /// The entry of [`ELEMENT_KINDS`] (or the registered kind) describing this element.
+    ///
+    /// Custom elements whose kind was never registered are described by [`UNREGISTERED`], see [`Element::try_kind`].
+    pub fn kind(&self) -> &'static ElementKind {
+        self.try_kind().unwrap_or(&UNREGISTERED)
+    }
+
+    /// The entry of [`ELEMENT_KINDS`] (or the registered kind) describing this element, or an error for a custom
+    /// element whose kind was never registered
+    pub fn try_kind(&self) -> Result<&'static ElementKind, UnregisteredKind> {
+        // comparing whole elements (not just the variant) tells open and closed switches apart
+        let reference = self.control().or(self.wiper()).unwrap_or_default();
+        element_kinds()
+            .find(|kind| (kind.make)(kind.prefix, self.id().unwrap_or_default(), reference) == *self)
+            .ok_or_else(|| UnregisteredKind { prefix: self.custom_prefix().unwrap_or_default().to_string() })
+    }
+
+    /// The prefix of a custom element, which its kind may not know
+    fn custom_prefix(&self) -> Option<&'a str> {
+        match self {
+            Element::Custom { prefix, .. } => Some(prefix),
+            _ => None,
+        }
+    }
+
+    /// The id following the prefix, e.g. `1` for `R1`.
+    ///
+    /// Open circuits don't have an id.
+    pub fn id(&self) -> Option<&'a str> {
+        match self {
+            Element::R(id) | Element::C(id) | Element::L(id) | Element::Z(id)
+                | Element::D { id, .. } | Element::Dz { id, .. } | Element::Switch { id, .. } => Some(id),
+            Element::V { id, .. } | Element::I { id, .. } => Some(id),
+            Element::E { id, .. } | Element::G { id, .. } | Element::H { id, .. } | Element::F { id, .. } => Some(id),
+            Element::P { id, .. } => Some(id),
+            Element::Led { id, .. } | Element::Ntc(id) | Element::Xtal(id) | Element::Fuse(id) | Element::Custom { id, .. } => Some(id),
+            Element::Open => None,
+        }
+    }
+
+    /// The label of the element controlling a controlled source, e.g. `V1` for `Egain(V1)`
+    pub fn control(&self) -> Option<&'a str> {
+        match self {
+            Element::E { control, .. } | Element::G { control, .. } | Element::H { control, .. } | Element::F { control, .. } => Some(control),
+            _ => None,
+        }
+    }
+
+    /// Which way a source or diode is marked to point, [`Polarity::Unmarked`] for other elements
+    pub fn polarity(&self) -> Polarity {
+        match self {
+            Element::V { polarity, .. } | Element::I { polarity, .. } | Element::D { polarity, .. } | Element::Dz { polarity, .. }
+                | Element::Led { polarity, .. } => *polarity,
+            _ => Polarity::Unmarked,
+        }
+    }
+
+    /// The node connected to the wiper of a potentiometer, e.g. `out` for `P1>out`
+    pub fn wiper(&self) -> Option<&'a str> {
+        match self {
+            Element::P { wiper, .. } => *wiper,
+            _ => None,
+        }
+    }
+
     pub fn label(&self) -> String {
+        match self.id() {
+            Some(id) => format!("{}{id}", self.custom_prefix().unwrap_or(self.kind().prefix)),
+            None => String::new(),
+        }
+    }
+
+    /// The letter(s) designating the kind of element, e.g. `R` for resistors.
+    ///
+    /// Open circuits don't have a designator.
+    pub fn prefix(&self) -> Option<&'static str> {
+        let kind = self.kind();
+        kind.has_id.then_some(kind.prefix)
+    }
+}
+
+impl<'a> SubCircuit<'a> {
+    /// All elements of this sub-circuit, from left to right and top to bottom
+    pub fn elements(&self) -> Vec<&Element<'a>> {
+        match self {
+            SubCircuit::Element(element, _) => vec![element],
+            SubCircuit::Group(group) => match group.as_ref() {
+                SubCircuitGroup::Single(circuit) => circuit.elements(),
+                SubCircuitGroup::Series(a, b) | SubCircuitGroup::Parallel(a, b) => {
+                    let mut elements = a.elements();
+                    elements.extend(b.elements());
+                    elements
+                }
+            },
+        }
+    }
+}
+
+impl<'a> TwoportLink<'a> {
+    /// The sub-circuit of a series or shunt link, `None` for transformers, node names and annotations
+    pub fn circuit(&self) -> Option<&SubCircuit<'a>> {
+        match self {
+            TwoportLink::Series(circuit) | TwoportLink::Shunt(circuit) => Some(circuit),
+            TwoportLink::Transformer { .. } | TwoportLink::Node(_) | TwoportLink::Annotation { .. } => None,
+        }
+    }
+
+    pub fn circuit_mut(&mut self) -> Option<&mut SubCircuit<'a>> {
+        match self {
+            TwoportLink::Series(circuit) | TwoportLink::Shunt(circuit) => Some(circuit),
+            TwoportLink::Transformer { .. } | TwoportLink::Node(_) | TwoportLink::Annotation { .. } => None,
+        }
+    }
+
+    /// The label of a transformer, e.g. `T1`, which also names its turns ratio in [`crate::analysis`]
+    pub fn label(&self) -> Option<String> {
         match self {
-            Element::R(id) => format!("R{id}"),
-            Element::C(id) => format!("C{id}"),
-            Element::V(id) => format!("V{id}"),
-            Element::L(id) => format!("L{id}"),
-            Element::Z(id) => format!("Z{id}"),
-            Element::I(id) => format!("I{id}"),
-            Element::Open => format!(""),
+            TwoportLink::Transformer { id, .. } => Some(format!("T{id}")),
+            _ => None,
         }
     }
 }
 
-impl<'a> Into<SubCircuit<'a>> for SubCircuitGroup<'a> {
-    fn into(self) -> SubCircuit<'a> {
+impl<'a> Twoport<'a> {
+    /// All elements of this twoport, in the order of the links
+    pub fn elements(&self) -> Vec<&Element<'a>> {
+        self.links.iter().filter_map(TwoportLink::circuit).flat_map(SubCircuit::elements).collect()
+    }
+}
+
+impl<'a> Document<'a> {
+    /// All elements of this document
+    pub fn elements(&self) -> Vec<&Element<'a>> {
         match self {
+            Document::Circuit(circuit) => circuit.elements(),
+            Document::Twoport(twoport) => twoport.elements(),
+        }
+    }
+}
+
+impl<'a> From<SubCircuitGroup<'a>> for SubCircuit<'a> {
+    fn from(group: SubCircuitGroup<'a>) -> Self {
+        match group {
             SubCircuitGroup::Single(circuit) => circuit,
-            _ => SubCircuit::Group(Box::new(self))
+            _ => SubCircuit::Group(Box::new(group))
         }
     }
 }
 
-pub fn document<'a>(input: &'a str) -> IResult<&'a str, Document<'a>, VerboseError<&str>> {
-    match input.chars().nth(0) {
-        Some('|' | '-') => map(twoport, Document::Twoport)(input),
-        _ => map(sub_circuit, Document::Circuit)(input),
+/// Parses a whole document, i.e. a twoport or a circuit, which may be surrounded by whitespace and comments
+pub fn document(input: &str) -> Result<Document<'_>, crate::ParseError> {
+    let start = space::<VerboseError<&str>>(input).map_or(input, |(rest, ())| rest);
+    let parsed = match start.chars().next() {
+        Some('|' | '-' | '=' | '.' | '{') => terminated(map(twoport::<VerboseError<&str>>, Document::Twoport), space)(start),
+        _ => terminated(map(sub_circuit::<VerboseError<&str>>, Document::Circuit), space)(start),
+    };
+    match parsed {
+        Ok(("", document)) => Ok(document),
+        // the recognizer finds where the input stops matching the grammar and what was expected there
+        _ => Err(crate::ParseError::new(input, validate_only(input).expect_err("recognizer agrees with the parser"))),
+    }
+}
+
+/// Parses a whole twoport like [`document`], failing for circuits
+///
+/// ```
+/// use circmark_parse::circuit;
+/// assert_eq!(circuit::parse_twoport("|V1-R1").unwrap().links.len(), 2);
+/// assert_eq!(circuit::parse_twoport("(R1+R2)").unwrap_err().to_string(), "expected `|`, `-`, `=`, `.` or `{` at line 1, column 1, found `(`\n(R1+R2)\n^");
+/// ```
+pub fn parse_twoport(input: &str) -> Result<Twoport<'_>, crate::ParseError> {
+    match document(input)? {
+        Document::Twoport(twoport) => Ok(twoport),
+        Document::Circuit(_) => Err(crate::ParseError::new(input, Diagnostics { offset: content_start(input), expected: &["`|`", "`-`", "`=`", "`.`", "`{`"] })),
     }
 }
 
+/// Parses a whole circuit like [`document`], failing for twoports
+pub fn parse_sub_circuit(input: &str) -> Result<SubCircuit<'_>, crate::ParseError> {
+    match document(input)? {
+        Document::Circuit(circuit) => Ok(circuit),
+        Document::Twoport(_) => Err(crate::ParseError::new(input, Diagnostics { offset: content_start(input), expected: &["element", "`(`"] })),
+    }
+}
+
+/// Byte offset of the first token, after whitespace and comments
+fn content_start(input: &str) -> usize {
+    input.len() - space::<VerboseError<&str>>(input).map_or(input, |(rest, ())| rest).len()
+}
+
+/// Skips insignificant whitespace (including newlines), `#` line comments and `/* */` block comments.
+///
+/// These may appear between any two tokens, but not within elements, values or transformers.
+pub fn space<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, (), E> {
+    map(
+        many0_count(alt((
+            multispace1,
+            preceded(char('#'), not_line_ending),
+            delimited(tag("/*"), take_until("*/"), tag("*/")),
+        ))),
+        |_| (),
+    )(input)
+}
+
+/// Low-level [nom] parser of a twoport, for combining with other parsers: it stops where the twoport does, without
+/// skipping whitespace and comments around it, and fails with nom's errors. [`parse_twoport`] parses a whole input
+/// into a twoport, failing with a [`crate::ParseError`] instead.
 pub fn twoport<'a, E: ParseError<&'a str> + ContextError<&'a str>>(input: &'a str) -> IResult<&'a str, Twoport<'a>, E> {
     context("twoport", map(many1(twoport_link), |links| Twoport { links }))(input)
 }
 
 pub fn twoport_link<'a, E: ParseError<&'a str> + ContextError<&'a str>>(input: &'a str) -> IResult<&'a str, TwoportLink<'a>, E> {
-    alt((
+    preceded(space, alt((
         map(preceded(tag("-"), sub_circuit), TwoportLink::Series),
         map(preceded(tag("|"), sub_circuit), TwoportLink::Shunt),
-    ))(input)
+        map(
+            preceded(tag("=T"), pair(alphanumeric1, opt(delimited(tag("(n="), value, char(')'))))),
+            |(id, ratio)| TwoportLink::Transformer { id, ratio },
+        ),
+        map(preceded(char('.'), alphanumeric1), TwoportLink::Node),
+        map(
+            delimited(char('{'), separated_pair(quantity, char(':'), alphanumeric1), char('}')),
+            |(quantity, name)| TwoportLink::Annotation { quantity, name },
+        ),
+    )))(input)
 }
 
-pub fn element<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Element<'a>, E> {
+pub fn quantity<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Quantity, E> {
     alt((
-        map(preceded(tag("R"), alphanumeric1), Element::R),
-        map(preceded(tag("C"), alphanumeric1), Element::C),
-        map(preceded(tag("V"), alphanumeric1), Element::V),
-        map(preceded(tag("L"), alphanumeric1), Element::L),
-        map(preceded(tag("Z"), alphanumeric1), Element::Z),
-        map(preceded(tag("I"), alphanumeric1), Element::I),
-        map(tag("O"), |_| Element::Open),
+        map(char('i'), |_| Quantity::Current),
+        map(char('u'), |_| Quantity::Voltage),
     ))(input)
 }
 
+pub fn element<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Element<'a>, E> {
+    for kind in candidate_kinds(input) {
+        let rest = &input[kind.marker.len() + kind.prefix.len()..];
+        if !kind.has_id {
+            return Ok((rest, (kind.make)(kind.prefix, "", "")));
+        }
+        let Ok((rest, id)) = alphanumeric1::<_, E>(rest) else {
+            continue;
+        };
+        let Some(rest) = rest.strip_prefix(kind.suffix) else {
+            continue;
+        };
+        match kind.reference {
+            Reference::None => return Ok((rest, (kind.make)(kind.prefix, id, ""))),
+            Reference::Control => {
+                if let Ok((rest, control)) = delimited(char::<_, E>('('), alphanumeric1, char(')'))(rest) {
+                    return Ok((rest, (kind.make)(kind.prefix, id, control)));
+                }
+            }
+            Reference::Wiper => {
+                let (rest, wiper) = opt(preceded(char::<_, E>('>'), alphanumeric1))(rest)?;
+                return Ok((rest, (kind.make)(kind.prefix, id, wiper.unwrap_or_default())));
+            }
+        }
+    }
+    Err(nom::Err::Error(E::from_error_kind(input, ErrorKind::Alt)))
+}
+
+/// Parses a value, consisting of a number, an optional SI prefix and an optional unit, e.g. `4.7k` or `100nF`.
+///
+/// A letter following the number is always taken as a prefix if it is one, so `1m` is one milli (not one meter).
+pub fn value<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, Value<'a>, E> {
+    map(
+        tuple((
+            recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+            opt(one_of("TGMkmuµnpf")),
+            take_while(|c: char| c.is_alphabetic()),
+        )),
+        |(number, prefix, unit)| {
+            let exponent = match prefix {
+                Some('T') => 12,
+                Some('G') => 9,
+                Some('M') => 6,
+                Some('k') => 3,
+                Some('m') => -3,
+                Some('u' | 'µ') => -6,
+                Some('n') => -9,
+                Some('p') => -12,
+                Some('f') => -15,
+                _ => 0,
+            };
+            // parsing the scaled number as a whole avoids rounding errors (`100n` being exactly `1e-7`),
+            // digits with an optional fractional part and an exponent always parse as a float
+            let number = format!("{number}e{exponent}").parse().unwrap();
+            Value { number, unit }
+        },
+    )(input)
+}
+
+/// Low-level [nom] parser of a sub-circuit, for combining with other parsers like [`twoport`]. [`parse_sub_circuit`]
+/// parses a whole input into a circuit, failing with a [`crate::ParseError`] instead.
 pub fn sub_circuit<'a, E: ParseError<&'a str> + ContextError<&'a str>>(input: &'a str) -> IResult<&'a str, SubCircuit<'a>, E> {
-    alt((
-        context("sub_circuit-group", map(delimited(tag("("), sub_circuit_series, tag(")")), |group| group.into())),
-        context("sub_circuit-element", map(element, SubCircuit::Element)),
-    ))(input)
+    preceded(space, alt((
+        context("sub_circuit-group", map(delimited(tag("("), sub_circuit_series, preceded(space, tag(")"))), |group| group.into())),
+        context("sub_circuit-element", map(
+            pair(element, opt(preceded(tuple((space, tag("="), space)), value))),
+            |(element, value)| SubCircuit::Element(element, value),
+        )),
+    )))(input)
 }
 
+// each operand is parsed once and then followed by an optional operator, as trying `a + b` before `a` alone would
+// parse `a` twice, and each nested group twice more
 pub fn sub_circuit_series<'a, E: ParseError<&'a str> + ContextError<&'a str>>(input: &'a str) -> IResult<&'a str, SubCircuitGroup<'a>, E> {
-    alt((
-        map(separated_pair(sub_circuit_parallel, tag("+"), sub_circuit_series), |(left, right)| SubCircuitGroup::Series(left.into(), right.into())),
-        sub_circuit_parallel
-    ))(input)
+    map(
+        pair(sub_circuit_parallel, opt(preceded(pair(space, tag("+")), sub_circuit_series))),
+        |(left, right)| match right {
+            Some(right) => SubCircuitGroup::Series(left.into(), right.into()),
+            None => left,
+        },
+    )(input)
 }
 
 pub fn sub_circuit_parallel<'a, E: ParseError<&'a str> + ContextError<&'a str>>(input: &'a str) -> IResult<&'a str, SubCircuitGroup<'a>, E> {
-    alt((
-        map(separated_pair(sub_circuit, tag("||"), sub_circuit_parallel), |(left, right)| SubCircuitGroup::Parallel(left, right.into())),
-        map(sub_circuit, SubCircuitGroup::Single),
-    ))(input)
+    map(
+        pair(sub_circuit, opt(preceded(pair(space, tag("||")), sub_circuit_parallel))),
+        |(left, right)| match right {
+            Some(right) => SubCircuitGroup::Parallel(left, right.into()),
+            None => SubCircuitGroup::Single(left),
+        },
+    )(input)
 }
 
 #[cfg(test)]
@@ -173,41 +770,117 @@ mod tests {
     fn test_element() {
         assert_eq!(element::<E>("R1").unwrap().1, Element::R("1"));
         assert_eq!(element::<E>("C2").unwrap().1, Element::C("2"));
-        assert_eq!(element::<E>("V3").unwrap().1, Element::V("3"));
+        assert_eq!(element::<E>("V3").unwrap().1, Element::V { id: "3", polarity: Polarity::Unmarked });
         assert_eq!(element::<E>("L4").unwrap().1, Element::L("4"));
         assert_eq!(element::<E>("Zth1").unwrap().1, Element::Z("th1"));
-        assert_eq!(element::<E>("Ino").unwrap().1, Element::I("no"));
+        assert_eq!(element::<E>("Ino").unwrap().1, Element::I { id: "no", polarity: Polarity::Unmarked });
+        assert_eq!(element::<E>("^V1").unwrap().1, Element::V { id: "1", polarity: Polarity::Up });
+        assert_eq!(element::<E>("vI1").unwrap().1, Element::I { id: "1", polarity: Polarity::Down });
+        assert_eq!(element::<E>("vI1").unwrap().1.label(), "I1");
+        assert!(element::<E>("^R1").is_err());
         assert_eq!(element::<E>("O").unwrap().1, Element::Open);
         assert_eq!(element::<E>("Req").unwrap().1, Element::R("eq"));
+        assert_eq!(element::<E>("D1").unwrap().1, Element::D { id: "1", polarity: Polarity::Unmarked });
+        assert_eq!(element::<E>("Dz5v1").unwrap().1, Element::Dz { id: "5v1", polarity: Polarity::Unmarked });
+        assert_eq!(element::<E>("^D1").unwrap().1, Element::D { id: "1", polarity: Polarity::Up });
+        assert_eq!(element::<E>("vDz1").unwrap().1, Element::Dz { id: "1", polarity: Polarity::Down });
+        assert_eq!(element::<E>("^LED1").unwrap().1, Element::Led { id: "1", polarity: Polarity::Up });
+    }
+
+    #[test]
+    fn test_value() {
+        assert_eq!(value::<E>("4.7k").unwrap().1, Value { number: 4700.0, unit: "" });
+        assert_eq!(value::<E>("100nF").unwrap().1, Value { number: 100e-9, unit: "F" });
+        assert_eq!(value::<E>("2.2µH").unwrap().1, Value { number: 2.2e-6, unit: "H" });
+        assert_eq!(value::<E>("12V").unwrap().1, Value { number: 12.0, unit: "V" });
+        assert_eq!(value::<E>("1m").unwrap().1, Value { number: 1e-3, unit: "" });
+        assert!(value::<E>("k").is_err());
+        assert_eq!(Value { number: 4700.0, unit: "Ω" }.to_string(), "4.7kΩ");
+    }
+
+    #[test]
+    fn test_element_kinds() {
+        for kind in ELEMENT_KINDS {
+            let reference = if kind.reference == Reference::Wiper { "out" } else { "V1" };
+            let made = (kind.make)(kind.prefix, "1", reference);
+            assert!(std::ptr::eq(made.kind(), kind));
+            let input = SubCircuit::Element(made.clone(), None).to_string();
+            if kind.has_id {
+                assert_eq!(element::<VerboseError<&str>>(&input).unwrap().1, made);
+            }
+        }
+        assert_eq!(element::<E>("Dz").unwrap().1, Element::D { id: "z", polarity: Polarity::Unmarked });
+        assert_eq!(element::<E>("S1*+").unwrap(), ("+", Element::Switch { id: "1", closed: true }));
+        assert_eq!(element::<E>("S1+").unwrap(), ("+", Element::Switch { id: "1", closed: false }));
+        assert_eq!(element::<E>("Egain(V1)+").unwrap(), ("+", Element::E { id: "gain", control: "V1" }));
+        assert_eq!(element::<E>("F1(Vsense)").unwrap().1, Element::F { id: "1", control: "Vsense" });
+        assert_eq!(element::<E>("P1>out|").unwrap(), ("|", Element::P { id: "1", wiper: Some("out") }));
+        assert_eq!(element::<E>("P1>|").unwrap(), (">|", Element::P { id: "1", wiper: None }));
+        assert_eq!(element::<E>("LED1").unwrap().1, Element::Led { id: "1", polarity: Polarity::Unmarked });
+        assert_eq!(element::<E>("Led1").unwrap().1, Element::L("ed1"));
+        assert_eq!(element::<E>("NTC1").unwrap().1, Element::Ntc("1"));
+        assert_eq!(element::<E>("XTAL1").unwrap().1, Element::Xtal("1"));
+        assert_eq!(element::<E>("FUSE1(V1)").unwrap(), ("(V1)", Element::Fuse("1")));
+        assert!(element::<E>("E1").is_err());
+        assert!(element::<E>("G1()").is_err());
+        assert!(element::<E>("X1").is_err());
+    }
+
+    #[test]
+    fn test_register() {
+        // extends the prefix of resistors, like the built-in kinds of other letters do, with a character that ids
+        // can't have, so that the registration doesn't change what tests running alongside parse
+        let kind = register(ElementKind::custom("R_", Symbol::Resistor, Impedance::Resistive, Spice::Passive("R")));
+        let element = element::<E>("R_1").unwrap().1;
+        assert_eq!(element, Element::Custom { prefix: "R_", id: "1" });
+        assert!(std::ptr::eq(element.kind(), kind));
+        assert_eq!(element.label(), "R_1");
+        assert_eq!(element.to_owned().borrow(), element);
+        assert_eq!(document("|V1-R_1=10k").unwrap().to_string(), "|V1-R_1=10k");
+        assert!(validate_only("-R_1").is_ok());
+
+        let unregistered = Element::Custom { prefix: "Q_", id: "1" };
+        assert_eq!(unregistered.try_kind().unwrap_err(), UnregisteredKind { prefix: "Q_".to_string() });
+        assert!(std::ptr::eq(unregistered.kind(), &UNREGISTERED));
+        assert_eq!(SubCircuit::Element(unregistered, None).to_string(), "Q_1");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let document = document("|V1=5V-(R1+O)|(C1||Dz5v1=100nF)").unwrap();
+        let json = serde_json::to_string(&document).unwrap();
+        assert!(json.starts_with(r#"{"Twoport":{"links":[{"Shunt":{"Element":[{"V":{"id":"1","polarity":"Unmarked"}},{"number":5.0,"unit":"V"}]}}"#));
+        assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
     }
 
     #[test]
     fn test_sub_circuit() {
-        assert_eq!(sub_circuit::<E>("R1").unwrap().1, SubCircuit::Element(Element::R("1")));
+        assert_eq!(sub_circuit::<E>("R1").unwrap().1, SubCircuit::Element(Element::R("1"), None));
         assert_eq!(sub_circuit::<E>("(R1+R2)").unwrap().1, SubCircuit::Group(Box::new(
             SubCircuitGroup::Series(
-                SubCircuit::Element(Element::R("1")),
-                SubCircuit::Element(Element::R("2"))
+                SubCircuit::Element(Element::R("1"), None),
+                SubCircuit::Element(Element::R("2"), None)
             )
         )));
         assert_eq!(sub_circuit::<E>("(R1+R2||R3)").unwrap().1, SubCircuit::Group(Box::new(
             SubCircuitGroup::Series(
-                SubCircuit::Element(Element::R("1")),
+                SubCircuit::Element(Element::R("1"), None),
                 SubCircuit::Group(Box::new(
                     SubCircuitGroup::Parallel(
-                        SubCircuit::Element(Element::R("2")),
-                        SubCircuit::Element(Element::R("3")),
+                        SubCircuit::Element(Element::R("2"), None),
+                        SubCircuit::Element(Element::R("3"), None),
                     )
                 ))
             )
         )));
         assert_eq!(sub_circuit::<E>("(R1+(R2||R3))").unwrap().1, SubCircuit::Group(Box::new(
             SubCircuitGroup::Series(
-                SubCircuit::Element(Element::R("1")),
+                SubCircuit::Element(Element::R("1"), None),
                 SubCircuit::Group(Box::new(
                     SubCircuitGroup::Parallel(
-                        SubCircuit::Element(Element::R("2")),
-                        SubCircuit::Element(Element::R("3")),
+                        SubCircuit::Element(Element::R("2"), None),
+                        SubCircuit::Element(Element::R("3"), None),
                     )
                 ))
             )
@@ -216,24 +889,42 @@ mod tests {
             SubCircuitGroup::Parallel(
                 SubCircuit::Group(Box::new(
                     SubCircuitGroup::Series(
-                        SubCircuit::Element(Element::R("1")),
-                        SubCircuit::Element(Element::R("2")),
+                        SubCircuit::Element(Element::R("1"), None),
+                        SubCircuit::Element(Element::R("2"), None),
                     )
                 )),
-                SubCircuit::Element(Element::R("3")),
+                SubCircuit::Element(Element::R("3"), None),
             )
         )));
     }
 
+    #[test]
+    fn test_parse_wrappers() {
+        assert_eq!(parse_sub_circuit(" (R1+R2) # divider").unwrap().to_string(), "(R1+R2)");
+        let error = parse_sub_circuit("\n  |V1-R1").unwrap_err();
+        assert_eq!((error.line, error.column, error.expected), (2, 3, &["element", "`(`"][..]));
+        assert_eq!(parse_twoport("|V1-(R1").unwrap_err().expected, ["`+`", "`||`", "`)`"]);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // `(R10+C10||(R9+C9||(…R0…)))`, which took half a minute to parse while operands were parsed again for each alternative
+        let input = (1..=10).fold("R0".to_string(), |inner, depth| format!("(R{depth}+C{depth}||{inner})"));
+        let start = std::time::Instant::now();
+        assert_eq!(document(&input).unwrap().to_string(), input);
+        assert!(document(&format!("{input})")).is_err());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1), "parsing took {:?}", start.elapsed());
+    }
+
     #[test]
     fn test_multi_series() {
         assert_eq!(try_parse(sub_circuit, "(R1+R2+R3)").unwrap().1, SubCircuit::Group(Box::new(
             SubCircuitGroup::Series(
-                SubCircuit::Element(Element::R("1")),
+                SubCircuit::Element(Element::R("1"), None),
                 SubCircuit::Group(Box::new(
                     SubCircuitGroup::Series(
-                        SubCircuit::Element(Element::R("2")),
-                        SubCircuit::Element(Element::R("3")),
+                        SubCircuit::Element(Element::R("2"), None),
+                        SubCircuit::Element(Element::R("3"), None),
                     ),
                 )),
             )
@@ -244,31 +935,95 @@ mod tests {
     fn test_multi_parallel() {
         assert_eq!(try_parse(sub_circuit, "(R1||R2||R3)").unwrap().1, SubCircuit::Group(Box::new(
             SubCircuitGroup::Parallel(
-                SubCircuit::Element(Element::R("1")),
+                SubCircuit::Element(Element::R("1"), None),
                 SubCircuit::Group(Box::new(
                     SubCircuitGroup::Parallel(
-                        SubCircuit::Element(Element::R("2")),
-                        SubCircuit::Element(Element::R("3")),
+                        SubCircuit::Element(Element::R("2"), None),
+                        SubCircuit::Element(Element::R("3"), None),
                     ),
                 )),
             )
         )));
     }
 
+    #[test]
+    fn test_elements() {
+        let document = document("|V1-(R1+R2||C1)|O").unwrap();
+        assert_eq!(document.elements(), vec![
+            &Element::V { id: "1", polarity: Polarity::Unmarked }, &Element::R("1"), &Element::R("2"), &Element::C("1"), &Element::Open,
+        ]);
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(twoport::<E>("-R1=4.7k-C1=100nF").unwrap().1, Twoport {
+            links: vec![
+                TwoportLink::Series(SubCircuit::Element(Element::R("1"), Some(Value { number: 4700.0, unit: "" }))),
+                TwoportLink::Series(SubCircuit::Element(Element::C("1"), Some(Value { number: 100e-9, unit: "F" }))),
+            ],
+        });
+        assert_eq!(sub_circuit::<E>("(R1=1k||C1)").unwrap().1, SubCircuit::Group(Box::new(
+            SubCircuitGroup::Parallel(
+                SubCircuit::Element(Element::R("1"), Some(Value { number: 1000.0, unit: "" })),
+                SubCircuit::Element(Element::C("1"), None),
+            )
+        )));
+    }
+
     #[test]
     fn test_twoport() {
         assert_eq!(twoport::<E>("|O-((L1+R1)||C1)|O").unwrap().1, Twoport {
             links: vec![
-                TwoportLink::Shunt(SubCircuit::Element(Element::Open)),
+                TwoportLink::Shunt(SubCircuit::Element(Element::Open, None)),
                 TwoportLink::Series(SubCircuit::Group(Box::new(SubCircuitGroup::Parallel(
                     SubCircuit::Group(Box::new(SubCircuitGroup::Series(
-                        SubCircuit::Element(Element::L("1")),
-                        SubCircuit::Element(Element::R("1")),
+                        SubCircuit::Element(Element::L("1"), None),
+                        SubCircuit::Element(Element::R("1"), None),
                     ))),
-                    SubCircuit::Element(Element::C("1"))
+                    SubCircuit::Element(Element::C("1"), None)
                 )))),
-                TwoportLink::Shunt(SubCircuit::Element(Element::Open)),
+                TwoportLink::Shunt(SubCircuit::Element(Element::Open, None)),
             ],
         });
     }
+
+    #[test]
+    fn test_transformer() {
+        let twoport = twoport::<E>("-R1=Tin(n=10)|C1=T2").unwrap().1;
+        assert_eq!(twoport.links[1], TwoportLink::Transformer { id: "in", ratio: Some(Value { number: 10.0, unit: "" }) });
+        assert_eq!(twoport.links[3], TwoportLink::Transformer { id: "2", ratio: None });
+        assert_eq!(twoport.links[3].label().as_deref(), Some("T2"));
+        assert_eq!(twoport.elements().len(), 2);
+        assert!(document("=T1").is_ok());
+        assert!(document("-R1=T1(n=)").is_err());
+    }
+
+    #[test]
+    fn test_annotations() {
+        let twoport = twoport::<E>("|V1-R1{i:i1}|R2 {u:u2}").unwrap().1;
+        assert_eq!(twoport.links[2], TwoportLink::Annotation { quantity: Quantity::Current, name: "i1" });
+        assert_eq!(twoport.links[4], TwoportLink::Annotation { quantity: Quantity::Voltage, name: "u2" });
+        assert_eq!(twoport.elements().len(), 3);
+        assert!(document("{i:iin}-R1").is_ok());
+        assert!(document("-R1{v:u1}").is_err());
+        assert!(document("-R1{i:}").is_err());
+    }
+
+    #[test]
+    fn test_space_and_comments() {
+        let input = "# low pass filter
+            |V1=5V   /* supply */
+            -(R1 + C1
+              || L1) # load
+            .vout |C2 = 100n
+        ";
+        assert_eq!(document(input).unwrap(), document("|V1=5V-(R1+C1||L1).vout|C2=100n").unwrap());
+        assert_eq!(document("/* one */ R1 # two").unwrap(), Document::Circuit(SubCircuit::Element(Element::R("1"), None)));
+        assert!(document("|V1 =T1 |R1").is_ok());
+        assert!(document("-R 1").is_err());
+        assert!(document("-R1=4.7 k").is_err());
+        let error = document("|V1\n-(R1 |C1)").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 6));
+        assert_eq!(document("-R1 /* open").unwrap_err().expected, &["`*/`"]);
+    }
 }
@@ -0,0 +1,214 @@
+//! Owned counterparts of the syntax tree, which don't borrow from the input.
+//!
+//! They are meant for storing parsed documents, e.g. in application state. Drawing,
+//! layout and the exports work on the borrowed tree, which [`Document::borrow`] returns.
+//!
+//! ```
+//! use circmark_parse::circuit::{self, owned};
+//! let document: owned::Document = {
+//!     let input = String::from("|V1-R1=1k|C1");
+//!     circuit::document(&input).unwrap().to_owned()
+//! };
+//! assert_eq!(document.borrow().elements().len(), 3);
+//! ```
+
+use super::{ElementKind, Quantity};
+
+/// Owned [`super::Element`]
+#[derive(Clone, Debug)]
+pub struct Element {
+    pub kind: &'static ElementKind,
+    /// The id following the prefix, empty for open circuits
+    pub id: String,
+    /// The controlling element of controlled sources or the wiper's node of potentiometers, empty if there is none
+    pub reference: String,
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.kind, other.kind) && self.id == other.id && self.reference == other.reference
+    }
+}
+
+/// Owned [`super::Value`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct Value {
+    pub number: f64,
+    pub unit: String,
+}
+
+/// Owned [`super::SubCircuit`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum SubCircuit {
+    Element(Element, Option<Value>),
+    Group(Box<SubCircuitGroup>),
+}
+
+/// Owned [`super::SubCircuitGroup`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum SubCircuitGroup {
+    Single(SubCircuit),
+    Series(SubCircuit, SubCircuit),
+    Parallel(SubCircuit, SubCircuit),
+}
+
+/// Owned [`super::TwoportLink`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum TwoportLink {
+    Series(SubCircuit),
+    Shunt(SubCircuit),
+    Transformer { id: String, ratio: Option<Value> },
+    Node(String),
+    Annotation { quantity: Quantity, name: String },
+}
+
+/// Owned [`super::Twoport`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct Twoport {
+    pub links: Vec<TwoportLink>,
+}
+
+/// Owned [`super::Document`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum Document {
+    Circuit(SubCircuit),
+    Twoport(Twoport),
+}
+
+impl Element {
+    pub fn borrow(&self) -> super::Element<'_> {
+        (self.kind.make)(self.kind.prefix, &self.id, &self.reference)
+    }
+}
+
+impl super::Element<'_> {
+    pub fn to_owned(&self) -> Element {
+        Element {
+            kind: self.kind(),
+            id: self.id().unwrap_or_default().to_string(),
+            reference: self.control().or(self.wiper()).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl Value {
+    pub fn borrow(&self) -> super::Value<'_> {
+        super::Value { number: self.number, unit: &self.unit }
+    }
+}
+
+impl super::Value<'_> {
+    pub fn to_owned(&self) -> Value {
+        Value { number: self.number, unit: self.unit.to_string() }
+    }
+}
+
+impl SubCircuit {
+    pub fn borrow(&self) -> super::SubCircuit<'_> {
+        match self {
+            SubCircuit::Element(element, value) => super::SubCircuit::Element(element.borrow(), value.as_ref().map(Value::borrow)),
+            SubCircuit::Group(group) => super::SubCircuit::Group(Box::new(group.borrow())),
+        }
+    }
+}
+
+impl super::SubCircuit<'_> {
+    pub fn to_owned(&self) -> SubCircuit {
+        match self {
+            super::SubCircuit::Element(element, value) => SubCircuit::Element(element.to_owned(), value.as_ref().map(super::Value::to_owned)),
+            super::SubCircuit::Group(group) => SubCircuit::Group(Box::new(group.as_ref().to_owned())),
+        }
+    }
+}
+
+impl SubCircuitGroup {
+    pub fn borrow(&self) -> super::SubCircuitGroup<'_> {
+        match self {
+            SubCircuitGroup::Single(circuit) => super::SubCircuitGroup::Single(circuit.borrow()),
+            SubCircuitGroup::Series(a, b) => super::SubCircuitGroup::Series(a.borrow(), b.borrow()),
+            SubCircuitGroup::Parallel(a, b) => super::SubCircuitGroup::Parallel(a.borrow(), b.borrow()),
+        }
+    }
+}
+
+impl super::SubCircuitGroup<'_> {
+    pub fn to_owned(&self) -> SubCircuitGroup {
+        match self {
+            super::SubCircuitGroup::Single(circuit) => SubCircuitGroup::Single(circuit.to_owned()),
+            super::SubCircuitGroup::Series(a, b) => SubCircuitGroup::Series(a.to_owned(), b.to_owned()),
+            super::SubCircuitGroup::Parallel(a, b) => SubCircuitGroup::Parallel(a.to_owned(), b.to_owned()),
+        }
+    }
+}
+
+impl TwoportLink {
+    pub fn borrow(&self) -> super::TwoportLink<'_> {
+        match self {
+            TwoportLink::Series(circuit) => super::TwoportLink::Series(circuit.borrow()),
+            TwoportLink::Shunt(circuit) => super::TwoportLink::Shunt(circuit.borrow()),
+            TwoportLink::Transformer { id, ratio } => super::TwoportLink::Transformer { id, ratio: ratio.as_ref().map(Value::borrow) },
+            TwoportLink::Node(name) => super::TwoportLink::Node(name),
+            TwoportLink::Annotation { quantity, name } => super::TwoportLink::Annotation { quantity: *quantity, name },
+        }
+    }
+}
+
+impl super::TwoportLink<'_> {
+    pub fn to_owned(&self) -> TwoportLink {
+        match self {
+            super::TwoportLink::Series(circuit) => TwoportLink::Series(circuit.to_owned()),
+            super::TwoportLink::Shunt(circuit) => TwoportLink::Shunt(circuit.to_owned()),
+            super::TwoportLink::Transformer { id, ratio } => TwoportLink::Transformer {
+                id: id.to_string(),
+                ratio: ratio.as_ref().map(super::Value::to_owned),
+            },
+            super::TwoportLink::Node(name) => TwoportLink::Node(name.to_string()),
+            super::TwoportLink::Annotation { quantity, name } => TwoportLink::Annotation { quantity: *quantity, name: name.to_string() },
+        }
+    }
+}
+
+impl Twoport {
+    pub fn borrow(&self) -> super::Twoport<'_> {
+        super::Twoport { links: self.links.iter().map(TwoportLink::borrow).collect() }
+    }
+}
+
+impl super::Twoport<'_> {
+    pub fn to_owned(&self) -> Twoport {
+        Twoport { links: self.links.iter().map(super::TwoportLink::to_owned).collect() }
+    }
+}
+
+impl Document {
+    pub fn borrow(&self) -> super::Document<'_> {
+        match self {
+            Document::Circuit(circuit) => super::Document::Circuit(circuit.borrow()),
+            Document::Twoport(twoport) => super::Document::Twoport(twoport.borrow()),
+        }
+    }
+}
+
+impl super::Document<'_> {
+    pub fn to_owned(&self) -> Document {
+        match self {
+            super::Document::Circuit(circuit) => Document::Circuit(circuit.to_owned()),
+            super::Document::Twoport(twoport) => Document::Twoport(twoport.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit;
+
+    #[test]
+    fn test_round_trip() {
+        for input in ["|V1=5V-(R1+O)|(C1||Dz5v1=5.1V)", "(((R1))+L1)", "-R1=T1(n=2)|C1=T2", "|V1-Egain(V1)=2", "-P1>out|P2", ".in-R1.out|C1", "-LED1|NTC1-FUSE1|XTAL1"] {
+            let document = circuit::document(input).unwrap();
+            let owned = document.to_owned();
+            assert_eq!(owned.borrow(), document);
+            assert_eq!(owned.borrow().to_owned(), owned);
+        }
+    }
+}
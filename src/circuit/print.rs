@@ -0,0 +1,196 @@
+//! Writing the syntax tree back as circmark source.
+//!
+//! The output is canonical: it uses as few parentheses as possible, and writes values with the
+//! SI prefix that keeps the number between 1 and 1000 (`4700` becomes `4.7k`). Parsing it yields the same tree.
+
+use std::fmt::{self, Display, Formatter};
+use super::{Document, SubCircuit, SubCircuitGroup, Twoport, TwoportLink, Value};
+
+/// Where a sub-circuit appears, which determines whether groups need parentheses
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Level {
+    /// Twoport link, document or operand of `||`: any group needs parentheses
+    Operand,
+    /// Right side of `||` or left side of `+`: series groups need parentheses
+    Parallel,
+    /// Right side of `+` or inside parentheses
+    Series,
+}
+
+fn write_circuit(f: &mut Formatter, circuit: &SubCircuit, level: Level) -> fmt::Result {
+    match circuit {
+        SubCircuit::Element(element, value) => {
+            match element.id() {
+                Some(_) => write!(f, "{}{}{}", element.kind().marker, element.label(), element.kind().suffix)?,
+                None => f.write_str(element.kind().prefix)?,
+            }
+            if let Some(control) = element.control() {
+                write!(f, "({control})")?;
+            }
+            if let Some(wiper) = element.wiper() {
+                write!(f, ">{wiper}")?;
+            }
+            if let Some(value) = value {
+                write!(f, "={}", value.to_circmark())?;
+            }
+            Ok(())
+        }
+        SubCircuit::Group(group) => write_group(f, group, level),
+    }
+}
+
+fn write_group(f: &mut Formatter, group: &SubCircuitGroup, level: Level) -> fmt::Result {
+    match group {
+        SubCircuitGroup::Single(circuit) => write_circuit(f, circuit, level),
+        SubCircuitGroup::Series(left, right) => parenthesized(f, level != Level::Series, |f| {
+            write_circuit(f, left, Level::Parallel)?;
+            f.write_str("+")?;
+            write_circuit(f, right, Level::Series)
+        }),
+        SubCircuitGroup::Parallel(top, bottom) => parenthesized(f, level == Level::Operand, |f| {
+            write_circuit(f, top, Level::Operand)?;
+            f.write_str("||")?;
+            write_circuit(f, bottom, Level::Parallel)
+        }),
+    }
+}
+
+fn parenthesized(f: &mut Formatter, parenthesize: bool, write: impl FnOnce(&mut Formatter) -> fmt::Result) -> fmt::Result {
+    if !parenthesize {
+        return write(f);
+    }
+    f.write_str("(")?;
+    write(f)?;
+    f.write_str(")")
+}
+
+impl Value<'_> {
+    /// Writes the value as circmark source, e.g. `4.7kΩ`.
+    ///
+    /// Unlike the [`Display`] implementation meant for labels, the number is not rounded,
+    /// so parsing the result gives back the exact same value.
+    ///
+    /// ```
+    /// use circmark_parse::circuit::Value;
+    /// assert_eq!(Value { number: 4.7e-6, unit: "F" }.to_circmark(), "4.7uF");
+    /// assert_eq!(Value { number: 12345.0, unit: "" }.to_circmark(), "12.345k");
+    /// ```
+    pub fn to_circmark(&self) -> String {
+        const PREFIXES: [(i32, &str); 10] = [
+            (12, "T"), (9, "G"), (6, "M"), (3, "k"), (0, ""),
+            (-3, "m"), (-6, "u"), (-9, "n"), (-12, "p"), (-15, "f"),
+        ];
+        if self.number == 0.0 {
+            return format!("0{}", self.unit);
+        }
+        // shortest digits that parse back to the same number, e.g. `4.7e3`
+        let scientific = format!("{:e}", self.number.abs());
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        let exponent: i32 = exponent.parse().unwrap();
+        let digits = mantissa.replace('.', "");
+        let (mut prefix_exponent, mut prefix) = *PREFIXES.iter()
+            .find(|(prefix_exponent, _)| exponent >= *prefix_exponent)
+            .unwrap_or(&PREFIXES[PREFIXES.len() - 1]);
+        // a unit starting with a prefix letter would be taken as prefix (`1mol` is one milli-`ol`)
+        if prefix.is_empty() && self.unit.starts_with(['T', 'G', 'M', 'k', 'm', 'u', 'µ', 'n', 'p', 'f']) {
+            (prefix_exponent, prefix) = PREFIXES[5];
+        }
+        // number of digits before the decimal point
+        let integer_digits = exponent - prefix_exponent + 1;
+        let number = if integer_digits <= 0 {
+            format!("0.{}{digits}", "0".repeat(-integer_digits as usize))
+        } else if integer_digits as usize >= digits.len() {
+            format!("{digits}{}", "0".repeat(integer_digits as usize - digits.len()))
+        } else {
+            let (integer, fraction) = digits.split_at(integer_digits as usize);
+            format!("{integer}.{fraction}")
+        };
+        let sign = if self.number < 0.0 { "-" } else { "" };
+        format!("{sign}{number}{prefix}{}", self.unit)
+    }
+}
+
+/// Writes the sub-circuit as it appears in a twoport link, parenthesizing groups
+impl Display for SubCircuit<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_circuit(f, self, Level::Operand)
+    }
+}
+
+/// Writes the group as it appears inside parentheses
+impl Display for SubCircuitGroup<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_group(f, self, Level::Series)
+    }
+}
+
+impl Display for TwoportLink<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TwoportLink::Series(circuit) => write!(f, "-{circuit}"),
+            TwoportLink::Shunt(circuit) => write!(f, "|{circuit}"),
+            TwoportLink::Transformer { id, ratio: Some(ratio) } => write!(f, "=T{id}(n={})", ratio.to_circmark()),
+            TwoportLink::Transformer { id, ratio: None } => write!(f, "=T{id}"),
+            TwoportLink::Node(name) => write!(f, ".{name}"),
+            TwoportLink::Annotation { quantity, name } => write!(f, "{{{}:{name}}}", quantity.letter()),
+        }
+    }
+}
+
+impl Display for Twoport<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.links.iter().try_for_each(|link| write!(f, "{link}"))
+    }
+}
+
+/// Writes the document as circmark source.
+///
+/// ```
+/// use circmark_parse::circuit;
+/// let document = circuit::document("|V1=5V-(((R1)+(R2||C1=100nF)))|C2").unwrap();
+/// assert_eq!(document.to_string(), "|V1=5V-(R1+R2||C1=100nF)|C2");
+/// ```
+impl Display for Document<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Document::Circuit(circuit) => write!(f, "{circuit}"),
+            Document::Twoport(twoport) => write!(f, "{twoport}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit;
+
+    #[test]
+    fn test_canonical() {
+        for input in [
+            "R1", "O", "Dz5v1=5.1V", "(R1+C1)", "(R1||C1)", "(R1+C1||L1)", "((R1+C1)||L1)", "(R1||(C1+L1))",
+            "((R1+C1)+L1)", "((R1||C1)||L1)", "(R1||C1||L1)", "|V1-R1|C1", "-(R1+O)|(C1||Dz1)", "|V1=T1(n=10)-R1=T2", "(S1||S2*)", "|V1-Egain(V1)=10|F1(V1)", "|V1-P1>out=10k|P2", "|V1-R1=1k.vout|C1", "-LED1|NTC1=10k-FUSE1|XTAL1=16MHz", "|^V1=5V-vI1|I2", "|V1-R1{i:i1}|R2{u:u2}",
+        ] {
+            assert_eq!(circuit::document(input).unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for input in ["((R1))", "(((R1)+(C1)))", "|V1=5V-R1=1k|C1=100n", "R1=0.000047", "R1=1000mmol", "R1=12345678T"] {
+            let document = circuit::document(input).unwrap();
+            let printed = document.to_string();
+            assert_eq!(circuit::document(&printed).unwrap(), document, "{input:?} printed as {printed:?}");
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let value = |number, unit| circuit::Value { number, unit }.to_circmark();
+        assert_eq!(value(4700.0, "Ω"), "4.7kΩ");
+        assert_eq!(value(1e-7, "F"), "100nF");
+        assert_eq!(value(0.5, ""), "500m");
+        assert_eq!(value(5.0, "V"), "5V");
+        assert_eq!(value(1.0, "mol"), "1000mmol");
+        assert_eq!(value(1e-18, ""), "0.001f");
+        assert_eq!(value(0.0, "A"), "0A");
+    }
+}
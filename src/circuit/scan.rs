@@ -0,0 +1,326 @@
+//! Recognizing documents without building the syntax tree.
+
+use std::ops::Range;
+use super::{candidate_kinds, Reference};
+
+/// Why [`validate_only`] rejected the input
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// Byte offset at which the input stopped matching the grammar
+    pub offset: usize,
+    /// What would have been accepted there, e.g. `` `)` ``
+    pub expected: &'static [&'static str],
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {} at offset {}", crate::error::one_of(self.expected), self.offset)
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Checks whether the whole input is a document that [`super::document`] accepts, without allocating.
+///
+/// ```
+/// use circmark_parse::circuit::{validate_only, Diagnostics};
+/// assert_eq!(validate_only("|V1-R1=1k|C1"), Ok(()));
+/// assert_eq!(validate_only("|V1-(R1+)"), Err(Diagnostics { offset: 8, expected: &["element", "`(`"] }));
+/// ```
+pub fn validate_only(input: &str) -> Result<(), Diagnostics> {
+    let mut scanner = Scanner { input, offset: 0, spans: None, element_spans: None };
+    scanner.document()?;
+    if scanner.offset < input.len() {
+        return scanner.fail(&["end of input"]);
+    }
+    Ok(())
+}
+
+/// Byte ranges of the links of a twoport, or of the whole circuit, without surrounding whitespace and comments
+pub(crate) fn spans(input: &str) -> Result<Vec<Range<usize>>, Diagnostics> {
+    let mut scanner = Scanner { input, offset: 0, spans: Some(vec![]), element_spans: None };
+    scanner.document()?;
+    if scanner.offset < input.len() {
+        scanner.fail(&["end of input"])?;
+    }
+    Ok(scanner.spans.unwrap())
+}
+
+/// Byte ranges of the elements (without their values), in the order of [`super::Document::elements`]
+pub(crate) fn element_spans(input: &str) -> Result<Vec<Range<usize>>, Diagnostics> {
+    let mut scanner = Scanner { input, offset: 0, spans: None, element_spans: Some(vec![]) };
+    scanner.document()?;
+    if scanner.offset < input.len() {
+        scanner.fail(&["end of input"])?;
+    }
+    Ok(scanner.element_spans.unwrap())
+}
+
+struct Scanner<'a> {
+    input: &'a str,
+    offset: usize,
+    /// Spans of the links seen so far, only recorded for [`spans`]
+    spans: Option<Vec<Range<usize>>>,
+    /// Spans of the elements seen so far, only recorded for [`element_spans`]
+    element_spans: Option<Vec<Range<usize>>>,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    /// Skips `token` if the input continues with it
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.offset += token.len();
+        }
+        found
+    }
+
+    fn record(&mut self, start: usize) {
+        if let Some(spans) = &mut self.spans {
+            spans.push(start..self.offset);
+        }
+    }
+
+    fn fail(&self, expected: &'static [&'static str]) -> Result<(), Diagnostics> {
+        Err(Diagnostics { offset: self.offset, expected })
+    }
+
+    /// Skips whitespace and comments
+    fn space(&mut self) -> Result<(), Diagnostics> {
+        loop {
+            let rest = self.rest();
+            let whitespace = rest.bytes().take_while(|byte| b" \t\r\n".contains(byte)).count();
+            if whitespace > 0 {
+                self.offset += whitespace;
+            } else if rest.starts_with('#') {
+                self.offset += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let Some(end) = comment.find("*/") else {
+                    self.offset = self.input.len();
+                    return self.fail(&["`*/`"]);
+                };
+                self.offset += 2 + end + 2;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn document(&mut self) -> Result<(), Diagnostics> {
+        self.space()?;
+        if !self.rest().starts_with(['|', '-', '=', '.', '{']) {
+            let start = self.offset;
+            self.sub_circuit()?;
+            self.record(start);
+            return self.space();
+        }
+        loop {
+            self.space()?;
+            let start = self.offset;
+            if self.eat("-") || self.eat("|") {
+                self.sub_circuit()?;
+            } else if self.eat("=") {
+                self.transformer()?;
+            } else if self.eat(".") {
+                self.node()?;
+            } else if self.eat("{") {
+                self.annotation()?;
+            } else {
+                return Ok(());
+            }
+            self.record(start);
+        }
+    }
+
+    /// Skips a transformer following its `=`, e.g. `T1(n=10)`
+    fn transformer(&mut self) -> Result<(), Diagnostics> {
+        let id = self.rest().strip_prefix('T').map_or(0, |rest| rest.bytes().take_while(u8::is_ascii_alphanumeric).count());
+        if id == 0 {
+            return self.fail(&["transformer"]);
+        }
+        self.offset += 1 + id;
+        if self.eat("(n=") {
+            self.value()?;
+            if !self.eat(")") {
+                return self.fail(&["`)`"]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips the name of a node following its `.`
+    fn node(&mut self) -> Result<(), Diagnostics> {
+        let name = self.rest().bytes().take_while(u8::is_ascii_alphanumeric).count();
+        if name == 0 {
+            return self.fail(&["node name"]);
+        }
+        self.offset += name;
+        Ok(())
+    }
+
+    /// Skips an annotation following its `{`, e.g. `i:i1}`
+    fn annotation(&mut self) -> Result<(), Diagnostics> {
+        if !(self.eat("i") || self.eat("u")) {
+            return self.fail(&["`i`", "`u`"]);
+        }
+        if !self.eat(":") {
+            return self.fail(&["`:`"]);
+        }
+        let name = self.rest().bytes().take_while(u8::is_ascii_alphanumeric).count();
+        if name == 0 {
+            return self.fail(&["annotation name"]);
+        }
+        self.offset += name;
+        if !self.eat("}") {
+            return self.fail(&["`}`"]);
+        }
+        Ok(())
+    }
+
+    fn sub_circuit(&mut self) -> Result<(), Diagnostics> {
+        self.space()?;
+        if self.eat("(") {
+            self.series()?;
+            self.space()?;
+            if !self.eat(")") {
+                return self.fail(&["`+`", "`||`", "`)`"]);
+            }
+            return Ok(());
+        }
+        let start = self.offset;
+        self.element()?;
+        let end = self.offset;
+        if let Some(element_spans) = &mut self.element_spans {
+            element_spans.push(start..end);
+        }
+        self.space()?;
+        // values are numbers, so `R1=T1` is followed by a transformer
+        if !self.rest().starts_with("=T") && self.eat("=") {
+            self.space()?;
+            self.value()?;
+        } else {
+            // the space belongs to whatever follows
+            self.offset = end;
+        }
+        Ok(())
+    }
+
+    fn series(&mut self) -> Result<(), Diagnostics> {
+        loop {
+            self.parallel()?;
+            self.space()?;
+            if !self.eat("+") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parallel(&mut self) -> Result<(), Diagnostics> {
+        loop {
+            self.sub_circuit()?;
+            self.space()?;
+            if !self.eat("||") {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skips the marker and the longest prefix that is followed by an id (if the kind of element has one), the kind's suffix
+    /// and the controlling element of controlled sources or the wiper's node of potentiometers.
+    ///
+    /// Like the parser, the kind with the longest prefix (and then suffix) that matches wins, even if a
+    /// shorter prefix would match more of the input (`FUSE1(V1)` is not `F` controlled by `V1`).
+    fn element(&mut self) -> Result<(), Diagnostics> {
+        let rest = self.rest();
+        let alphanumeric = |start: usize| rest[start..].bytes().take_while(u8::is_ascii_alphanumeric).count();
+        let length = candidate_kinds(rest)
+            .find_map(|kind| {
+                let start = kind.marker.len() + kind.prefix.len();
+                if !kind.has_id {
+                    return Some(start);
+                }
+                let id = alphanumeric(start);
+                let end = start + id;
+                if id == 0 || !rest[end..].starts_with(kind.suffix) {
+                    return None;
+                }
+                let end = end + kind.suffix.len();
+                match kind.reference {
+                    Reference::None => Some(end),
+                    Reference::Control => {
+                        let control = rest[end..].strip_prefix('(').map(|_| alphanumeric(end + 1)).unwrap_or(0);
+                        (control > 0 && rest[end + 1 + control..].starts_with(')')).then_some(end + control + 2)
+                    }
+                    Reference::Wiper => {
+                        let wiper = rest[end..].strip_prefix('>').map(|_| alphanumeric(end + 1)).unwrap_or(0);
+                        Some(if wiper > 0 { end + 1 + wiper } else { end })
+                    }
+                }
+            });
+        match length {
+            Some(length) => {
+                self.offset += length;
+                Ok(())
+            }
+            None => self.fail(&["element", "`(`"]),
+        }
+    }
+
+    /// Skips a number with optional fractional part, SI prefix and unit
+    fn value(&mut self) -> Result<(), Diagnostics> {
+        if !self.digits() {
+            return self.fail(&["number"]);
+        }
+        let before_fraction = self.offset;
+        if self.eat(".") && !self.digits() {
+            // the dot is not part of the value
+            self.offset = before_fraction;
+        }
+        let unit = self.rest().chars().take_while(|c| c.is_alphabetic()).map(char::len_utf8).sum::<usize>();
+        self.offset += unit;
+        Ok(())
+    }
+
+    fn digits(&mut self) -> bool {
+        let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+        self.offset += digits;
+        digits > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_agrees_with_parser() {
+        for input in [
+            "R1", "O", "Dz5v1", "Dz", "R1=4.7kΩ", "R1=1.", "R1=", "(R1+C1||L1)", "((R1))", "(R1|C1)", "(R1+)",
+            "|V1-R1|C1", "-R1", "|V1-", "R1+R2", "X1", "", "()", "(R1||)", "|V1=5V-(R1+O)|(C1||Dz5v1=5.1V)",
+            "Egain(V1)", "|V1-Egain(V1)|F1(V1)", "|V1-P1>out|C1", "P1>", "P1>+R1", "(P1+R1)", "E1", "E1()", "E1(V1", "G1(R1)+R1",
+            ".in-R1.out|C1", ".", "-R1.", "-R1=1.vout", "-R1=1.5.vout", "-R1.a.b", ".in",
+            " R1 ", "-R1 .vout |C1", "|V1\n-(R1 + C1\n  || L1)\n|C2", "R1 = 4.7k", "R1 =T1", "-R1 =T1", "-R1= T1", "- R1",
+            "R 1", "P1 >out", "R1=4.7 k", "( R1 )", "(R1 | | C1)", "# comment\n-R1 # comment", "# comment", "-R1 #",
+            "-R1 /* comment */ |C1", "/**/R1/**/", "-R1 /* unterminated", "/* R1", "-R1/* a */* b */", "R1\u{a0}",
+            "LED1", "Led1", "LED", "-LED1|NTC1=10k", "XTAL1=16MHz", "FUSE1=500mA", "FUSE1(V1)", "F1(V1)", "FUSE", "RT1",
+            "=T1", "|V1=T1(n=10)|R1", "|V1=T1", "R1=T1", "-R1=T", "=T", "=T1(n=", "=T1(n=2", "=T1(n=2)-R1", "=R1",
+            "|^V1-vI1", "^R1", "^ V1", "vV", "^", "|v1", "-^D1|vDz1|^LED1", "^Dz", "vLED",
+            "|V1-R1{i:i1}|R2{u:u2}", "{i:iin}-R1", "-R1 {u:u1}", "-R1{i:}", "-R1{x:a}", "-R1{i:a", "-R1{i a}", "-R1{ i:a}", "{",
+        ] {
+            let parsed = circuit::document(input).is_ok();
+            assert_eq!(validate_only(input).is_ok(), parsed, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        assert_eq!(validate_only("(R1|C1)"), Err(Diagnostics { offset: 3, expected: &["`+`", "`||`", "`)`"] }));
+        assert_eq!(validate_only("R1+R2"), Err(Diagnostics { offset: 2, expected: &["end of input"] }));
+        assert_eq!(validate_only("R1=k").unwrap_err().to_string(), "expected number at offset 3");
+    }
+}
@@ -0,0 +1,123 @@
+//! Plain English descriptions of circuits, e.g. for alt texts.
+
+use crate::{
+    circuit::{Document, Element, Quantity, SubCircuit, SubCircuitGroup, TwoportLink},
+    legend,
+};
+
+/// Describes the structure of the document in a sentence.
+///
+/// ```
+/// use circmark_parse::{circuit, describe::describe};
+/// let document = circuit::document("|V1-R1|C1").unwrap();
+/// assert_eq!(
+///     describe(&document),
+///     "A twoport consisting of a voltage source V1 in shunt, followed by a resistor R1 in series, followed by a capacitor C1 in shunt.",
+/// );
+/// ```
+pub fn describe(document: &Document) -> String {
+    match document {
+        Document::Circuit(circuit) => format!("A circuit consisting of {}.", sub_circuit(circuit)),
+        Document::Twoport(twoport) => {
+            let links: Vec<String> = twoport.links.iter().map(|link| match link {
+                TwoportLink::Series(circuit) => format!("{} in series", sub_circuit(circuit)),
+                TwoportLink::Shunt(circuit) => format!("{} in shunt", sub_circuit(circuit)),
+                TwoportLink::Transformer { ratio: Some(ratio), .. } => format!("a transformer {} with a ratio of {}:1", link.label().unwrap(), ratio.number),
+                TwoportLink::Transformer { ratio: None, .. } => format!("a transformer {}", link.label().unwrap()),
+                TwoportLink::Node(name) => format!("the node {name}"),
+                TwoportLink::Annotation { quantity: Quantity::Current, name } => format!("the current {name}"),
+                TwoportLink::Annotation { quantity: Quantity::Voltage, name } => format!("the voltage {name}"),
+            }).collect();
+            format!("A twoport consisting of {}.", links.join(", followed by "))
+        }
+    }
+}
+
+fn element(element: &Element) -> String {
+    let Some(prefix) = element.prefix() else {
+        return "an open circuit".to_string();
+    };
+    let name = legend::ENGLISH.iter()
+        .find(|(p, _)| *p == prefix)
+        .map(|(_, name)| *name)
+        .unwrap_or("element");
+    let article = if name.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+    match (element.control(), element.wiper()) {
+        (Some(control), _) => format!("{article} {name} {} controlled by {control}", element.label()),
+        (None, Some(wiper)) => format!("{article} {name} {} with its wiper at {wiper}", element.label()),
+        (None, None) => format!("{article} {name} {}", element.label()),
+    }
+}
+
+fn sub_circuit(circuit: &SubCircuit) -> String {
+    match circuit {
+        SubCircuit::Element(e, _) => element(e),
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => sub_circuit(circuit),
+            SubCircuitGroup::Series(..) => format!("a series combination of {}", list(&chain(circuit, true))),
+            SubCircuitGroup::Parallel(..) => format!("a parallel combination of {}", list(&chain(circuit, false))),
+        },
+    }
+}
+
+/// Flattens nested series (or parallel) groups into a single list of descriptions
+fn chain(circuit: &SubCircuit, series: bool) -> Vec<String> {
+    if let SubCircuit::Group(group) = circuit {
+        match (group.as_ref(), series) {
+            (SubCircuitGroup::Series(a, b), true) | (SubCircuitGroup::Parallel(a, b), false) => {
+                let mut parts = chain(a, series);
+                parts.extend(chain(b, series));
+                return parts;
+            }
+            _ => {}
+        }
+    }
+    vec![sub_circuit(circuit)]
+}
+
+/// Joins with commas, except for the last item, which is joined with "and"
+fn list(parts: &[String]) -> String {
+    match parts {
+        [] => String::new(),
+        [single] => single.clone(),
+        [init @ .., last] => format!("{} and {last}", init.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_describe_circuit() {
+        let document = circuit::document("(R1+R2+(L1||C1||Z1))").unwrap();
+        assert_eq!(
+            describe(&document),
+            "A circuit consisting of a series combination of a resistor R1, a resistor R2 and \
+             a parallel combination of an inductor L1, a capacitor C1 and an impedance Z1.",
+        );
+        let document = circuit::document("I1").unwrap();
+        assert_eq!(describe(&document), "A circuit consisting of a current source I1.");
+        let document = circuit::document("Egain(R1)").unwrap();
+        assert_eq!(describe(&document), "A circuit consisting of a voltage controlled voltage source Egain controlled by R1.");
+        let document = circuit::document("P1>out").unwrap();
+        assert_eq!(describe(&document), "A circuit consisting of a potentiometer P1 with its wiper at out.");
+    }
+
+    #[test]
+    fn test_describe_twoport() {
+        let document = circuit::document("|O-(R1+R2)|O").unwrap();
+        assert_eq!(
+            describe(&document),
+            "A twoport consisting of an open circuit in shunt, followed by \
+             a series combination of a resistor R1 and a resistor R2 in series, followed by an open circuit in shunt.",
+        );
+        let document = circuit::document("|V1=T1(n=10)|R1").unwrap();
+        assert_eq!(
+            describe(&document),
+            "A twoport consisting of a voltage source V1 in shunt, followed by a transformer T1 with a ratio of 10:1, \
+             followed by a resistor R1 in shunt.",
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Sources with several sections, each holding a twoport or a circuit.
+//!
+//! A section starts with a line naming its kind, `@twoport` or `@circuit`, optionally followed by the
+//! section's name and a title in double quotes, which is drawn above it. Twoports can continue a named twoport with `extends NAME`, appending links to it (see [`crate::resolve`]).
+//! The rest of the header line already belongs to the section's content, so short sections fit on one line:
+//! ```text
+//! @twoport lowpass "Low pass filter"
+//! |V1-R1|C1
+//! @circuit
+//! (R1+R2||C1)
+//! @twoport extends lowpass -R2
+//! ```
+//! Sources without any section header are a single section, whose kind follows from its content.
+//!
+//! Sections can belong to a variant, named in brackets after the keyword, to keep e.g. an ideal and a
+//! real version of a circuit in one source. A section extending a name gets the section of that name in
+//! its own variant if there is one, and the one without a variant otherwise:
+//! ```text
+//! @twoport lowpass
+//! |V1-R1
+//! @twoport[variant=ideal] extends lowpass |C1
+//! @twoport[variant=real] extends lowpass |(C1+Resr)
+//! ```
+//! Sections without a variant belong to all of them, see [`Section::in_variant`].
+
+use std::ops::Range;
+use nom::error::VerboseError;
+use crate::{
+    circuit::{self, owned, Diagnostics, Document},
+    resolve::{ResolveError, Resolver},
+    ParseError,
+};
+
+/// What a section holds, named by the keyword of its header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SectionKind {
+    Twoport,
+    Circuit,
+    /// A twoport drawn together with its Smith chart, see [`crate::smith`]
+    Smith,
+}
+
+impl SectionKind {
+    /// The keyword following `@`, e.g. `twoport`
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SectionKind::Twoport => "twoport",
+            SectionKind::Circuit => "circuit",
+            SectionKind::Smith => "smith",
+        }
+    }
+
+    /// Whether the section holds a twoport rather than a circuit
+    pub fn is_twoport(self) -> bool {
+        self != SectionKind::Circuit
+    }
+}
+
+/// A section of a source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: SectionKind,
+    /// Name given in the header, which later twoports can extend
+    pub name: Option<String>,
+    /// Title given in quotes in the header, e.g. `Input matching network` for `@twoport "Input matching network"`
+    pub title: Option<String>,
+    /// Variant given in the header, e.g. `ideal` for `@twoport[variant=ideal]`
+    pub variant: Option<String>,
+    /// Frequency in Hz given in the header, e.g. `100e6` for `@smith[f=100MHz]`
+    pub frequency: Option<f64>,
+    /// Reference impedance in Ω given in the header, e.g. `75` for `@smith[f=100MHz,z0=75]`
+    pub z0: Option<f64>,
+    /// The content, with the twoport it extends written out
+    pub document: owned::Document,
+    /// Byte range of the section in the source, including its header
+    pub span: Range<usize>,
+    /// Byte range of the content in the source, following the header (and its name, title and base)
+    pub content: Range<usize>,
+}
+
+impl Section {
+    /// Whether the section is part of `variant`, which sections without a variant always are
+    pub fn in_variant(&self, variant: &str) -> bool {
+        self.variant.as_deref().is_none_or(|own| own == variant)
+    }
+}
+
+/// Error returned by [`parse`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionError {
+    /// The header names no known kind of section, e.g. `@foo`
+    UnknownKind { line: usize, keyword: String },
+    /// `extends` is not followed by a name or follows `@circuit`, the attributes are malformed, the title isn't closed
+    /// or a `@smith` section has no frequency
+    InvalidHeader { line: usize },
+    /// The header has an attribute other than `variant`, `f` and `z0`
+    UnknownAttribute { line: usize, key: String },
+    /// There is content before the first header
+    OutsideSection { line: usize },
+    /// The content of a section doesn't parse, located in the whole source
+    Parse(ParseError),
+    /// The content is a circuit in a `@twoport` section or vice versa
+    WrongKind { line: usize, expected: SectionKind },
+    /// The twoport to extend doesn't exist or isn't one
+    Resolve { line: usize, error: ResolveError },
+}
+
+impl std::fmt::Display for SectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SectionError::UnknownKind { line, keyword } => write!(f, "unknown kind of section @{keyword} at line {line}, expected @twoport, @circuit or @smith"),
+            SectionError::InvalidHeader { line } => write!(f, "invalid section header at line {line}, expected e.g. `@twoport[variant=NAME] NAME \"TITLE\" extends NAME`"),
+            SectionError::UnknownAttribute { line, key } => write!(f, "unknown section attribute {key:?} at line {line}, expected variant, f or z0"),
+            SectionError::OutsideSection { line } => write!(f, "content before the first section at line {line}"),
+            SectionError::Parse(error) => write!(f, "{error}"),
+            SectionError::WrongKind { line, expected } => write!(f, "the section at line {line} is not a {}", expected.keyword()),
+            SectionError::Resolve { line, error } => write!(f, "{error} (at line {line})"),
+        }
+    }
+}
+
+impl std::error::Error for SectionError {}
+
+/// Splits the source into its sections and parses them.
+///
+/// ```
+/// use circmark_parse::document::{parse, SectionKind};
+/// let sections = parse("@twoport lowpass\n|V1-R1|C1\n@circuit\n(R1+R2)\n@twoport extends lowpass -R2").unwrap();
+/// assert_eq!(sections.iter().map(|section| section.kind).collect::<Vec<_>>(), [SectionKind::Twoport, SectionKind::Circuit, SectionKind::Twoport]);
+/// assert_eq!(sections[2].document.borrow().to_string(), "|V1-R1|C1-R2");
+/// ```
+pub fn parse(source: &str) -> Result<Vec<Section>, SectionError> {
+    let headers: Vec<usize> = line_starts(source)
+        .filter(|start| source[*start..].trim_start_matches([' ', '\t']).starts_with('@'))
+        .collect();
+    let Some(&first) = headers.first() else {
+        let document = circuit::document(source).map_err(SectionError::Parse)?;
+        let kind = match document {
+            Document::Twoport(_) => SectionKind::Twoport,
+            Document::Circuit(_) => SectionKind::Circuit,
+        };
+        return Ok(vec![Section { kind, name: None, title: None, variant: None, frequency: None, z0: None, document: document.to_owned(), span: 0..source.len(), content: 0..source.len() }]);
+    };
+    let before = skip_space(&source[..first]);
+    if !before.is_empty() {
+        return Err(SectionError::OutsideSection { line: line(source, first - before.len()) });
+    }
+    let mut resolver = Resolver::new();
+    let mut sections = vec![];
+    for (i, &start) in headers.iter().enumerate() {
+        let end = headers.get(i + 1).copied().unwrap_or(source.len());
+        sections.push(section(source, start..end, &mut resolver)?);
+    }
+    Ok(sections)
+}
+
+/// The parts of a section's header line
+pub(crate) struct Header<'a> {
+    pub kind: SectionKind,
+    pub attributes: Attributes<'a>,
+    pub name: Option<&'a str>,
+    pub title: Option<&'a str>,
+    /// The name following `extends`
+    pub base: Option<&'a str>,
+    /// Byte offset of the content in the section, following the header (and its name, title and base)
+    pub content_start: usize,
+}
+
+/// Parses the header the section's text starts with, which is at line `line` of the source
+pub(crate) fn header(text: &str, line: usize) -> Result<Header<'_>, SectionError> {
+    let header = text.split('\n').next().unwrap();
+    let mut words = header.split_whitespace().peekable();
+    let first = words.next().filter(|first| first.starts_with('@')).ok_or(SectionError::InvalidHeader { line })?;
+    let (keyword, attributes) = match first[1..].split_once('[') {
+        Some((keyword, attributes)) => (keyword, header_attributes(attributes, line)?),
+        None => (&first[1..], Attributes::default()),
+    };
+    let kind = [SectionKind::Twoport, SectionKind::Circuit, SectionKind::Smith].into_iter()
+        .find(|kind| kind.keyword() == keyword)
+        .ok_or_else(|| SectionError::UnknownKind { line, keyword: keyword.to_string() })?;
+    if kind == SectionKind::Smith && attributes.frequency.is_none() {
+        return Err(SectionError::InvalidHeader { line });
+    }
+    // where a word of the header ends within the section
+    let end_of = |word: &str| word.as_ptr() as usize - text.as_ptr() as usize + word.len();
+    let mut content_start = end_of(first);
+    let name = words.next_if(|word| *word != "extends" && word.bytes().all(|byte| byte.is_ascii_alphanumeric()));
+    if let Some(name) = name {
+        content_start = end_of(name);
+    }
+    let mut title = None;
+    if let Some(quoted) = header[content_start..].trim_start_matches([' ', '\t']).strip_prefix('"') {
+        let (quoted, _) = quoted.split_once('"').ok_or(SectionError::InvalidHeader { line })?;
+        title = Some(quoted);
+        content_start = end_of(quoted) + 1;
+        words = header[content_start..].split_whitespace().peekable();
+    }
+    let mut base = None;
+    if words.next_if_eq(&"extends").is_some() {
+        let name = words.next().filter(|_| kind.is_twoport()).ok_or(SectionError::InvalidHeader { line })?;
+        content_start = end_of(name);
+        base = Some(name);
+    }
+    Ok(Header { kind, attributes, name, title, base, content_start })
+}
+
+/// Parses the section at `span`, remembering it in `resolver` if it has a name
+fn section(source: &str, span: Range<usize>, resolver: &mut Resolver) -> Result<Section, SectionError> {
+    let line = line(source, span.start);
+    let text = &source[span.clone()];
+    let Header { kind, attributes, name, title, base, content_start } = header(text, line)?;
+    let variant = attributes.variant;
+    let content = &text[content_start..];
+    // a variant extends the base of its own variant, if there is one
+    let base = base.map(|base| match variant {
+        Some(variant) if resolver.contains(&variant_key(base, variant)) => variant_key(base, variant),
+        _ => base.to_string(),
+    });
+    let base = base.as_deref();
+    // a section extending another one may just repeat it
+    let resolved = if let Some(base) = base.filter(|_| skip_space(content).is_empty()) {
+        resolver.extend(base, "").map_err(|error| SectionError::Resolve { line, error })?
+    } else {
+        let document = circuit::document(content).map_err(|error| {
+            let diagnostics = Diagnostics { offset: span.start + content_start + error.offset, expected: error.expected };
+            SectionError::Parse(ParseError::new(source, diagnostics))
+        })?;
+        if kind.is_twoport() != matches!(document, Document::Twoport(_)) {
+            return Err(SectionError::WrongKind { line, expected: kind });
+        }
+        match base {
+            Some(base) => resolver.extend(base, content).map_err(|error| SectionError::Resolve { line, error })?,
+            None => content.to_string(),
+        }
+    };
+    let document = circuit::document(&resolved).expect("resolved sections parse").to_owned();
+    if let Some(name) = name {
+        resolver.add(&variant.map_or_else(|| name.to_string(), |variant| variant_key(name, variant)), resolved);
+    }
+    let content = span.start + content_start..span.end;
+    Ok(Section { kind, name: name.map(str::to_string), title: title.map(str::to_string), variant: variant.map(str::to_string), frequency: attributes.frequency, z0: attributes.z0, document, span, content })
+}
+
+/// Writes the source in canonical form, printing the content of each section the way displaying a [`circuit::Document`] does.
+///
+/// Headers and the comments following them are kept as written, as are sections with comments within their
+/// content, which printing would drop. Content starting on the line of its header stays there.
+///
+/// ```
+/// use circmark_parse::document::format;
+/// assert_eq!(format("( R1 + R2 )").unwrap(), "(R1+R2)\n");
+/// assert_eq!(format("@twoport a # input\n| V1 - R1\n\n@twoport extends a |C1").unwrap(), "@twoport a # input\n|V1-R1\n\n@twoport extends a |C1\n");
+/// ```
+pub fn format(source: &str) -> Result<String, SectionError> {
+    let sections = parse(source)?;
+    // comments before the first header
+    let mut formatted = source[..sections[0].span.start].to_string();
+    for (i, section) in sections.iter().enumerate() {
+        let start = formatted.len();
+        formatted.push_str(&source[section.span.start..section.content.start]);
+        let content = &source[section.content.clone()];
+        let body = skip_space(content).trim_end();
+        let leading = &content[..content.len() - skip_space(content).len()];
+        if body.contains('#') || body.contains("/*") {
+            formatted.push_str(content.trim_end());
+        } else {
+            formatted.push_str(leading.trim_end());
+            if !body.is_empty() {
+                if formatted.len() > start {
+                    formatted.push(if leading.contains('\n') { '\n' } else { ' ' });
+                }
+                formatted.push_str(&circuit::document(body).expect("sections parse").to_string());
+            }
+        }
+        formatted.push('\n');
+        // a blank line separating sections is kept
+        if i + 1 < sections.len() && content[content.trim_end().len()..].matches('\n').count() > 1 {
+            formatted.push('\n');
+        }
+    }
+    Ok(formatted)
+}
+
+/// Attributes given in brackets after the keyword of a header
+#[derive(Default)]
+pub(crate) struct Attributes<'a> {
+    pub variant: Option<&'a str>,
+    pub frequency: Option<f64>,
+    pub z0: Option<f64>,
+}
+
+/// Parses the attributes following `[` in a header, e.g. `variant=ideal,f=1kHz]`
+fn header_attributes(attributes: &str, line: usize) -> Result<Attributes<'_>, SectionError> {
+    let attributes = attributes.strip_suffix(']').ok_or(SectionError::InvalidHeader { line })?;
+    let mut parsed = Attributes::default();
+    for attribute in attributes.split(',') {
+        let (key, value) = attribute.split_once('=').ok_or(SectionError::InvalidHeader { line })?;
+        let number = || match circuit::value::<VerboseError<&str>>(value) {
+            Ok(("", value)) => Ok(value.number),
+            _ => Err(SectionError::InvalidHeader { line }),
+        };
+        let repeated = match key {
+            "variant" if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_alphanumeric()) => parsed.variant.replace(value).is_some(),
+            "variant" => return Err(SectionError::InvalidHeader { line }),
+            "f" => parsed.frequency.replace(number()?).is_some(),
+            "z0" => parsed.z0.replace(number()?).is_some(),
+            _ => return Err(SectionError::UnknownAttribute { line, key: key.to_string() }),
+        };
+        if repeated {
+            return Err(SectionError::InvalidHeader { line });
+        }
+    }
+    Ok(parsed)
+}
+
+/// The name under which a section of a variant is remembered, so it doesn't replace the others
+fn variant_key(name: &str, variant: &str) -> String {
+    format!("{name}[{variant}]")
+}
+
+/// The text following whitespace and comments
+fn skip_space(text: &str) -> &str {
+    circuit::space::<VerboseError<&str>>(text).map_or(text, |(rest, ())| rest)
+}
+
+/// Byte offsets at which the lines of the source start
+fn line_starts(source: &str) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1))
+}
+
+/// The line number (starting at 1) of the byte offset
+fn line(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        let source = "# filters\n@twoport lowpass\n|V1-R1|C1\n\n@circuit divider # two resistors\n(R1+R2)\n@twoport extends lowpass\n-R2|C2\n";
+        let sections = parse(source).unwrap();
+        let summary: Vec<_> = sections.iter().map(|section| (section.kind, section.name.as_deref(), section.document.borrow().to_string())).collect();
+        assert_eq!(summary, [
+            (SectionKind::Twoport, Some("lowpass"), "|V1-R1|C1".to_string()),
+            (SectionKind::Circuit, Some("divider"), "(R1+R2)".to_string()),
+            (SectionKind::Twoport, None, "|V1-R1|C1-R2|C2".to_string()),
+        ]);
+        assert_eq!(&source[sections[1].span.clone()], "@circuit divider # two resistors\n(R1+R2)\n");
+    }
+
+    #[test]
+    fn test_single_section() {
+        let sections = parse("(R1+C1)").unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].kind, sections[0].name.as_deref(), sections[0].span.clone()), (SectionKind::Circuit, None, 0..7));
+        assert_eq!(parse("@twoport |V1-R1").unwrap()[0].document.borrow().to_string(), "|V1-R1");
+        assert_eq!(parse("@twoport a\n|V1\n@twoport extends a").unwrap()[1].document.borrow().to_string(), "|V1");
+    }
+
+    #[test]
+    fn test_titles() {
+        let sections = parse("@twoport matching \"Input matching network\" |V1-L1|C1\n@twoport \"With load\" extends matching\n|R1").unwrap();
+        let summary: Vec<_> = sections.iter().map(|section| (section.name.as_deref(), section.title.as_deref(), section.document.borrow().to_string())).collect();
+        assert_eq!(summary, [
+            (Some("matching"), Some("Input matching network"), "|V1-L1|C1".to_string()),
+            (None, Some("With load"), "|V1-L1|C1|R1".to_string()),
+        ]);
+        assert_eq!(parse("@circuit \"Divider\nR1"), Err(SectionError::InvalidHeader { line: 1 }));
+    }
+
+    #[test]
+    fn test_variants() {
+        let source = "@twoport lowpass\n|V1-R1\n@twoport[variant=ideal] lowpass extends lowpass |C1\n@twoport[variant=real] lowpass extends lowpass |(C1+Resr)\n@twoport extends lowpass -R2\n@twoport[variant=real] extends lowpass -R2";
+        let sections = parse(source).unwrap();
+        let summary: Vec<_> = sections.iter().map(|section| (section.variant.as_deref(), section.document.borrow().to_string())).collect();
+        assert_eq!(summary, [
+            (None, "|V1-R1".to_string()),
+            (Some("ideal"), "|V1-R1|C1".to_string()),
+            (Some("real"), "|V1-R1|(C1+Resr)".to_string()),
+            (None, "|V1-R1-R2".to_string()),
+            (Some("real"), "|V1-R1|(C1+Resr)-R2".to_string()),
+        ]);
+        let ideal: Vec<_> = sections.iter().filter(|section| section.in_variant("ideal")).map(|section| section.document.borrow().to_string()).collect();
+        assert_eq!(ideal, ["|V1-R1", "|V1-R1|C1", "|V1-R1-R2"]);
+        assert_eq!(parse("@circuit[variant=a,variant=b]\nR1"), Err(SectionError::InvalidHeader { line: 1 }));
+        assert_eq!(parse("@circuit[variant=a\nR1"), Err(SectionError::InvalidHeader { line: 1 }));
+        assert_eq!(parse("@circuit[tag=a]\nR1"), Err(SectionError::UnknownAttribute { line: 1, key: "tag".to_string() }));
+    }
+
+    #[test]
+    fn test_smith() {
+        let sections = parse("@twoport match\n|V1-L1=100n\n@smith[f=100MHz,z0=75] extends match |C1=10p|R1=100").unwrap();
+        assert_eq!((sections[1].kind, sections[1].frequency, sections[1].z0), (SectionKind::Smith, Some(100e6), Some(75.0)));
+        assert_eq!(sections[1].document.borrow().to_string(), "|V1-L1=100n|C1=10p|R1=100");
+        assert_eq!(parse("@smith\n|V1-R1"), Err(SectionError::InvalidHeader { line: 1 }));
+        assert_eq!(parse("@smith[f=fast]\n|V1-R1"), Err(SectionError::InvalidHeader { line: 1 }));
+        assert_eq!(parse("@smith[f=1MHz]\nR1"), Err(SectionError::WrongKind { line: 1, expected: SectionKind::Smith }));
+    }
+
+    #[test]
+    fn test_format() {
+        let source = "# filters\n@twoport lowpass\n|V1 - R1|C1\n\n@circuit divider # two resistors\n( R1+R2 )\n@twoport extends lowpass\n-R2 /* later */ |C2\n";
+        assert_eq!(format(source).unwrap(), "# filters\n@twoport lowpass\n|V1-R1|C1\n\n@circuit divider # two resistors\n(R1+R2)\n@twoport extends lowpass\n-R2 /* later */ |C2\n");
+        assert_eq!(format("@twoport a |V1\n@twoport extends a").unwrap(), "@twoport a |V1\n@twoport extends a\n");
+        assert_eq!(format("(R1|R2)").map_err(|error| matches!(error, SectionError::Parse(_))), Err(true));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("@network\n|V1"), Err(SectionError::UnknownKind { line: 1, keyword: "network".to_string() }));
+        assert_eq!(parse("@circuit extends a"), Err(SectionError::InvalidHeader { line: 1 }));
+        assert_eq!(parse("R1\n@circuit\nR2"), Err(SectionError::OutsideSection { line: 1 }));
+        assert_eq!(parse("@circuit\n|V1-R1"), Err(SectionError::WrongKind { line: 1, expected: SectionKind::Circuit }));
+        assert_eq!(parse("@twoport\n|V1\n@twoport extends b -R1"), Err(SectionError::Resolve { line: 3, error: ResolveError::Unknown("b".to_string()) }));
+        let SectionError::Parse(error) = parse("@twoport\n|V1\n@circuit\n(R1|R2)").unwrap_err() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!((error.line, error.column), (4, 4));
+    }
+}
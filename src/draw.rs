@@ -1,21 +1,36 @@
-use crate::{layout::{Size, Position, Layout}, circuit};
+use crate::{layout::{self, Size, Position, Layout}, circuit::{self, Impedance, Polarity, Symbol}, analysis::{self, Complex64}, format};
 
 pub mod svg;
+pub mod drawio;
+pub mod display_list;
+pub mod dxf;
+pub mod hpgl;
+pub mod gcode;
+pub mod tikz;
+pub mod ascii;
+pub mod symbols;
+#[cfg(feature = "raster")]
+pub mod raster;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct Context {
     position: Position,
     rotate: bool,
+    mirror: bool,
+    rules: layout::Rules,
+    shortening: layout::Shortening,
+    /// Frequency at which the impedance of reactive elements is annotated
+    impedance_at: Option<f64>,
+    /// How values are written
+    locale: format::Locale,
 }
 
 impl Context {
     fn translate(self, x: i32, y: i32) -> Self {
+        let (dx, dy) = if self.rotate { (y, x) } else { (x, y) };
+        let dx = if self.mirror { -dx } else { dx };
         Self {
-            position: if self.rotate {
-                Position(self.position.0 + y, self.position.1 + x)
-            } else {
-                Position(self.position.0 + x, self.position.1 + y)
-            },
+            position: Position(self.position.0 + dx, self.position.1 + dy),
             ..self
         }
     }
@@ -26,34 +41,256 @@ impl Context {
             ..self
         }
     }
+
+    /// Mirror the drawing horizontally, so that a twoport's input port ends up on the right.
+    ///
+    /// Asymmetric symbols (such as voltage sources) are flipped accordingly.
+    pub fn mirror(self) -> Self {
+        Self {
+            mirror: !self.mirror,
+            ..self
+        }
+    }
+
+    /// Sets the rules for wires and leads ([`layout::Rules::STANDARD`] by default).
+    ///
+    /// The size passed to [`Draw::draw`] should be computed with the same rules, see [`Layout::layout_size_with`].
+    pub fn rules(self, rules: layout::Rules) -> Self {
+        Self { rules, ..self }
+    }
+
+    /// Sets how labels and values that don't fit are shortened (not at all by default)
+    pub fn shortening(self, shortening: layout::Shortening) -> Self {
+        Self { shortening, ..self }
+    }
+
+    /// Annotates capacitors and inductors having a value with their impedance at `frequency` (in Hz),
+    /// e.g. `-j159Ω @ 1kHz` for `C1=1u`, written after their value
+    pub fn impedance_at(self, frequency: f64) -> Self {
+        Self { impedance_at: Some(frequency), ..self }
+    }
+
+    /// Sets how values are written ([`format::Locale::EN`] by default), e.g. [`format::Locale::SI`] for `4,7 kΩ`
+    pub fn locale(self, locale: format::Locale) -> Self {
+        Self { locale, ..self }
+    }
+
+    /// Whether elements drawn in this context have their left and right ends swapped
+    fn flip(self) -> bool {
+        self.mirror && !self.rotate
+    }
 }
 
 pub trait Draw {
     fn draw<D: Drawer>(&self, size: Size, ctx: Context, drawer: &mut D);
 }
 
+/// What a drawer can render, so that drawing adapts instead of every drawer approximating on its own
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DrawerCapabilities {
+    /// Curves, such as the loops of inductors. Without them inductors are drawn as boxes, like impedances
+    pub curves: bool,
+    /// Symbols and text turned by a quarter, as elements of shunt links are. Drawers without it are still told to
+    /// draw those elements vertically, and do so with upright symbols of their own, e.g. characters stacked along
+    /// the wire
+    pub rotation: bool,
+    /// Colors other than the foreground's, e.g. of themes and heatmaps
+    pub colors: bool,
+    /// Labels of elements. Without them elements get empty labels
+    pub text: bool,
+    /// Values below the labels, see [`Drawer::value`]. Without them values aren't passed to the drawer,
+    /// and are not taken into account when shortening
+    pub values: bool,
+    /// What [`Drawer::fallback`] does with elements whose symbol the drawer lacks
+    pub fallback: Fallback,
+}
+
+impl DrawerCapabilities {
+    pub const ALL: DrawerCapabilities = DrawerCapabilities { curves: true, rotation: true, colors: true, text: true, values: true, fallback: Fallback::GenericBox };
+}
+
+/// How strictly drawers handle symbols they don't have, e.g. ones added to [`Drawer`] after they were written
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// Draw a box with the element's label, like a generic impedance
+    GenericBox,
+    /// Leave the element out, with a gap between its ends
+    Omit,
+    /// Panic, for drawers that are meant to draw every symbol themselves
+    Panic,
+}
+
+impl Default for DrawerCapabilities {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
 pub trait Drawer {
+    /// What the drawer can render, everything by default
+    fn capabilities(&self) -> DrawerCapabilities {
+        DrawerCapabilities::ALL
+    }
+
     fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool);
     fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool);
     fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool);
-    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool);
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool);
     fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool);
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool);
+
+    // symbols that were added later have a fallback, so that drawers written before keep working
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, _flip: bool) {
+        self.fallback(Symbol::ZenerDiode, label, position, size, rotate);
+    }
+
+    /// Draws an ideal switch, open or closed
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        self.fallback(Symbol::Switch { closed }, label, position, size, rotate);
+    }
+
+    /// Draws a controlled source, a voltage source unless `current`
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, _flip: bool, current: bool) {
+        self.fallback(Symbol::ControlledSource { current }, label, position, size, rotate);
+    }
+
+    /// Draws a potentiometer, its wiper coming from `tap`
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, _tap: Position) {
+        self.fallback(Symbol::Potentiometer, label, position, size, rotate);
+    }
+
+    /// Draws a light emitting diode, conducting to the right (or downwards) unless flipped
+    fn led(&mut self, label: &str, position: Position, size: Size, rotate: bool, _flip: bool) {
+        self.fallback(Symbol::Led, label, position, size, rotate);
+    }
+
+    fn thermistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.fallback(Symbol::Thermistor, label, position, size, rotate);
+    }
+
+    fn crystal(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.fallback(Symbol::Crystal, label, position, size, rotate);
+    }
+
+    fn fuse(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.fallback(Symbol::Fuse, label, position, size, rotate);
+    }
+
+    /// Draws an element of a registered kind, see [`circuit::register`], by the name of its symbol
+    fn custom(&mut self, name: &'static str, label: &str, position: Position, size: Size, rotate: bool) {
+        self.fallback(Symbol::Custom(name), label, position, size, rotate);
+    }
+
+    /// Draws an element whose symbol the drawer doesn't have, as set by [`DrawerCapabilities::fallback`]
+    fn fallback(&mut self, symbol: Symbol, label: &str, position: Position, size: Size, rotate: bool) {
+        match self.capabilities().fallback {
+            Fallback::GenericBox => self.resistor(label, position, size, rotate),
+            Fallback::Omit => {}
+            Fallback::Panic => panic!("the drawer has no symbol for {symbol:?} (element {label})"),
+        }
+    }
+
     fn open(&mut self, label: &str, position: Position, size: Size, rotate: bool);
+    /// Draws an ideal transformer link, `size` spanning the whole link: the primary is connected
+    /// to its left corners, the secondary to its right ones
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool);
     fn wire(&mut self, a: Position, b: Position);
     fn junction(&mut self, position: Position);
+
+    /// Adds the value of the element drawn last, e.g. `4.7kΩ`, below its label.
+    ///
+    /// Drawers that have no place for values may ignore them.
+    fn value(&mut self, _value: &str) {}
+
+    /// Sets the full label of the element drawn next, whose label had to be shortened.
+    ///
+    /// Drawers that have no tooltips may ignore it.
+    fn tooltip(&mut self, _text: &str) {}
+
+    /// Marks which way the source drawn last points, `current` telling current sources (drawn with an
+    /// arrow) from voltage sources (drawn with `+` and `−` signs). The marks point to the left (or top) end
+    /// unless flipped.
+    ///
+    /// Drawers that have no place for marks may ignore them.
+    fn polarity(&mut self, _position: Position, _rotate: bool, _flip: bool, _current: bool) {}
+
+    /// Draws the head of an arrow for the current `name` on the wire through `position`, pointing to the
+    /// right (or downwards) unless flipped.
+    ///
+    /// Drawers that have no place for annotations may ignore it.
+    fn current_arrow(&mut self, _name: &str, _position: Position, _rotate: bool, _flip: bool) {}
+
+    /// Draws an arrow for the voltage `name` from `a` to `b`, e.g. from the top to the bottom line, beside
+    /// the line between them.
+    ///
+    /// Drawers that have no place for annotations may ignore it.
+    fn voltage_arrow(&mut self, _name: &str, _a: Position, _b: Position) {}
+
+    /// Names the node at `position`, e.g. the one a potentiometer's wiper is connected to.
+    ///
+    /// Drawers without text may ignore it.
+    fn node_label(&mut self, _name: &str, _position: Position) {}
+
+    /// Writes a title centered on `position`, e.g. the one of a section drawn below it.
+    ///
+    /// Drawers without text may ignore it.
+    fn title(&mut self, _title: &str, _position: Position) {}
 }
 
 impl Draw for circuit::Element<'_> {
     fn draw<D: Drawer>(&self, size: Size, ctx: Context, drawer: &mut D) {
-        match self {
-            circuit::Element::R(_) => drawer.resistor(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::C(_) => drawer.capacitor(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::L(_) => drawer.inductor(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::V(_) => drawer.voltage_source(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::Z(_) => drawer.resistor(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::I(_) => drawer.current_source(&self.label(), ctx.position, size, ctx.rotate),
-            circuit::Element::Open => drawer.open(&self.label(), ctx.position, size, ctx.rotate),
+        let label = if drawer.capabilities().text { self.label() } else { String::new() };
+        draw_element(self, &label, size, ctx, drawer);
+    }
+}
+
+/// Draws the element's symbol with the given label, which may have been shortened
+fn draw_element<D: Drawer>(element: &circuit::Element, label: &str, size: Size, ctx: Context, drawer: &mut D) {
+    let Context { position, rotate, .. } = ctx;
+    // sources point to the right (or bottom) when pointing to their second end, unless the context swaps the ends
+    let polarity_flip = ctx.flip() != (element.polarity() == Polarity::Down);
+    // while diodes point there unless marked otherwise
+    let diode_flip = ctx.flip() != (element.polarity() == Polarity::Up);
+    let symbol = match element.kind().symbol {
+        Symbol::Inductor if !drawer.capabilities().curves => Symbol::Resistor,
+        symbol => symbol,
+    };
+    match symbol {
+        Symbol::Resistor => drawer.resistor(label, position, size, rotate),
+        Symbol::Capacitor => drawer.capacitor(label, position, size, rotate),
+        Symbol::Inductor => drawer.inductor(label, position, size, rotate),
+        // the long plate is the positive one, so it moves with the `+` sign
+        Symbol::VoltageSource => {
+            drawer.voltage_source(label, position, size, rotate, polarity_flip);
+            if element.polarity() != Polarity::Unmarked {
+                drawer.polarity(position, rotate, polarity_flip, false);
+            }
+        }
+        Symbol::CurrentSource => {
+            drawer.current_source(label, position, size, rotate);
+            if element.polarity() != Polarity::Unmarked {
+                drawer.polarity(position, rotate, polarity_flip, true);
+            }
         }
+        Symbol::Diode => drawer.diode(label, position, size, rotate, diode_flip),
+        Symbol::ZenerDiode => drawer.zener_diode(label, position, size, rotate, diode_flip),
+        Symbol::Switch { closed } => drawer.switch(label, position, size, rotate, closed),
+        Symbol::ControlledSource { current } => drawer.controlled_source(label, position, size, rotate, ctx.flip(), current),
+        // the wiper comes from the edge of the room the element has, above (or right of) its body
+        Symbol::Potentiometer => {
+            let tap = symbols::place(position, rotate, (0, -size.1 / 2));
+            drawer.potentiometer(label, position, size, rotate, tap);
+            if let Some(wiper) = element.wiper().filter(|_| drawer.capabilities().text) {
+                drawer.node_label(wiper, tap);
+            }
+        }
+        Symbol::Led => drawer.led(label, position, size, rotate, diode_flip),
+        Symbol::Thermistor => drawer.thermistor(label, position, size, rotate),
+        Symbol::Crystal => drawer.crystal(label, position, size, rotate),
+        Symbol::Fuse => drawer.fuse(label, position, size, rotate),
+        Symbol::Custom(name) => drawer.custom(name, label, position, size, rotate),
+        Symbol::Open => drawer.open(label, position, size, rotate),
     }
 }
 
@@ -62,8 +299,8 @@ impl Draw for circuit::SubCircuitGroup<'_> {
         match self {
             circuit::SubCircuitGroup::Single(circuit) => circuit.draw(size, ctx, drawer),
             circuit::SubCircuitGroup::Series(left, right) => {
-                let left_size = left.layout_size();
-                let right_size = right.layout_size();
+                let left_size = left.layout_size_with(ctx.rules);
+                let right_size = right.layout_size_with(ctx.rules);
                 let width_requested = left_size.0 + right_size.0;
                 let height = left_size.1.max(right_size.1);
                 let left_size = Size(size.0 * left_size.0 / width_requested, height);
@@ -72,11 +309,11 @@ impl Draw for circuit::SubCircuitGroup<'_> {
                 right.draw(right_size, ctx.translate(size.0 / 2 - right_size.0 / 2, 0), drawer);
             }
             circuit::SubCircuitGroup::Parallel(top, bottom) => {
-                let end_wire_length = 20;
-                let top_size = top.layout_size();
-                let bottom_size = bottom.layout_size();
+                let end_wire_length = ctx.rules.end_wire;
+                let top_size = top.layout_size_with(ctx.rules);
+                let bottom_size = bottom.layout_size_with(ctx.rules);
                 let height_requested = top_size.1 + bottom_size.1;
-                let width = top_size.0.max(bottom_size.0) - 2 * end_wire_length;
+                let width = size.0 - 2 * end_wire_length;
                 let top_size = Size(width, size.1 * top_size.1 / height_requested);
                 let bottom_size = Size(width, size.1 * bottom_size.1 / height_requested);
                 top.draw(top_size, ctx.translate(0, -top_size.1 / 2), drawer);
@@ -107,12 +344,48 @@ impl Draw for circuit::SubCircuitGroup<'_> {
 impl Draw for circuit::SubCircuit<'_> {
     fn draw<D: Drawer>(&self, size: Size, ctx: Context, drawer: &mut D) {
         match self {
-            circuit::SubCircuit::Element(element) => element.draw(size, ctx, drawer),
+            circuit::SubCircuit::Element(element, value) => {
+                let capabilities = drawer.capabilities();
+                let full_label = if capabilities.text { element.label() } else { String::new() };
+                let annotation = value.as_ref().zip(ctx.impedance_at).and_then(|(value, frequency)| impedance_annotation(element, value.number, frequency, &ctx.locale));
+                let value = value.as_ref().filter(|_| capabilities.text && capabilities.values).map(|value| {
+                    let value = format::si(value.number, value.unit, &ctx.locale);
+                    match annotation {
+                        Some(annotation) => format!("{value}, {annotation}"),
+                        None => value,
+                    }
+                });
+                let (label, value) = ctx.shortening.apply(&full_label, value, size, ctx.rotate);
+                if label != full_label {
+                    drawer.tooltip(&full_label);
+                }
+                draw_element(element, &label, size, ctx, drawer);
+                if let Some(value) = value {
+                    drawer.value(&value);
+                }
+            }
             circuit::SubCircuit::Group(group) => group.draw(size, ctx, drawer),
         }
     }
 }
 
+/// The impedance of a capacitor or inductor with the given value at `frequency`, e.g. `-j159Ω @ 1kHz`
+fn impedance_annotation(element: &circuit::Element, value: f64, frequency: f64, locale: &format::Locale) -> Option<String> {
+    if !matches!(element.kind().impedance, Impedance::Capacitive | Impedance::Inductive) {
+        return None;
+    }
+    let values = analysis::Values::from([(element.label(), value)]);
+    let s = Complex64::new(0.0, 2.0 * std::f64::consts::PI * frequency);
+    let reactance = element.impedance()?.eval(s, &values)?.im;
+    if !reactance.is_finite() {
+        return None;
+    }
+    // three significant digits are plenty for an annotation
+    let reactance = format::significant(reactance, 3);
+    let sign = if reactance < 0.0 { "-" } else { "" };
+    Some(format!("{sign}j{} @ {}", format::si(reactance.abs(), "Ω", locale), format::si(frequency, "Hz", locale)))
+}
+
 impl Draw for circuit::Document<'_> {
     fn draw<D: Drawer>(&self, size: Size, ctx: Context, drawer: &mut D) {
         match self {
@@ -124,45 +397,184 @@ impl Draw for circuit::Document<'_> {
 
 impl Draw for circuit::Twoport<'_> {
     fn draw<D: Drawer>(&self, size: Size, ctx: Context, drawer: &mut D) {
-        let top_line = -size.1 / 2;
-        let bottom_line = size.1 / 2;
-        let mut offset = -size.0 / 2;
-        let mut links = self.links.iter().enumerate().peekable();
-        while let Some((i, link)) = links.next() {
-            let requested_size = link.layout_size();
-
-            offset += requested_size.0/2;
-                
-            match link {
-                circuit::TwoportLink::Series(circuit) => {
-                    circuit.draw(requested_size, ctx.translate(offset, -size.1/2), drawer);
-                    drawer.wire(Position(offset - requested_size.0 / 2, bottom_line), Position(offset + requested_size.0 / 2, bottom_line));
-                },
-                circuit::TwoportLink::Shunt(circuit) => {
-                    let left_exists = i != 0;
-                    let right_exists = links.peek().is_some();
-
-                    if left_exists {
-                        // top wire to the left
-                        drawer.wire(Position(offset - requested_size.0/2, top_line), Position(offset, top_line));
-                        // bottom wire to the left
-                        drawer.wire(Position(offset - requested_size.0/2, bottom_line), Position(offset, bottom_line));
-                    }
-                    if right_exists {
-                        // top wire to the right
-                        drawer.wire(Position(offset, top_line), Position(offset + requested_size.0/2, top_line));
-                        // bottom wire to the right
-                        drawer.wire(Position(offset, bottom_line), Position(offset + requested_size.0/2, bottom_line));
-                    }
-                    if left_exists && right_exists {
-                        drawer.junction(Position(offset, top_line));
-                        drawer.junction(Position(offset, bottom_line));
-                    }
+        let columns: Vec<i32> = self.links.iter().map(|link| link.layout_size_with(ctx.rules).0).collect();
+        draw_twoport_columns(self, &columns, size.1, ctx, drawer);
+    }
+}
+
+/// Draws a twoport, placing each link in a column of the given width.
+///
+/// Links that are narrower than their column have their wires extended to fill it.
+fn draw_twoport_columns<D: Drawer>(twoport: &circuit::Twoport, columns: &[i32], height: i32, ctx: Context, drawer: &mut D) {
+    let top_line = -height / 2;
+    let bottom_line = height / 2;
+    let mut offset = -columns.iter().sum::<i32>() / 2;
+    let mut links = twoport.links.iter().zip(columns).enumerate().peekable();
+    while let Some((i, (link, &width))) = links.next() {
+        let requested_size = link.layout_size_with(ctx.rules);
 
-                    circuit.draw(Size(size.1, requested_size.0), ctx.translate(offset, 0).rotate(), drawer);
-                },
+        offset += width / 2;
+
+        match link {
+            circuit::TwoportLink::Series(circuit) => {
+                circuit.draw(Size(width, requested_size.1), ctx.translate(offset, top_line), drawer);
+                drawer.wire(ctx.translate(offset - width / 2, bottom_line).position, ctx.translate(offset + width / 2, bottom_line).position);
+            },
+            circuit::TwoportLink::Shunt(circuit) => {
+                let left_exists = i != 0;
+                let right_exists = links.peek().is_some();
+
+                if left_exists {
+                    // top wire to the left
+                    drawer.wire(ctx.translate(offset - width / 2, top_line).position, ctx.translate(offset, top_line).position);
+                    // bottom wire to the left
+                    drawer.wire(ctx.translate(offset - width / 2, bottom_line).position, ctx.translate(offset, bottom_line).position);
+                }
+                if right_exists {
+                    // top wire to the right
+                    drawer.wire(ctx.translate(offset, top_line).position, ctx.translate(offset + width / 2, top_line).position);
+                    // bottom wire to the right
+                    drawer.wire(ctx.translate(offset, bottom_line).position, ctx.translate(offset + width / 2, bottom_line).position);
+                }
+                if left_exists && right_exists {
+                    drawer.junction(ctx.translate(offset, top_line).position);
+                    drawer.junction(ctx.translate(offset, bottom_line).position);
+                }
+
+                circuit.draw(Size(height, requested_size.0), ctx.translate(offset, 0).rotate(), drawer);
+            },
+            circuit::TwoportLink::Transformer { .. } => {
+                drawer.transformer(&link.label().unwrap_or_default(), ctx.translate(offset, 0).position, Size(width, height), ctx.rotate);
+            }
+            // stacked twoports may give the name a column, which is filled with wires
+            circuit::TwoportLink::Node(name) => {
+                if width > 0 {
+                    drawer.wire(ctx.translate(offset - width / 2, top_line).position, ctx.translate(offset + width / 2, top_line).position);
+                    drawer.wire(ctx.translate(offset - width / 2, bottom_line).position, ctx.translate(offset + width / 2, bottom_line).position);
+                }
+                if drawer.capabilities().text {
+                    drawer.node_label(name, ctx.translate(offset, top_line).position);
+                }
+            }
+            // like node names, annotations apply to the point of the top line where they appear
+            circuit::TwoportLink::Annotation { quantity, name } => {
+                if width > 0 {
+                    drawer.wire(ctx.translate(offset - width / 2, top_line).position, ctx.translate(offset + width / 2, top_line).position);
+                    drawer.wire(ctx.translate(offset - width / 2, bottom_line).position, ctx.translate(offset + width / 2, bottom_line).position);
+                }
+                let name = if drawer.capabilities().text { name } else { "" };
+                let top = ctx.translate(offset, top_line).position;
+                match quantity {
+                    circuit::Quantity::Current => drawer.current_arrow(name, top, ctx.rotate, ctx.flip()),
+                    circuit::Quantity::Voltage => drawer.voltage_arrow(name, top, ctx.translate(offset, bottom_line).position),
+                }
             }
-            offset += requested_size.0/2;
         }
+        offset += width / 2;
+    }
+}
+
+/// Draws several twoports below each other, for comparing variants of a circuit.
+///
+/// The n-th link of every twoport is placed in the same column, so corresponding links line up vertically.
+pub fn draw_stacked<D: Drawer>(twoports: &[circuit::Twoport], ctx: Context, drawer: &mut D) {
+    let columns = layout::shared_columns(twoports, ctx.rules);
+    let mut offset = -layout::stacked_size(twoports, ctx.rules).1 / 2;
+    for twoport in twoports {
+        let height = twoport.layout_size_with(ctx.rules).1;
+        offset += height / 2;
+        draw_twoport_columns(twoport, &columns, height, ctx.translate(0, offset), drawer);
+        offset += height / 2 + layout::STACK_SPACING;
+    }
+}
+
+/// Draws documents of any kind below each other, e.g. the sections of a source, each one centered horizontally.
+///
+/// Unlike [`draw_stacked`], the links of twoports are not aligned.
+pub fn draw_below<D: Drawer>(documents: &[circuit::Document], ctx: Context, drawer: &mut D) {
+    let untitled: Vec<_> = documents.iter().map(|document| (None, document)).collect();
+    draw_titled(&untitled, ctx, drawer);
+}
+
+/// Draws documents below each other like [`draw_below`], writing the title of each one above it
+pub fn draw_titled<D: Drawer>(documents: &[(Option<&str>, &circuit::Document)], ctx: Context, drawer: &mut D) {
+    let text = drawer.capabilities().text;
+    let title_height = |title: Option<&str>| if text && title.is_some() { layout::TITLE_HEIGHT } else { 0 };
+    let sizes: Vec<Size> = documents.iter().map(|(_, document)| document.layout_size_with(ctx.rules)).collect();
+    let height = documents.iter().zip(&sizes).map(|((title, _), size)| title_height(*title) + size.1).sum::<i32>()
+        + layout::STACK_SPACING * (documents.len() as i32 - 1).max(0);
+    let mut offset = -height / 2;
+    for ((title, document), size) in documents.iter().zip(sizes) {
+        if let Some(title) = title.filter(|_| text) {
+            drawer.title(title, ctx.translate(0, offset + layout::TITLE_HEIGHT / 3).position);
+            offset += layout::TITLE_HEIGHT;
+        }
+        offset += size.1 / 2;
+        document.draw(size, ctx.translate(0, offset), drawer);
+        offset += size.1 / 2 + layout::STACK_SPACING;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    /// Drawer written before switches and potentiometers, recording what it draws
+    struct OldDrawer {
+        calls: Vec<String>,
+        fallback: Fallback,
+    }
+
+    impl Drawer for OldDrawer {
+        fn capabilities(&self) -> DrawerCapabilities {
+            DrawerCapabilities { fallback: self.fallback, ..DrawerCapabilities::ALL }
+        }
+
+        fn resistor(&mut self, label: &str, _position: Position, _size: Size, _rotate: bool) {
+            self.calls.push(format!("resistor {label}"));
+        }
+
+        fn capacitor(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool) {}
+        fn inductor(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool) {}
+        fn voltage_source(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool, _flip: bool) {}
+        fn current_source(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool) {}
+        fn diode(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool, _flip: bool) {}
+        fn open(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool) {}
+        fn transformer(&mut self, _label: &str, _position: Position, _size: Size, _rotate: bool) {}
+        fn wire(&mut self, _a: Position, _b: Position) {}
+        fn junction(&mut self, _position: Position) {}
+    }
+
+    fn draw_with(input: &'static str, fallback: Fallback) -> Vec<String> {
+        let circuit = circuit::sub_circuit::<E>(input).unwrap().1;
+        let mut drawer = OldDrawer { calls: vec![], fallback };
+        circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
+        drawer.calls
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(draw_with("(R1+S1+P1>out)", Fallback::GenericBox), ["resistor R1", "resistor S1", "resistor P1"]);
+        assert_eq!(draw_with("(R1+S1+P1>out)", Fallback::Omit), ["resistor R1"]);
+    }
+
+    #[test]
+    fn test_impedance_annotation() {
+        let circuit = circuit::sub_circuit::<E>("(R1=1k+C1=1u+L1=10m+C2)").unwrap().1;
+        let mut list = display_list::DisplayList::new();
+        circuit.draw(circuit.layout_size(), Context::default().impedance_at(1e3), &mut list);
+        let texts: Vec<_> = list.shapes.iter().filter_map(|(_, shape)| match shape {
+            display_list::Shape::Text { text, .. } => Some(text.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(texts, ["R1", "1k", "C1", "1µ, -j159Ω @ 1kHz", "L1", "10m, j62.8Ω @ 1kHz", "C2"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the drawer has no symbol for Switch { closed: true } (element S1)")]
+    fn test_strict_fallback() {
+        draw_with("S1*", Fallback::Panic);
     }
 }
@@ -0,0 +1,334 @@
+//! Rendering to a grid of characters, using box-drawing characters for wires.
+
+use std::collections::BTreeMap;
+use crate::layout::{Size, Position};
+use super::symbols::{self, Geometry};
+
+/// Drawing units per column
+const COLUMN_WIDTH: i32 = 10;
+/// Drawing units per row (characters are about twice as high as wide)
+const ROW_HEIGHT: i32 = 20;
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+#[derive(Debug, Copy, Clone)]
+enum Cell {
+    /// Lines leaving the center of the cell, as a combination of `UP`, `DOWN`, `LEFT` and `RIGHT`
+    Lines(u8),
+    Char(char),
+}
+
+impl Cell {
+    fn to_char(self) -> char {
+        match self {
+            Cell::Char(c) => c,
+            Cell::Lines(lines) => match lines {
+                UP => '╵',
+                DOWN => '╷',
+                LEFT => '╴',
+                RIGHT => '╶',
+                0b0011 => '│',
+                0b1100 => '─',
+                0b1001 => '└',
+                0b0101 => '┘',
+                0b1010 => '┌',
+                0b0110 => '┐',
+                0b1011 => '├',
+                0b0111 => '┤',
+                0b1110 => '┬',
+                0b1101 => '┴',
+                0b1111 => '┼',
+                _ => ' ',
+            },
+        }
+    }
+}
+
+/// Drawer rendering the circuit as text, e.g. for terminals and doc comments.
+///
+/// Resistors, inductors and sources show their label in the symbol (`[R1]`, `[L1]`, `(V1)`, `<E1>`), other
+/// elements have it written below (or next to) them. Characters can't be turned or curved, so vertical elements
+/// get symbols of their own and inductors are boxes, see [`super::DrawerCapabilities`].
+#[derive(Default)]
+pub struct AsciiDrawer {
+    /// Cells by row and column
+    cells: BTreeMap<(i32, i32), Cell>,
+}
+
+impl AsciiDrawer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Produces the text, with trailing spaces removed from each line
+    pub fn finalize(self) -> String {
+        let Some(min_column) = self.cells.keys().map(|(_, column)| *column).min() else {
+            return String::new();
+        };
+        let max_column = self.cells.keys().map(|(_, column)| *column).max().unwrap();
+        let min_row = self.cells.keys().next().unwrap().0;
+        let max_row = self.cells.keys().next_back().unwrap().0;
+        let mut text = String::new();
+        for row in min_row..=max_row {
+            let line: String = (min_column..=max_column)
+                .map(|column| self.cells.get(&(row, column)).map_or(' ', |cell| cell.to_char()))
+                .collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn add_lines(&mut self, row: i32, column: i32, lines: u8) {
+        let cell = self.cells.entry((row, column)).or_insert(Cell::Lines(0));
+        if let Cell::Lines(existing) = cell {
+            *existing |= lines;
+        }
+    }
+
+    fn text(&mut self, row: i32, column: i32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.cells.insert((row, column + i as i32), Cell::Char(c));
+        }
+    }
+
+    /// Draws a line between two cells in the same row or column
+    fn line(&mut self, (row1, column1): (i32, i32), (row2, column2): (i32, i32)) {
+        if row1 == row2 {
+            let (start, end) = (column1.min(column2), column1.max(column2));
+            for column in start..=end {
+                let lines = if column > start { LEFT } else { 0 } | if column < end { RIGHT } else { 0 };
+                self.add_lines(row1, column, lines);
+            }
+        } else {
+            let (start, end) = (row1.min(row2), row1.max(row2));
+            for row in start..=end {
+                let lines = if row > start { UP } else { 0 } | if row < end { DOWN } else { 0 };
+                self.add_lines(row, column1, lines);
+            }
+        }
+    }
+
+    /// Draws an element with the given symbol, its label being written beside it unless it is part of the symbol.
+    ///
+    /// Horizontal elements use the first line of `symbol`, vertical ones use one line per row.
+    fn element(&mut self, symbol: &[&str], label: Option<&str>, position: Position, size: Size, rotate: bool) {
+        let center = cell(position);
+        let half = if rotate { size.0 / 2 / ROW_HEIGHT } else { size.0 / 2 / COLUMN_WIDTH };
+        if rotate {
+            let top = center.0 - symbol.len() as i32 / 2;
+            let bottom = top + symbol.len() as i32 - 1;
+            for (i, line) in symbol.iter().enumerate() {
+                let width = line.chars().count() as i32;
+                self.text(top + i as i32, center.1 - (width - 1) / 2, line);
+            }
+            self.line((center.0 - half, center.1), (top - 1, center.1));
+            self.add_lines(top - 1, center.1, DOWN);
+            self.line((bottom + 1, center.1), (center.0 + half, center.1));
+            self.add_lines(bottom + 1, center.1, UP);
+            if let Some(label) = label {
+                self.text(center.0, center.1 + 2, label);
+            }
+        } else {
+            let width = symbol[0].chars().count() as i32;
+            let left = center.1 - width / 2;
+            let right = left + width - 1;
+            self.text(center.0, left, symbol[0]);
+            self.line((center.0, center.1 - half), (center.0, left - 1));
+            self.add_lines(center.0, left - 1, RIGHT);
+            self.line((center.0, right + 1), (center.0, center.1 + half));
+            self.add_lines(center.0, right + 1, LEFT);
+            if let Some(label) = label {
+                self.text(center.0 + 1, center.1 - (label.chars().count() as i32 - 1) / 2, label);
+            }
+        }
+    }
+}
+
+/// Returns the row and column of the cell containing the position
+fn cell(Position(x, y): Position) -> (i32, i32) {
+    ((y + ROW_HEIGHT / 2).div_euclid(ROW_HEIGHT), (x + COLUMN_WIDTH / 2).div_euclid(COLUMN_WIDTH))
+}
+
+impl super::Drawer for AsciiDrawer {
+    fn capabilities(&self) -> super::DrawerCapabilities {
+        super::DrawerCapabilities { curves: false, rotation: false, colors: false, values: false, ..super::DrawerCapabilities::ALL }
+    }
+
+    fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element(&[&format!("[{label}]")], None, position, size, rotate);
+    }
+
+    fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let symbol: &[&str] = if rotate { &["─┴─", "─┬─"] } else { &["┤├"] };
+        self.element(symbol, Some(label), position, size, rotate);
+    }
+
+    // without curves, inductors are drawn as boxes like impedances
+    fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.resistor(label, position, size, rotate);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, _flip: bool) {
+        self.element(&[&format!("({label})")], None, position, size, rotate);
+    }
+
+    fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element(&[&format!("({label})")], None, position, size, rotate);
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let symbol: &[&str] = match (rotate, flip) {
+            (true, false) => &["▼", "─"],
+            (true, true) => &["─", "▲"],
+            (false, false) => &["▶|"],
+            (false, true) => &["|◀"],
+        };
+        self.element(symbol, Some(label), position, size, rotate);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let symbol: &[&str] = match (rotate, flip) {
+            (true, false) => &["▼", "Z"],
+            (true, true) => &["Z", "▲"],
+            (false, false) => &["▶Z"],
+            (false, true) => &["Z◀"],
+        };
+        self.element(symbol, Some(label), position, size, rotate);
+    }
+
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        let symbol: &[&str] = match (rotate, closed) {
+            (true, false) => &["o", "\\", "o"],
+            (true, true) => &["o", "│", "o"],
+            (false, false) => &["o╱ o"],
+            (false, true) => &["o──o"],
+        };
+        self.element(symbol, Some(label), position, size, rotate);
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, _flip: bool, _current: bool) {
+        self.element(&[&format!("<{label}>")], None, position, size, rotate);
+    }
+
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, tap: Position) {
+        self.element(&[&format!("[{label}]")], None, position, size, rotate);
+        let arrow = if rotate { '◀' } else { '▼' };
+        self.cells.insert(cell(tap), Cell::Char(arrow));
+    }
+
+    fn open(&mut self, _label: &str, position: Position, size: Size, rotate: bool) {
+        let (dx, dy) = if rotate { (0, size.0 / 2) } else { (size.0 / 2, 0) };
+        for (x, y) in [(position.0 - dx, position.1 - dy), (position.0 + dx, position.1 + dy)] {
+            let (row, column) = cell(Position(x, y));
+            self.cells.insert((row, column), Cell::Char('o'));
+        }
+    }
+
+    // drawn upright, as part of a twoport
+    fn transformer(&mut self, label: &str, position: Position, size: Size, _rotate: bool) {
+        let transformer = Geometry::STANDARD.transformer;
+        let place = |point| cell(symbols::place(position, false, point));
+        for [corner, bend, end] in transformer.leads(size) {
+            self.line(place(corner), place(bend));
+            self.line(place(bend), place(end));
+        }
+        let length = transformer.coil_length();
+        let ((top, primary), (bottom, secondary)) = (place((-transformer.separation / 2, -length / 2)), place((transformer.separation / 2, length / 2)));
+        for row in top + 1..bottom {
+            self.text(row, primary, "(");
+            self.text(row, (primary + secondary) / 2, "║");
+            self.text(row, secondary, ")");
+        }
+        self.text((top + bottom) / 2, primary - 1 - label.chars().count() as i32, label);
+    }
+
+    fn wire(&mut self, a: Position, b: Position) {
+        self.line(cell(a), cell(b));
+    }
+
+    fn node_label(&mut self, name: &str, position: Position) {
+        let (row, column) = cell(position);
+        self.text(row, column + 1, name);
+    }
+
+    fn title(&mut self, title: &str, position: Position) {
+        let (row, column) = cell(position);
+        self.text(row, column - (title.chars().count() as i32 - 1) / 2, title);
+    }
+
+    // junctions follow from the wires meeting in a cell
+    fn junction(&mut self, _position: Position) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_draw_twoport() {
+        let mut drawer = AsciiDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-R1|C1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        assert_eq!(drawer.finalize(), [
+            " ┌──────────[R1]───────────┐",
+            " │                         │",
+            " │                         │",
+            " │                         │",
+            " │                        ─┴─",
+            "(V1)                      ─┬─C1",
+            " │                         │",
+            " │                         │",
+            " │                         │",
+            " │                         │",
+            " └─────────────────────────┘",
+        ].map(|line| format!("{line}\n")).concat());
+    }
+
+    #[test]
+    fn test_inductors() {
+        let mut drawer = AsciiDrawer::new();
+        let twoport = circuit::twoport::<E>("-L1|L2").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        assert_eq!(drawer.finalize(), [
+            "╶───────[L1]───────────┐",
+            "                       │",
+            "                       │",
+            "                       │",
+            "                       │",
+            "                      [L2]",
+            "                       │",
+            "                       │",
+            "                       │",
+            "                       │",
+            "╶──────────────────────┘",
+        ].map(|line| format!("{line}\n")).concat());
+    }
+
+    #[test]
+    fn test_diodes() {
+        let mut drawer = AsciiDrawer::new();
+        // pointing to their first end, i.e. up
+        let twoport = circuit::twoport::<E>("|^D1|^Dz1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        assert_eq!(drawer.finalize(), [
+            "┌─────┐",
+            "│     │",
+            "│     │",
+            "│     │",
+            "─     Z",
+            "▲ D1  ▲ Dz1",
+            "│     │",
+            "│     │",
+            "│     │",
+            "│     │",
+            "└─────┘",
+        ].map(|line| format!("{line}\n")).concat());
+    }
+}
@@ -0,0 +1,474 @@
+use crate::layout::{Size, Position};
+use super::symbols::{self, Geometry, SymbolStandard, JUNCTION_RADIUS, LINE_HEIGHT, STROKE_WIDTH};
+
+/// Layer a shape belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layer {
+    Wires,
+    Symbols,
+    Labels,
+}
+
+/// Vector primitive, in drawing coordinates (y pointing down)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shape {
+    /// Straight line, `width` being the stroke width
+    Line { a: Position, b: Position, width: i32 },
+    /// Circular arc, drawn from angle `start` to angle `end`, in degrees.
+    ///
+    /// Angles are measured from the x axis towards the y axis, like in SVG.
+    Arc { center: Position, radius: i32, start: i32, end: i32 },
+    Circle { center: Position, radius: i32, filled: bool },
+    /// Horizontal text, centered on `position`, which is on the baseline
+    Text { position: Position, text: String },
+}
+
+/// Drawer recording the drawing as a list of vector primitives.
+///
+/// Used by backends that only deal with lines, arcs and text (such as [`super::dxf`]).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DisplayList {
+    pub shapes: Vec<(Layer, Shape)>,
+    /// Position of the label of the element drawn last, where its value goes below
+    last_label: Option<Position>,
+    geometry: Geometry,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the geometry of the symbols ([`Geometry::STANDARD`] by default)
+    pub fn geometry(self, geometry: Geometry) -> Self {
+        Self { geometry, ..self }
+    }
+
+    /// Returns the top left and bottom right corners of the area covered by the shapes.
+    ///
+    /// Text is only accounted for by its anchor position.
+    pub fn bounds(&self) -> (Position, Position) {
+        let mut min = Position(i32::MAX, i32::MAX);
+        let mut max = Position(i32::MIN, i32::MIN);
+        let mut extend = |Position(x, y): Position, r: i32| {
+            min = Position(min.0.min(x - r), min.1.min(y - r));
+            max = Position(max.0.max(x + r), max.1.max(y + r));
+        };
+        for (_, shape) in &self.shapes {
+            match shape {
+                Shape::Line { a, b, .. } => {
+                    extend(*a, 0);
+                    extend(*b, 0);
+                }
+                Shape::Arc { center, radius, .. } | Shape::Circle { center, radius, .. } => extend(*center, *radius),
+                Shape::Text { position, .. } => extend(*position, 0),
+            }
+        }
+        if self.shapes.is_empty() {
+            (Position::zero(), Position::zero())
+        } else {
+            (min, max)
+        }
+    }
+
+    fn push(&mut self, layer: Layer, shape: Shape) {
+        self.shapes.push((layer, shape));
+    }
+
+    /// Adds a line given in element coordinates
+    fn symbol_line(&mut self, element: Element, a: (i32, i32), b: (i32, i32), width: i32) {
+        self.push(Layer::Symbols, Shape::Line { a: element.place(a), b: element.place(b), width });
+    }
+
+    /// Adds the leads from both ends of the element to its body, `body_width` wide
+    fn leads(&mut self, element: Element, body_width: i32) {
+        self.symbol_line(element, (-element.size.0 / 2, 0), (-body_width / 2, 0), STROKE_WIDTH);
+        self.symbol_line(element, (body_width / 2, 0), (element.size.0 / 2, 0), STROKE_WIDTH);
+    }
+
+    /// Adds the body of a resistor, a rectangle or a zigzag line depending on the standard
+    fn resistor_body(&mut self, element: Element) {
+        let resistor = self.geometry.resistor;
+        let (w, h) = (resistor.width, resistor.height);
+        let corners = match self.geometry.standard {
+            SymbolStandard::Iec => vec![(-w / 2, -h / 2), (w / 2, -h / 2), (w / 2, h / 2), (-w / 2, h / 2), (-w / 2, -h / 2)],
+            SymbolStandard::Ansi => resistor.zigzag().to_vec(),
+        };
+        for pair in corners.windows(2) {
+            self.symbol_line(element, pair[0], pair[1], STROKE_WIDTH);
+        }
+    }
+
+    /// Adds the leads, triangle and bar of a diode, pointing right unless flipped
+    fn diode_body(&mut self, element: Element, flip: bool) {
+        let d = if flip { -1 } else { 1 };
+        let (w, h) = (self.geometry.diode.half_width, self.geometry.diode.half_height);
+        self.leads(element, 2 * w);
+        self.symbol_line(element, (-d * w, -h), (-d * w, h), STROKE_WIDTH);
+        self.symbol_line(element, (-d * w, h), (d * w, 0), STROKE_WIDTH);
+        self.symbol_line(element, (d * w, 0), (-d * w, -h), STROKE_WIDTH);
+        self.symbol_line(element, (d * w, -h), (d * w, h), STROKE_WIDTH);
+    }
+
+    fn label(&mut self, element: Element, label: &str, (xoff, yoff): (i32, i32)) {
+        // labels stay horizontal, even for rotated elements
+        let Position(x, y) = element.position;
+        let position = if element.rotate { Position(x + xoff, y + 5) } else { Position(x, y + yoff) };
+        self.last_label = Some(position);
+        if label.is_empty() {
+            return;
+        }
+        self.push(Layer::Labels, Shape::Text { position, text: label.to_string() });
+    }
+}
+
+/// Placement of a single element
+#[derive(Copy, Clone)]
+struct Element {
+    position: Position,
+    size: Size,
+    rotate: bool,
+}
+
+impl Element {
+    /// Translates element coordinates into drawing coordinates
+    fn place(self, (x, y): (i32, i32)) -> Position {
+        if self.rotate {
+            Position(self.position.0 - y, self.position.1 + x)
+        } else {
+            Position(self.position.0 + x, self.position.1 + y)
+        }
+    }
+
+    fn angle(self, angle: i32) -> i32 {
+        if self.rotate { angle + 90 } else { angle }
+    }
+}
+
+impl super::Drawer for DisplayList {
+    fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        let resistor = self.geometry.resistor;
+        self.leads(element, resistor.width);
+        self.resistor_body(element);
+        self.label(element, label, resistor.label(self.geometry.standard));
+    }
+
+    fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        let capacitor = self.geometry.capacitor;
+        let (w, h) = (capacitor.gap, capacitor.plate_length);
+        self.leads(element, w);
+        self.symbol_line(element, (-w / 2, -h / 2), (-w / 2, h / 2), capacitor.plate_width);
+        self.symbol_line(element, (w / 2, -h / 2), (w / 2, h / 2), capacitor.plate_width);
+        self.label(element, label, capacitor.label);
+    }
+
+    fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        let inductor = self.geometry.inductor;
+        let (w, radius) = (inductor.width(), inductor.radius);
+        self.leads(element, w);
+        for i in 0..inductor.loops {
+            self.push(Layer::Symbols, Shape::Arc {
+                center: element.place((-w / 2 + radius * (2 * i + 1), 0)),
+                radius,
+                start: element.angle(180),
+                end: element.angle(360),
+            });
+        }
+        self.label(element, label, inductor.label);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let element = Element { position, size, rotate };
+        let source = self.geometry.voltage_source;
+        let (w, long, short) = (source.gap, source.long_plate, source.short_plate);
+        let direction = if flip { -1 } else { 1 };
+        self.leads(element, w);
+        self.symbol_line(element, (-direction * w / 2, -long / 2), (-direction * w / 2, long / 2), source.plate_width);
+        self.symbol_line(element, (direction * w / 2, -short / 2), (direction * w / 2, short / 2), source.plate_width);
+        self.label(element, label, source.label);
+    }
+
+    fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        let source = self.geometry.current_source;
+        self.leads(element, source.width());
+        for x in [-source.offset, source.offset] {
+            self.push(Layer::Symbols, Shape::Circle { center: element.place((x, 0)), radius: source.radius, filled: false });
+        }
+        self.label(element, label, source.label);
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let element = Element { position, size, rotate };
+        self.diode_body(element, flip);
+        self.label(element, label, self.geometry.diode.label);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let element = Element { position, size, rotate };
+        let d = if flip { -1 } else { 1 };
+        let diode = self.geometry.diode;
+        let (w, h, bend) = (diode.half_width, diode.half_height, diode.zener_bend);
+        self.diode_body(element, flip);
+        // bent ends of the bar
+        self.symbol_line(element, (d * w, -h), (d * (w - bend), -h - bend), STROKE_WIDTH);
+        self.symbol_line(element, (d * w, h), (d * (w + bend), h + bend), STROKE_WIDTH);
+        self.label(element, label, diode.label);
+    }
+
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        let element = Element { position, size, rotate };
+        let switch = self.geometry.switch;
+        let (w, r) = (switch.width, switch.contact_radius);
+        self.leads(element, w + 2 * r);
+        for x in [-w / 2, w / 2] {
+            self.push(Layer::Symbols, Shape::Circle { center: element.place((x, 0)), radius: r, filled: false });
+        }
+        let end = if closed { (w / 2, -r) } else { (w / 2, -switch.lift) };
+        self.symbol_line(element, (-w / 2, -r), end, STROKE_WIDTH);
+        self.label(element, label, switch.label);
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool, current: bool) {
+        let element = Element { position, size, rotate };
+        let source = self.geometry.controlled_source;
+        let d = source.half_diagonal;
+        let direction = if flip { -1 } else { 1 };
+        self.leads(element, 2 * d);
+        for (a, b) in [((-d, 0), (0, -d)), ((0, -d), (d, 0)), ((d, 0), (0, d)), ((0, d), (-d, 0))] {
+            self.symbol_line(element, a, b, STROKE_WIDTH);
+        }
+        for [(x1, y1), (x2, y2)] in source.marks(current) {
+            self.symbol_line(element, (direction * x1, y1), (direction * x2, y2), STROKE_WIDTH);
+        }
+        self.label(element, label, source.label);
+    }
+
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, tap: Position) {
+        let element = Element { position, size, rotate };
+        let (resistor, potentiometer) = (self.geometry.resistor, self.geometry.potentiometer);
+        self.leads(element, resistor.width);
+        self.resistor_body(element);
+        for [a, b] in potentiometer.wiper(resistor, symbols::unplace(position, rotate, tap)) {
+            self.symbol_line(element, a, b, STROKE_WIDTH);
+        }
+        self.label(element, label, resistor.label(self.geometry.standard));
+    }
+
+    fn open(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        for x in [-size.0 / 2, size.0 / 2] {
+            self.push(Layer::Symbols, Shape::Circle { center: element.place((x, 0)), radius: self.geometry.open.terminal_radius, filled: false });
+        }
+        self.label(element, label, self.geometry.open.label);
+    }
+
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let element = Element { position, size, rotate };
+        let transformer = self.geometry.transformer;
+        for [corner, bend, end] in transformer.leads(size) {
+            self.symbol_line(element, corner, bend, STROKE_WIDTH);
+            self.symbol_line(element, bend, end, STROKE_WIDTH);
+        }
+        let (x, radius, length) = (transformer.separation / 2, transformer.radius, transformer.coil_length());
+        for i in 0..transformer.loops {
+            let y = -length / 2 + radius * (2 * i + 1);
+            // both coils bulge away from the core
+            self.push(Layer::Symbols, Shape::Arc { center: element.place((-x, y)), radius, start: element.angle(90), end: element.angle(270) });
+            self.push(Layer::Symbols, Shape::Arc { center: element.place((x, y)), radius, start: element.angle(270), end: element.angle(450) });
+        }
+        for bar in [-transformer.core_gap / 2, transformer.core_gap / 2] {
+            self.symbol_line(element, (bar, -length / 2), (bar, length / 2), STROKE_WIDTH);
+        }
+        self.label(element, label, transformer.label);
+    }
+
+    fn wire(&mut self, a: Position, b: Position) {
+        self.push(Layer::Wires, Shape::Line { a, b, width: STROKE_WIDTH });
+    }
+
+    fn junction(&mut self, position: Position) {
+        self.push(Layer::Wires, Shape::Circle { center: position, radius: JUNCTION_RADIUS, filled: true });
+    }
+
+    fn polarity(&mut self, position: Position, rotate: bool, flip: bool, current: bool) {
+        let marks = self.geometry.polarity;
+        if current {
+            let element = Element { position, size: Size(0, 0), rotate };
+            for [a, b] in marks.arrow(flip) {
+                self.symbol_line(element, a, b, STROKE_WIDTH);
+            }
+        } else {
+            for [a, b] in marks.signs(position, rotate, flip) {
+                self.push(Layer::Symbols, Shape::Line { a, b, width: STROKE_WIDTH });
+            }
+        }
+    }
+
+    fn current_arrow(&mut self, name: &str, position: Position, rotate: bool, flip: bool) {
+        let element = Element { position, size: Size(0, 0), rotate };
+        let annotation = self.geometry.annotation;
+        for [a, b] in annotation.current_head(flip) {
+            self.symbol_line(element, a, b, STROKE_WIDTH);
+        }
+        if !name.is_empty() {
+            self.push(Layer::Labels, Shape::Text { position: element.place(annotation.label), text: name.to_string() });
+        }
+    }
+
+    fn voltage_arrow(&mut self, name: &str, a: Position, b: Position) {
+        let annotation = self.geometry.annotation;
+        for [a, b] in annotation.voltage_arrow(a, b) {
+            self.push(Layer::Symbols, Shape::Line { a, b, width: STROKE_WIDTH });
+        }
+        if !name.is_empty() {
+            // the text is centered, while the position is where it starts
+            let Position(x, y) = annotation.voltage_label(a, b);
+            self.push(Layer::Labels, Shape::Text { position: Position(x + symbols::text_width(name) / 2, y), text: name.to_string() });
+        }
+    }
+
+    fn node_label(&mut self, name: &str, Position(x, y): Position) {
+        self.junction(Position(x, y));
+        self.push(Layer::Labels, Shape::Text { position: Position(x + JUNCTION_RADIUS, y - JUNCTION_RADIUS), text: name.to_string() });
+    }
+
+    fn title(&mut self, title: &str, Position(x, y): Position) {
+        self.push(Layer::Labels, Shape::Text { position: Position(x, y + 5), text: title.to_string() });
+    }
+
+    fn value(&mut self, value: &str) {
+        if let Some(Position(x, y)) = self.last_label {
+            self.push(Layer::Labels, Shape::Text { position: Position(x, y + LINE_HEIGHT), text: value.to_string() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    #[test]
+    fn test_rotated_resistor() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::R("1");
+        element.draw(element.layout_size(), Context::default().rotate(), &mut list);
+        assert_eq!(list.shapes[0], (Layer::Symbols, Shape::Line { a: Position(0, -100), b: Position(0, -35), width: 2 }));
+        assert_eq!(list.shapes[6], (Layer::Labels, Shape::Text { position: Position(0, 5), text: "R1".to_string() }));
+    }
+
+    #[test]
+    fn test_geometry() {
+        let mut geometry = Geometry::STANDARD;
+        geometry.resistor.width = 100;
+        let mut list = DisplayList::new().geometry(geometry);
+        let element = circuit::Element::R("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        assert_eq!(list.shapes[0], (Layer::Symbols, Shape::Line { a: Position(-100, 0), b: Position(-50, 0), width: 2 }));
+    }
+
+    #[test]
+    fn test_ansi_resistor() {
+        let mut list = DisplayList::new().geometry(Geometry { standard: SymbolStandard::Ansi, ..Geometry::STANDARD });
+        let element = circuit::Element::R("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        // two leads and the seven lines of the zigzag
+        assert_eq!(list.shapes.iter().filter(|(_, shape)| matches!(shape, Shape::Line { .. })).count(), 9);
+        assert_eq!(list.shapes[2], (Layer::Symbols, Shape::Line { a: Position(-35, 0), b: Position(-29, -10), width: 2 }));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::I { id: "1", polarity: circuit::Polarity::Unmarked };
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        assert_eq!(list.bounds(), (Position(-100, -15), Position(100, 30)));
+        assert_eq!(DisplayList::new().bounds(), (Position(0, 0), Position(0, 0)));
+    }
+
+    #[test]
+    fn test_polarity() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::V { id: "1", polarity: circuit::Polarity::Up };
+        element.draw(element.layout_size(), Context::default().rotate(), &mut list);
+        // the `+` is beside the top plate, and the `−` stays horizontal beside the bottom one
+        assert!(list.shapes.contains(&(Layer::Symbols, Shape::Line { a: Position(16, -22), b: Position(16, -14), width: 2 })));
+        assert!(list.shapes.contains(&(Layer::Symbols, Shape::Line { a: Position(12, 18), b: Position(20, 18), width: 2 })));
+        let mut list = DisplayList::new();
+        let element = circuit::Element::I { id: "1", polarity: circuit::Polarity::Down };
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        // the arrow's head points to the right
+        assert!(list.shapes.contains(&(Layer::Symbols, Shape::Line { a: Position(15, -23), b: Position(11, -27), width: 2 })));
+
+        // diodes point to their second end unless marked to point to the first
+        let shapes = |polarity, mirror: bool| {
+            let mut list = DisplayList::new();
+            let element = circuit::Element::D { id: "1", polarity };
+            let ctx = if mirror { Context::default().mirror() } else { Context::default() };
+            element.draw(element.layout_size(), ctx, &mut list);
+            list.shapes.into_iter().filter(|(layer, _)| *layer == Layer::Symbols).collect::<Vec<_>>()
+        };
+        assert_eq!(shapes(circuit::Polarity::Down, false), shapes(circuit::Polarity::Unmarked, false));
+        assert_eq!(shapes(circuit::Polarity::Up, false), shapes(circuit::Polarity::Unmarked, true));
+        assert_ne!(shapes(circuit::Polarity::Up, false), shapes(circuit::Polarity::Unmarked, false));
+    }
+
+    #[test]
+    fn test_annotations() {
+        type E = nom::error::VerboseError<&'static str>;
+        let mut list = DisplayList::new();
+        let twoport = circuit::twoport::<E>("-R1{i:i1}|R2{u:u2}").unwrap().1;
+        let size = twoport.layout_size();
+        twoport.draw(size, Context::default(), &mut list);
+        let text = |name: &str| list.shapes.iter().find_map(|(_, shape)| match shape {
+            Shape::Text { position, text } if text == name => Some(*position),
+            _ => None,
+        });
+        // the current is named above the top line, the voltage right of the shunt
+        let (current, voltage) = (text("i1").unwrap(), text("u2").unwrap());
+        assert!(current.1 < -size.1 / 2);
+        assert!(voltage.0 > current.0);
+    }
+
+    #[test]
+    fn test_value() {
+        let mut list = DisplayList::new();
+        let circuit = circuit::sub_circuit::<nom::error::VerboseError<&str>>("C1=100nF").unwrap().1;
+        circuit.draw(circuit.layout_size(), Context::default(), &mut list);
+        assert_eq!(list.shapes.last(), Some(&(Layer::Labels, Shape::Text { position: Position(0, 50), text: "100nF".to_string() })));
+    }
+
+    #[test]
+    fn test_inductor_arcs() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::L("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        assert_eq!(list.shapes[2], (Layer::Symbols, Shape::Arc { center: Position(-30, 0), radius: 10, start: 180, end: 360 }));
+    }
+
+    #[test]
+    fn test_transformer() {
+        type E = nom::error::VerboseError<&'static str>;
+        let mut list = DisplayList::new();
+        let twoport = circuit::twoport::<E>("=T1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut list);
+        // the primary's top lead starts at the top left corner
+        assert_eq!(list.shapes[0], (Layer::Symbols, Shape::Line { a: Position(-60, -100), b: Position(-20, -100), width: 2 }));
+        assert_eq!(list.shapes[8], (Layer::Symbols, Shape::Arc { center: Position(-20, -30), radius: 10, start: 90, end: 270 }));
+        assert_eq!(list.shapes.iter().filter(|(_, shape)| matches!(shape, Shape::Arc { .. })).count(), 8);
+    }
+
+    #[test]
+    fn test_potentiometer() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::P { id: "1", wiper: Some("out") };
+        element.draw(element.layout_size(), Context::default().rotate(), &mut list);
+        // the wiper comes from the right of the vertical element
+        assert_eq!(list.shapes[6], (Layer::Symbols, Shape::Line { a: Position(30, 0), b: Position(10, 0), width: 2 }));
+        assert!(list.shapes.contains(&(Layer::Labels, Shape::Text { position: Position(33, -3), text: "out".to_string() })));
+    }
+}
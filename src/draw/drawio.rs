@@ -0,0 +1,241 @@
+use crate::layout::{Size, Position};
+use super::symbols::{self, Geometry};
+
+/// The stencils' heights follow the standard geometry, so drawings look alike
+const GEOMETRY: Geometry = Geometry::STANDARD;
+
+/// Common style of all element shapes, taken from the draw.io electrical library
+const ELEMENT_STYLE: &str = "pointerEvents=1;verticalLabelPosition=bottom;shadow=0;dashed=0;align=center;html=1;verticalAlign=top;";
+
+const WIRE_STYLE: &str = "endArrow=none;html=1;strokeWidth=2;";
+
+const TERMINAL_STYLE: &str = "ellipse;aspect=fixed;strokeWidth=2;fillColor=#FFFFFF;";
+
+const JUNCTION_STYLE: &str = "ellipse;aspect=fixed;strokeColor=none;fillColor=#000000;";
+
+enum Cell {
+    Vertex { style: String, label: String, position: Position, size: Size },
+    Edge { a: Position, b: Position },
+}
+
+/// Drawer producing a diagram for [draw.io](https://www.drawio.com/) (also known as diagrams.net).
+///
+/// Elements are placed as shapes from the draw.io electrical library, wires become unconnected edges.
+pub struct DrawioDrawer {
+    cells: Vec<Cell>,
+}
+
+impl Default for DrawioDrawer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawioDrawer {
+    pub fn new() -> Self {
+        Self { cells: vec![] }
+    }
+
+    /// Produces the mxGraph XML document, which can be opened with draw.io
+    pub fn finalize(self) -> String {
+        let margin = 30;
+        let min_x = self.cells.iter().map(|cell| match cell {
+            Cell::Vertex { position, size, .. } => position.0 - size.0 / 2,
+            Cell::Edge { a, b } => a.0.min(b.0),
+        }).min().unwrap_or(0) - margin;
+        let min_y = self.cells.iter().map(|cell| match cell {
+            Cell::Vertex { position, size, .. } => position.1 - size.1 / 2,
+            Cell::Edge { a, b } => a.1.min(b.1),
+        }).min().unwrap_or(0) - margin;
+
+        let mut xml = String::new();
+        xml.push_str("<mxfile host=\"circmark\">\n");
+        xml.push_str("  <diagram name=\"circmark\" id=\"circmark\">\n");
+        xml.push_str("    <mxGraphModel grid=\"1\" gridSize=\"10\" guides=\"1\" page=\"0\">\n");
+        xml.push_str("      <root>\n");
+        xml.push_str("        <mxCell id=\"0\"/>\n");
+        xml.push_str("        <mxCell id=\"1\" parent=\"0\"/>\n");
+        for (i, cell) in self.cells.iter().enumerate() {
+            let id = i + 2;
+            match cell {
+                Cell::Vertex { style, label, position, size } => {
+                    xml.push_str(&format!(
+                        "        <mxCell id=\"{id}\" value=\"{}\" style=\"{}\" vertex=\"1\" parent=\"1\">\n",
+                        escape(label), escape(style),
+                    ));
+                    xml.push_str(&format!(
+                        "          <mxGeometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" as=\"geometry\"/>\n",
+                        position.0 - size.0 / 2 - min_x, position.1 - size.1 / 2 - min_y, size.0, size.1,
+                    ));
+                }
+                Cell::Edge { a, b } => {
+                    xml.push_str(&format!("        <mxCell id=\"{id}\" style=\"{WIRE_STYLE}\" edge=\"1\" parent=\"1\">\n"));
+                    xml.push_str("          <mxGeometry relative=\"1\" as=\"geometry\">\n");
+                    xml.push_str(&format!("            <mxPoint x=\"{}\" y=\"{}\" as=\"sourcePoint\"/>\n", a.0 - min_x, a.1 - min_y));
+                    xml.push_str(&format!("            <mxPoint x=\"{}\" y=\"{}\" as=\"targetPoint\"/>\n", b.0 - min_x, b.1 - min_y));
+                    xml.push_str("          </mxGeometry>\n");
+                }
+            }
+            xml.push_str("        </mxCell>\n");
+        }
+        xml.push_str("      </root>\n");
+        xml.push_str("    </mxGraphModel>\n");
+        xml.push_str("  </diagram>\n");
+        xml.push_str("</mxfile>\n");
+        xml
+    }
+
+    /// Places a shape of the electrical library, `size` being the unrotated size of the symbol including its leads
+    fn element(&mut self, shape: &str, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let mut style = format!("{ELEMENT_STYLE}shape=mxgraph.electrical.{shape};");
+        if rotate {
+            style.push_str("rotation=90;");
+        }
+        if flip {
+            style.push_str("flipH=1;");
+        }
+        self.cells.push(Cell::Vertex { style, label: label.to_string(), position, size });
+    }
+}
+
+impl super::Drawer for DrawioDrawer {
+    fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element("resistors.resistor_2", label, position, Size(size.0, GEOMETRY.resistor.height), rotate, false);
+    }
+
+    fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element("capacitors.capacitor_1", label, position, Size(size.0, GEOMETRY.capacitor.plate_length), rotate, false);
+    }
+
+    fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element("inductors.inductor_3", label, position, Size(size.0, GEOMETRY.inductor.radius), rotate, false);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.element("miscellaneous.monocell_battery", label, position, Size(size.0, GEOMETRY.voltage_source.long_plate), rotate, flip);
+    }
+
+    fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.element("signal_sources.dc_source_2", label, position, Size(size.0, 2 * GEOMETRY.current_source.radius), rotate, false);
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.element("diodes.diode", label, position, Size(size.0, 2 * GEOMETRY.diode.half_height), rotate, flip);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.element("diodes.zener_diode_1", label, position, Size(size.0, 2 * GEOMETRY.diode.half_height), rotate, flip);
+    }
+
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        let shape = if closed { "electro-mechanical.break_contact" } else { "electro-mechanical.make_contact" };
+        self.element(shape, label, position, Size(size.0, GEOMETRY.switch.lift), rotate, false);
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool, current: bool) {
+        let shape = if current { "signal_sources.dependent_source_2" } else { "signal_sources.dependent_source_3" };
+        self.element(shape, label, position, Size(size.0, 2 * GEOMETRY.controlled_source.half_diagonal), rotate, flip);
+    }
+
+    // the shape has its own wiper, which is connected to the tap
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, tap: Position) {
+        let height = 2 * GEOMETRY.resistor.height;
+        self.element("resistors.potentiometer_1", label, position, Size(size.0, height), rotate, false);
+        self.wire(tap, symbols::place(position, rotate, (0, -height / 2)));
+    }
+
+    fn open(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let (dx, dy) = if rotate { (0, size.0 / 2) } else { (size.0 / 2, 0) };
+        for (x, y) in [(position.0 - dx, position.1 - dy), (position.0 + dx, position.1 + dy)] {
+            self.cells.push(Cell::Vertex {
+                style: TERMINAL_STYLE.to_string(),
+                label: String::new(),
+                position: Position(x, y),
+                size: Size(10, 10),
+            });
+        }
+        if !label.is_empty() {
+            self.cells.push(Cell::Vertex {
+                style: "text;html=1;align=center;verticalAlign=middle;".to_string(),
+                label: label.to_string(),
+                position,
+                size: Size(40, 20),
+            });
+        }
+    }
+
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let transformer = GEOMETRY.transformer;
+        for [corner, bend, end] in transformer.leads(size) {
+            let [corner, bend, end] = [corner, bend, end].map(|point| symbols::place(position, rotate, point));
+            self.wire(corner, bend);
+            self.wire(bend, end);
+        }
+        let shape_size = Size(transformer.coil_length(), transformer.separation + 2 * transformer.radius);
+        // the shape's coils are horizontal, so it is rotated unless the link is
+        self.element("inductors.transformer_1", label, position, shape_size, !rotate, false);
+    }
+
+    fn wire(&mut self, a: Position, b: Position) {
+        self.cells.push(Cell::Edge { a, b });
+    }
+
+    fn value(&mut self, value: &str) {
+        // shown on a second line of the label of the element drawn last (labels are HTML)
+        if let Some(Cell::Vertex { label, .. }) = self.cells.last_mut() {
+            label.push_str("<br>");
+            label.push_str(value);
+        }
+    }
+
+    fn junction(&mut self, position: Position) {
+        self.cells.push(Cell::Vertex {
+            style: JUNCTION_STYLE.to_string(),
+            label: String::new(),
+            position,
+            size: Size(6, 6),
+        });
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_draw_twoport() {
+        let mut drawer = DrawioDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-R1|C1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let xml = drawer.finalize();
+        assert!(xml.starts_with("<mxfile"));
+        assert_eq!(xml.matches("vertex=\"1\"").count(), 3);
+        assert_eq!(xml.matches("edge=\"1\"").count(), 5);
+        assert!(xml.contains("value=\"R1\" style=\"pointerEvents=1;verticalLabelPosition=bottom;shadow=0;dashed=0;align=center;html=1;verticalAlign=top;shape=mxgraph.electrical.resistors.resistor_2;\""));
+        assert!(xml.contains("value=\"C1\""));
+        assert!(xml.contains("rotation=90;"));
+    }
+
+    #[test]
+    fn test_value() {
+        let mut drawer = DrawioDrawer::new();
+        let twoport = circuit::twoport::<E>("-R1=4.7k").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        assert!(drawer.finalize().contains("value=\"R1&lt;br&gt;4.7k\""));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
@@ -0,0 +1,111 @@
+//! DXF (R12) export of a [`DisplayList`], with wires, symbols and labels on separate layers.
+
+use super::display_list::{DisplayList, Layer, Shape};
+
+/// Height of label text, in drawing units
+const TEXT_HEIGHT: i32 = 16;
+
+fn layer_name(layer: Layer) -> &'static str {
+    match layer {
+        Layer::Wires => "WIRES",
+        Layer::Symbols => "SYMBOLS",
+        Layer::Labels => "LABELS",
+    }
+}
+
+struct Writer {
+    output: String,
+}
+
+impl Writer {
+    fn pair<T: std::fmt::Display>(&mut self, code: i32, value: T) {
+        self.output.push_str(&format!("{code}\n{value}\n"));
+    }
+
+    /// Writes a point, flipping the y axis (DXF's y axis points up)
+    fn point(&mut self, code: i32, x: i32, y: i32) {
+        self.pair(code, x);
+        self.pair(code + 10, -y);
+        self.pair(code + 20, 0);
+    }
+}
+
+/// Produces a DXF document containing all shapes of the display list
+pub fn to_dxf(list: &DisplayList) -> String {
+    let mut w = Writer { output: String::new() };
+
+    w.pair(0, "SECTION");
+    w.pair(2, "TABLES");
+    w.pair(0, "TABLE");
+    w.pair(2, "LAYER");
+    w.pair(70, 3);
+    for layer in [Layer::Wires, Layer::Symbols, Layer::Labels] {
+        w.pair(0, "LAYER");
+        w.pair(2, layer_name(layer));
+        w.pair(70, 0);
+        w.pair(62, 7);
+        w.pair(6, "CONTINUOUS");
+    }
+    w.pair(0, "ENDTAB");
+    w.pair(0, "ENDSEC");
+
+    w.pair(0, "SECTION");
+    w.pair(2, "ENTITIES");
+    for (layer, shape) in &list.shapes {
+        match shape {
+            Shape::Line { a, b, .. } => {
+                w.pair(0, "LINE");
+                w.pair(8, layer_name(*layer));
+                w.point(10, a.0, a.1);
+                w.point(11, b.0, b.1);
+            }
+            Shape::Arc { center, radius, start, end } => {
+                // flipping the y axis turns the arc around, so start and end swap
+                w.pair(0, "ARC");
+                w.pair(8, layer_name(*layer));
+                w.point(10, center.0, center.1);
+                w.pair(40, radius);
+                w.pair(50, (-end).rem_euclid(360));
+                w.pair(51, (-start).rem_euclid(360));
+            }
+            Shape::Circle { center, radius, .. } => {
+                w.pair(0, "CIRCLE");
+                w.pair(8, layer_name(*layer));
+                w.point(10, center.0, center.1);
+                w.pair(40, radius);
+            }
+            Shape::Text { position, text } => {
+                w.pair(0, "TEXT");
+                w.pair(8, layer_name(*layer));
+                w.point(10, position.0, position.1);
+                w.pair(40, TEXT_HEIGHT);
+                w.pair(1, text);
+                // centered on the baseline
+                w.pair(72, 1);
+                w.point(11, position.0, position.1);
+            }
+        }
+    }
+    w.pair(0, "ENDSEC");
+    w.pair(0, "EOF");
+    w.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    #[test]
+    fn test_inductor() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::L("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        let dxf = to_dxf(&list);
+        assert!(dxf.starts_with("0\nSECTION\n2\nTABLES\n"));
+        assert!(dxf.ends_with("0\nENDSEC\n0\nEOF\n"));
+        assert_eq!(dxf.matches("0\nARC\n8\nSYMBOLS\n").count(), 4);
+        assert!(dxf.contains("0\nARC\n8\nSYMBOLS\n10\n-30\n20\n0\n30\n0\n40\n10\n50\n0\n51\n180\n"));
+        assert!(dxf.contains("0\nTEXT\n8\nLABELS\n10\n0\n20\n20\n30\n0\n40\n16\n1\nL1\n"));
+    }
+}
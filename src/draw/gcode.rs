@@ -0,0 +1,87 @@
+//! G-code export of a [`DisplayList`], for laser engravers.
+//!
+//! Lines, arcs and circles are engraved as paths. Text is left out, since engraving
+//! it would require a stroke font.
+
+use super::display_list::{DisplayList, Shape};
+
+/// Parameters of the engraving job
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GcodeOptions {
+    /// Size of one drawing unit, in millimeters
+    pub scale: f64,
+    /// Feed rate while engraving, in millimeters per minute
+    pub feed: f64,
+    /// Laser power, as passed to the `S` word of `M4`
+    pub power: u32,
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        Self {
+            scale: 0.25,
+            feed: 1000.0,
+            power: 1000,
+        }
+    }
+}
+
+/// Produces a G-code program engraving all shapes of the display list.
+///
+/// The drawing is moved so that its bottom left corner is at the machine origin.
+pub fn to_gcode(list: &DisplayList, options: &GcodeOptions) -> String {
+    let (min, max) = list.bounds();
+    // the machine's y axis points up
+    let point = |x: f64, y: f64| -> String {
+        format!("X{:.3} Y{:.3}", (x - min.0 as f64) * options.scale, (max.1 as f64 - y) * options.scale)
+    };
+    let mut output = String::from("G21\nG90\nM5\n");
+    let mut engrave = |start: String, path: String| {
+        output.push_str(&format!("G0 {start}\nM4 S{}\n{path}\nM5\n", options.power));
+    };
+    let feed = options.feed;
+    for (_, shape) in &list.shapes {
+        match shape {
+            Shape::Line { a, b, .. } => {
+                engrave(point(a.0 as f64, a.1 as f64), format!("G1 {} F{feed}", point(b.0 as f64, b.1 as f64)));
+            }
+            Shape::Arc { center, radius, start, end } => {
+                let at = |angle: i32| {
+                    let angle = (angle as f64).to_radians();
+                    (center.0 as f64 + *radius as f64 * angle.cos(), center.1 as f64 + *radius as f64 * angle.sin())
+                };
+                let (start_x, start_y) = at(*start);
+                let (end_x, end_y) = at(*end);
+                // arcs are drawn clockwise, once the y axis is flipped
+                let (i, j) = ((center.0 as f64 - start_x) * options.scale, (start_y - center.1 as f64) * options.scale);
+                engrave(point(start_x, start_y), format!("G2 {} I{i:.3} J{j:.3} F{feed}", point(end_x, end_y)));
+            }
+            Shape::Circle { center, radius, .. } => {
+                let start = point((center.0 + radius) as f64, center.1 as f64);
+                let i = -*radius as f64 * options.scale;
+                engrave(start.clone(), format!("G2 {start} I{i:.3} J0.000 F{feed}"));
+            }
+            Shape::Text { .. } => {}
+        }
+    }
+    output.push_str("G0 X0 Y0\nM2\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    #[test]
+    fn test_inductor() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::L("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        let gcode = to_gcode(&list, &GcodeOptions::default());
+        assert!(gcode.starts_with("G21\nG90\nM5\nG0 X0.000 Y2.500\nM4 S1000\nG1 X15.000 Y2.500 F1000\nM5\n"));
+        assert!(gcode.contains("G0 X15.000 Y2.500\nM4 S1000\nG2 X20.000 Y2.500 I2.500 J0.000 F1000\nM5\n"));
+        assert!(gcode.ends_with("G0 X0 Y0\nM2\n"));
+        assert!(!gcode.contains("L1"));
+    }
+}
@@ -0,0 +1,81 @@
+//! HPGL export of a [`DisplayList`], for pen plotters.
+//!
+//! Each layer is drawn with its own pen: wires with pen 1, symbols with pen 2, labels with pen 3.
+
+use super::display_list::{DisplayList, Layer, Shape};
+use crate::layout::Position;
+
+/// Plotter units per millimeter, as defined by HPGL
+pub const UNITS_PER_MM: f64 = 40.0;
+
+fn pen(layer: Layer) -> i32 {
+    match layer {
+        Layer::Wires => 1,
+        Layer::Symbols => 2,
+        Layer::Labels => 3,
+    }
+}
+
+/// Produces HPGL commands plotting all shapes of the display list.
+///
+/// `scale` is the size of one drawing unit in millimeters. The drawing is
+/// moved so that its bottom left corner is at the plotter's origin.
+pub fn to_hpgl(list: &DisplayList, scale: f64) -> String {
+    let (min, max) = list.bounds();
+    // HPGL's y axis points up
+    let point = |x: f64, y: f64| -> String {
+        let x = ((x - min.0 as f64) * scale * UNITS_PER_MM).round() as i32;
+        let y = ((max.1 as f64 - y) * scale * UNITS_PER_MM).round() as i32;
+        format!("{x},{y}")
+    };
+    let at = |Position(x, y): Position| point(x as f64, y as f64);
+
+    let mut output = String::from("IN;");
+    // character size in centimeters, for 16 units high text
+    output.push_str(&format!("SI{:.3},{:.3};", 0.6 * 16.0 * scale / 10.0, 16.0 * scale / 10.0));
+    output.push_str("LO4;");
+    let mut current_pen = None;
+    for (layer, shape) in &list.shapes {
+        if current_pen != Some(pen(*layer)) {
+            current_pen = Some(pen(*layer));
+            output.push_str(&format!("SP{};", pen(*layer)));
+        }
+        match shape {
+            Shape::Line { a, b, .. } => {
+                output.push_str(&format!("PU{};PD{};", at(*a), at(*b)));
+            }
+            Shape::Arc { center, radius, start, end } => {
+                let start_x = center.0 as f64 + *radius as f64 * (*start as f64).to_radians().cos();
+                let start_y = center.1 as f64 + *radius as f64 * (*start as f64).to_radians().sin();
+                // flipping the y axis reverses the direction of the arc
+                output.push_str(&format!("PU{};PD;AA{},{};", point(start_x, start_y), at(*center), start - end));
+            }
+            Shape::Circle { center, radius, .. } => {
+                let radius = (*radius as f64 * scale * UNITS_PER_MM).round() as i32;
+                output.push_str(&format!("PU{};CI{radius};", at(*center)));
+            }
+            Shape::Text { position, text } => {
+                output.push_str(&format!("PU{};LB{text}\u{3};", at(*position)));
+            }
+        }
+    }
+    output.push_str("PU;SP0;");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    #[test]
+    fn test_capacitor() {
+        let mut list = DisplayList::new();
+        let element = circuit::Element::C("1");
+        element.draw(element.layout_size(), Context::default(), &mut list);
+        assert_eq!(
+            to_hpgl(&list, 0.25),
+            "IN;SI0.240,0.400;LO4;SP2;PU0,300;PD950,300;PU1050,300;PD2000,300;PU950,450;PD950,150;PU1050,450;PD1050,150;SP3;PU1000,0;LBC1\u{3};PU;SP0;",
+        );
+    }
+}
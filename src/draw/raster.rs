@@ -0,0 +1,340 @@
+//! Raster output, for places that don't accept SVG.
+
+use std::path::{Path, PathBuf};
+use resvg::{tiny_skia, usvg};
+use crate::{analysis::response::Response, layout::{Size, Position}, smith::SmithChart, sweep::Matrix};
+use super::{svg::{CustomSymbol, EmptyDrawing, Font, FontSource, SvgDrawer, Theme}, symbols::Geometry};
+
+pub use resvg::tiny_skia::Pixmap;
+
+/// Font labels are rendered with when no serif font is installed (or system fonts aren't used), so that rendering
+/// works on systems without any fonts, e.g. build servers
+const EMBEDDED_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSerif.ttf");
+
+/// Family of [`EMBEDDED_FONT`]
+const EMBEDDED_FAMILY: &str = "DejaVu Serif";
+
+/// Most pixels a drawing is rendered to, 64 megapixels taking 256 MB, so that large scales fail rather than
+/// running out of memory
+pub const MAX_PIXELS: u64 = 1 << 26;
+
+/// Error returned by [`RasterDrawer::finalize`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RasterError {
+    /// Nothing was drawn
+    Empty(EmptyDrawing),
+    /// The scaled drawing would be less than a pixel wide or high
+    TooSmall { scale: f32 },
+    /// The scaled drawing would have more than [`MAX_PIXELS`] pixels
+    TooLarge { width: f32, height: f32 },
+    /// The SVG document doesn't parse, e.g. as an attribute set with [`SvgDrawer::attribute`] has an invalid value
+    Svg(String),
+}
+
+impl std::fmt::Display for RasterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RasterError::Empty(error) => write!(f, "{error}"),
+            RasterError::TooSmall { scale } => write!(f, "scale {scale} makes the drawing less than a pixel in size"),
+            RasterError::TooLarge { width, height } => write!(f, "{width}×{height} pixels are too many, at most {MAX_PIXELS} are rendered"),
+            RasterError::Svg(error) => write!(f, "can't render SVG: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RasterError {}
+
+impl From<EmptyDrawing> for RasterError {
+    fn from(error: EmptyDrawing) -> Self {
+        RasterError::Empty(error)
+    }
+}
+
+/// Drawer producing a pixmap, by drawing SVG and rendering it with [resvg](https://github.com/linebender/resvg).
+///
+/// Labels are rendered with the fonts installed on the system, falling back to the DejaVu Serif font built into
+/// the binary. A font given with [`RasterDrawer::font`] is used instead if it has an embedded file, and the fonts of
+/// [`RasterDrawer::font_dir`] replace the embedded font.
+pub struct RasterDrawer {
+    svg: SvgDrawer,
+    scale: f32,
+    system_fonts: bool,
+    /// Font files to load besides the embedded font
+    fonts: Vec<Vec<u8>>,
+    /// Directory of font files to load besides the embedded font
+    font_dir: Option<PathBuf>,
+}
+
+impl Default for RasterDrawer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RasterDrawer {
+    pub fn new() -> Self {
+        Self { svg: SvgDrawer::new(), scale: 1.0, system_fonts: true, fonts: vec![], font_dir: None }
+    }
+
+    /// Sets the number of pixels per drawing unit (1 by default)
+    pub fn scale(self, scale: f32) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// Draws the symbol named `name` of registered kinds of elements, see [`SvgDrawer::custom_symbol`]
+    pub fn custom_symbol(self, name: &'static str, draw: CustomSymbol) -> Self {
+        Self { svg: self.svg.custom_symbol(name, draw), ..self }
+    }
+
+    /// Sets the geometry of the symbols, see [`SvgDrawer::geometry`]
+    pub fn geometry(self, geometry: Geometry) -> Self {
+        Self { svg: self.svg.geometry(geometry), ..self }
+    }
+
+    /// Sets the colors, strokes and text of the drawing, see [`SvgDrawer::theme`]
+    pub fn theme(mut self, theme: Theme) -> Self {
+        if let Some(Font { source: Some(FontSource::Embedded { data, .. }), .. }) = &theme.font {
+            self.fonts.push(data.clone());
+        }
+        Self { svg: self.svg.theme(theme), ..self }
+    }
+
+    /// Sets the font of labels, see [`SvgDrawer::font`]. Font files embedded with [`FontSource::Embedded`] are
+    /// loaded for rendering, while fonts referenced by URL must be installed
+    pub fn font(mut self, font: Font) -> Self {
+        if let Some(FontSource::Embedded { data, .. }) = &font.source {
+            self.fonts.push(data.clone());
+        }
+        Self { svg: self.svg.font(font), ..self }
+    }
+
+    /// Whether to look up the fonts installed on the system (the default), rather than only using the embedded
+    /// font and those given with [`RasterDrawer::font`], which makes the result the same on every system
+    pub fn system_fonts(self, system_fonts: bool) -> Self {
+        Self { system_fonts, ..self }
+    }
+
+    /// Loads the font files of the directory (and its subdirectories) for rendering, the first of which is used
+    /// instead of the embedded font where no serif font is installed, e.g. for labels in another script
+    pub fn font_dir(self, font_dir: impl Into<PathBuf>) -> Self {
+        Self { font_dir: Some(font_dir.into()), ..self }
+    }
+
+    /// Draws a Smith chart below the drawing, see [`SvgDrawer::smith_chart`]
+    pub fn smith_chart(&mut self, chart: &SmithChart) {
+        self.svg.smith_chart(chart);
+    }
+
+    /// Draws plots of a frequency response below the drawing, see [`SvgDrawer::response_plot`]
+    pub fn response_plot(&mut self, response: &Response) {
+        self.svg.response_plot(response);
+    }
+
+    /// Draws the table of a sweep below the drawing, see [`SvgDrawer::heatmap`]
+    pub fn heatmap(&mut self, matrix: &Matrix) {
+        self.svg.heatmap(matrix);
+    }
+
+    /// Renders the drawing on a transparent background, failing if nothing was drawn or the scale makes it less than
+    /// a pixel or more than [`MAX_PIXELS`]
+    pub fn finalize(mut self) -> Result<Pixmap, RasterError> {
+        let svg = self.svg.finalize()?.to_string();
+        let mut options = usvg::Options::default();
+        load_fonts(options.fontdb_mut(), self.system_fonts, self.fonts, self.font_dir.as_deref());
+        let tree = usvg::Tree::from_str(&svg, &options).map_err(|error| RasterError::Svg(error.to_string()))?;
+        let size = tree.size().to_int_size();
+        let width = (size.width() as f32 * self.scale).round();
+        let height = (size.height() as f32 * self.scale).round();
+        // also rejects NaN
+        if !(width >= 1.0 && height >= 1.0) {
+            return Err(RasterError::TooSmall { scale: self.scale });
+        }
+        if width * height > MAX_PIXELS as f32 {
+            return Err(RasterError::TooLarge { width, height });
+        }
+        let mut pixmap = Pixmap::new(width as u32, height as u32).ok_or(RasterError::TooLarge { width, height })?;
+        resvg::render(&tree, tiny_skia::Transform::from_scale(self.scale, self.scale), &mut pixmap.as_mut());
+        Ok(pixmap)
+    }
+}
+
+/// Loads the given fonts, those of the directory and (optionally) the system fonts, falling back to the embedded
+/// font when the default (Times New Roman) is missing, as it is on many Linux systems
+fn load_fonts(fontdb: &mut usvg::fontdb::Database, system_fonts: bool, fonts: Vec<Vec<u8>>, font_dir: Option<&Path>) {
+    if system_fonts {
+        fontdb.load_system_fonts();
+    }
+    for font in fonts {
+        fontdb.load_font_data(font);
+    }
+    let loaded = fontdb.len();
+    if let Some(font_dir) = font_dir {
+        fontdb.load_fonts_dir(font_dir);
+    }
+    let query = usvg::fontdb::Query { families: &[usvg::fontdb::Family::Serif], ..Default::default() };
+    if fontdb.query(&query).is_none() {
+        // the first font of the directory replaces the embedded font
+        let family = fontdb.faces().nth(loaded).map(|face| face.families[0].0.clone());
+        match family {
+            Some(family) => fontdb.set_serif_family(family),
+            None => {
+                fontdb.load_font_data(EMBEDDED_FONT.to_vec());
+                fontdb.set_serif_family(EMBEDDED_FAMILY);
+            }
+        }
+    }
+}
+
+impl super::Drawer for RasterDrawer {
+    fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.resistor(label, position, size, rotate);
+    }
+
+    fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.capacitor(label, position, size, rotate);
+    }
+
+    fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.inductor(label, position, size, rotate);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.svg.voltage_source(label, position, size, rotate, flip);
+    }
+
+    fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.current_source(label, position, size, rotate);
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.svg.diode(label, position, size, rotate, flip);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.svg.zener_diode(label, position, size, rotate, flip);
+    }
+
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        self.svg.switch(label, position, size, rotate, closed);
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool, current: bool) {
+        self.svg.controlled_source(label, position, size, rotate, flip, current);
+    }
+
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, tap: Position) {
+        self.svg.potentiometer(label, position, size, rotate, tap);
+    }
+
+    fn led(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.svg.led(label, position, size, rotate, flip);
+    }
+
+    fn thermistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.thermistor(label, position, size, rotate);
+    }
+
+    fn crystal(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.crystal(label, position, size, rotate);
+    }
+
+    fn fuse(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.fuse(label, position, size, rotate);
+    }
+
+    fn custom(&mut self, name: &'static str, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.custom(name, label, position, size, rotate);
+    }
+
+    fn open(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.open(label, position, size, rotate);
+    }
+
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.svg.transformer(label, position, size, rotate);
+    }
+
+    fn wire(&mut self, a: Position, b: Position) {
+        self.svg.wire(a, b);
+    }
+
+    fn junction(&mut self, position: Position) {
+        self.svg.junction(position);
+    }
+
+    fn tooltip(&mut self, text: &str) {
+        self.svg.tooltip(text);
+    }
+
+    fn polarity(&mut self, position: Position, rotate: bool, flip: bool, current: bool) {
+        self.svg.polarity(position, rotate, flip, current);
+    }
+
+    fn current_arrow(&mut self, name: &str, position: Position, rotate: bool, flip: bool) {
+        self.svg.current_arrow(name, position, rotate, flip);
+    }
+
+    fn voltage_arrow(&mut self, name: &str, a: Position, b: Position) {
+        self.svg.voltage_arrow(name, a, b);
+    }
+
+    fn node_label(&mut self, name: &str, position: Position) {
+        self.svg.node_label(name, position);
+    }
+
+    fn title(&mut self, title: &str, position: Position) {
+        self.svg.title(title, position);
+    }
+
+    fn value(&mut self, value: &str) {
+        self.svg.value(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_draw_twoport() {
+        let twoport = circuit::twoport::<E>("|V1-R1|C1").unwrap().1;
+        let mut svg_drawer = SvgDrawer::new();
+        twoport.draw(twoport.layout_size(), Context::default(), &mut svg_drawer);
+        let svg = svg_drawer.finalize().unwrap();
+
+        let mut drawer = RasterDrawer::new().scale(2.0);
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let pixmap = drawer.finalize().unwrap();
+        let view_box = svg.get_attributes()["viewBox"].to_string();
+        let [_, _, width, height] = view_box.split(' ').collect::<Vec<_>>()[..] else { panic!() };
+        assert_eq!(pixmap.width(), 2 * width.parse::<u32>().unwrap());
+        assert_eq!(pixmap.height(), 2 * height.parse::<u32>().unwrap());
+        assert!(pixmap.pixels().iter().any(|pixel| pixel.alpha() > 0));
+
+        let finalize = |scale| {
+            let mut drawer = RasterDrawer::new().scale(scale);
+            twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+            drawer.finalize()
+        };
+        assert_eq!(finalize(0.0001).unwrap_err(), RasterError::TooSmall { scale: 0.0001 });
+        assert!(matches!(finalize(1e6), Err(RasterError::TooLarge { .. })));
+        assert_eq!(RasterDrawer::new().finalize().unwrap_err(), RasterError::Empty(EmptyDrawing));
+    }
+
+    #[test]
+    fn test_embedded_font() {
+        let mut fontdb = usvg::fontdb::Database::new();
+        load_fonts(&mut fontdb, false, vec![], None);
+        let query = usvg::fontdb::Query { families: &[usvg::fontdb::Family::Serif], ..Default::default() };
+        let face = fontdb.face(fontdb.query(&query).unwrap()).unwrap();
+        assert_eq!(face.families[0].0, EMBEDDED_FAMILY);
+
+        let mut fontdb = usvg::fontdb::Database::new();
+        load_fonts(&mut fontdb, false, vec![], Some(Path::new("assets/fonts")));
+        assert_eq!(fontdb.len(), 1);
+        assert_eq!(fontdb.face(fontdb.query(&query).unwrap()).unwrap().families[0].0, EMBEDDED_FAMILY);
+    }
+}
@@ -1,18 +1,225 @@
-use svg::node::element::{Path, Rectangle, Group, Text, Circle, path::Data};
-use crate::layout::{self, Size, Position};
+use std::collections::HashMap;
+use svg::{node::element::{Path, Rectangle, Group, Text, Circle, Style, Script, Title, Description, path::Data}, Node};
+use crate::{
+    analysis::{response::{Response, ResponsePoint}, Complex64},
+    circuit, format, layout::{self, Size, Position}, legend::Legend, smith::SmithChart, sweep::{Matrix, Metric},
+};
+use super::{symbols::{self, text_width, Geometry, SymbolStandard, JUNCTION_RADIUS, STROKE_WIDTH}, DrawerCapabilities};
 
+/// Font used for labels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    /// CSS font family name
+    pub family: String,
+    /// Where the font can be loaded from, if it is not expected to be installed
+    pub source: Option<FontSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontSource {
+    /// Font file referenced by URL
+    Url(String),
+    /// Font file embedded into the document, `format` being its CSS font format
+    /// (`truetype`, `opentype`, `woff` or `woff2`)
+    Embedded { data: Vec<u8>, format: String },
+}
+
+impl Font {
+    /// Returns the `@font-face` rule for this font, if it has a source
+    fn font_face(&self) -> Option<String> {
+        let src = match self.source.as_ref()? {
+            FontSource::Url(url) => format!("url({})", css_string(url)),
+            FontSource::Embedded { data, format } => {
+                let mime = match format.as_str() {
+                    "truetype" => "font/ttf",
+                    "opentype" => "font/otf",
+                    "woff" => "font/woff",
+                    _ => "font/woff2",
+                };
+                format!("url(\"data:{mime};base64,{}\") format(\"{format}\")", base64(data))
+            }
+        };
+        Some(format!("@font-face {{ font-family: {}; src: {src}; }}", css_string(&self.family)))
+    }
+}
+
+/// Colors, strokes and text of a drawing, see [`SvgDrawer::theme`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Color of strokes and text
+    pub color: &'static str,
+    /// Background of the document and fill of hollow symbols, transparent if `None`
+    pub background: Option<&'static str>,
+    /// Width of wires and leads
+    pub stroke_width: i32,
+    /// Font size of labels, the renderer's default if `None`
+    pub font_size: Option<&'static str>,
+    /// Font of labels, the renderer's default if `None`
+    pub font: Option<Font>,
+    /// Whether the document's width and height are given in `em` (at 16px per em) rather than pixels
+    pub em_size: bool,
+    /// Space around the drawing, which may differ by side
+    pub margin: Margin,
+    /// Additional distance of labels from their symbols
+    pub label_distance: i32,
+    /// How the values of charts are written. Values of elements are written as set by [`crate::draw::Context::locale`]
+    pub locale: format::Locale,
+}
+
+impl Theme {
+    /// Black on white
+    pub const LIGHT: Theme = Theme {
+        color: "black",
+        background: Some("white"),
+        stroke_width: STROKE_WIDTH,
+        font_size: None,
+        font: None,
+        em_size: false,
+        margin: Margin::uniform(30),
+        label_distance: 0,
+        locale: format::Locale::EN,
+    };
+    /// Light gray on a dark background
+    pub const DARK: Theme = Theme { color: "#e6e6e6", background: Some("#1e1e1e"), ..Theme::LIGHT };
+    /// Taking color and text size from the page the drawing is embedded in, see [`SvgDrawer::inherit_page_style`]
+    pub const PAGE: Theme = Theme { color: "currentColor", background: None, font_size: Some("1em"), em_size: true, ..Theme::LIGHT };
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::LIGHT
+    }
+}
+
+impl std::str::FromStr for Theme {
+    type Err = crate::error::UnknownName;
+
+    /// Parses the name of a theme, `light` or `dark`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "light" => Ok(Theme::LIGHT),
+            "dark" => Ok(Theme::DARK),
+            _ => Err(crate::error::UnknownName { option: "theme", name: name.to_string(), expected: &["light", "dark"] }),
+        }
+    }
+}
+
+/// Space around a drawing on each side, e.g. more at the bottom for a caption added by the page
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Margin {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Margin {
+    /// The same space on every side
+    pub const fn uniform(margin: i32) -> Self {
+        Margin { top: margin, right: margin, bottom: margin, left: margin }
+    }
+}
+
+impl From<i32> for Margin {
+    fn from(margin: i32) -> Self {
+        Margin::uniform(margin)
+    }
+}
+
+/// Draws the symbol of a registered kind of element, horizontally and centered at the origin, with its
+/// leads ending at `±size.0 / 2` and its strokes in the given color
+pub type CustomSymbol = fn(Size, &str) -> Group;
+
+/// Where labels of custom symbols are placed, below the symbol
+const CUSTOM_LABEL: (i32, i32) = (30, 30);
+
+/// Style applied to focused elements, when keyboard navigation is enabled
+const FOCUS_STYLE: &str = "#symbols g[tabindex]:focus { outline: none; } \
+#symbols g[tabindex]:focus * { stroke: #0060df; stroke-width: 3; }";
+
+/// Script announcing the focused element through the live region
+const ANNOUNCE_SCRIPT: &str = "(function () {
+  var root = document.currentScript ? document.currentScript.parentNode : document;
+  var announcer = root.querySelector('.circmark-announcer');
+  root.addEventListener('focusin', function (event) {
+    var label = event.target.getAttribute('aria-label');
+    if (label) { announcer.textContent = label; }
+  });
+})();";
+
+/// Error returned by [`SvgDrawer::finalize`] when nothing was drawn
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EmptyDrawing;
+
+impl std::fmt::Display for EmptyDrawing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "nothing was drawn")
+    }
+}
+
+impl std::error::Error for EmptyDrawing {}
+
+/// Drawer producing an SVG document.
+///
+/// The document is structured into Inkscape layers for wires, symbols and labels,
+/// with one group per element in the symbol and label layers.
 pub struct SvgDrawer {
-    root: Option<Group>,
+    wires: Group,
+    symbols: Group,
+    labels: Group,
+    /// Whether anything was drawn since the drawing was last finalized
+    drawn: bool,
+    element_count: usize,
+    theme: Theme,
+    capabilities: DrawerCapabilities,
+    keyboard_navigation: Option<KeyboardNavigation>,
+    last_label: Option<LabelAnchor>,
+    /// Full label of the element drawn next, when its label was shortened
+    tooltip: Option<String>,
+    geometry: Geometry,
+    /// Drawing functions for the symbols of registered kinds of elements, by name
+    custom_symbols: HashMap<&'static str, CustomSymbol>,
+    /// Titles drawn so far, which also become the document's `<title>`
+    titles: Vec<String>,
+    /// The document's `<title>` when set, rather than the titles drawn
+    title: Option<String>,
+    description: Option<String>,
+    /// Attributes set on the document's root element, in the order they were given
+    attributes: Vec<(String, String)>,
+    /// Factor of the document's width and height over the drawing units
+    scale: f64,
     min_x: i32,
     max_x: i32,
     min_y: i32,
     max_y: i32,
 }
 
+impl Default for SvgDrawer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SvgDrawer {
     pub fn new() -> Self {
         Self {
-            root: Some(Group::new()),
+            wires: layer("wires", "Wires"),
+            symbols: layer("symbols", "Symbols"),
+            labels: layer("labels", "Labels"),
+            drawn: false,
+            element_count: 0,
+            theme: Theme::LIGHT,
+            capabilities: DrawerCapabilities::ALL,
+            keyboard_navigation: None,
+            last_label: None,
+            tooltip: None,
+            geometry: Geometry::STANDARD,
+            custom_symbols: HashMap::new(),
+            titles: Vec::new(),
+            title: None,
+            description: None,
+            attributes: Vec::new(),
+            scale: 1.0,
             min_x: 0,
             max_x: 0,
             min_y: 0,
@@ -20,26 +227,519 @@ impl SvgDrawer {
         }
     }
 
-    pub fn finalize(self) -> svg::Document {
-        let margin = 30;
-        let w = self.max_x - self.min_x + 2 * margin;
-        let h = self.max_y - self.min_y + 2 * margin;
-        let document = svg::Document::new();
-        document
-            .add(
-                self.root.unwrap()
-                    .set("transform", format!("translate({},{})", w/2, h/2))
-            )
-            .set("viewBox", format!("0 0 {} {}", w, h))
-            .set("width", w)
-            .set("height", h)
-            .set("style", "background: white")
+    /// Makes the drawing inherit its styling from the page it is embedded in.
+    ///
+    /// Strokes and text use `currentColor`, there is no background, label font size is `1em`
+    /// and the document's width and height are given in `em` (at 16px per em).
+    /// The font and other settings of the theme are kept.
+    pub fn inherit_page_style(self) -> Self {
+        let theme = Theme { font: self.theme.font.clone(), stroke_width: self.theme.stroke_width, margin: self.theme.margin, label_distance: self.theme.label_distance, locale: self.theme.locale, ..Theme::PAGE };
+        Self { theme, ..self }
+    }
+
+    /// Sets the colors, strokes and text of the drawing ([`Theme::LIGHT`] by default)
+    ///
+    /// ```
+    /// use circmark_parse::{circuit, draw::{svg::{SvgDrawer, Theme}, Context, Draw}, layout::Layout};
+    /// let mut drawer = SvgDrawer::new().theme(Theme { stroke_width: 3, ..Theme::DARK });
+    /// let document = circuit::document("|V1-R1").unwrap();
+    /// document.draw(document.layout_size(), Context::default(), &mut drawer);
+    /// assert!(drawer.finalize().unwrap().to_string().contains("stroke=\"#e6e6e6\" stroke-width=\"3\""));
+    /// ```
+    pub fn theme(self, theme: Theme) -> Self {
+        Self { theme, ..self }
+    }
+
+    /// Limits what is drawn ([`DrawerCapabilities::ALL`] by default), e.g. leaving out labels and values of drawings
+    /// too small to read them
+    pub fn capabilities(self, capabilities: DrawerCapabilities) -> Self {
+        Self { capabilities, ..self }
+    }
+
+    /// Sets the geometry of the symbols ([`Geometry::STANDARD`] by default)
+    pub fn geometry(self, geometry: Geometry) -> Self {
+        Self { geometry, ..self }
+    }
+
+    /// Draws the symbol named `name` of registered kinds of elements (see [`circuit::register`]) with `draw`.
+    ///
+    /// ```
+    /// use circmark_parse::{circuit::{self, ElementKind, Impedance, Spice, Symbol}, draw::{svg::SvgDrawer, Context, Draw}, layout::Layout};
+    /// use svg::node::element::{Circle, Group, Path};
+    /// circuit::register(ElementKind::custom("M", Symbol::Custom("motor"), Impedance::Resistive, Spice::Passive("R")));
+    /// let mut drawer = SvgDrawer::new().custom_symbol("motor", |size, color| Group::new()
+    ///     .add(Path::new().set("stroke", color).set("d", format!("M{} 0H-15M15 0H{}", -size.0 / 2, size.0 / 2)))
+    ///     .add(Circle::new().set("r", 15).set("stroke", color).set("fill", "none")));
+    /// let document = circuit::document("|V1-M1").unwrap();
+    /// document.draw(document.layout_size(), Context::default(), &mut drawer);
+    /// assert!(drawer.finalize().unwrap().to_string().contains("<circle"));
+    /// ```
+    pub fn custom_symbol(mut self, name: &'static str, draw: CustomSymbol) -> Self {
+        self.custom_symbols.insert(name, draw);
+        self
+    }
+
+    /// Sets the document's `<title>`, which otherwise names the titles drawn
+    pub fn document_title(self, title: String) -> Self {
+        Self {
+            title: Some(title),
+            ..self
+        }
+    }
+
+    /// Sets the document's `<desc>`, e.g. a description of the circuit for assistive technology
+    pub fn description(self, description: String) -> Self {
+        Self {
+            description: Some(description),
+            ..self
+        }
+    }
+
+    /// Sets an attribute of the document's root `<svg>` element, e.g. `preserveAspectRatio` for a page
+    /// stretching the drawing. It replaces the attribute set by the drawer, if any, like `width`.
+    ///
+    /// The value is escaped. Attributes whose name is not an XML name (see [`is_xml_name`]) are left out, as they would
+    /// make the document malformed
+    ///
+    /// ```
+    /// use circmark_parse::{circuit, draw::{svg::SvgDrawer, Context, Draw}, layout::Layout};
+    /// let mut drawer = SvgDrawer::new().attribute("preserveAspectRatio", "xMinYMin meet").attribute("width", "100%");
+    /// let document = circuit::document("|V1-R1").unwrap();
+    /// document.draw(document.layout_size(), Context::default(), &mut drawer);
+    /// let svg = drawer.finalize().unwrap().to_string();
+    /// assert!(svg.contains("preserveAspectRatio=\"xMinYMin meet\"") && svg.contains("width=\"100%\""));
+    /// ```
+    pub fn attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((name.into(), value.into()));
+        self
+    }
+
+    /// Scales the document's width and height by `scale`, e.g. 2 for a drawing twice as large (1 by default)
+    pub fn scale(self, scale: f64) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// Sets the font used for labels, optionally loading it via `@font-face`
+    pub fn font(self, font: Font) -> Self {
+        let theme = Theme { font: Some(font), ..self.theme };
+        Self { theme, ..self }
+    }
+
+    /// Draws a legend box below everything drawn so far
+    pub fn legend(&mut self, legend: &Legend) {
+        let line_height = 20;
+        let padding = 10;
+        // rough estimate, assuming an average character width of 9px
+        let width = legend.entries.iter().map(|entry| text_width(&entry.to_string())).max().unwrap_or(0) + 2 * padding;
+        let height = legend.entries.len() as i32 * line_height + padding;
+        let (x, y) = (self.min_x, self.max_y + 20);
+        let mut group = Group::new()
+            .set("id", "legend")
+            .set("inkscape:label", "Legend")
+            .add(Rectangle::new()
+                .set("x", x)
+                .set("y", y)
+                .set("width", width)
+                .set("height", height)
+                .set("stroke", self.theme.color)
+                .set("fill", "none")
+                .set("stroke-width", 1));
+        for (i, entry) in legend.entries.iter().enumerate() {
+            let mut text = Text::new()
+                .add(text_node(entry.to_string()))
+                .set("x", x + padding)
+                .set("y", y + (i as i32 + 1) * line_height)
+                .set("fill", self.theme.color);
+            if let Some(font_size) = self.theme.font_size {
+                text = text.set("font-size", font_size);
+            }
+            group = group.add(text);
+        }
+        self.labels.append(group);
+        self.grow_viewbox(Position(x + width / 2, y + height / 2), Size(width, height), false);
+    }
+
+    /// Draws a Smith chart below everything drawn so far, with the trajectory of the impedance
+    /// along the links as a solid line and the input impedance over frequency as a dashed one
+    pub fn smith_chart(&mut self, chart: &SmithChart) {
+        let radius = 120;
+        let center = Position((self.min_x + self.max_x) / 2, self.max_y + 30 + radius);
+        // position on the drawing of a point of the reflection coefficient plane
+        let point = |gamma: Complex64| (center.0 as f64 + gamma.re * radius as f64, center.1 as f64 - gamma.im * radius as f64);
+        // adds a line through the points to the path
+        let polyline = |mut data: Data, points: &mut dyn Iterator<Item = (f64, f64)>| {
+            for (i, point) in points.enumerate() {
+                data = if i == 0 { data.move_to(point) } else { data.line_to(point) };
+            }
+            data
+        };
+        let mut grid = Data::new()
+            .move_to((center.0 - radius, center.1))
+            .line_to((center.0 + radius, center.1));
+        // lines of constant reactance, from the rim towards infinite resistance
+        for x in [0.2, 0.5, 1.0, 2.0, 5.0, -0.2, -0.5, -1.0, -2.0, -5.0] {
+            let points = (0..50).map(|i| {
+                let r = i as f64 / (50 - i) as f64;
+                let gamma = (Complex64::new(r, x) - 1.0) / (Complex64::new(r, x) + 1.0);
+                point(gamma)
+            });
+            grid = polyline(grid, &mut points.chain([point(Complex64::new(1.0, 0.0))]));
+        }
+        let mut group = Group::new()
+            .set("id", "smith-chart")
+            .set("inkscape:label", "Smith chart")
+            .add(Path::new()
+                .set("d", grid)
+                .set("stroke", self.theme.color)
+                .set("stroke-opacity", 0.4)
+                .set("fill", "none")
+                .set("stroke-width", 1));
+        // the rim and circles of constant resistance
+        for r in [0.0, 0.2, 0.5, 1.0, 2.0, 5.0] {
+            group = group.add(Circle::new()
+                .set("cx", center.0 as f64 + r / (1.0 + r) * radius as f64)
+                .set("cy", center.1)
+                .set("r", radius as f64 / (1.0 + r))
+                .set("stroke", self.theme.color)
+                .set("stroke-opacity", if r == 0.0 { 1.0 } else { 0.4 })
+                .set("fill", "none")
+                .set("stroke-width", 1));
+        }
+        let normalized = |z: Complex64| chart.reflection(z);
+        group = group.add(Path::new()
+            .set("d", polyline(Data::new(), &mut chart.sweep.iter().map(|(_, z)| point(normalized(*z)))))
+            .set("stroke", self.theme.color)
+            .set("stroke-dasharray", "4 3")
+            .set("fill", "none")
+            .set("stroke-width", 1));
+        group = group.add(Path::new()
+            .set("d", polyline(Data::new(), &mut chart.trajectory.iter().map(|(_, z)| point(normalized(*z)))))
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width));
+        for (label, z) in &chart.trajectory {
+            let (x, y) = point(normalized(*z));
+            group = group
+                .add(Circle::new().set("cx", x).set("cy", y).set("r", JUNCTION_RADIUS).set("fill", self.theme.color))
+                .add(self.chart_text(label, x + 6.0, y - 6.0, "start"));
+        }
+        let locale = self.theme.locale;
+        let caption = format!("Z0 = {}, f = {}", format::si(chart.z0, "Ω", &locale), format::si(chart.frequency, "Hz", &locale));
+        group = group.add(self.chart_text(&caption, center.0 as f64, (center.1 + radius + 25) as f64, "middle"));
+        self.labels.append(group);
+        self.grow_viewbox(Position(center.0, center.1 + 15), Size(2 * radius, 2 * radius + 30), false);
+    }
+
+    /// Draws plots of the magnitude, the unwrapped phase and the group delay of a response below everything
+    /// drawn so far, over a logarithmic frequency axis with lines at each decade
+    pub fn response_plot(&mut self, response: &Response) {
+        let (width, height, gap) = (360.0, 90.0, 50.0);
+        let left = ((self.min_x + self.max_x) / 2) as f64 - width / 2.0;
+        let top = (self.max_y + 40) as f64;
+        let locale = self.theme.locale;
+        let (first, last) = match &response.points[..] {
+            [first, .., last] => (first.frequency.log10(), last.frequency.log10()),
+            _ => return,
+        };
+        let x = |frequency: f64| left + (frequency.log10() - first) / (last - first) * width;
+        let series = |value: fn(&ResponsePoint) -> f64| response.points.iter().map(|point| (point.frequency, value(point))).collect::<Vec<_>>();
+        let panels = [
+            ("Magnitude", series(|point| point.magnitude), "dB"),
+            ("Phase", series(|point| point.phase), "°"),
+            ("Group delay", series(|point| point.group_delay), "s"),
+        ];
+        // delays span many orders of magnitude, decibels and degrees don't need prefixes
+        let text = |value: f64, unit: &str| match unit {
+            "s" => format::si(value, unit, &locale),
+            _ => format!("{}{unit}", format::number((value * 10.0).round() / 10.0, &locale)),
+        };
+        let mut group = Group::new()
+            .set("id", "response-plot")
+            .set("inkscape:label", "Frequency response");
+        for (i, (title, points, unit)) in panels.into_iter().enumerate() {
+            let top = top + i as f64 * (height + gap);
+            let points: Vec<_> = points.into_iter().filter(|(_, value)| value.is_finite()).collect();
+            let (mut min, mut max) = points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), (_, value)| (min.min(*value), max.max(*value)));
+            if points.is_empty() {
+                (min, max) = (-1.0, 1.0);
+            } else if max - min <= max.abs() * 1e-9 {
+                let pad = if max == 0.0 { 1.0 } else { max.abs() / 10.0 };
+                (min, max) = (min - pad, max + pad);
+            }
+            let y = |value: f64| top + (max - value) / (max - min) * height;
+            let mut grid = Data::new();
+            for decade in (first.ceil() as i32)..=(last.floor() as i32) {
+                grid = grid.move_to((x(10f64.powi(decade)), top)).line_to((x(10f64.powi(decade)), top + height));
+            }
+            let mut curve = Data::new();
+            for (j, (frequency, value)) in points.iter().enumerate() {
+                let position = (x(*frequency), y(*value));
+                curve = if j == 0 { curve.move_to(position) } else { curve.line_to(position) };
+            }
+            group = group
+                .add(Rectangle::new()
+                    .set("x", left)
+                    .set("y", top)
+                    .set("width", width)
+                    .set("height", height)
+                    .set("stroke", self.theme.color)
+                    .set("fill", "none")
+                    .set("stroke-width", 1))
+                .add(Path::new()
+                    .set("d", grid)
+                    .set("stroke", self.theme.color)
+                    .set("stroke-opacity", 0.4)
+                    .set("stroke-width", 1))
+                .add(Path::new()
+                    .set("d", curve)
+                    .set("stroke", self.theme.color)
+                    .set("fill", "none")
+                    .set("stroke-width", self.theme.stroke_width))
+                .add(self.chart_text(title, left, top - 8.0, "start"))
+                .add(self.chart_text(&text(max, unit), left - 6.0, top + 5.0, "end"))
+                .add(self.chart_text(&text(min, unit), left - 6.0, top + height, "end"));
+        }
+        let bottom = top + 3.0 * height + 2.0 * gap;
+        group = group
+            .add(self.chart_text(&format::si(response.points[0].frequency, "Hz", &locale), left, bottom + 18.0, "middle"))
+            .add(self.chart_text(&format::si(response.points[response.points.len() - 1].frequency, "Hz", &locale), left + width, bottom + 18.0, "middle"));
+        self.labels.append(group);
+        // room for the value labels left of the plots
+        let (center, bottom) = ((left + width / 2.0) as i32, bottom as i32 + 25);
+        self.grow_viewbox(Position(center, (top as i32 - 30 + bottom) / 2), Size(width as i32 + 2 * 70, bottom - top as i32 + 30), false);
+    }
+
+    /// Draws the table of a sweep below everything drawn so far, shading each cell by its value from light
+    /// to dark (by the logarithm for frequencies and impedances, which span decades)
+    pub fn heatmap(&mut self, matrix: &Matrix) {
+        let (cell_width, cell_height) = (80.0, 28.0);
+        let locale = self.theme.locale;
+        let columns = matrix.columns.as_ref().map_or(1, |columns| columns.values.len());
+        let width = (columns + 1) as f64 * cell_width;
+        let left = ((self.min_x + self.max_x) / 2) as f64 - width / 2.0;
+        let top = (self.max_y + 70) as f64;
+        let logarithmic = matches!(matrix.metric, Metric::Cutoff | Metric::InputImpedance(_));
+        let scale = |value: f64| if logarithmic { value.log10() } else { value };
+        let scaled: Vec<_> = matrix.cells.iter().flatten().flatten().map(|value| scale(*value)).filter(|value| value.is_finite()).collect();
+        let min = scaled.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = scaled.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        // three significant digits fit the cells
+        let text = |value: f64| match matrix.metric.unit() {
+            "dB" => format!("{}dB", format::number((value * 10.0).round() / 10.0, &locale)),
+            unit => format::si(format::significant(value, 3), unit, &locale),
+        };
+        let corner = match &matrix.columns {
+            Some(columns) => format!("{} \\ {}", matrix.rows.label, columns.label),
+            None => matrix.rows.label.clone(),
+        };
+        let mut group = Group::new()
+            .set("id", "heatmap")
+            .set("inkscape:label", "Sweep")
+            .add(self.chart_text(&matrix.metric.name(), left + width / 2.0, top - 40.0, "middle"))
+            .add(self.chart_text(&corner, left + cell_width / 2.0, top - 10.0, "middle"));
+        let header = matrix.columns.as_ref().map_or_else(|| vec![String::new()], |columns| {
+            columns.values.iter().map(|value| format::si(*value, "", &locale)).collect()
+        });
+        for (j, header) in header.iter().enumerate() {
+            group = group.add(self.chart_text(header, left + (j as f64 + 1.5) * cell_width, top - 10.0, "middle"));
+        }
+        for (i, (row, cells)) in matrix.rows.values.iter().zip(&matrix.cells).enumerate() {
+            let y = top + i as f64 * cell_height;
+            group = group.add(self.chart_text(&format::si(*row, "", &locale), left + cell_width / 2.0, y + cell_height / 2.0 + 5.0, "middle"));
+            for (j, cell) in cells.iter().enumerate() {
+                let x = left + (j + 1) as f64 * cell_width;
+                let shade = cell.map(|value| if max > min { (scale(value) - min) / (max - min) } else { 0.5 }).filter(|shade| shade.is_finite());
+                let rectangle = Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", cell_width)
+                    .set("height", cell_height)
+                    .set("stroke", self.theme.color)
+                    .set("stroke-width", 1);
+                let (rectangle, label) = match (cell, shade) {
+                    (Some(value), Some(shade)) => (rectangle.set("fill", heat_color(shade)), text(*value)),
+                    _ => (rectangle.set("fill", "none"), "–".to_string()),
+                };
+                let label = self.chart_text(&label, x + cell_width / 2.0, y + cell_height / 2.0 + 5.0, "middle");
+                // dark cells get white text, whatever the theme
+                let label = match shade {
+                    Some(shade) if shade > 0.55 => label.set("fill", "white"),
+                    Some(_) => label.set("fill", "black"),
+                    None => label,
+                };
+                group = group.add(rectangle).add(label);
+            }
+        }
+        self.labels.append(group);
+        let height = matrix.rows.values.len() as f64 * cell_height;
+        self.grow_viewbox(Position((left + width / 2.0) as i32, (top + height / 2.0 - 25.0) as i32), Size(width as i32, height as i32 + 50), false);
+    }
+
+    fn chart_text(&self, text: &str, x: f64, y: f64, anchor: &str) -> Text {
+        let text = Text::new()
+            .add(text_node(text))
+            .set("x", x)
+            .set("y", y)
+            .set("text-anchor", anchor)
+            .set("fill", self.theme.color);
+        match self.theme.font_size {
+            Some(font_size) => text.set("font-size", font_size),
+            None => text,
+        }
+    }
+
+    /// Makes every element focusable with the keyboard, highlighting the focused one.
+    ///
+    /// Elements are labelled for assistive technology (e.g. "resistor R1").
+    /// With `announce`, the document also contains a script which announces
+    /// the focused element through an ARIA live region.
+    pub fn keyboard_navigation(self, announce: bool) -> Self {
+        Self {
+            keyboard_navigation: Some(KeyboardNavigation { announce }),
+            ..self
+        }
+    }
+
+    /// Whether nothing was drawn since the drawing was last finalized
+    pub fn is_empty(&self) -> bool {
+        !self.drawn
+    }
+
+    /// The width and height of the `viewBox` of everything drawn so far, margins included, which [`Self::finalize`]
+    /// would return the document with
+    pub fn size(&self) -> (i32, i32) {
+        let margin = self.theme.margin;
+        (self.max_x - self.min_x + margin.left + margin.right, self.max_y - self.min_y + margin.top + margin.bottom)
+    }
+
+    /// Returns the document of everything drawn so far, and clears the drawing so that the drawer (with its theme
+    /// and other settings) can draw the next document.
+    ///
+    /// Fails if nothing was drawn, as an empty drawing has no size to make its `viewBox` from.
+    ///
+    /// ```
+    /// use circmark_parse::{circuit, draw::{svg::{EmptyDrawing, SvgDrawer}, Context, Draw}, layout::Layout};
+    /// let mut drawer = SvgDrawer::new();
+    /// assert_eq!(drawer.finalize().unwrap_err(), EmptyDrawing);
+    /// for source in ["|V1-R1", "|V1-C1"] {
+    ///     let document = circuit::document(source).unwrap();
+    ///     document.draw(document.layout_size(), Context::default(), &mut drawer);
+    ///     let svg = drawer.finalize().unwrap().to_string();
+    ///     assert!(svg.contains("id=\"element2\"") && !svg.contains("id=\"element3\""));
+    /// }
+    /// ```
+    pub fn finalize(&mut self) -> Result<svg::Document, EmptyDrawing> {
+        if !self.drawn {
+            return Err(EmptyDrawing);
+        }
+        let wires = std::mem::replace(&mut self.wires, layer("wires", "Wires"));
+        let symbols = std::mem::replace(&mut self.symbols, layer("symbols", "Symbols"));
+        let labels = std::mem::replace(&mut self.labels, layer("labels", "Labels"));
+        let titles = std::mem::take(&mut self.titles);
+        let (w, h) = self.size();
+        let (min_x, min_y) = (self.min_x, self.min_y);
+        (self.min_x, self.max_x, self.min_y, self.max_y) = (0, 0, 0, 0);
+        self.drawn = false;
+        self.element_count = 0;
+        self.last_label = None;
+        self.tooltip = None;
+
+        let margin = self.theme.margin;
+        let transform = format!("translate({},{})", margin.left - min_x, margin.top - min_y);
+        let mut labels = labels.set("transform", transform.clone());
+        let mut document = svg::Document::new();
+        if let Some(title) = &self.title {
+            document = document.add(Title::new().add(text_node(title)));
+        } else if !titles.is_empty() {
+            document = document.add(Title::new().add(text_node(titles.join("; "))));
+        }
+        if let Some(description) = &self.description {
+            document = document.add(Description::new().add(text_node(description)));
+        }
+        if let Some(font) = &self.theme.font {
+            if let Some(font_face) = font.font_face() {
+                document = document.add(Style::new(font_face));
+            }
+            labels = labels.set("font-family", css_string(&font.family));
+        }
+        if let Some(keyboard_navigation) = self.keyboard_navigation {
+            document = document.add(Style::new(FOCUS_STYLE));
+            if keyboard_navigation.announce {
+                document = document
+                    .add(Text::new()
+                        .set("class", "circmark-announcer")
+                        .set("aria-live", "polite")
+                        .set("opacity", 0))
+                    .add(Script::new(ANNOUNCE_SCRIPT));
+            }
+        }
+        let mut document = document
+            .add(wires.set("transform", transform.clone()))
+            .add(symbols.set("transform", transform))
+            .add(labels)
+            .set("xmlns:inkscape", "http://www.inkscape.org/namespaces/inkscape")
+            .set("viewBox", format!("0 0 {} {}", w, h));
+        if self.theme.em_size {
+            document = document
+                .set("width", format!("{}em", w as f64 * self.scale / 16.0))
+                .set("height", format!("{}em", h as f64 * self.scale / 16.0));
+        } else {
+            document = document
+                .set("width", w as f64 * self.scale)
+                .set("height", h as f64 * self.scale);
+        }
+        if let Some(background) = self.theme.background {
+            document = document.set("style", format!("background: {background}"));
+        }
+        for (name, value) in self.attributes.iter().filter(|(name, _)| is_xml_name(name)) {
+            document = document.set(name.as_str(), escape(value));
+        }
+        Ok(document)
     }
 }
 
+fn layer(id: &str, label: &str) -> Group {
+    Group::new()
+        .set("id", id)
+        .set("inkscape:groupmode", "layer")
+        .set("inkscape:label", label)
+}
+
 impl SvgDrawer {
-    fn add<N: svg::Node>(&mut self, node: N) {
-        self.root = Some(self.root.take().unwrap().add(node));
+    fn add_wire<N: svg::Node>(&mut self, node: N) {
+        self.wires.append(node);
+    }
+
+    /// Adds the symbol of an element to the symbol layer, and its label to the label layer
+    ///
+    /// `kind` names the kind of element, e.g. "resistor". The label is placed at `label_offset`, see [`Self::label`].
+    fn add_element(&mut self, kind: &str, label: &str, symbol: Group, label_offset: (i32, i32), position: Position, rotate: bool) {
+        self.element_count += 1;
+        let id = format!("element{}", self.element_count);
+        self.last_label = Some(LabelAnchor { position, rotate, offset: label_offset });
+        let text = self.label(label, rotate, label_offset.0, label_offset.1);
+        let mut symbol = self.transform(symbol, position, rotate).set("id", id.clone());
+        if let Some(tooltip) = self.tooltip.take() {
+            symbol = symbol.add(Title::new().add(text_node(tooltip)));
+        }
+        if !label.is_empty() {
+            symbol = symbol.set("inkscape:label", label);
+        }
+        if self.keyboard_navigation.is_some() {
+            let description = if label.is_empty() { kind.to_string() } else { format!("{kind} {label}") };
+            symbol = symbol
+                .set("tabindex", 0)
+                .set("role", "img")
+                .set("aria-label", description);
+        }
+        self.symbols.append(symbol);
+        if !label.is_empty() {
+            self.grow_text(self.label_position(position, rotate, label_offset), text_width(label), "middle");
+            let text = self.transform(Group::new().add(text), position, rotate)
+                .set("id", format!("{id}-label"))
+                .set("inkscape:label", label);
+            self.labels.append(text);
+        }
     }
 
     fn transform(&self, group: Group, position: layout::Position, rotate: bool) -> Group {
@@ -56,188 +756,550 @@ impl SvgDrawer {
         self.max_x = self.max_x.max(max_x);
         self.min_y = self.min_y.min(min_y);
         self.max_y = self.max_y.max(max_y);
+        self.drawn = true;
+    }
+
+    /// Grows the viewbox by a line of text with its baseline at `position`, aligned by `anchor` like the text's
+    /// `text-anchor` and assumed to be 20 units high
+    fn grow_text(&mut self, position: Position, width: i32, anchor: &str) {
+        let x = match anchor {
+            "start" => position.0 + width / 2,
+            "end" => position.0 - width / 2,
+            _ => position.0,
+        };
+        self.grow_viewbox(Position(x, position.1 - 5), Size(width, 20), false);
+    }
+
+    /// Where the baseline of an element's label is centered on the drawing, see [`Self::label`]
+    fn label_position(&self, position: Position, rotate: bool, offset: (i32, i32)) -> Position {
+        let (xoff, yoff) = self.label_offset(offset);
+        // rotated labels are turned back upright, beside the element
+        if rotate {
+            Position(position.0 + xoff, position.1 + 5)
+        } else {
+            Position(position.0, position.1 + yoff)
+        }
+    }
+
+    /// The offset of labels from their symbols, moved further away by the theme's label distance
+    fn label_offset(&self, (xoff, yoff): (i32, i32)) -> (i32, i32) {
+        let distance = self.theme.label_distance;
+        (xoff + distance * xoff.signum(), yoff + distance * yoff.signum())
+    }
+
+    /// Adds the body of a resistor to the group, a rectangle or a zigzag line depending on the standard
+    fn resistor_body(&self, group: Group) -> Group {
+        let resistor = self.geometry.resistor;
+        match self.geometry.standard {
+            SymbolStandard::Iec => group.add(Rectangle::new()
+                .set("x", -resistor.width / 2)
+                .set("y", -resistor.height / 2)
+                .set("width", resistor.width)
+                .set("height", resistor.height)
+                .set("stroke", self.theme.color)
+                .set("fill", "none")
+                .set("stroke-width", self.theme.stroke_width)),
+            SymbolStandard::Ansi => group.add(Path::new()
+                .set("stroke", self.theme.color)
+                .set("fill", "none")
+                .set("stroke-width", self.theme.stroke_width)
+                .set("stroke-linejoin", "round")
+                .set("d", resistor.zigzag()[1..].iter().fold(Data::new().move_to(resistor.zigzag()[0]), |data, corner| data.line_to(*corner)))),
+        }
+    }
+
+    /// Builds the symbol of a (zener) diode, conducting to the right unless flipped
+    fn diode_symbol(&self, size: Size, flip: bool, zener: bool) -> Group {
+        let super::symbols::Diode { half_width, half_height, zener_bend, .. } = self.geometry.diode;
+        let d = if flip { -1 } else { 1 };
+        let line1 = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to((-size.0 / 2, 0)).line_to((-half_width, 0)));
+        let triangle = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-d * half_width, -half_height))
+                 .line_to((-d * half_width, half_height))
+                 .line_to((d * half_width, 0))
+                 .close());
+        let bar = if zener {
+            Data::new()
+                .move_to((d * (half_width - zener_bend), -half_height - zener_bend))
+                .line_to((d * half_width, -half_height))
+                .line_to((d * half_width, half_height))
+                .line_to((d * (half_width + zener_bend), half_height + zener_bend))
+        } else {
+            Data::new()
+                .move_to((d * half_width, -half_height))
+                .line_to((d * half_width, half_height))
+        };
+        let bar = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", bar);
+        let line2 = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to((half_width, 0)).line_to((size.0 / 2, 0)));
+        Group::new()
+            .add(line1)
+            .add(triangle)
+            .add(bar)
+            .add(line2)
+    }
+
+    /// Builds the name of an annotated current or voltage, in italics like quantities in formulas
+    fn annotation_label(&self, name: &str, x: i32, y: i32, anchor: &str) -> Text {
+        let text = Text::new()
+            .add(text_node(name))
+            .set("x", x)
+            .set("y", y)
+            .set("text-anchor", anchor)
+            .set("font-style", "italic")
+            .set("fill", self.theme.color);
+        match self.theme.font_size {
+            Some(font_size) => text.set("font-size", font_size),
+            None => text,
+        }
     }
 
     fn label(&self, label: &str, rotate: bool, xoff: i32, yoff: i32) -> Text {
+        let (xoff, yoff) = self.label_offset((xoff, yoff));
         let (lx, ly, ltrans) = if rotate {
             (xoff, 5, "rotate(-90)")
         } else {
             (0, yoff, "")
         };
-        Text::new()
-            .add(svg::node::Text::new(label))
+        let text = Text::new()
+            .add(text_node(label))
             .set("x", lx)
             .set("y", ly)
             .set("text-anchor", "middle")
             .set("transform", ltrans)
-    }        
+            .set("fill", self.theme.color);
+        match self.theme.font_size {
+            Some(font_size) => text.set("font-size", font_size),
+            None => text,
+        }
+    }
+}
+
+/// Color of a heatmap cell, from light (0) to dark blue (1)
+fn heat_color(shade: f64) -> String {
+    let (light, dark) = ([0xf7, 0xfb, 0xff], [0x08, 0x30, 0x6b]);
+    let channel = |i: usize| (light[i] as f64 + (dark[i] as f64 - light[i] as f64) * shade.clamp(0.0, 1.0)).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(0), channel(1), channel(2))
+}
+
+/// Where the label of an element was placed, so its value can be added below
+#[derive(Debug, Copy, Clone)]
+struct LabelAnchor {
+    position: Position,
+    rotate: bool,
+    offset: (i32, i32),
+}
+
+#[derive(Debug, Copy, Clone)]
+struct KeyboardNavigation {
+    announce: bool,
 }
 
 impl super::Drawer for SvgDrawer {
+    fn capabilities(&self) -> DrawerCapabilities {
+        self.capabilities
+    }
+
     fn resistor(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool) {
         self.grow_viewbox(position, size, rotate);
-        let element_width = 70;
-        let element_height = 20;
+        let resistor = self.geometry.resistor;
+        let element_width = resistor.width;
         let line1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((-size.0 / 2, 0)).line_to((-element_width/2, 0)));
-        let rect = Rectangle::new()
-            .set("x", -element_width/2)
-            .set("y", -element_height/2)
-            .set("width", element_width)
-            .set("height", element_height)
-            .set("stroke", "black")
-            .set("fill", "none")
-            .set("stroke-width", 2);
         let line2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((element_width/2, 0)).line_to((size.0/2, 0)));
-        self.add(self.transform(
-            Group::new()
-                .add(line1)
-                .add(rect)
-                .add(line2)
-                .add(self.label(label, rotate, 0, 4)),
+        self.add_element(
+            "resistor",
+            label,
+            self.resistor_body(Group::new().add(line1))
+                .add(line2),
+            resistor.label(self.geometry.standard),
             position,
             rotate
-        ));
+        );
     }
 
     fn capacitor(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool) {
         self.grow_viewbox(position, size, rotate);
-        let element_width = 10;
-        let element_height = 30;
-        let plate_width = 5;
+        let capacitor = self.geometry.capacitor;
+        let element_width = capacitor.gap;
+        let element_height = capacitor.plate_length;
+        let plate_width = capacitor.plate_width;
         let line1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((-size.0 / 2, 0)).line_to((-element_width/2, 0)));
         let plate1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
             .set("stroke-width", plate_width)
             .set("d", Data::new().move_to((-element_width/2, -element_height/2)).line_to((-element_width/2, element_height/2)));
         let plate2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
             .set("stroke-width", plate_width)
             .set("d", Data::new().move_to((element_width/2, -element_height/2)).line_to((element_width/2, element_height/2)));
         let line2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((element_width / 2, 0)).line_to((size.0/2, 0)));
-        self.add(self.transform(
+        self.add_element(
+            "capacitor",
+            label,
             Group::new()
                 .add(line1)
                 .add(plate1)
                 .add(plate2)
-                .add(line2)
-                .add(self.label(label, rotate, 30, 30)),
+                .add(line2),
+            capacitor.label,
             position,
             rotate
-        ));
+        );
     }
 
     fn inductor(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool) {
         self.grow_viewbox(position, size, rotate);
-        let element_width = 80;
-        let radius = 10;
+        let inductor = self.geometry.inductor;
+        let element_width = inductor.width();
+        let radius = inductor.radius;
+        let mut data = Data::new()
+            .move_to((-size.0/2, 0))
+            .line_to((-element_width/2, 0));
+        for i in 1..=inductor.loops {
+            data = data.elliptical_arc_to((radius, radius, 0, 0, 1, -element_width/2 + radius * 2 * i, 0));
+        }
         let path = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", 2)
-            .set("d", Data::new()
-                 .move_to((-size.0/2, 0))
-                 .line_to((-element_width/2, 0))
-                 .elliptical_arc_to((radius, radius, 0, 0, 1, -element_width/2 + radius * 2, 0))
-                 .elliptical_arc_to((radius, radius, 0, 0, 1, -element_width/2 + radius * 4, 0))
-                 .elliptical_arc_to((radius, radius, 0, 0, 1, -element_width/2 + radius * 6, 0))
-                 .elliptical_arc_to((radius, radius, 0, 0, 1, -element_width/2 + radius * 8, 0))
-                 .line_to((size.0/2, 0))
-            );
-        self.add(self.transform(Group::new().add(path).add(self.label(label, rotate, 30, -20)), position, rotate));
-    }
-
-    fn voltage_source(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool) {
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", data.line_to((size.0/2, 0)));
+        self.add_element("inductor", label, Group::new().add(path), inductor.label, position, rotate);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool, flip: bool) {
         self.grow_viewbox(position, size, rotate);
-        let element_width = 10;
-        let element_height = 40;
+        let source = self.geometry.voltage_source;
+        let element_width = source.gap;
+        // the long (positive) plate is on the left, unless flipped
+        let direction = if flip { -1 } else { 1 };
         let line1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((-size.0 / 2, 0)).line_to((-element_width/2, 0)));
         let plate1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "4")
-            .set("d", Data::new().move_to((-element_width / 2, -element_height/2)).line_to((-element_width/2, element_height/2)));
+            .set("stroke-width", source.plate_width)
+            .set("d", Data::new().move_to((-direction * element_width / 2, -source.long_plate/2)).line_to((-direction * element_width/2, source.long_plate/2)));
         let plate2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "4")
-            .set("d", Data::new().move_to((element_width / 2, -element_height/4)).line_to((element_width/2, element_height/4)));
+            .set("stroke-width", source.plate_width)
+            .set("d", Data::new().move_to((direction * element_width / 2, -source.short_plate/2)).line_to((direction * element_width/2, source.short_plate/2)));
         let line2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((element_width/2, 0)).line_to((size.0/2, 0)));
-        self.add(self.transform(
+        self.add_element(
+            "voltage source",
+            label,
             Group::new()
                 .add(line1)
                 .add(plate1)
                 .add(plate2)
-                .add(line2)
-                .add(self.label(label, rotate, 30, 30)),
+                .add(line2),
+            source.label,
             position,
             rotate
-        ))
+        );
     }
 
     fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
-        let radius = 15;
-        let offset = 10;
+        let source = self.geometry.current_source;
+        let (radius, offset) = (source.radius, source.offset);
         self.grow_viewbox(position, size, rotate);
         let circle1 = Circle::new()
             .set("cx", -offset)
             .set("cy", 0)
             .set("r", radius)
-            .set("stroke-width", 2)
-            .set("stroke", "black")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("stroke", self.theme.color)
             .set("fill", "none");
         let circle2 = Circle::new()
             .set("cx", offset)
             .set("cy", 0)
             .set("r", radius)
-            .set("stroke-width", 2)
-            .set("stroke", "black")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("stroke", self.theme.color)
             .set("fill", "none");
         let line1 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((-size.0/2, 0)).line_to((-(offset + radius), 0)));
         let line2 = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((offset + radius, 0)).line_to((size.0 / 2, 0)));
-        self.add(self.transform(
+        self.add_element(
+            "current source",
+            label,
             Group::new()
                 .add(line1)
                 .add(circle1)
                 .add(circle2)
-                .add(line2)
-                .add(self.label(label, rotate, 30, 30)),
+                .add(line2),
+            source.label,
+            position,
+            rotate
+        );
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let symbol = self.diode_symbol(size, flip, false);
+        self.grow_viewbox(position, size, rotate);
+        self.add_element("diode", label, symbol, self.geometry.diode.label, position, rotate);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let symbol = self.diode_symbol(size, flip, true);
+        self.grow_viewbox(position, size, rotate);
+        self.add_element("zener diode", label, symbol, self.geometry.diode.label, position, rotate);
+    }
+
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        let switch = self.geometry.switch;
+        let (w, r) = (switch.width, switch.contact_radius);
+        self.grow_viewbox(position, size, rotate);
+        let leads = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-size.0 / 2, 0)).line_to((-w / 2 - r, 0))
+                 .move_to((w / 2 + r, 0)).line_to((size.0 / 2, 0)));
+        let contact = |x| Circle::new()
+            .set("cx", x)
+            .set("cy", 0)
+            .set("r", r)
+            .set("stroke-width", self.theme.stroke_width)
+            .set("stroke", self.theme.color)
+            .set("fill", "none");
+        // the lever rests on the right contact when closed, and is raised when open
+        let end = if closed { (w / 2, -r) } else { (w / 2, -switch.lift) };
+        let lever = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to((-w / 2, -r)).line_to(end));
+        self.add_element(
+            if closed { "closed switch" } else { "open switch" },
+            label,
+            Group::new()
+                .add(leads)
+                .add(contact(-w / 2))
+                .add(contact(w / 2))
+                .add(lever),
+            switch.label,
+            position,
+            rotate
+        );
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool, current: bool) {
+        let source = self.geometry.controlled_source;
+        let d = source.half_diagonal;
+        let direction = if flip { -1 } else { 1 };
+        self.grow_viewbox(position, size, rotate);
+        let leads = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-size.0 / 2, 0)).line_to((-d, 0))
+                 .move_to((d, 0)).line_to((size.0 / 2, 0)));
+        let diamond = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to((-d, 0)).line_to((0, -d)).line_to((d, 0)).line_to((0, d)).close());
+        let marks = source.marks(current).into_iter().fold(Data::new(), |data, [(x1, y1), (x2, y2)]| {
+            data.move_to((direction * x1, y1)).line_to((direction * x2, y2))
+        });
+        let marks = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", marks);
+        self.add_element(
+            if current { "controlled current source" } else { "controlled voltage source" },
+            label,
+            Group::new()
+                .add(leads)
+                .add(diamond)
+                .add(marks),
+            source.label,
+            position,
+            rotate
+        );
+    }
+
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, tap: Position) {
+        let (resistor, potentiometer) = (self.geometry.resistor, self.geometry.potentiometer);
+        let w = resistor.width;
+        self.grow_viewbox(position, size, rotate);
+        let leads = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-size.0 / 2, 0)).line_to((-w / 2, 0))
+                 .move_to((w / 2, 0)).line_to((size.0 / 2, 0)));
+        let wiper = potentiometer.wiper(resistor, symbols::unplace(position, rotate, tap)).into_iter()
+            .fold(Data::new(), |data, [a, b]| data.move_to(a).line_to(b));
+        let wiper = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", wiper);
+        self.add_element(
+            "potentiometer",
+            label,
+            self.resistor_body(Group::new().add(leads))
+                .add(wiper),
+            resistor.label(self.geometry.standard),
+            position,
+            rotate
+        );
+    }
+
+    fn led(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let arrows = self.geometry.led.arrows(self.geometry.diode).into_iter()
+            .fold(Data::new(), |data, [a, b]| data.move_to(a).line_to(b));
+        let arrows = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", arrows);
+        let symbol = self.diode_symbol(size, flip, false).add(arrows);
+        self.grow_viewbox(position, size, rotate);
+        self.add_element("light emitting diode", label, symbol, self.geometry.led.label, position, rotate);
+    }
+
+    fn thermistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let (w, h) = (self.geometry.resistor.width, self.geometry.resistor.height);
+        self.grow_viewbox(position, size, rotate);
+        let leads = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-size.0 / 2, 0)).line_to((-w / 2, 0))
+                 .move_to((w / 2, 0)).line_to((size.0 / 2, 0)));
+        let rect = Rectangle::new()
+            .set("x", -w / 2)
+            .set("y", -h / 2)
+            .set("width", w)
+            .set("height", h)
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width);
+        let [foot, start, end] = self.geometry.thermistor.line();
+        let line = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to(foot).line_to(start).line_to(end));
+        self.add_element(
+            "thermistor",
+            label,
+            Group::new()
+                .add(leads)
+                .add(rect)
+                .add(line),
+            self.geometry.thermistor.label,
             position,
             rotate
-        ))
+        );
+    }
+
+    fn crystal(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let crystal = self.geometry.crystal;
+        let (w, h, plate) = (crystal.width, crystal.height, crystal.width / 2 + crystal.gap);
+        self.grow_viewbox(position, size, rotate);
+        let lines = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new()
+                 .move_to((-size.0 / 2, 0)).line_to((-plate, 0))
+                 .move_to((plate, 0)).line_to((size.0 / 2, 0))
+                 .move_to((-plate, -crystal.plate_length / 2)).line_to((-plate, crystal.plate_length / 2))
+                 .move_to((plate, -crystal.plate_length / 2)).line_to((plate, crystal.plate_length / 2)));
+        let rect = Rectangle::new()
+            .set("x", -w / 2)
+            .set("y", -h / 2)
+            .set("width", w)
+            .set("height", h)
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width);
+        self.add_element("crystal", label, Group::new().add(lines).add(rect), crystal.label, position, rotate);
+    }
+
+    fn fuse(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let fuse = self.geometry.fuse;
+        let (w, h) = (fuse.width, fuse.height);
+        self.grow_viewbox(position, size, rotate);
+        let wire = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", Data::new().move_to((-size.0 / 2, 0)).line_to((size.0 / 2, 0)));
+        let rect = Rectangle::new()
+            .set("x", -w / 2)
+            .set("y", -h / 2)
+            .set("width", w)
+            .set("height", h)
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width);
+        self.add_element("fuse", label, Group::new().add(wire).add(rect), fuse.label, position, rotate);
+    }
+
+    fn custom(&mut self, name: &'static str, label: &str, position: Position, size: Size, rotate: bool) {
+        let Some(draw) = self.custom_symbols.get(name) else {
+            return self.fallback(circuit::Symbol::Custom(name), label, position, size, rotate);
+        };
+        let symbol = draw(size, self.theme.color);
+        self.grow_viewbox(position, size, rotate);
+        self.add_element(name, label, symbol, CUSTOM_LABEL, position, rotate);
     }
 
     fn open(&mut self, label: &str, position: layout::Position, size: layout::Size, rotate: bool) {
@@ -245,52 +1307,252 @@ impl super::Drawer for SvgDrawer {
         let circle1 = Circle::new()
             .set("cx", -size.0 / 2)
             .set("cy", 0)
-            .set("r", 5)
-            .set("stroke-width", 2)
-            .set("stroke", "black")
-            .set("fill", "white");
+            .set("r", self.geometry.open.terminal_radius)
+            .set("stroke-width", self.theme.stroke_width)
+            .set("stroke", self.theme.color)
+            .set("fill", self.theme.background.unwrap_or("none"));
         let circle2 = Circle::new()
             .set("cx", size.0 / 2)
             .set("cy", 0)
-            .set("r", 5)
-            .set("stroke-width", 2)
-            .set("stroke", "black")
-            .set("fill", "white");
-        self.add(self.transform(Group::new().add(circle1).add(circle2).add(self.label(label, rotate, 30, 30)), position, rotate))
+            .set("r", self.geometry.open.terminal_radius)
+            .set("stroke-width", self.theme.stroke_width)
+            .set("stroke", self.theme.color)
+            .set("fill", self.theme.background.unwrap_or("none"));
+        self.add_element("open circuit", label, Group::new().add(circle1).add(circle2), self.geometry.open.label, position, rotate);
+    }
+
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let transformer = self.geometry.transformer;
+        let (x, radius, length) = (transformer.separation / 2, transformer.radius, transformer.coil_length());
+        self.grow_viewbox(position, size, rotate);
+        let mut leads = Data::new();
+        for [corner, bend, end] in transformer.leads(size) {
+            leads = leads.move_to(corner).line_to(bend).line_to(end);
+        }
+        // both coils run downwards, bulging away from the core
+        let mut primary = Data::new().move_to((-x, -length / 2));
+        let mut secondary = Data::new().move_to((x, -length / 2));
+        for i in 1..=transformer.loops {
+            primary = primary.elliptical_arc_to((radius, radius, 0, 0, 0, -x, -length / 2 + radius * 2 * i));
+            secondary = secondary.elliptical_arc_to((radius, radius, 0, 0, 1, x, -length / 2 + radius * 2 * i));
+        }
+        let mut core = Data::new();
+        for bar in [-transformer.core_gap / 2, transformer.core_gap / 2] {
+            core = core.move_to((bar, -length / 2)).line_to((bar, length / 2));
+        }
+        let mut group = Group::new();
+        for data in [leads, primary, secondary, core] {
+            group = group.add(Path::new()
+                .set("stroke", self.theme.color)
+                .set("fill", "none")
+                .set("stroke-width", self.theme.stroke_width)
+                .set("d", data));
+        }
+        self.add_element("transformer", label, group, transformer.label, position, rotate);
     }
 
     fn wire(&mut self, a: layout::Position, b: layout::Position) {
         let line = Path::new()
-            .set("stroke", "black")
+            .set("stroke", self.theme.color)
             .set("fill", "none")
-            .set("stroke-width", "2")
+            .set("stroke-width", self.theme.stroke_width)
             .set("d", Data::new().move_to((a.0, a.1)).line_to((b.0, b.1)));
-        self.add(line);
+        self.add_wire(line);
+        self.grow_viewbox(a, Size(0, 0), false);
+        self.grow_viewbox(b, Size(0, 0), false);
     }
 
     fn junction(&mut self, position: layout::Position) {
         let circle = Circle::new()
             .set("cx", position.0)
             .set("cy", position.1)
-            .set("r", 3)
-            .set("fill", "black");
-        self.add(circle);
+            .set("r", JUNCTION_RADIUS)
+            .set("fill", self.theme.color);
+        self.add_wire(circle);
+        self.grow_viewbox(position, Size(2 * JUNCTION_RADIUS, 2 * JUNCTION_RADIUS), false);
+    }
+
+    fn tooltip(&mut self, text: &str) {
+        self.tooltip = Some(text.to_string());
+    }
+
+    // a dot, with the name above and to the right of it
+    fn polarity(&mut self, position: Position, rotate: bool, flip: bool, current: bool) {
+        let marks = self.geometry.polarity;
+        let path = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width);
+        // the arrow turns with the source, while the signs stay upright
+        let group = if current {
+            let arrow = marks.arrow(flip).into_iter().fold(Data::new(), |data, [a, b]| data.move_to(a).line_to(b));
+            self.transform(Group::new().add(path.set("d", arrow)), position, rotate)
+        } else {
+            let signs = marks.signs(position, rotate, flip);
+            for point in signs.iter().flatten() {
+                self.grow_viewbox(*point, Size(0, 0), false);
+            }
+            let signs = signs.into_iter().fold(Data::new(), |data, [a, b]| data.move_to((a.0, a.1)).line_to((b.0, b.1)));
+            Group::new().add(path.set("d", signs))
+        };
+        let group = group.set("id", format!("element{}-polarity", self.element_count));
+        self.symbols.append(group);
+    }
+
+    fn current_arrow(&mut self, name: &str, position: Position, rotate: bool, flip: bool) {
+        let annotation = self.geometry.annotation;
+        let head = annotation.current_head(flip).into_iter().fold(Data::new(), |data, [a, b]| data.move_to(a).line_to(b));
+        let head = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", head);
+        self.symbols.append(self.transform(Group::new().add(head), position, rotate));
+        if !name.is_empty() {
+            let Position(x, y) = symbols::place(position, rotate, annotation.label);
+            self.labels.append(self.annotation_label(name, x, y, "middle"));
+            self.grow_text(Position(x, y), text_width(name), "middle");
+        }
+    }
+
+    fn voltage_arrow(&mut self, name: &str, a: Position, b: Position) {
+        let annotation = self.geometry.annotation;
+        let arrow = annotation.voltage_arrow(a, b);
+        for point in arrow.iter().flatten() {
+            self.grow_viewbox(*point, Size(0, 0), false);
+        }
+        let arrow = arrow.into_iter().fold(Data::new(), |data, [a, b]| data.move_to((a.0, a.1)).line_to((b.0, b.1)));
+        let arrow = Path::new()
+            .set("stroke", self.theme.color)
+            .set("fill", "none")
+            .set("stroke-width", self.theme.stroke_width)
+            .set("d", arrow);
+        self.symbols.append(arrow);
+        if !name.is_empty() {
+            let Position(x, y) = annotation.voltage_label(a, b);
+            self.labels.append(self.annotation_label(name, x, y, "start"));
+            self.grow_text(Position(x, y), text_width(name), "start");
+        }
+    }
+
+    fn node_label(&mut self, name: &str, position: Position) {
+        self.junction(position);
+        let text = Text::new()
+            .add(text_node(name))
+            .set("x", position.0 + JUNCTION_RADIUS)
+            .set("y", position.1 - JUNCTION_RADIUS)
+            .set("font-size", "0.8em")
+            .set("fill", self.theme.color);
+        self.labels.append(text);
+        // at 0.8em, characters are narrower than those of other labels
+        self.grow_text(Position(position.0 + JUNCTION_RADIUS, position.1 - JUNCTION_RADIUS), text_width(name) * 4 / 5, "start");
+    }
+
+    fn title(&mut self, title: &str, position: Position) {
+        let mut text = Text::new()
+            .add(text_node(title))
+            .set("x", position.0)
+            .set("y", position.1 + 5)
+            .set("text-anchor", "middle")
+            .set("font-weight", "bold")
+            .set("fill", self.theme.color);
+        if let Some(font_size) = self.theme.font_size {
+            text = text.set("font-size", font_size);
+        }
+        self.labels.append(text);
+        self.titles.push(title.to_string());
+        self.grow_text(Position(position.0, position.1 + 5), text_width(title), "middle");
+    }
+
+    fn value(&mut self, value: &str) {
+        let Some(anchor) = self.last_label else {
+            return;
+        };
+        // one line below the label
+        let text = self.label(value, anchor.rotate, anchor.offset.0, anchor.offset.1).set("dy", "1.2em");
+        let text = self.transform(Group::new().add(text), anchor.position, anchor.rotate)
+            .set("id", format!("element{}-value", self.element_count));
+        self.labels.append(text);
+        // 1.2em at 16 units per em
+        let Position(x, y) = self.label_position(anchor.position, anchor.rotate, anchor.offset);
+        self.grow_text(Position(x, y + 19), text_width(value), "middle");
+    }
+}
+
+/// A text node, escaping what would be taken for markup, as the `svg` crate writes text as it is
+fn text_node(content: impl AsRef<str>) -> svg::node::Text {
+    svg::node::Text::new(escape(content.as_ref()))
+}
+
+/// Whether the text can name an XML attribute or element, e.g. `data-id` or `xml:lang` but not `on click`
+///
+/// ```
+/// use circmark_parse::draw::svg::is_xml_name;
+/// assert!(is_xml_name("data-id") && is_xml_name("xml:lang"));
+/// assert!(!is_xml_name("1st") && !is_xml_name("on click") && !is_xml_name("x\"y"));
+/// ```
+pub fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// Escapes text for XML, in content and attribute values
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Quotes text as a CSS string, escaping quotes, backslashes and control characters as well as markup, as the
+/// string ends up in a `<style>` element or an attribute (which the `svg` crate quotes with `'`)
+fn css_string(text: &str) -> String {
+    let mut string = String::with_capacity(text.len() + 2);
+    string.push('"');
+    for c in text.chars() {
+        if matches!(c, '"' | '\\' | '\'' | '<' | '>' | '&') || c.is_control() {
+            string.push_str(&format!("\\{:x} ", u32::from(c)));
+        } else {
+            string.push(c);
+        }
+    }
+    string.push('"');
+    string
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i)) & 63] as char);
+            } else {
+                output.push('=');
+            }
+        }
     }
+    output
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+    use crate::{circuit, draw::{self, Draw, Context}, layout::Layout};
 
     type E = nom::error::VerboseError<&'static str>;
 
+    fn save(name: &str, mut drawer: SvgDrawer) {
+        std::fs::create_dir_all("test-output").unwrap();
+        svg::save(format!("test-output/{name}.svg"), &drawer.finalize().unwrap()).unwrap();
+    }
+
     #[test]
     fn test_draw_single_resistor() {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::R("1");
         element.draw(element.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_single_resistor.svg", &drawer.finalize()).unwrap();
+        save("draw_single_resistor", drawer);
     }
 
     #[test]
@@ -298,7 +1560,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::C("1");
         element.draw(element.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_single_capacitor.svg", &drawer.finalize()).unwrap();
+        save("draw_single_capacitor", drawer);
     }
 
     #[test]
@@ -306,7 +1568,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::L("1");
         element.draw(element.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_single_inductor.svg", &drawer.finalize()).unwrap();
+        save("draw_single_inductor", drawer);
     }
 
     #[test]
@@ -314,7 +1576,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::R("1");
         element.draw(element.layout_size(), Context::default().rotate(), &mut drawer);
-        svg::save("test-output/draw_single_resistor_rotated.svg", &drawer.finalize()).unwrap();
+        save("draw_single_resistor_rotated", drawer);
     }
 
     #[test]
@@ -322,7 +1584,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::C("1");
         element.draw(element.layout_size(), Context::default().rotate(), &mut drawer);
-        svg::save("test-output/draw_single_capacitor_rotated.svg", &drawer.finalize()).unwrap();
+        save("draw_single_capacitor_rotated", drawer);
     }
 
     #[test]
@@ -330,7 +1592,104 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let element = circuit::Element::L("1");
         element.draw(element.layout_size(), Context::default().rotate(), &mut drawer);
-        svg::save("test-output/draw_single_inductor_rotated.svg", &drawer.finalize()).unwrap();
+        save("draw_single_inductor_rotated", drawer);
+    }
+
+    #[test]
+    fn test_draw_diodes() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-D1|Dz5v1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        save("draw_diodes", drawer);
+    }
+
+    #[test]
+    fn test_draw_multi_letter_kinds() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-FUSE1-NTC1|LED1|XTAL1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        save("draw_multi_letter_kinds", drawer);
+    }
+
+    #[test]
+    fn test_draw_titled() {
+        let mut drawer = SvgDrawer::new().description("A low pass filter".to_string());
+        let documents = [circuit::document("|V1-R1|C1").unwrap(), circuit::document("(R1+R2)").unwrap()];
+        crate::draw::draw_titled(&[(Some("Low pass"), &documents[0]), (None, &documents[1])], Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("<title>\nLow pass\n</title>"));
+        assert!(output.contains("<desc>\nA low pass filter\n</desc>"));
+        assert!(output.contains("font-weight=\"bold\""));
+
+        crate::draw::draw_titled(&[(Some("Tom & Jerry <1>"), &documents[0])], Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("<title>\nTom &amp; Jerry &lt;1&gt;\n</title>"));
+        assert!(output.contains(">\nTom &amp; Jerry &lt;1&gt;\n</text>"));
+    }
+
+    #[test]
+    fn test_draw_switches() {
+        let mut drawer = SvgDrawer::new().keyboard_navigation(false);
+        let twoport = circuit::twoport::<E>("|V1-S1|S2*").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("aria-label=\"open switch S1\""));
+        assert!(output.contains("aria-label=\"closed switch S2\""));
+    }
+
+    #[test]
+    fn test_draw_source_polarity() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|^V1-vI1-V2|vV3").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("id=\"element1-polarity\""));
+        assert!(output.contains("id=\"element2-polarity\""));
+        assert!(!output.contains("id=\"element3-polarity\""));
+        assert!(output.contains("id=\"element4-polarity\""));
+    }
+
+    #[test]
+    fn test_draw_values() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1=5V-R1=4.7k|C1=100nF").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("id=\"element2-value\""));
+        assert!(output.contains(">\n4.7k\n</text>"));
+        assert!(output.contains(">\n100nF\n</text>"));
+    }
+
+    #[test]
+    fn test_draw_shortened_labels() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|Vsupplyvoltage=5V-R1=4.7k|Cload=100nF").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default().shortening(layout::Shortening::ALL), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains(">\nVsupplyvol…\n</text>"));
+        assert!(output.contains("<title>\nVsupplyvoltage\n</title>"));
+        assert!(output.contains(">\nCload\n</text>"));
+        assert!(output.contains(">\n4.7k\n</text>"));
+    }
+
+    #[test]
+    fn test_draw_annotations() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-R1{i:i1}|R2{u:u2}").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains(">\ni1\n</text>"));
+        assert!(output.contains(">\nu2\n</text>"));
+        assert!(output.contains("font-style=\"italic\""));
+    }
+
+    #[test]
+    fn test_draw_named_nodes() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-R1.vout|C1").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains(">\nvout\n</text>"));
     }
 
     #[test]
@@ -338,7 +1697,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("(R1+R2)").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_two_series_resistors.svg", &drawer.finalize()).unwrap();
+        save("draw_two_series_resistors", drawer);
     }
 
     #[test]
@@ -346,7 +1705,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("(R1+R2)").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default().rotate(), &mut drawer);
-        svg::save("test-output/draw_two_series_resistors_rotated.svg", &drawer.finalize()).unwrap();
+        save("draw_two_series_resistors_rotated", drawer);
     }
 
     #[test]
@@ -354,7 +1713,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("(R1||R2)").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_two_parallel_resistors.svg", &drawer.finalize()).unwrap();
+        save("draw_two_parallel_resistors", drawer);
     }
 
     #[test]
@@ -362,7 +1721,7 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("(R1||(R2+R3))").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_parallel_series_combi.svg", &drawer.finalize()).unwrap();
+        save("draw_parallel_series_combi", drawer);
     }
 
     #[test]
@@ -370,7 +1729,146 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("(R1+R2||R3)").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_parallel_series_combi2.svg", &drawer.finalize()).unwrap();
+        save("draw_parallel_series_combi2", drawer);
+    }
+
+    #[test]
+    fn test_draw_twoport_mirrored() {
+        let mut drawer = SvgDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-(R1||C1)-V2|R2").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default().mirror(), &mut drawer);
+        save("draw_twoport_mirrored", drawer);
+    }
+
+    #[test]
+    fn test_draw_stacked_twoports() {
+        let mut drawer = SvgDrawer::new();
+        let twoports = [
+            circuit::twoport::<E>("|V1-R1|C1").unwrap().1,
+            circuit::twoport::<E>("|V1-(R1+R2)|(C1||R3)").unwrap().1,
+        ];
+        draw::draw_stacked(&twoports, Context::default(), &mut drawer);
+        save("draw_stacked_twoports", drawer);
+    }
+
+    #[test]
+    fn test_inherit_page_style() {
+        let mut drawer = SvgDrawer::new().inherit_page_style();
+        let twoport = circuit::twoport::<E>("|V1-R1|O").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(!output.contains("black"));
+        assert!(!output.contains("background"));
+        assert!(output.contains("stroke=\"currentColor\""));
+        assert!(output.contains("font-size=\"1em\""));
+        assert!(output.contains("width=\"23.75em\""));
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut drawer = SvgDrawer::new().scale(2.0);
+        let twoport = circuit::twoport::<E>("|V1-R1|O").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        // the drawing units stay the same
+        assert!(output.contains("viewBox=\"0 0 380 "));
+        assert!(output.contains("width=\"760\""));
+    }
+
+    #[test]
+    fn test_theme() {
+        let draw = |drawer: SvgDrawer| {
+            let mut drawer = drawer;
+            let twoport = circuit::twoport::<E>("|V1-R1|O").unwrap().1;
+            twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+            drawer.finalize().unwrap().to_string()
+        };
+        assert!(draw(SvgDrawer::new().theme(Theme::DARK)).contains("style=\"background: #1e1e1e\""));
+        let output = draw(SvgDrawer::new().theme(Theme { margin: Margin::uniform(10), label_distance: 5, ..Theme::DARK }).inherit_page_style());
+        // the page's colors replace the theme's, its spacing remains
+        assert!(output.contains("stroke=\"currentColor\""));
+        assert!(output.contains("width=\"21.25em\""));
+        assert!(output.contains("x=\"35\""));
+    }
+
+    #[test]
+    fn test_viewbox_fits_labels() {
+        let view_box = |source: &'static str, margin: Margin| {
+            let mut drawer = SvgDrawer::new().theme(Theme { margin, ..Theme::LIGHT });
+            let twoport = circuit::twoport::<E>(source).unwrap().1;
+            twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+            let output = drawer.finalize().unwrap().to_string();
+            let view_box = output.split("viewBox=\"0 0 ").nth(1).unwrap().split('"').next().unwrap().to_string();
+            let (w, h) = view_box.split_once(' ').unwrap();
+            (w.parse::<i32>().unwrap(), h.parse::<i32>().unwrap())
+        };
+        let (w, h) = view_box("|V1-R1|R2", Margin::uniform(0));
+        // a long label is wider than the last shunt, and the value of a capacitor sticks out beside it
+        assert!(view_box("|V1-R1|Rloadresistance", Margin::uniform(0)).0 > w);
+        assert!(view_box("|V1-R1|C2=470nF", Margin::uniform(0)).0 > view_box("|V1-R1|C2", Margin::uniform(0)).0);
+        let margin = Margin { top: 0, right: 5, bottom: 40, left: 10 };
+        assert_eq!(view_box("|V1-R1|R2", margin), (w + 15, h + 40));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_embedded_font() {
+        let mut drawer = SvgDrawer::new().font(Font {
+            family: "Circuit Sans".to_string(),
+            source: Some(FontSource::Embedded { data: b"foo".to_vec(), format: "woff2".to_string() }),
+        });
+        let element = circuit::Element::R("1");
+        element.draw(element.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains("@font-face { font-family: \"Circuit Sans\"; src: url(\"data:font/woff2;base64,Zm9v\") format(\"woff2\"); }"));
+        assert!(output.contains("font-family='\"Circuit Sans\"'"));
+    }
+
+    #[test]
+    fn test_font_url() {
+        let mut drawer = SvgDrawer::new().font(Font {
+            family: "Bob's \"Sans\"".to_string(),
+            source: Some(FontSource::Url("fonts/a\"b</style>.woff2".to_string())),
+        });
+        let element = circuit::Element::R("1");
+        element.draw(element.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert!(output.contains(r#"@font-face { font-family: "Bob\27 s \22 Sans\22 "; src: url("fonts/a\22 b\3c /style\3e .woff2"); }"#));
+        assert!(output.contains(r#"font-family='"Bob\27 s \22 Sans\22 "'"#));
+        assert_eq!(css_string("a\\b\n"), r#""a\5c b\a ""#);
+    }
+
+    #[test]
+    fn test_draw_legend() {
+        let mut drawer = SvgDrawer::new();
+        let document = circuit::document("|V1-(R1||C1)|L1").unwrap();
+        document.draw(document.layout_size(), Context::default(), &mut drawer);
+        drawer.legend(&Legend::new(&document, crate::legend::GERMAN));
+        let output = drawer.finalize().unwrap();
+        assert!(output.to_string().contains("V — Spannungsquelle"));
+        std::fs::create_dir_all("test-output").unwrap();
+        svg::save("test-output/draw_legend.svg", &output).unwrap();
+    }
+
+    #[test]
+    fn test_keyboard_navigation() {
+        let mut drawer = SvgDrawer::new().keyboard_navigation(true);
+        let twoport = circuit::twoport::<E>("|V1-R1|O").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let output = drawer.finalize().unwrap().to_string();
+        assert_eq!(output.matches("tabindex=\"0\"").count(), 3);
+        assert!(output.contains("aria-label=\"voltage source V1\""));
+        assert!(output.contains("aria-label=\"open circuit\""));
+        assert!(output.contains("aria-live=\"polite\""));
+        assert!(output.contains("<script>"));
     }
 
     #[test]
@@ -378,6 +1876,6 @@ mod tests {
         let mut drawer = SvgDrawer::new();
         let circuit = circuit::sub_circuit::<E>("((R1+R2||R3)+R4)").unwrap().1;
         circuit.draw(circuit.layout_size(), Context::default(), &mut drawer);
-        svg::save("test-output/draw_parallel_series_combi3.svg", &drawer.finalize()).unwrap();
+        save("draw_parallel_series_combi3", drawer);
     }
 }
@@ -0,0 +1,446 @@
+//! Geometry of the element symbols, shared by the drawers.
+//!
+//! Sizes are in drawing units and element coordinates: the element is centered on the origin and runs
+//! along the x axis, with leads connecting its body to both ends. Label offsets are the x offset of the
+//! label for vertical elements, and the y offset of its baseline for horizontal ones.
+//!
+//! Other styles are made by changing fields of [`Geometry::STANDARD`], and passed to a drawer, e.g.
+//! with [`super::svg::SvgDrawer::geometry`].
+
+use crate::layout::{Position, Size};
+
+/// Stroke width of wires, leads and symbol outlines
+pub const STROKE_WIDTH: i32 = 2;
+
+/// Radius of the dots marking junctions
+pub const JUNCTION_RADIUS: i32 = 3;
+
+/// Distance between the baselines of a label and a value
+pub const LINE_HEIGHT: i32 = 20;
+
+/// Average advance of a character in labels, at the default font size
+pub const CHAR_WIDTH: i32 = 9;
+
+/// Estimated width of a label or value, as there are no font metrics at layout time
+pub fn text_width(text: &str) -> i32 {
+    text.chars().count() as i32 * CHAR_WIDTH
+}
+
+/// Standard the symbols follow, where they differ between standards
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SymbolStandard {
+    /// IEC 60617, as used in Europe: resistors are rectangles
+    #[default]
+    Iec,
+    /// ANSI/IEEE 315, as used in the US: resistors are zigzag lines
+    Ansi,
+}
+
+impl std::str::FromStr for SymbolStandard {
+    type Err = crate::error::UnknownName;
+
+    /// Parses the name of a standard, `iec` or `ansi`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "iec" => Ok(SymbolStandard::Iec),
+            "ansi" => Ok(SymbolStandard::Ansi),
+            _ => Err(crate::error::UnknownName { option: "symbol standard", name: name.to_string(), expected: &["iec", "ansi"] }),
+        }
+    }
+}
+
+/// Body of resistors and impedances, a rectangle or a zigzag line within it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Resistor {
+    pub width: i32,
+    pub height: i32,
+    /// Label offset of IEC resistors, whose labels are written within the rectangle
+    pub label: (i32, i32),
+    /// Label offset of ANSI resistors, whose labels are written next to the zigzag line
+    pub ansi_label: (i32, i32),
+}
+
+impl Resistor {
+    /// Corners of the zigzag line of ANSI resistors, from the left to the right end of the body
+    pub fn zigzag(&self) -> [(i32, i32); 8] {
+        let (w, h) = (self.width, self.height);
+        std::array::from_fn(|i| match i {
+            0 => (-w / 2, 0),
+            7 => (w / 2, 0),
+            _ => ((2 * i as i32 - 7) * w / 12, if i % 2 == 1 { -h / 2 } else { h / 2 }),
+        })
+    }
+
+    /// The label offset of the standard
+    pub fn label(&self, standard: SymbolStandard) -> (i32, i32) {
+        match standard {
+            SymbolStandard::Iec => self.label,
+            SymbolStandard::Ansi => self.ansi_label,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capacitor {
+    /// Distance between the plates
+    pub gap: i32,
+    pub plate_length: i32,
+    pub plate_width: i32,
+    pub label: (i32, i32),
+}
+
+/// Inductor drawn as a row of half circles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Inductor {
+    pub loops: i32,
+    pub radius: i32,
+    pub label: (i32, i32),
+}
+
+impl Inductor {
+    pub fn width(&self) -> i32 {
+        2 * self.loops * self.radius
+    }
+}
+
+/// Voltage source drawn as a cell, with a long (positive) and a short plate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VoltageSource {
+    /// Distance between the plates
+    pub gap: i32,
+    pub long_plate: i32,
+    pub short_plate: i32,
+    pub plate_width: i32,
+    pub label: (i32, i32),
+}
+
+/// Current source drawn as two overlapping circles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CurrentSource {
+    pub radius: i32,
+    /// Distance of the circles' centers from the element's center
+    pub offset: i32,
+    pub label: (i32, i32),
+}
+
+impl CurrentSource {
+    pub fn width(&self) -> i32 {
+        2 * (self.offset + self.radius)
+    }
+}
+
+/// Marks of sources with a polarity: `+` and `−` signs beside the plates of voltage sources, and an
+/// arrow beside current sources
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PolarityMarks {
+    /// Center of the `+` sign relative to the center of a voltage source, the `−` sign being mirrored
+    pub sign: (i32, i32),
+    /// Half the length of the signs' strokes, and the length of both strokes of the arrow's head
+    pub mark: i32,
+    /// Distance of the arrow from the axis of a current source
+    pub arrow_offset: i32,
+    pub arrow_length: i32,
+}
+
+impl PolarityMarks {
+    /// Strokes of the signs of a voltage source as lines in drawing coordinates, the `+` on its left (or top)
+    /// end unless flipped.
+    ///
+    /// Unlike the symbol itself, the signs are not rotated with the source, so that the `−` stays horizontal.
+    pub fn signs(&self, position: Position, rotate: bool, flip: bool) -> Vec<[Position; 2]> {
+        let (x, y) = self.sign;
+        let direction = if flip { -1 } else { 1 };
+        let plus = place(position, rotate, (-direction * x, y));
+        let minus = place(position, rotate, (direction * x, y));
+        let m = self.mark;
+        vec![
+            [Position(plus.0 - m, plus.1), Position(plus.0 + m, plus.1)],
+            [Position(plus.0, plus.1 - m), Position(plus.0, plus.1 + m)],
+            [Position(minus.0 - m, minus.1), Position(minus.0 + m, minus.1)],
+        ]
+    }
+
+    /// Strokes of the arrow beside a current source as lines, pointing to the left unless flipped
+    pub fn arrow(&self, flip: bool) -> Vec<[(i32, i32); 2]> {
+        let (x, y, m) = (self.arrow_length / 2, -self.arrow_offset, self.mark);
+        let tip = if flip { x } else { -x };
+        let back = if flip { tip - m } else { tip + m };
+        vec![[(-x, y), (x, y)], [(tip, y), (back, y - m)], [(tip, y), (back, y + m)]]
+    }
+}
+
+/// Arrows annotating twoports with currents and voltages, see [`crate::circuit::TwoportLink::Annotation`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Length of both strokes of the arrows' heads
+    pub head: i32,
+    /// Distance of voltage arrows from the line between their ends
+    pub spacing: i32,
+    /// How much shorter voltage arrows are than the line between their ends, at both ends
+    pub margin: i32,
+    /// Offset of the name of a current from the arrow's head
+    pub label: (i32, i32),
+}
+
+impl Annotation {
+    /// Strokes of the head of a current arrow on a wire as lines, pointing to the right unless flipped
+    pub fn current_head(&self, flip: bool) -> [[(i32, i32); 2]; 2] {
+        let (h, back) = (self.head, if flip { self.head } else { -self.head });
+        [[(0, 0), (back, -h)], [(0, 0), (back, h)]]
+    }
+
+    /// Strokes of a voltage arrow from `a` to `b` as lines, to the left of the line between them as seen
+    /// from `a`, e.g. to the right of a line going down
+    pub fn voltage_arrow(&self, a: Position, b: Position) -> [[Position; 2]; 3] {
+        let ((ux, uy), (nx, ny)) = self.directions(a, b);
+        let point = |Position(x, y): Position, along: f64, beside: f64| {
+            Position(x + (ux * along + nx * beside).round() as i32, y + (uy * along + ny * beside).round() as i32)
+        };
+        let (h, spacing) = (self.head as f64, self.spacing as f64);
+        let start = point(a, self.margin as f64, spacing);
+        let tip = point(b, -(self.margin as f64), spacing);
+        [[start, tip], [tip, point(tip, -h, h)], [tip, point(tip, -h, -h)]]
+    }
+
+    /// Where the name of a voltage starts, beside the middle of its arrow
+    pub fn voltage_label(&self, a: Position, b: Position) -> Position {
+        let (_, (nx, ny)) = self.directions(a, b);
+        let beside = (self.spacing + self.head + 4) as f64;
+        Position((a.0 + b.0) / 2 + (nx * beside).round() as i32, (a.1 + b.1) / 2 + (ny * beside).round() as i32 + 5)
+    }
+
+    /// The unit vector from `a` to `b`, and the one to its left
+    fn directions(&self, a: Position, b: Position) -> ((f64, f64), (f64, f64)) {
+        let (dx, dy) = ((b.0 - a.0) as f64, (b.1 - a.1) as f64);
+        let length = dx.hypot(dy).max(1.0);
+        let (ux, uy) = (dx / length, dy / length);
+        ((ux, uy), (uy, -ux))
+    }
+}
+
+/// Triangle and bar of diodes and zener diodes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Diode {
+    pub half_width: i32,
+    pub half_height: i32,
+    /// Length of the bent ends of a zener diode's bar, in both directions
+    pub zener_bend: i32,
+    pub label: (i32, i32),
+}
+
+/// Terminals of an open circuit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Open {
+    pub terminal_radius: i32,
+    pub label: (i32, i32),
+}
+
+/// Switch drawn as a lever hinged on the left contact, raised when open
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Switch {
+    /// Distance between the contacts
+    pub width: i32,
+    /// Height of the open lever's end above the right contact
+    pub lift: i32,
+    pub contact_radius: i32,
+    pub label: (i32, i32),
+}
+
+/// Controlled source drawn as a diamond, containing a `+` and a `-` (voltage) or an arrow (current)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ControlledSource {
+    /// Distance of the corners from the center
+    pub half_diagonal: i32,
+    /// Half the length of the marks' strokes, and of the arrow's head
+    pub mark: i32,
+    pub label: (i32, i32),
+}
+
+impl ControlledSource {
+    /// Strokes of the marks inside the diamond as lines, with the positive terminal (or where the
+    /// current comes from) on the left
+    pub fn marks(&self, current: bool) -> Vec<[(i32, i32); 2]> {
+        let (x, m) = (self.half_diagonal / 2, self.mark);
+        if current {
+            vec![[(-x, 0), (x, 0)], [(x, 0), (x - m, -m)], [(x, 0), (x - m, m)]]
+        } else {
+            vec![[(-x - m, 0), (-x + m, 0)], [(-x, -m), (-x, m)], [(x - m, 0), (x + m, 0)]]
+        }
+    }
+}
+
+/// Wiper of a potentiometer, an arrow pointing at the body of a resistor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Potentiometer {
+    /// Length of both strokes of the arrow's head
+    pub arrow: i32,
+}
+
+impl Potentiometer {
+    /// Lines of the wiper, going from the tap (in element coordinates) straight to the body's top edge
+    pub fn wiper(&self, resistor: Resistor, (x, y): (i32, i32)) -> [[(i32, i32); 2]; 3] {
+        let (top, a) = (-resistor.height / 2, self.arrow);
+        [[(x, y), (x, top)], [(x, top), (x - a, top - a)], [(x, top), (x + a, top - a)]]
+    }
+}
+
+/// Arrows of a light emitting diode, pointing away from the top of a diode
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Led {
+    /// Horizontal and vertical extent of each arrow
+    pub arrow: i32,
+    /// Length of both strokes of the arrows' heads
+    pub head: i32,
+    /// Distance between the arrows
+    pub spacing: i32,
+    /// Replaces the diode's label offset, leaving room for the arrows
+    pub label: (i32, i32),
+}
+
+impl Led {
+    /// Strokes of the arrows as lines, above a diode conducting to the right
+    pub fn arrows(&self, diode: Diode) -> Vec<[(i32, i32); 2]> {
+        let (a, h) = (self.arrow, self.head);
+        [-self.spacing / 2, self.spacing / 2].into_iter().flat_map(|x| {
+            let (start, end) = ((x, -diode.half_height), (x + a, -diode.half_height - a));
+            [[start, end], [end, (end.0 - h, end.1)], [end, (end.0, end.1 + h)]]
+        }).collect()
+    }
+}
+
+/// Line crossing the body of a thermistor diagonally, with a short horizontal foot on its lower end
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Thermistor {
+    /// Horizontal distance of the line's ends from the center
+    pub half_width: i32,
+    /// Vertical distance of the line's ends from the center
+    pub half_height: i32,
+    pub foot: i32,
+    pub label: (i32, i32),
+}
+
+impl Thermistor {
+    /// The line as a polyline, starting at the end of the foot
+    pub fn line(&self) -> [(i32, i32); 3] {
+        let (w, h) = (self.half_width, self.half_height);
+        [(-w - self.foot, h), (-w, h), (w, -h)]
+    }
+}
+
+/// Quartz crystal drawn as a box between two plates
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Crystal {
+    pub width: i32,
+    pub height: i32,
+    /// Distance between the box and each plate
+    pub gap: i32,
+    pub plate_length: i32,
+    pub label: (i32, i32),
+}
+
+/// Fuse drawn as a box with the wire running through it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Fuse {
+    pub width: i32,
+    pub height: i32,
+    pub label: (i32, i32),
+}
+
+/// Transformer drawn as two vertical coils facing each other, with a core of two bars between them.
+///
+/// Unlike the other symbols it spans the height of a twoport, its leads going to the corners of the link.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Transformer {
+    pub loops: i32,
+    pub radius: i32,
+    /// Distance between the axes of the coils
+    pub separation: i32,
+    /// Distance between the bars of the core
+    pub core_gap: i32,
+    pub label: (i32, i32),
+}
+
+impl Transformer {
+    pub fn coil_length(&self) -> i32 {
+        2 * self.loops * self.radius
+    }
+
+    /// Leads from the corners of a link of the given size to the ends of the coils, as polylines.
+    ///
+    /// The primary's leads (going to the left corners) come first, top before bottom.
+    pub fn leads(&self, size: Size) -> [[(i32, i32); 3]; 4] {
+        let (x, y) = (self.separation / 2, self.coil_length() / 2);
+        [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .map(|(dx, dy)| [(dx * size.0 / 2, dy * size.1 / 2), (dx * x, dy * size.1 / 2), (dx * x, dy * y)])
+    }
+}
+
+/// Translates element coordinates into drawing coordinates, for an element at `position`
+pub fn place(position: Position, rotate: bool, (x, y): (i32, i32)) -> Position {
+    if rotate {
+        Position(position.0 - y, position.1 + x)
+    } else {
+        Position(position.0 + x, position.1 + y)
+    }
+}
+
+/// Translates drawing coordinates into element coordinates, the inverse of [`place`]
+pub fn unplace(position: Position, rotate: bool, Position(x, y): Position) -> (i32, i32) {
+    if rotate {
+        (y - position.1, position.0 - x)
+    } else {
+        (x - position.0, y - position.1)
+    }
+}
+
+/// Geometry of all symbols
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Geometry {
+    pub resistor: Resistor,
+    pub capacitor: Capacitor,
+    pub inductor: Inductor,
+    pub voltage_source: VoltageSource,
+    pub current_source: CurrentSource,
+    pub polarity: PolarityMarks,
+    pub annotation: Annotation,
+    pub diode: Diode,
+    pub switch: Switch,
+    pub controlled_source: ControlledSource,
+    pub potentiometer: Potentiometer,
+    pub led: Led,
+    pub thermistor: Thermistor,
+    pub crystal: Crystal,
+    pub fuse: Fuse,
+    pub transformer: Transformer,
+    pub open: Open,
+    pub standard: SymbolStandard,
+}
+
+impl Geometry {
+    pub const STANDARD: Geometry = Geometry {
+        resistor: Resistor { width: 70, height: 20, label: (0, 4), ansi_label: (30, 30) },
+        capacitor: Capacitor { gap: 10, plate_length: 30, plate_width: 5, label: (30, 30) },
+        inductor: Inductor { loops: 4, radius: 10, label: (30, -20) },
+        voltage_source: VoltageSource { gap: 10, long_plate: 40, short_plate: 20, plate_width: 4, label: (30, 30) },
+        current_source: CurrentSource { radius: 15, offset: 10, label: (30, 30) },
+        polarity: PolarityMarks { sign: (18, -16), mark: 4, arrow_offset: 23, arrow_length: 30 },
+        annotation: Annotation { head: 6, spacing: 12, margin: 25, label: (0, -10) },
+        diode: Diode { half_width: 10, half_height: 12, zener_bend: 4, label: (30, 30) },
+        switch: Switch { width: 40, lift: 20, contact_radius: 3, label: (30, 30) },
+        controlled_source: ControlledSource { half_diagonal: 20, mark: 5, label: (30, 30) },
+        potentiometer: Potentiometer { arrow: 6 },
+        led: Led { arrow: 8, head: 4, spacing: 8, label: (45, 30) },
+        thermistor: Thermistor { half_width: 25, half_height: 16, foot: 8, label: (30, 30) },
+        crystal: Crystal { width: 14, height: 26, gap: 4, plate_length: 30, label: (45, 30) },
+        fuse: Fuse { width: 50, height: 14, label: (30, 30) },
+        transformer: Transformer { loops: 4, radius: 10, separation: 40, core_gap: 6, label: (40, -50) },
+        open: Open { terminal_radius: 5, label: (30, 30) },
+        standard: SymbolStandard::Iec,
+    };
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
@@ -0,0 +1,241 @@
+use crate::layout::{Size, Position};
+use super::symbols::{self, Geometry};
+
+/// Drawing units per TikZ unit (cm)
+const SCALE: f64 = 100.0;
+
+enum Item {
+    /// Two-terminal component drawn with `to[kind]`, from `a` to `b`
+    Bipole { kind: &'static str, label: String, value: Option<String>, a: Position, b: Position },
+    Open { a: Position, b: Position },
+    Wire { a: Position, b: Position },
+    Junction(Position),
+}
+
+/// Drawer producing [CircuiTikZ](https://ctan.org/pkg/circuitikz) code for LaTeX documents.
+///
+/// Elements are drawn as `to[...]` paths between their ends, one unit being 1cm (100 drawing units).
+pub struct TikzDrawer {
+    items: Vec<Item>,
+}
+
+impl Default for TikzDrawer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TikzDrawer {
+    pub fn new() -> Self {
+        Self { items: vec![] }
+    }
+
+    /// Produces the `circuitikz` environment
+    pub fn finalize(self) -> String {
+        let mut tikz = String::from("\\begin{circuitikz}\n");
+        for item in &self.items {
+            match item {
+                Item::Bipole { kind, label, value, a, b } => {
+                    let mut options = kind.to_string();
+                    if !label.is_empty() {
+                        options.push_str(&format!(", l={}", math(label)));
+                    }
+                    if let Some(value) = value {
+                        options.push_str(&format!(", a={{{}}}", escape(value)));
+                    }
+                    tikz.push_str(&format!("  \\draw {} to[{options}] {};\n", point(*a), point(*b)));
+                }
+                Item::Open { a, b } => {
+                    tikz.push_str(&format!("  \\draw {} node[ocirc] {{}} {} node[ocirc] {{}};\n", point(*a), point(*b)));
+                }
+                Item::Wire { a, b } => tikz.push_str(&format!("  \\draw {} -- {};\n", point(*a), point(*b))),
+                Item::Junction(position) => tikz.push_str(&format!("  \\node[circ] at {} {{}};\n", point(*position))),
+            }
+        }
+        tikz.push_str("\\end{circuitikz}\n");
+        tikz
+    }
+
+    /// Adds a component, `flip` swapping its ends
+    fn bipole(&mut self, kind: &'static str, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        let (a, b) = ends(position, size, rotate);
+        let (a, b) = if flip { (b, a) } else { (a, b) };
+        self.items.push(Item::Bipole { kind, label: label.to_string(), value: None, a, b });
+    }
+}
+
+/// Returns the left and right (or top and bottom) end of an element
+fn ends(position: Position, size: Size, rotate: bool) -> (Position, Position) {
+    let (dx, dy) = if rotate { (0, size.0 / 2) } else { (size.0 / 2, 0) };
+    (Position(position.0 - dx, position.1 - dy), Position(position.0 + dx, position.1 + dy))
+}
+
+/// Formats a coordinate, flipping the y axis (TikZ's y axis points up)
+fn point(Position(x, y): Position) -> String {
+    format!("({},{})", x as f64 / SCALE, -y as f64 / SCALE)
+}
+
+/// Formats a label as math, the prefix letter being followed by the id as subscript, e.g. `$R_{1}$`
+fn math(label: &str) -> String {
+    let mut chars = label.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some(prefix), id) if !id.is_empty() => format!("${prefix}_{{{id}}}$"),
+        _ => format!("${label}$"),
+    }
+}
+
+/// Escapes characters of values that have no direct equivalent in LaTeX text mode, writing the exponents of
+/// [`crate::format::si`] as `\textsuperscript`
+fn escape(text: &str) -> String {
+    const SUPERSCRIPTS: [char; 11] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹', '⁻'];
+    let plain = |c: char| SUPERSCRIPTS.iter().position(|superscript| *superscript == c).and_then(|i| "0123456789-".chars().nth(i));
+    let mut escaped = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'µ' => escaped.push_str("\\textmu{}"),
+            'Ω' => escaped.push_str("\\ensuremath{\\Omega}"),
+            '×' => escaped.push_str("\\ensuremath{\\times}"),
+            '\u{2009}' | '\u{202F}' => escaped.push_str("\\,"),
+            c => match plain(c) {
+                Some(first) => {
+                    let mut exponent = first.to_string();
+                    while let Some(c) = chars.next_if(|c| plain(*c).is_some()) {
+                        exponent.extend(plain(c));
+                    }
+                    escaped.push_str(&format!("\\textsuperscript{{{exponent}}}"));
+                }
+                None => escaped.push(c),
+            },
+        }
+    }
+    escaped
+}
+
+impl super::Drawer for TikzDrawer {
+    fn resistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("R", label, position, size, rotate, false);
+    }
+
+    fn capacitor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("C", label, position, size, rotate, false);
+    }
+
+    fn inductor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("L", label, position, size, rotate, false);
+    }
+
+    fn voltage_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.bipole("V", label, position, size, rotate, flip);
+    }
+
+    fn current_source(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("I", label, position, size, rotate, false);
+    }
+
+    fn diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.bipole("D", label, position, size, rotate, flip);
+    }
+
+    fn zener_diode(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.bipole("zD", label, position, size, rotate, flip);
+    }
+
+    // normally open and normally closed switches, drawn in their resting position
+    fn switch(&mut self, label: &str, position: Position, size: Size, rotate: bool, closed: bool) {
+        self.bipole(if closed { "ncs" } else { "nos" }, label, position, size, rotate, false);
+    }
+
+    fn controlled_source(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool, current: bool) {
+        self.bipole(if current { "cI" } else { "cV" }, label, position, size, rotate, flip);
+    }
+
+    // CircuiTikZ draws the wiper itself
+    fn potentiometer(&mut self, label: &str, position: Position, size: Size, rotate: bool, _tap: Position) {
+        self.bipole("pR", label, position, size, rotate, false);
+    }
+
+    fn led(&mut self, label: &str, position: Position, size: Size, rotate: bool, flip: bool) {
+        self.bipole("leD", label, position, size, rotate, flip);
+    }
+
+    fn thermistor(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("thermistor ntc", label, position, size, rotate, false);
+    }
+
+    fn crystal(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("piezoelectric", label, position, size, rotate, false);
+    }
+
+    fn fuse(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        self.bipole("fuse", label, position, size, rotate, false);
+    }
+
+    fn open(&mut self, _label: &str, position: Position, size: Size, rotate: bool) {
+        let (a, b) = ends(position, size, rotate);
+        self.items.push(Item::Open { a, b });
+    }
+
+    fn transformer(&mut self, label: &str, position: Position, size: Size, rotate: bool) {
+        let transformer = Geometry::STANDARD.transformer;
+        let place = |point| symbols::place(position, rotate, point);
+        let [primary_top, primary_bottom, secondary_top, secondary_bottom] = transformer.leads(size).map(|[corner, bend, end]| {
+            self.items.push(Item::Wire { a: place(corner), b: place(bend) });
+            self.items.push(Item::Wire { a: place(bend), b: place(end) });
+            place(end)
+        });
+        self.items.push(Item::Bipole { kind: "L", label: label.to_string(), value: None, a: primary_top, b: primary_bottom });
+        self.items.push(Item::Bipole { kind: "L", label: String::new(), value: None, a: secondary_bottom, b: secondary_top });
+        let length = transformer.coil_length();
+        for bar in [-transformer.core_gap / 2, transformer.core_gap / 2] {
+            self.items.push(Item::Wire { a: place((bar, -length / 2)), b: place((bar, length / 2)) });
+        }
+    }
+
+    fn wire(&mut self, a: Position, b: Position) {
+        self.items.push(Item::Wire { a, b });
+    }
+
+    fn junction(&mut self, position: Position) {
+        self.items.push(Item::Junction(position));
+    }
+
+    fn value(&mut self, value: &str) {
+        if let Some(Item::Bipole { value: last, .. }) = self.items.last_mut() {
+            *last = Some(value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit, draw::{Draw, Context}, layout::Layout};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_draw_twoport() {
+        let mut drawer = TikzDrawer::new();
+        let twoport = circuit::twoport::<E>("|V1-R1=4.7k|C1=1uF").unwrap().1;
+        twoport.draw(twoport.layout_size(), Context::default(), &mut drawer);
+        let tikz = drawer.finalize();
+        assert!(tikz.starts_with("\\begin{circuitikz}\n"));
+        assert!(tikz.ends_with("\\end{circuitikz}\n"));
+        assert!(tikz.contains("  \\draw (-1.3,1) to[V, l=$V_{1}$] (-1.3,-1);\n"));
+        assert!(tikz.contains("  \\draw (-1,1) to[R, l=$R_{1}$, a={4.7k}] (1,1);\n"));
+        assert!(tikz.contains("to[C, l=$C_{1}$, a={1\\textmu{}F}]"));
+        assert_eq!(tikz.matches(" -- ").count(), 5);
+    }
+
+    #[test]
+    fn test_units() {
+        let mut drawer = TikzDrawer::new();
+        let circuit = circuit::sub_circuit::<E>("(R1=4.7kohm+C1=100nF)").unwrap().1;
+        circuit.draw(circuit.layout_size(), Context::default().locale(crate::format::Locale::SI), &mut drawer);
+        let tikz = drawer.finalize();
+        assert!(tikz.contains("a={4,7\\,k\\ensuremath{\\Omega}}"));
+        assert!(tikz.contains("a={100\\,nF}"));
+        assert_eq!(escape("2×10⁻¹⁸\u{202F}F"), "2\\ensuremath{\\times}10\\textsuperscript{-18}\\,F");
+    }
+}
@@ -0,0 +1,265 @@
+//! Machine-readable dumps of the parsed tree, for debugging and external tooling.
+//!
+//! Single-element groups such as `(R1)` are transparent, so they don't show up in the dump.
+
+use std::ops::Range;
+use crate::circuit::{self, Document, Element, SubCircuit, SubCircuitGroup, TwoportLink, Value};
+
+/// Byte ranges of the links (or the circuit) and the elements of a document, in order, for [`to_json_with_spans`]
+type Spans<'a> = &'a mut dyn Iterator<Item = Range<usize>>;
+
+/// Dumps the document as JSON.
+///
+/// ```
+/// use circmark_parse::{circuit, dump::to_json};
+/// let document = circuit::document("-R1=1k").unwrap();
+/// assert_eq!(
+///     to_json(&document),
+///     r#"{"type":"twoport","links":[{"type":"series","circuit":{"type":"element","kind":"R","id":"1","value":{"number":1000,"unit":""}}}]}"#,
+/// );
+/// ```
+pub fn to_json(document: &Document) -> String {
+    document_json(document, &mut std::iter::empty(), &mut std::iter::empty())
+}
+
+/// Dumps a sub-circuit as JSON like [`to_json`] does within a document, e.g. one found by its path (see
+/// [`crate::search`]).
+///
+/// ```
+/// use circmark_parse::{builder::{c, r}, dump::sub_circuit_to_json};
+/// assert_eq!(
+///     sub_circuit_to_json(&r("1").parallel(c("1"))),
+///     r#"{"type":"parallel","top":{"type":"element","kind":"R","id":"1"},"bottom":{"type":"element","kind":"C","id":"1"}}"#,
+/// );
+/// ```
+pub fn sub_circuit_to_json(circuit: &SubCircuit) -> String {
+    circuit_json(circuit, &mut std::iter::empty())
+}
+
+/// Parses the source and dumps it as JSON like [`to_json`], adding the `"span"` of each link (or the circuit) and
+/// element: its byte range in the source as `[start, end]`, shifted by `offset`, e.g. the start of the section
+/// the source was taken from. Element spans leave out the value.
+///
+/// ```
+/// use circmark_parse::dump::to_json_with_spans;
+/// assert_eq!(
+///     to_json_with_spans("-R1=1k", 0).unwrap(),
+///     r#"{"type":"twoport","links":[{"type":"series","circuit":{"type":"element","kind":"R","id":"1","value":{"number":1000,"unit":""},"span":[1,3]},"span":[0,6]}]}"#,
+/// );
+/// ```
+pub fn to_json_with_spans(source: &str, offset: usize) -> Result<String, crate::ParseError> {
+    let document = circuit::document(source)?;
+    let shift = |spans: Vec<Range<usize>>| spans.into_iter().map(move |span| span.start + offset..span.end + offset);
+    let links = circuit::spans(source).map_err(|error| crate::ParseError::new(source, error))?;
+    let elements = circuit::element_spans(source).map_err(|error| crate::ParseError::new(source, error))?;
+    Ok(document_json(&document, &mut shift(links), &mut shift(elements)))
+}
+
+/// Adds the next span, if there is one, to the JSON object
+fn with_span(mut json: String, spans: Spans) -> String {
+    if let Some(span) = spans.next() {
+        json.pop();
+        json.push_str(&format!(",\"span\":[{},{}]}}", span.start, span.end));
+    }
+    json
+}
+
+fn document_json(document: &Document, links: Spans, elements: Spans) -> String {
+    match document {
+        Document::Circuit(circuit) => {
+            let circuit = circuit_json(circuit, elements);
+            with_span(format!("{{\"type\":\"circuit\",\"circuit\":{circuit}}}"), links)
+        }
+        Document::Twoport(twoport) => {
+            let links: Vec<String> = twoport.links.iter().map(|link| with_span(match link {
+                TwoportLink::Series(circuit) => format!("{{\"type\":\"series\",\"circuit\":{}}}", circuit_json(circuit, elements)),
+                TwoportLink::Shunt(circuit) => format!("{{\"type\":\"shunt\",\"circuit\":{}}}", circuit_json(circuit, elements)),
+                TwoportLink::Transformer { id, ratio } => {
+                    let mut json = format!("{{\"type\":\"transformer\",\"id\":\"{}\"", escape(id));
+                    if let Some(ratio) = ratio {
+                        json.push_str(&format!(",\"ratio\":{}", ratio.number));
+                    }
+                    json.push('}');
+                    json
+                }
+                TwoportLink::Node(name) => format!("{{\"type\":\"node\",\"name\":\"{}\"}}", escape(name)),
+                TwoportLink::Annotation { quantity, name } => {
+                    format!("{{\"type\":\"annotation\",\"quantity\":\"{}\",\"name\":\"{}\"}}", quantity.letter(), escape(name))
+                }
+            }, links)).collect();
+            format!("{{\"type\":\"twoport\",\"links\":[{}]}}", links.join(","))
+        }
+    }
+}
+
+fn circuit_json(circuit: &SubCircuit, spans: Spans) -> String {
+    match circuit {
+        SubCircuit::Element(element, value) => {
+            let mut json = String::from("{\"type\":\"element\"");
+            if let Some((kind, id)) = kind_and_id(element) {
+                json.push_str(&format!(",\"kind\":\"{kind}\",\"id\":\"{}\"", escape(id)));
+            } else {
+                json.push_str(",\"kind\":\"O\"");
+            }
+            if let Element::Switch { closed, .. } = element {
+                json.push_str(&format!(",\"closed\":{closed}"));
+            }
+            if let Some(control) = element.control() {
+                json.push_str(&format!(",\"control\":\"{}\"", escape(control)));
+            }
+            if let Some(wiper) = element.wiper() {
+                json.push_str(&format!(",\"wiper\":\"{}\"", escape(wiper)));
+            }
+            if let Some(Value { number, unit }) = value {
+                json.push_str(&format!(",\"value\":{{\"number\":{number},\"unit\":\"{}\"}}", escape(unit)));
+            }
+            json.push('}');
+            with_span(json, spans)
+        }
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => circuit_json(circuit, spans),
+            SubCircuitGroup::Series(a, b) => {
+                let (a, b) = (circuit_json(a, spans), circuit_json(b, spans));
+                format!("{{\"type\":\"series\",\"left\":{a},\"right\":{b}}}")
+            }
+            SubCircuitGroup::Parallel(a, b) => {
+                let (a, b) = (circuit_json(a, spans), circuit_json(b, spans));
+                format!("{{\"type\":\"parallel\",\"top\":{a},\"bottom\":{b}}}")
+            }
+        },
+    }
+}
+
+/// Dumps the document as an S-expression.
+///
+/// ```
+/// use circmark_parse::{circuit, dump::to_sexpr};
+/// let document = circuit::document("|V1-(R1||C1=100n)").unwrap();
+/// assert_eq!(to_sexpr(&document), r#"(twoport (shunt (V "1")) (series (parallel (R "1") (C "1" (value 0.0000001 "")))))"#);
+/// ```
+pub fn to_sexpr(document: &Document) -> String {
+    match document {
+        Document::Circuit(circuit) => format!("(circuit {})", circuit_sexpr(circuit)),
+        Document::Twoport(twoport) => {
+            let links: Vec<String> = twoport.links.iter().map(|link| match link {
+                TwoportLink::Series(circuit) => format!("(series {})", circuit_sexpr(circuit)),
+                TwoportLink::Shunt(circuit) => format!("(shunt {})", circuit_sexpr(circuit)),
+                TwoportLink::Transformer { id, ratio: Some(ratio) } => format!("(transformer \"{}\" (ratio {}))", escape(id), ratio.number),
+                TwoportLink::Transformer { id, ratio: None } => format!("(transformer \"{}\")", escape(id)),
+                TwoportLink::Node(name) => format!("(node \"{}\")", escape(name)),
+                TwoportLink::Annotation { quantity, name } => format!("(annotation {} \"{}\")", quantity.letter(), escape(name)),
+            }).collect();
+            format!("(twoport {})", links.join(" "))
+        }
+    }
+}
+
+fn circuit_sexpr(circuit: &SubCircuit) -> String {
+    match circuit {
+        SubCircuit::Element(element, value) => {
+            let mut sexpr = match kind_and_id(element) {
+                Some((kind, id)) => format!("({kind} \"{}\"", escape(id)),
+                None => "(O".to_string(),
+            };
+            if let Element::Switch { closed, .. } = element {
+                sexpr.push_str(if *closed { " closed" } else { " open" });
+            }
+            if let Some(control) = element.control() {
+                sexpr.push_str(&format!(" (control \"{}\")", escape(control)));
+            }
+            if let Some(wiper) = element.wiper() {
+                sexpr.push_str(&format!(" (wiper \"{}\")", escape(wiper)));
+            }
+            if let Some(Value { number, unit }) = value {
+                sexpr.push_str(&format!(" (value {number} \"{}\")", escape(unit)));
+            }
+            sexpr.push(')');
+            sexpr
+        }
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => circuit_sexpr(circuit),
+            SubCircuitGroup::Series(a, b) => format!("(series {} {})", circuit_sexpr(a), circuit_sexpr(b)),
+            SubCircuitGroup::Parallel(a, b) => format!("(parallel {} {})", circuit_sexpr(a), circuit_sexpr(b)),
+        },
+    }
+}
+
+/// Returns the prefix and id of the element, `None` for open circuits
+fn kind_and_id<'a>(element: &Element<'a>) -> Option<(&'static str, &'a str)> {
+    Some((element.prefix()?, element.id()?))
+}
+
+/// Escapes quotes and backslashes, for both JSON and S-expression strings
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_circuit() {
+        let document = circuit::document("((R1)+O||Dz5v1)").unwrap();
+        assert_eq!(to_json(&document), concat!(
+            r#"{"type":"circuit","circuit":{"type":"series","left":{"type":"element","kind":"R","id":"1"},"#,
+            r#""right":{"type":"parallel","top":{"type":"element","kind":"O"},"bottom":{"type":"element","kind":"Dz","id":"5v1"}}}}"#,
+        ));
+        assert_eq!(to_sexpr(&document), r#"(circuit (series (R "1") (parallel (O) (Dz "5v1"))))"#);
+    }
+
+    #[test]
+    fn test_transformers() {
+        let document = circuit::document("=T1(n=10)=T2").unwrap();
+        assert_eq!(to_json(&document), r#"{"type":"twoport","links":[{"type":"transformer","id":"1","ratio":10},{"type":"transformer","id":"2"}]}"#);
+        assert_eq!(to_sexpr(&document), r#"(twoport (transformer "1" (ratio 10)) (transformer "2"))"#);
+        let document = circuit::document("-R1.vout").unwrap();
+        assert_eq!(to_json(&document), r#"{"type":"twoport","links":[{"type":"series","circuit":{"type":"element","kind":"R","id":"1"}},{"type":"node","name":"vout"}]}"#);
+        assert_eq!(to_sexpr(&document), r#"(twoport (series (R "1")) (node "vout"))"#);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let document = circuit::document("{i:i1}|R1{u:u1}").unwrap();
+        assert_eq!(to_json(&document), concat!(
+            r#"{"type":"twoport","links":[{"type":"annotation","quantity":"i","name":"i1"},"#,
+            r#"{"type":"shunt","circuit":{"type":"element","kind":"R","id":"1"}},{"type":"annotation","quantity":"u","name":"u1"}]}"#,
+        ));
+        assert_eq!(to_sexpr(&document), r#"(twoport (annotation i "i1") (shunt (R "1")) (annotation u "u1"))"#);
+    }
+
+    #[test]
+    fn test_switches() {
+        let document = circuit::document("-S1|S2*").unwrap();
+        assert_eq!(to_json(&document), concat!(
+            r#"{"type":"twoport","links":[{"type":"series","circuit":{"type":"element","kind":"S","id":"1","closed":false}},"#,
+            r#"{"type":"shunt","circuit":{"type":"element","kind":"S","id":"2","closed":true}}]}"#,
+        ));
+        assert_eq!(to_sexpr(&document), r#"(twoport (series (S "1" open)) (shunt (S "2" closed)))"#);
+    }
+
+    #[test]
+    fn test_controlled_sources() {
+        let document = circuit::document("Egain(V1)=10").unwrap();
+        assert_eq!(
+            to_json(&document),
+            r#"{"type":"circuit","circuit":{"type":"element","kind":"E","id":"gain","control":"V1","value":{"number":10,"unit":""}}}"#,
+        );
+        assert_eq!(to_sexpr(&document), r#"(circuit (E "gain" (control "V1") (value 10 "")))"#);
+        let document = circuit::document("(P1>out+P2)").unwrap();
+        assert_eq!(to_sexpr(&document), r#"(circuit (series (P "1" (wiper "out")) (P "2")))"#);
+    }
+
+    #[test]
+    fn test_spans() {
+        let json = to_json_with_spans("(R1 + O||(Dz5v1))", 10).unwrap();
+        assert_eq!(json, concat!(
+            r#"{"type":"circuit","circuit":{"type":"series","left":{"type":"element","kind":"R","id":"1","span":[11,13]},"#,
+            r#""right":{"type":"parallel","top":{"type":"element","kind":"O","span":[16,17]},"bottom":{"type":"element","kind":"Dz","id":"5v1","span":[20,25]}}},"span":[10,27]}"#,
+        ));
+        let json = to_json_with_spans("|V1 =T1 .out", 0).unwrap();
+        assert!(json.contains(r#"{"type":"transformer","id":"1","span":[4,7]},{"type":"node","name":"out","span":[8,12]}"#));
+        assert!(to_json_with_spans("|V1-(R1", 0).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Parse errors pointing at the offending character, and errors naming unknown options.
+
+use crate::circuit::Diagnostics;
+
+/// Error returned by [`crate::circuit::document`].
+///
+/// Its [`Display`](std::fmt::Display) implementation shows the offending line with a caret below the offending character:
+///
+/// ```
+/// use circmark_parse::circuit;
+/// let error = circuit::document("|V1-(R1|C1)").unwrap_err();
+/// assert_eq!((error.line, error.column), (1, 8));
+/// assert_eq!(error.to_string(), "\
+/// expected `+`, `||` or `)` at line 1, column 8, found `|`
+/// |V1-(R1|C1)
+///        ^");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the offending character
+    pub offset: usize,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column in characters, starting at 1
+    pub column: usize,
+    /// What would have been accepted at the offset, e.g. `` `)` ``
+    pub expected: &'static [&'static str],
+    /// The offending character, `None` at the end of the input
+    pub found: Option<char>,
+    /// The line containing the offending character
+    source_line: String,
+}
+
+impl ParseError {
+    /// Locates the diagnostics of [`crate::circuit::validate_only`] in the input
+    pub fn new(input: &str, diagnostics: Diagnostics) -> Self {
+        let Diagnostics { offset, expected } = diagnostics;
+        let line_start = input[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        Self {
+            offset,
+            line: input[..offset].matches('\n').count() + 1,
+            column: input[line_start..offset].chars().count() + 1,
+            expected,
+            found: input[offset..].chars().next(),
+            source_line: input[line_start..].lines().next().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {} at line {}, column {}", one_of(self.expected), self.line, self.column)?;
+        match self.found {
+            Some(c) => write!(f, ", found `{c}`")?,
+            None => write!(f, ", found end of input")?,
+        }
+        write!(f, "\n{}\n{}^", self.source_line, " ".repeat(self.column - 1))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Error of parsing the name of an option, e.g. of [`crate::format::Locale`] from `en`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownName {
+    /// What was named, e.g. `locale`
+    pub option: &'static str,
+    pub name: String,
+    /// The names that are known
+    pub expected: &'static [&'static str],
+}
+
+impl std::fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown {} {:?}, expected {}", self.option, self.name, one_of(self.expected))
+    }
+}
+
+impl std::error::Error for UnknownName {}
+
+/// Lists alternatives, e.g. `a, b or c`
+pub(crate) fn one_of(alternatives: &[&str]) -> String {
+    match alternatives {
+        [] => String::new(),
+        [alternative] => alternative.to_string(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{circuit, draw::{svg::Theme, symbols::SymbolStandard}, format::Locale, legend};
+
+    #[test]
+    fn test_parse_error() {
+        let error = circuit::document("|V1-R1=").unwrap_err();
+        assert_eq!((error.offset, error.expected, error.found), (7, &["number"][..], None));
+        let error = circuit::document("|V1-(R1").unwrap_err();
+        assert_eq!(error.to_string(), "expected `+`, `||` or `)` at line 1, column 8, found end of input\n|V1-(R1\n       ^");
+        let error = circuit::document("|V1-µ").unwrap_err();
+        assert_eq!((error.offset, error.column), (4, 5));
+    }
+
+    #[test]
+    fn test_unknown_name() {
+        assert_eq!("dark".parse::<Theme>().unwrap(), Theme::DARK);
+        assert_eq!("de".parse::<Locale>().unwrap(), Locale::DE);
+        assert_eq!("ansi".parse::<SymbolStandard>().unwrap(), SymbolStandard::Ansi);
+        assert_eq!(legend::names("de").unwrap(), legend::GERMAN);
+        assert_eq!("fr".parse::<Locale>().unwrap_err().to_string(), "unknown locale \"fr\", expected en, de, si or ascii");
+    }
+}
@@ -0,0 +1,123 @@
+//! Standard values of components (the E series of IEC 60063), for snapping computed values to ones that can
+//! be bought, e.g. the results of [`crate::fit`].
+//!
+//! ```
+//! use circmark_parse::eseries::Series;
+//! assert_eq!(Series::E12.nearest(5100.0), 4700.0);
+//! assert_eq!(Series::E24.nearest(5100.0), 5100.0);
+//! assert_eq!(Series::E96.nearest(0.95e-9), 0.953e-9);
+//! ```
+
+use crate::{analysis::Values, format};
+
+/// A series of standard values, named after the number of values per decade
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Series {
+    E6,
+    E12,
+    E24,
+    E48,
+    E96,
+}
+
+const E6: &[f64] = &[1.0, 1.5, 2.2, 3.3, 4.7, 6.8];
+
+const E12: &[f64] = &[1.0, 1.2, 1.5, 1.8, 2.2, 2.7, 3.3, 3.9, 4.7, 5.6, 6.8, 8.2];
+
+const E24: &[f64] = &[
+    1.0, 1.1, 1.2, 1.3, 1.5, 1.6, 1.8, 2.0, 2.2, 2.4, 2.7, 3.0,
+    3.3, 3.6, 3.9, 4.3, 4.7, 5.1, 5.6, 6.2, 6.8, 7.5, 8.2, 9.1,
+];
+
+const E48: &[f64] = &[
+    1.00, 1.05, 1.10, 1.15, 1.21, 1.27, 1.33, 1.40, 1.47, 1.54, 1.62, 1.69,
+    1.78, 1.87, 1.96, 2.05, 2.15, 2.26, 2.37, 2.49, 2.61, 2.74, 2.87, 3.01,
+    3.16, 3.32, 3.48, 3.65, 3.83, 4.02, 4.22, 4.42, 4.64, 4.87, 5.11, 5.36,
+    5.62, 5.90, 6.19, 6.49, 6.81, 7.15, 7.50, 7.87, 8.25, 8.66, 9.09, 9.53,
+];
+
+const E96: &[f64] = &[
+    1.00, 1.02, 1.05, 1.07, 1.10, 1.13, 1.15, 1.18, 1.21, 1.24, 1.27, 1.30,
+    1.33, 1.37, 1.40, 1.43, 1.47, 1.50, 1.54, 1.58, 1.62, 1.65, 1.69, 1.74,
+    1.78, 1.82, 1.87, 1.91, 1.96, 2.00, 2.05, 2.10, 2.15, 2.21, 2.26, 2.32,
+    2.37, 2.43, 2.49, 2.55, 2.61, 2.67, 2.74, 2.80, 2.87, 2.94, 3.01, 3.09,
+    3.16, 3.24, 3.32, 3.40, 3.48, 3.57, 3.65, 3.74, 3.83, 3.92, 4.02, 4.12,
+    4.22, 4.32, 4.42, 4.53, 4.64, 4.75, 4.87, 4.99, 5.11, 5.23, 5.36, 5.49,
+    5.62, 5.76, 5.90, 6.04, 6.19, 6.34, 6.49, 6.65, 6.81, 6.98, 7.15, 7.32,
+    7.50, 7.68, 7.87, 8.06, 8.25, 8.45, 8.66, 8.87, 9.09, 9.31, 9.53, 9.76,
+];
+
+impl Series {
+    /// The values of the series within the decade from 1 (included) to 10
+    pub fn values(self) -> &'static [f64] {
+        match self {
+            Series::E6 => E6,
+            Series::E12 => E12,
+            Series::E24 => E24,
+            Series::E48 => E48,
+            Series::E96 => E96,
+        }
+    }
+
+    /// The standard value closest to `value` on a logarithmic scale, i.e. by ratio rather than by difference.
+    ///
+    /// Values that aren't positive are returned as they are.
+    pub fn nearest(self, value: f64) -> f64 {
+        if !(value > 0.0 && value.is_finite()) {
+            return value;
+        }
+        let exponent = value.log10().floor() as i32;
+        // scaling by an exact power of ten keeps e.g. 4.7e-9 exact
+        let decade = |mantissa: f64| if exponent >= 0 { mantissa * 10f64.powi(exponent) } else { mantissa / 10f64.powi(-exponent) };
+        let mantissa = value / decade(1.0);
+        // the first value of the next decade may be closer than the last of this one
+        let nearest = self.values().iter().copied().chain([10.0])
+            .min_by(|a, b| (a.ln() - mantissa.ln()).abs().total_cmp(&(b.ln() - mantissa.ln()).abs()))
+            .unwrap();
+        format::significant(decade(nearest), 3)
+    }
+}
+
+/// Snaps the values of the elements with the given labels to the series, keeping all other values
+pub fn snap(values: &Values, labels: &[&str], series: Series) -> Values {
+    let mut values = values.clone();
+    for label in labels {
+        if let Some(value) = values.get_mut(*label) {
+            *value = series.nearest(*value);
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series() {
+        for (series, count) in [(Series::E6, 6), (Series::E12, 12), (Series::E24, 24), (Series::E48, 48), (Series::E96, 96)] {
+            assert_eq!(series.values().len(), count);
+            assert!(series.values().windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        assert_eq!(Series::E12.nearest(1e3), 1e3);
+        assert_eq!(Series::E12.nearest(4.3e-9), 4.7e-9);
+        assert_eq!(Series::E12.nearest(9.4), 10.0);
+        assert_eq!(Series::E6.nearest(0.12), 0.1);
+        assert_eq!(Series::E96.nearest(1234.0), 1240.0);
+        assert_eq!(Series::E24.nearest(0.0), 0.0);
+        assert_eq!(Series::E24.nearest(-5.0), -5.0);
+    }
+
+    #[test]
+    fn test_snap() {
+        let values = Values::from([("R1".to_string(), 2150.0), ("C1".to_string(), 9.7e-8), ("V1".to_string(), 5.3)]);
+        let snapped = snap(&values, &["R1", "C1"], Series::E12);
+        assert_eq!(snapped["R1"], 2200.0);
+        assert_eq!(snapped["C1"], 1e-7);
+        assert_eq!(snapped["V1"], 5.3);
+    }
+}
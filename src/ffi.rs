@@ -0,0 +1,97 @@
+//! C interface, for calling the renderer from other languages (built as a dynamic library with `cargo rustc --lib
+//! --release --features ffi --crate-type cdylib`, see `include/circmark.h`).
+//!
+//! Strings returned by circmark are owned by the caller, who frees them with [`circmark_free_string`]. Errors are
+//! kept per thread until the next call, and read with [`circmark_last_error`]. Panics don't cross into the caller
+//! (unless built with `panic = "abort"`), they fail the call instead.
+
+use std::{cell::RefCell, ffi::{c_char, CStr, CString}, panic, ptr};
+use crate::render::{render_svg, RenderOptions};
+
+thread_local! {
+    /// The error of the last call on this thread that failed
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(error: Option<String>) {
+    // messages can't contain NUL bytes, but sources can and are quoted in them
+    let error = error.map(|error| CString::new(error.replace('\0', "\\0")).expect("NUL bytes are escaped"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+}
+
+/// Renders circmark source to an SVG document, like `circmark` without options.
+///
+/// Returns a NUL-terminated string to be freed with [`circmark_free_string`], or NULL if the source is NULL, not
+/// UTF-8 or fails to render, [`circmark_last_error`] then telling why.
+///
+/// # Safety
+///
+/// `source` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn circmark_render_svg(source: *const c_char) -> *mut c_char {
+    if source.is_null() {
+        set_error(Some("source is NULL".to_string()));
+        return ptr::null_mut();
+    }
+    let source = CStr::from_ptr(source);
+    let result = panic::catch_unwind(|| {
+        let source = source.to_str().map_err(|error| format!("source is not UTF-8: {error}"))?;
+        let svg = render_svg(source, &RenderOptions::default()).map_err(|error| error.to_string())?;
+        // C strings have no NUL bytes to pass on, but the drawing isn't assumed to have none either
+        CString::new(svg).map_err(|_| "the SVG document contains a NUL byte".to_string())
+    }).unwrap_or_else(|panic| {
+        let message = panic.downcast_ref::<&str>().copied().or(panic.downcast_ref::<String>().map(String::as_str));
+        Err(format!("rendering panicked: {}", message.unwrap_or("unknown cause")))
+    });
+    match result {
+        Ok(svg) => {
+            set_error(None);
+            svg.into_raw()
+        }
+        Err(error) => {
+            set_error(Some(error));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The error of the last call on this thread, or NULL if it succeeded.
+///
+/// The string is owned by circmark and valid until the next call on this thread, so it must not be freed.
+#[no_mangle]
+pub extern "C" fn circmark_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// Frees a string returned by circmark. Does nothing if `string` is NULL.
+///
+/// # Safety
+///
+/// `string` must be NULL or have been returned by circmark, and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn circmark_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        unsafe {
+            let svg = circmark_render_svg(c"|V1-R1|C1".as_ptr());
+            assert!(CStr::from_ptr(svg).to_str().unwrap().starts_with("<svg"));
+            assert!(circmark_last_error().is_null());
+            circmark_free_string(svg);
+
+            assert!(circmark_render_svg(c"|V1-(R1".as_ptr()).is_null());
+            assert!(CStr::from_ptr(circmark_last_error()).to_str().unwrap().contains("expected"));
+            assert!(circmark_render_svg(ptr::null()).is_null());
+            assert_eq!(CStr::from_ptr(circmark_last_error()), c"source is NULL");
+            circmark_free_string(ptr::null_mut());
+        }
+    }
+}
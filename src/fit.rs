@@ -0,0 +1,271 @@
+//! Fitting the values of elements of a twoport to a target response, e.g. to match a measured one.
+//!
+//! The selected values are adjusted with the Nelder-Mead method, on a logarithmic scale since values span
+//! decades, until the relative error against the target settles. The other values are kept as they are.
+//! [`Fit::snap`] then moves the fitted values to standard ones.
+//!
+//! ```
+//! use circmark_parse::{analysis::{self, ac_sweep}, circuit, fit::{fit, Target}};
+//! // the response of a low pass with R1=2.2k
+//! let reference = circuit::document("|V1-R1=2.2k|C1=100n").unwrap();
+//! let circuit::Document::Twoport(reference) = &reference else { unreachable!() };
+//! let freqs = [100.0, 1e3, 10e3];
+//! let gains = ac_sweep(reference, &analysis::values(&circuit::Document::Twoport(reference.clone())), &freqs).unwrap();
+//! let target = Target::Gain(freqs.into_iter().zip(gains).collect());
+//!
+//! let document = circuit::document("|V1-R1=1k|C1=100n").unwrap();
+//! let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+//! let fitted = fit(twoport, &analysis::values(&document), &["R1"], &target).unwrap();
+//! assert_eq!(fitted.values["R1"], 2.2e3);
+//! assert_eq!(fitted.twoport.to_string(), "|V1-R1=2.2k|C1=100n");
+//! ```
+
+use crate::{
+    analysis::{ac_sweep, Complex64, Values},
+    circuit::{SubCircuit, SubCircuitGroup, Twoport, Value},
+    eseries::{self, Series},
+    format,
+    smith,
+};
+
+/// Evaluations after which the search gives up, keeping the best values found so far
+const MAX_ITERATIONS: usize = 2000;
+
+/// Relative spread of the errors at the corners of the simplex below which the search has settled
+const TOLERANCE: f64 = 1e-12;
+
+/// Significant digits the fitted values are rounded to
+const SIGNIFICANT_DIGITS: i32 = 6;
+
+/// Size of the initial simplex, as a factor by which each value is changed
+const INITIAL_STEP: f64 = 2.0;
+
+/// The response the twoport should have, as complex values at frequencies in Hz
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// Gain `Vout/Vin` of the unloaded twoport, see [`ac_sweep`]
+    Gain(Vec<(f64, Complex64)>),
+    /// Input impedance in Ω, see [`smith::input_impedance`]
+    InputImpedance(Vec<(f64, Complex64)>),
+}
+
+impl Target {
+    /// Root mean square of the relative deviations from the target, `None` if the response can't be evaluated
+    pub fn error(&self, twoport: &Twoport, values: &Values) -> Option<f64> {
+        let (points, actual) = match self {
+            Target::Gain(points) => {
+                let freqs: Vec<f64> = points.iter().map(|(frequency, _)| *frequency).collect();
+                (points, ac_sweep(twoport, values, &freqs)?)
+            }
+            Target::InputImpedance(points) => {
+                let actual = points.iter().map(|(frequency, _)| smith::input_impedance(twoport, values, *frequency)).collect::<Option<_>>()?;
+                (points, actual)
+            }
+        };
+        if points.is_empty() {
+            return None;
+        }
+        // deviations from a target of zero can only be absolute
+        let sum: f64 = points.iter().zip(&actual)
+            .map(|((_, target), actual)| ((actual - target) / target.norm().max(f64::MIN_POSITIVE)).norm_sqr())
+            .sum();
+        Some((sum / points.len() as f64).sqrt()).filter(|error| error.is_finite())
+    }
+}
+
+/// Result of [`fit`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit<'a> {
+    /// The twoport with the fitted values
+    pub twoport: Twoport<'a>,
+    /// The fitted values along with the unchanged ones
+    pub values: Values,
+    /// Remaining error, see [`Target::error`]
+    pub error: f64,
+    /// Number of evaluations of the error
+    pub iterations: usize,
+    /// Labels of the fitted elements
+    pub labels: Vec<String>,
+}
+
+impl<'a> Fit<'a> {
+    /// Snaps the fitted values to the standard values of the series, so that the circuit can be built,
+    /// with the error against the target (which grows by snapping) evaluated anew.
+    ///
+    /// ```
+    /// use circmark_parse::{analysis, circuit, eseries::Series, fit::{fit, Target}};
+    /// let target = Target::InputImpedance(vec![(1e3, analysis::Complex64::new(150.0, 0.0))]);
+    /// let document = circuit::document("|V1-R1=100|R2=10").unwrap();
+    /// let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+    /// let fitted = fit(twoport, &analysis::values(&document), &["R1"], &target).unwrap();
+    /// assert_eq!(fitted.values["R1"], 140.0);
+    /// let snapped = fitted.snap(Series::E12, &target).unwrap();
+    /// assert_eq!(snapped.twoport.to_string(), "|V1-R1=150|R2=10");
+    /// assert!((snapped.error - 10.0 / 150.0).abs() < 1e-9);
+    /// ```
+    pub fn snap(&self, series: Series, target: &Target) -> Option<Fit<'a>> {
+        let labels: Vec<&str> = self.labels.iter().map(String::as_str).collect();
+        let values = eseries::snap(&self.values, &labels, series);
+        let error = target.error(&self.twoport, &values)?;
+        let mut twoport = self.twoport.clone();
+        for link in &mut twoport.links {
+            if let Some(circuit) = link.circuit_mut() {
+                set_values(circuit, &labels, &values);
+            }
+        }
+        Some(Fit { twoport, values, error, iterations: self.iterations, labels: self.labels.clone() })
+    }
+}
+
+/// Adjusts the values of the elements with the given labels to match the target.
+///
+/// The values in `values` are the starting point, so each selected element needs a positive one. Returns `None`
+/// if one hasn't, or if the twoport's response can't be evaluated with them.
+pub fn fit<'a>(twoport: &Twoport<'a>, values: &Values, labels: &[&str], target: &Target) -> Option<Fit<'a>> {
+    let start: Vec<f64> = labels.iter().map(|label| values.get(*label).filter(|value| **value > 0.0).map(|value| value.ln())).collect::<Option<_>>()?;
+    let with = |point: &[f64]| {
+        let mut values = values.clone();
+        for (label, x) in labels.iter().zip(point) {
+            values.insert(label.to_string(), x.exp());
+        }
+        values
+    };
+    let mut iterations = 0;
+    let mut error = |point: &[f64]| {
+        iterations += 1;
+        target.error(twoport, &with(point)).unwrap_or(f64::INFINITY)
+    };
+    if !error(&start).is_finite() {
+        return None;
+    }
+    let (best, _) = nelder_mead(start, INITIAL_STEP.ln(), &mut error);
+    // more digits than any component is accurate to, so the values come out as e.g. 100 rather than 100.00000000000031
+    let mut values = values.clone();
+    for (label, x) in labels.iter().zip(best) {
+        values.insert(label.to_string(), format::significant(x.exp(), SIGNIFICANT_DIGITS));
+    }
+    let error = target.error(twoport, &values)?;
+    let mut twoport = twoport.clone();
+    for link in &mut twoport.links {
+        if let Some(circuit) = link.circuit_mut() {
+            set_values(circuit, labels, &values);
+        }
+    }
+    Some(Fit { twoport, values, error, iterations, labels: labels.iter().map(|label| label.to_string()).collect() })
+}
+
+/// Minimizes the function, starting with a simplex around `start` whose edges are `step` long.
+///
+/// Returns the best point found and the function's value there.
+fn nelder_mead(start: Vec<f64>, step: f64, f: &mut impl FnMut(&[f64]) -> f64) -> (Vec<f64>, f64) {
+    let n = start.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=n).map(|i| {
+        let mut point = start.clone();
+        if i > 0 {
+            point[i - 1] += step;
+        }
+        let value = f(&point);
+        (point, value)
+    }).collect();
+    let mut iterations = n + 1;
+    // the point on the line from `centroid` through `point` at `factor` times their distance, on the other side if negative
+    let towards = |centroid: &[f64], point: &[f64], factor: f64| -> Vec<f64> {
+        centroid.iter().zip(point).map(|(c, p)| c + factor * (p - c)).collect()
+    };
+    while iterations < MAX_ITERATIONS {
+        simplex.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        let (best, worst) = (simplex[0].1, simplex[n].1);
+        if n == 0 || (worst - best).abs() <= TOLERANCE * (best.abs() + TOLERANCE) {
+            break;
+        }
+        let centroid: Vec<f64> = (0..n).map(|i| simplex[..n].iter().map(|(point, _)| point[i]).sum::<f64>() / n as f64).collect();
+        let reflected = towards(&centroid, &simplex[n].0, -1.0);
+        let reflected_value = f(&reflected);
+        iterations += 1;
+        if reflected_value < best {
+            let expanded = towards(&centroid, &simplex[n].0, -2.0);
+            let expanded_value = f(&expanded);
+            iterations += 1;
+            simplex[n] = if expanded_value < reflected_value { (expanded, expanded_value) } else { (reflected, reflected_value) };
+        } else if reflected_value < simplex[n - 1].1 {
+            simplex[n] = (reflected, reflected_value);
+        } else {
+            let contracted = towards(&centroid, &simplex[n].0, 0.5);
+            let contracted_value = f(&contracted);
+            iterations += 1;
+            if contracted_value < worst {
+                simplex[n] = (contracted, contracted_value);
+            } else {
+                // shrink everything towards the best point
+                let best = simplex[0].0.clone();
+                for (point, value) in &mut simplex[1..] {
+                    *point = towards(&best, point, 0.5);
+                    *value = f(point);
+                }
+                iterations += n;
+            }
+        }
+    }
+    simplex.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    simplex.swap_remove(0)
+}
+
+/// Replaces the values of the elements with the given labels, keeping their units
+fn set_values(circuit: &mut SubCircuit, labels: &[&str], values: &Values) {
+    match circuit {
+        SubCircuit::Element(element, value) => {
+            let label = element.label();
+            if let (true, Some(number)) = (labels.contains(&label.as_str()), values.get(&label)) {
+                let unit = value.as_ref().map_or("", |value| value.unit);
+                *value = Some(Value { number: *number, unit });
+            }
+        }
+        SubCircuit::Group(group) => match group.as_mut() {
+            SubCircuitGroup::Single(circuit) => set_values(circuit, labels, values),
+            SubCircuitGroup::Series(a, b) | SubCircuitGroup::Parallel(a, b) => {
+                set_values(a, labels, values);
+                set_values(b, labels, values);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis, circuit};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_fit_gain() {
+        // an RLC low pass, whose resonance and damping both have to be found
+        let reference = circuit::twoport::<E>("|V1-R1=10-L1=1m|C1=1u").unwrap().1;
+        let reference_values = analysis::values(&circuit::Document::Twoport(reference.clone()));
+        let freqs: Vec<f64> = analysis::response::log_frequencies(100.0, 100e3, 10);
+        let gains = ac_sweep(&reference, &reference_values, &freqs).unwrap();
+        let target = Target::Gain(freqs.into_iter().zip(gains).collect());
+
+        let twoport = circuit::twoport::<E>("|V1-R1=50-L1=3m|C1=1u").unwrap().1;
+        let values = analysis::values(&circuit::Document::Twoport(twoport.clone()));
+        let fitted = fit(&twoport, &values, &["R1", "L1"], &target).unwrap();
+        assert_eq!(fitted.values["R1"], 10.0);
+        assert_eq!(fitted.values["L1"], 1e-3);
+        assert_eq!(fitted.values["C1"], 1e-6);
+        assert!(fitted.error < 1e-6);
+        assert!(fitted.iterations <= MAX_ITERATIONS + 2);
+    }
+
+    #[test]
+    fn test_fit_input_impedance() {
+        let target = Target::InputImpedance(vec![(1e3, Complex64::new(1100.0, 0.0)), (1e6, Complex64::new(1100.0, 0.0))]);
+        let twoport = circuit::twoport::<E>("|V1-R1=1kΩ|R2").unwrap().1;
+        let mut values = analysis::values(&circuit::Document::Twoport(twoport.clone()));
+        assert_eq!(fit(&twoport, &values, &["R1"], &target), None);
+        values.insert("R2".to_string(), 1e3);
+        let fitted = fit(&twoport, &values, &["R1"], &target).unwrap();
+        assert_eq!(fitted.values["R1"], 100.0);
+        assert_eq!(fitted.twoport.to_string(), "|V1-R1=100Ω|R2");
+        assert_eq!(fit(&twoport, &values, &["R3"], &target), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Locale-aware formatting of numeric values, as they appear in labels.
+
+/// Describes how numbers are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Locale {
+    /// Character between the integer and fractional part, e.g. `.` or `,`
+    pub decimal_separator: char,
+    /// Character inserted between groups of three digits in the integer part (none if `None`)
+    pub thousands_separator: Option<char>,
+    /// Character used for the SI prefix "micro", either `µ` or the ASCII fallback `u`
+    pub micro: char,
+    /// Character between a number and its prefix or unit, e.g. a narrow no-break space for `4.7 kΩ` (none if `None`)
+    pub unit_space: Option<char>,
+    /// Whether the exponents of values beyond the SI prefixes are written as superscripts (`1×10⁻¹⁸`) rather
+    /// than in E notation (`1e-18`)
+    pub superscripts: bool,
+}
+
+impl Locale {
+    /// English conventions: `1,000.5`, `4.7µF`
+    pub const EN: Locale = Locale { decimal_separator: '.', thousands_separator: Some(','), micro: 'µ', unit_space: None, superscripts: true };
+    /// German conventions: `1.000,5`, `4,7µF`
+    pub const DE: Locale = Locale { decimal_separator: ',', thousands_separator: Some('.'), micro: 'µ', unit_space: None, superscripts: true };
+    /// SI / ISO 80000 conventions, with a thin space between digit groups and a narrow space before units: `1 000,5`, `4,7 µF`
+    pub const SI: Locale = Locale { decimal_separator: ',', thousands_separator: Some('\u{2009}'), micro: 'µ', unit_space: Some('\u{202F}'), superscripts: true };
+    /// Plain ASCII output: `1000.5`, `4.7uF`, `1e-18F`
+    pub const ASCII: Locale = Locale { decimal_separator: '.', thousands_separator: None, micro: 'u', unit_space: None, superscripts: false };
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::EN
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = crate::error::UnknownName;
+
+    /// Parses the name of a locale, `en`, `de`, `si` or `ascii`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "en" => Ok(Locale::EN),
+            "de" => Ok(Locale::DE),
+            "si" => Ok(Locale::SI),
+            "ascii" => Ok(Locale::ASCII),
+            _ => Err(crate::error::UnknownName { option: "locale", name: name.to_string(), expected: &["en", "de", "si", "ascii"] }),
+        }
+    }
+}
+
+/// Maximum number of fractional digits written by [`number`].
+const PRECISION: usize = 3;
+
+/// Formats a plain number, with at most three fractional digits and trailing zeros removed.
+///
+/// ```
+/// use circmark_parse::format::{number, Locale};
+/// assert_eq!(number(12345.5, &Locale::DE), "12.345,5");
+/// ```
+pub fn number(value: f64, locale: &Locale) -> String {
+    let formatted = format!("{:.*}", PRECISION, value.abs());
+    let (int_part, frac_part) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let frac_part = frac_part.trim_end_matches('0');
+
+    let mut result = String::new();
+    if value < 0.0 && formatted.chars().any(|c| c != '0' && c != '.') {
+        result.push('-');
+    }
+    for (i, digit) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            if let Some(separator) = locale.thousands_separator {
+                result.push(separator);
+            }
+        }
+        result.push(digit);
+    }
+    if !frac_part.is_empty() {
+        result.push(locale.decimal_separator);
+        result.push_str(frac_part);
+    }
+    result
+}
+
+/// Formats a value in engineering notation, using an SI prefix followed by the given unit.
+///
+/// Units spelled out in ASCII are written with their symbol, e.g. `ohm` as `Ω`. Values beyond the prefixes
+/// (from femto to tera) are written with their exponent.
+///
+/// ```
+/// use circmark_parse::format::{si, Locale};
+/// assert_eq!(si(4700.0, "Ω", &Locale::EN), "4.7kΩ");
+/// assert_eq!(si(4.7e-6, "F", &Locale::DE), "4,7µF");
+/// assert_eq!(si(4.7e-6, "F", &Locale::ASCII), "4.7uF");
+/// assert_eq!(si(4700.0, "ohm", &Locale::SI), "4,7\u{202F}kΩ");
+/// assert_eq!(si(2e-18, "F", &Locale::EN), "2×10⁻¹⁸F");
+/// ```
+pub fn si(value: f64, unit: &str, locale: &Locale) -> String {
+    const PREFIXES: [(i32, char); 10] = [
+        (12, 'T'), (9, 'G'), (6, 'M'), (3, 'k'), (0, ' '),
+        (-3, 'm'), (-6, 'µ'), (-9, 'n'), (-12, 'p'), (-15, 'f'),
+    ];
+    let unit = unit_symbol(unit);
+    let magnitude = value.abs();
+    if magnitude.is_finite() && magnitude != 0.0 && !(1e-15..1e15).contains(&magnitude) {
+        let mut result = scientific(value, locale);
+        if !unit.is_empty() {
+            result.extend(locale.unit_space);
+            result.push_str(unit);
+        }
+        return result;
+    }
+    let mut index = PREFIXES.iter().position(|(exponent, _)| magnitude >= 10f64.powi(*exponent)).unwrap_or(4);
+    // values rounding up to a thousand take the next prefix, e.g. 999.9995 is 1k rather than 1,000
+    let rounded = format!("{:.*}", PRECISION, magnitude / 10f64.powi(PREFIXES[index].0));
+    if index > 0 && rounded.split('.').next().unwrap().len() > 3 {
+        index -= 1;
+    }
+    let (exponent, prefix) = PREFIXES[index];
+    let mut result = number(value / 10f64.powi(exponent), locale);
+    if prefix != ' ' || !unit.is_empty() {
+        result.extend(locale.unit_space);
+    }
+    match prefix {
+        ' ' => {}
+        'µ' => result.push(locale.micro),
+        _ => result.push(prefix),
+    }
+    result.push_str(unit);
+    result
+}
+
+/// The symbol of a unit that may be spelled out in ASCII, as values in circmark sources can only have letters as units
+fn unit_symbol(unit: &str) -> &str {
+    match unit {
+        "ohm" | "ohms" | "Ohm" | "Ohms" => "Ω",
+        unit => unit,
+    }
+}
+
+/// Formats a number with its power of ten, e.g. `2×10⁻¹⁸` (or `2e-18` without superscripts)
+fn scientific(value: f64, locale: &Locale) -> String {
+    let mut exponent = value.abs().log10().floor() as i32;
+    // scaling by an exact power of ten keeps the mantissa exact
+    let scale = |exponent: i32| if exponent >= 0 { value / 10f64.powi(exponent) } else { value * 10f64.powi(-exponent) };
+    let mut mantissa = significant(scale(exponent), 4);
+    if mantissa.abs() >= 10.0 {
+        exponent += 1;
+        mantissa = significant(scale(exponent), 4);
+    }
+    let mantissa = number(mantissa, locale);
+    if !locale.superscripts {
+        return format!("{mantissa}e{exponent}");
+    }
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    let power: String = exponent.to_string().chars()
+        .map(|c| c.to_digit(10).map_or('⁻', |digit| DIGITS[digit as usize]))
+        .collect();
+    format!("{mantissa}×10{power}")
+}
+
+/// Rounds a value to a number of significant digits, for values that needn't be written exactly.
+///
+/// ```
+/// use circmark_parse::format::significant;
+/// assert_eq!(significant(159154.9, 3), 159000.0);
+/// ```
+pub fn significant(value: f64, digits: i32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    // scaling by an exact power of ten keeps e.g. 1e-8 exact
+    let exponent = digits - 1 - value.abs().log10().floor() as i32;
+    if exponent >= 0 {
+        (value * 10f64.powi(exponent)).round() / 10f64.powi(exponent)
+    } else {
+        (value / 10f64.powi(-exponent)).round() * 10f64.powi(-exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number(0.0, &Locale::EN), "0");
+        assert_eq!(number(1.5, &Locale::EN), "1.5");
+        assert_eq!(number(1.5, &Locale::DE), "1,5");
+        assert_eq!(number(1234567.0, &Locale::EN), "1,234,567");
+        assert_eq!(number(1234567.0, &Locale::SI), "1\u{2009}234\u{2009}567");
+        assert_eq!(number(1234567.0, &Locale::ASCII), "1234567");
+        assert_eq!(number(-999.25, &Locale::DE), "-999,25");
+        assert_eq!(number(0.12345, &Locale::EN), "0.123");
+        assert_eq!(number(-0.0001, &Locale::EN), "0");
+    }
+
+    #[test]
+    fn test_si() {
+        assert_eq!(si(10_000.0, "Ω", &Locale::EN), "10kΩ");
+        assert_eq!(si(100e-9, "F", &Locale::EN), "100nF");
+        assert_eq!(si(2.2e-3, "H", &Locale::SI), "2,2\u{202F}mH");
+        assert_eq!(si(1.0e-6, "F", &Locale::EN), "1µF");
+        assert_eq!(si(1.0e-6, "F", &Locale::ASCII), "1uF");
+        assert_eq!(si(12.0, "V", &Locale::EN), "12V");
+        assert_eq!(si(0.0, "V", &Locale::EN), "0V");
+        assert_eq!(si(-3.3e6, "", &Locale::EN), "-3.3M");
+        assert_eq!(si(999.9995, "Ω", &Locale::EN), "1kΩ");
+        assert_eq!(si(-0.9999999, "V", &Locale::EN), "-1V");
+        assert_eq!(si(999.9, "Ω", &Locale::EN), "999.9Ω");
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(si(100e-9, "F", &Locale::SI), "100\u{202F}nF");
+        assert_eq!(si(12.0, "V", &Locale::SI), "12\u{202F}V");
+        assert_eq!(si(1500.0, "", &Locale::SI), "1,5\u{202F}k");
+        assert_eq!(si(12.0, "", &Locale::SI), "12");
+        assert_eq!(si(10.0, "Ohms", &Locale::EN), "10Ω");
+        assert_eq!(si(-4.7e16, "Hz", &Locale::SI), "-4,7×10¹⁶\u{202F}Hz");
+        assert_eq!(si(9.99999e-19, "F", &Locale::EN), "1×10⁻¹⁸F");
+        assert_eq!(si(1e-16, "", &Locale::ASCII), "1e-16");
+    }
+
+    #[test]
+    fn test_significant() {
+        assert_eq!(significant(-0.0012345, 2), -0.0012);
+        assert_eq!(significant(9.999999999999999e-9, 12), 1e-8);
+        assert_eq!(significant(0.0, 3), 0.0);
+    }
+}
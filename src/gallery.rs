@@ -0,0 +1,168 @@
+//! An HTML index of a library of circuits, with a thumbnail, caption and tags for each and links to the full
+//! drawings, as `circmark gallery` writes for a directory of circmark files.
+//!
+//! Files describe themselves in the comments they start with, one `# key: value` line per field. The `title` is
+//! the caption (the title of the first titled section, or the file's name if there is neither), `tags` a list
+//! separated by commas and `description` a line below the caption. Other comments are left alone:
+//! ```text
+//! # title: RC low pass
+//! # tags: filter, passive
+//! |V1-R1|C1
+//! ```
+
+/// The fields in the comments a source starts with, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+}
+
+impl Metadata {
+    /// Reads the fields of the comments before the first line of content, taking the title of the first titled
+    /// section (e.g. `@twoport "RC low pass"`) without a `title` field
+    ///
+    /// ```
+    /// use circmark_parse::gallery::Metadata;
+    /// let metadata = Metadata::parse("# title: RC low pass\n# tags: filter, passive\n|V1-R1|C1\n# tags: ignored");
+    /// assert_eq!(metadata.title.as_deref(), Some("RC low pass"));
+    /// assert_eq!(metadata.tags, ["filter", "passive"]);
+    /// ```
+    pub fn parse(source: &str) -> Metadata {
+        let mut metadata = Metadata::default();
+        let comments = source.lines().map(str::trim).take_while(|line| line.is_empty() || line.starts_with('#'));
+        for comment in comments.filter_map(|line| line.strip_prefix('#')) {
+            let Some((key, value)) = comment.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().to_string();
+            match key.trim() {
+                "title" => metadata.title = Some(value),
+                "tags" => metadata.tags.extend(value.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string)),
+                "description" => metadata.description = Some(value),
+                _ => {}
+            }
+        }
+        if metadata.title.is_none() {
+            metadata.title = crate::document::parse(source).ok().and_then(|sections| sections.into_iter().find_map(|section| section.title));
+        }
+        metadata
+    }
+}
+
+/// A circuit of the gallery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The name of the circuit, e.g. of the file without its extension, captioning it if it has no title
+    pub name: String,
+    pub metadata: Metadata,
+    /// The path of the thumbnail, relative to the index (which percent-encodes it)
+    pub thumbnail: String,
+    /// The path of the full drawing, relative to the index (which percent-encodes it)
+    pub link: String,
+}
+
+impl Entry {
+    /// The title of the circuit, or its name without one
+    pub fn caption(&self) -> &str {
+        self.metadata.title.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Style of the index, laying the circuits out in a grid of cards
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+.tags { list-style: none; padding: 0; }
+.tags li { display: inline-block; margin: 0 0.3em 0.3em 0; padding: 0 0.5em; border-radius: 0.8em; background: #e8e8e8; }
+.gallery { display: grid; grid-template-columns: repeat(auto-fill, minmax(12em, 1fr)); gap: 1.5em; }
+figure { margin: 0; }
+figure img { width: 100%; height: auto; border: 1px solid #ccc; }";
+
+/// Writes the HTML index of the entries, in the order given, with the tags of all entries and how many have them
+/// above the circuits
+///
+/// ```
+/// use circmark_parse::gallery::{index, Entry, Metadata};
+/// let entry = Entry { name: "lowpass".to_string(), metadata: Metadata::default(), thumbnail: "lowpass.thumb.svg".to_string(), link: "lowpass.svg".to_string() };
+/// assert!(index("Filters", &[entry]).contains("<a href=\"lowpass.svg\"><img src=\"lowpass.thumb.svg\" alt=\"lowpass\"></a>"));
+/// ```
+pub fn index(title: &str, entries: &[Entry]) -> String {
+    let mut tags: Vec<(&str, usize)> = vec![];
+    for tag in entries.iter().flat_map(|entry| &entry.metadata.tags) {
+        match tags.iter_mut().find(|(name, _)| name == tag) {
+            Some((_, count)) => *count += 1,
+            None => tags.push((tag, 1)),
+        }
+    }
+    tags.sort();
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n", escape(title), escape(title));
+    if !tags.is_empty() {
+        html.push_str("<ul class=\"tags\">\n");
+        for (tag, count) in tags {
+            html.push_str(&format!("<li>{} ({count})</li>\n", escape(tag)));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("<div class=\"gallery\">\n");
+    for entry in entries {
+        let caption = escape(entry.caption());
+        html.push_str(&format!("<figure data-tags=\"{}\">\n", escape(&entry.metadata.tags.join(" "))));
+        let (link, thumbnail) = (url(&entry.link), url(&entry.thumbnail));
+        html.push_str(&format!("<a href=\"{link}\"><img src=\"{thumbnail}\" alt=\"{caption}\"></a>\n"));
+        html.push_str(&format!("<figcaption>\n<a href=\"{link}\">{caption}</a>\n"));
+        if let Some(description) = &entry.metadata.description {
+            html.push_str(&format!("<p>{}</p>\n", escape(description)));
+        }
+        if !entry.metadata.tags.is_empty() {
+            let tags: Vec<_> = entry.metadata.tags.iter().map(|tag| format!("<li>{}</li>", escape(tag))).collect();
+            html.push_str(&format!("<ul class=\"tags\">{}</ul>\n", tags.concat()));
+        }
+        html.push_str("</figcaption>\n</figure>\n");
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// The relative URL of a path, percent-encoding its segments so that e.g. `#` and `?` in file names don't end them
+fn url(path: &str) -> String {
+    let segments = path.split('/').map(|segment| segment.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => char::from(byte).to_string(),
+        _ => format!("%{byte:02X}"),
+    }).collect::<String>());
+    segments.collect::<Vec<_>>().join("/")
+}
+
+/// Escapes text for HTML, in content and attribute values
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Metadata::parse("\n# A collection of filters\n#description: First order\n  # tags: filter,, rc\n@twoport\n|V1-R1|C1");
+        assert_eq!(metadata, Metadata { title: None, tags: vec!["filter".to_string(), "rc".to_string()], description: Some("First order".to_string()) });
+        assert_eq!(Metadata::parse("|V1-R1 # title: not metadata"), Metadata::default());
+        assert_eq!(Metadata::parse("@circuit\nR1\n@twoport \"RC low pass\"\n|V1-R1|C1").title.as_deref(), Some("RC low pass"));
+    }
+
+    #[test]
+    fn test_index() {
+        let entry = |name: &str, title: Option<&str>, tags: &[&str]| Entry {
+            name: name.to_string(),
+            metadata: Metadata { title: title.map(str::to_string), tags: tags.iter().map(|tag| tag.to_string()).collect(), description: None },
+            thumbnail: format!("{name}.thumb.svg"),
+            link: format!("{name}.svg"),
+        };
+        let html = index("Filters & dividers", &[entry("lowpass", Some("RC <low> pass"), &["filter", "rc"]), entry("divider", None, &["rc"])]);
+        assert!(html.contains("<h1>Filters &amp; dividers</h1>"));
+        assert!(html.contains("<li>filter (1)</li>\n<li>rc (2)</li>"));
+        assert!(html.contains("<a href=\"lowpass.svg\">RC &lt;low&gt; pass</a>"));
+        assert!(html.contains("<a href=\"divider.svg\">divider</a>"));
+        assert!(html.contains("<figure data-tags=\"filter rc\">"));
+        let html = index("Filters", &[entry("RC #1 ?100%", None, &[])]);
+        assert!(html.contains("<a href=\"RC%20%231%20%3F100%25.svg\"><img src=\"RC%20%231%20%3F100%25.thumb.svg\" alt=\"RC #1 ?100%\"></a>"));
+    }
+}
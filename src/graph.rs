@@ -0,0 +1,236 @@
+//! The electrical nodes of circuits and the elements between them, for analyses and queries on connectivity.
+//!
+//! The AST describes how elements are grouped; [`CircuitGraph`] describes which ends of them are joined, and is what
+//! [`crate::netlist::to_spice`] and [`crate::netlist::to_kicad`] connect elements by. Ground (the bottom line of
+//! twoports) is node 0, the input node 1 and the others are numbered in the order they appear. Nodes named in the
+//! circuit, with `.vout` or by the wiper of `P1>vout`, are one node wherever the name appears.
+//!
+//! ```
+//! use circmark_parse::{circuit, graph::NodeId};
+//! let document = circuit::document("|V1-R1|C1|L1").unwrap();
+//! let graph = document.graph();
+//! let labels: Vec<_> = graph.edges_at(NodeId(2)).map(|(_, _, element)| element.label()).collect();
+//! assert_eq!(labels, ["R1", "C1", "L1"]);
+//! ```
+
+use crate::circuit::{Document, Element, SubCircuit, SubCircuitGroup, Twoport, TwoportLink, Value};
+
+/// Index of a node in [`CircuitGraph::nodes`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub usize);
+
+impl NodeId {
+    /// The node of ground, which is the bottom line of twoports and the second terminal of circuits
+    pub const GROUND: NodeId = NodeId(0);
+}
+
+impl std::fmt::Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A node of a [`CircuitGraph`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Node {
+    /// The name given in the circuit, e.g. `vout` for `.vout`
+    pub name: Option<String>,
+}
+
+/// What an edge of a [`CircuitGraph`] stands for
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementRef<'a> {
+    /// An element, with its index among the elements of the document (see [`Document::elements`])
+    Element { index: usize, element: Element<'a>, value: Option<Value<'a>> },
+    /// The primary (between the node before it and ground) or secondary winding (between the node after it and
+    /// ground) of a transformer link
+    Winding { label: String, ratio: Option<Value<'a>>, primary: bool },
+}
+
+impl ElementRef<'_> {
+    /// The label of the element or transformer, e.g. `R1`
+    pub fn label(&self) -> String {
+        match self {
+            ElementRef::Element { element, .. } => element.label(),
+            ElementRef::Winding { label, .. } => label.clone(),
+        }
+    }
+}
+
+/// The nodes of a circuit and the elements connecting them, see the [module documentation](self)
+///
+/// Each edge joins two nodes, the first being the left (or top) end of the element. Potentiometers are two edges,
+/// one on either side of the wiper, and transformers one for each winding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitGraph<'a> {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<(NodeId, NodeId, ElementRef<'a>)>,
+    /// The node the circuit is driven at (against ground)
+    pub input: NodeId,
+    /// The output node of twoports (against ground)
+    pub output: Option<NodeId>,
+}
+
+impl<'a> CircuitGraph<'a> {
+    fn new() -> Self {
+        CircuitGraph { nodes: vec![Node::default()], edges: vec![], input: NodeId::GROUND, output: None }
+    }
+
+    /// Adds a node, which is the one of that name if it has one
+    fn node(&mut self, name: Option<&str>) -> NodeId {
+        if let Some(id) = name.and_then(|name| self.find(name)) {
+            return id;
+        }
+        self.nodes.push(Node { name: name.map(str::to_string) });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// The node of the name, e.g. `vout`
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name.as_deref() == Some(name)).map(NodeId)
+    }
+
+    /// The edges that have an end at the node, in the order they appear
+    pub fn edges_at(&self, node: NodeId) -> impl Iterator<Item = &(NodeId, NodeId, ElementRef<'a>)> {
+        self.edges.iter().filter(move |(a, b, _)| *a == node || *b == node)
+    }
+
+    /// The nodes joined to the node by an element, in the order they appear
+    pub fn neighbors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut neighbors = vec![];
+        for (a, b, _) in self.edges_at(node) {
+            let other = if *a == node { *b } else { *a };
+            if other != node && !neighbors.contains(&other) {
+                neighbors.push(other);
+            }
+        }
+        neighbors
+    }
+
+    /// The nodes of each end of the element (both ends of potentiometers, rather than the wiper), `None` if there is
+    /// no element of that label
+    pub fn terminals(&self, label: &str) -> Option<(NodeId, NodeId)> {
+        let mut ends = self.edges.iter().filter(|(_, _, element)| matches!(element, ElementRef::Element { .. }) && element.label() == label);
+        let (a, b, _) = ends.next()?;
+        Some((*a, ends.next_back().map_or(*b, |(_, b, _)| *b)))
+    }
+
+    /// Adds the edges of a sub-circuit between nodes `a` and `b`
+    fn sub_circuit(&mut self, circuit: &SubCircuit<'a>, a: NodeId, b: NodeId, index: &mut usize) {
+        match circuit {
+            SubCircuit::Element(element, value) => {
+                let edge = ElementRef::Element { index: *index, element: element.clone(), value: *value };
+                *index += 1;
+                if let Element::P { .. } = element {
+                    let wiper = self.node(element.wiper());
+                    self.edges.push((a, wiper, edge.clone()));
+                    self.edges.push((wiper, b, edge));
+                } else {
+                    self.edges.push((a, b, edge));
+                }
+            }
+            SubCircuit::Group(group) => match group.as_ref() {
+                SubCircuitGroup::Single(circuit) => self.sub_circuit(circuit, a, b, index),
+                SubCircuitGroup::Series(left, right) => {
+                    let middle = self.node(None);
+                    self.sub_circuit(left, a, middle, index);
+                    self.sub_circuit(right, middle, b, index);
+                }
+                SubCircuitGroup::Parallel(top, bottom) => {
+                    self.sub_circuit(top, a, b, index);
+                    self.sub_circuit(bottom, a, b, index);
+                }
+            },
+        }
+    }
+}
+
+impl<'a> SubCircuit<'a> {
+    /// The graph of the sub-circuit, connected between the input (node 1) and ground
+    pub fn graph(&self) -> CircuitGraph<'a> {
+        let mut graph = CircuitGraph::new();
+        graph.input = graph.node(None);
+        graph.sub_circuit(self, graph.input, NodeId::GROUND, &mut 0);
+        graph
+    }
+}
+
+impl<'a> Twoport<'a> {
+    /// The graph of the twoport, its top line being split into nodes by series links and transformers
+    pub fn graph(&self) -> CircuitGraph<'a> {
+        // the names of the nodes on the top line, which may be given after the link starting them
+        let mut names = vec![None];
+        for link in &self.links {
+            match link {
+                TwoportLink::Series(_) | TwoportLink::Transformer { .. } => names.push(None),
+                TwoportLink::Node(name) => *names.last_mut().unwrap() = Some(*name),
+                TwoportLink::Shunt(_) | TwoportLink::Annotation { .. } => {}
+            }
+        }
+        let mut names = names.into_iter();
+        let mut graph = CircuitGraph::new();
+        graph.input = graph.node(names.next().flatten());
+        let mut node = graph.input;
+        let mut index = 0;
+        for link in &self.links {
+            match link {
+                TwoportLink::Series(circuit) => {
+                    let next = graph.node(names.next().flatten());
+                    graph.sub_circuit(circuit, node, next, &mut index);
+                    node = next;
+                }
+                TwoportLink::Shunt(circuit) => graph.sub_circuit(circuit, node, NodeId::GROUND, &mut index),
+                TwoportLink::Transformer { ratio, .. } => {
+                    let next = graph.node(names.next().flatten());
+                    let label = link.label().unwrap();
+                    graph.edges.push((node, NodeId::GROUND, ElementRef::Winding { label: label.clone(), ratio: *ratio, primary: true }));
+                    graph.edges.push((next, NodeId::GROUND, ElementRef::Winding { label, ratio: *ratio, primary: false }));
+                    node = next;
+                }
+                TwoportLink::Node(_) | TwoportLink::Annotation { .. } => {}
+            }
+        }
+        graph.output = Some(node);
+        graph
+    }
+}
+
+impl<'a> Document<'a> {
+    /// The graph of the circuit or twoport, see [`SubCircuit::graph`] and [`Twoport::graph`]
+    pub fn graph(&self) -> CircuitGraph<'a> {
+        match self {
+            Document::Circuit(circuit) => circuit.graph(),
+            Document::Twoport(twoport) => twoport.graph(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    /// The edges of a graph like the lines of a netlist, e.g. `R1 1 2`
+    fn edges(graph: &CircuitGraph) -> Vec<String> {
+        graph.edges.iter().map(|(a, b, element)| format!("{} {a} {b}", element.label())).collect()
+    }
+
+    #[test]
+    fn test_circuit() {
+        let graph = circuit::document("(R1+(C1||L1)+D1)").unwrap().graph();
+        assert_eq!(edges(&graph), ["R1 1 2", "C1 2 3", "L1 2 3", "D1 3 0"]);
+        assert_eq!(graph.neighbors(NodeId(2)), [NodeId(1), NodeId(3)]);
+        assert_eq!((graph.input, graph.output), (NodeId(1), None));
+    }
+
+    #[test]
+    fn test_twoport() {
+        let graph = circuit::document("|V1-P1>vout|C1=T1(n=2).vout|R2").unwrap().graph();
+        assert_eq!(graph.nodes.len(), 4);
+        let vout = graph.find("vout").unwrap();
+        assert_eq!(edges(&graph), ["V1 1 0", "P1 1 3", "P1 3 2", "C1 2 0", "T1 2 0", "T1 3 0", "R2 3 0"]);
+        assert_eq!(vout, NodeId(3));
+        assert_eq!(graph.terminals("P1"), Some((NodeId(1), NodeId(2))));
+        assert_eq!(graph.output, Some(vout));
+    }
+}
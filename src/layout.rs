@@ -20,67 +20,223 @@ impl Position {
 
 pub const ELEMENT_SIZE: Size = Size(200, 60);
 
+/// Size of a transformer link, whose coils span the height of a shunt element
+pub const TRANSFORMER_SIZE: Size = Size(2 * ELEMENT_SIZE.1, ELEMENT_SIZE.0);
+
+/// Rules for the wires and leads the layout has to make room for.
+///
+/// Parallel groups take their end wires from the width they are given, so each level of nesting
+/// leaves less room for the elements inside. Groups are widened where that would make leads shorter
+/// than `min_lead`, which keeps room for labels in dense layouts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rules {
+    /// Wire between the junctions of a parallel group and its ends
+    pub end_wire: i32,
+    /// Minimum length of the leads between a symbol's body and the ends of the element
+    pub min_lead: i32,
+    /// Width of the widest symbol body, see [`crate::draw::symbols`]
+    pub body_width: i32,
+}
+
+impl Rules {
+    pub const STANDARD: Rules = Rules { end_wire: 20, min_lead: 10, body_width: 80 };
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+/// How labels and values that are wider than the room around their element are shortened.
+///
+/// Widths are estimated with [`crate::draw::symbols::text_width`]. Horizontal elements have their
+/// own width for their label and value. Vertical ones have their label beside them, and get half
+/// an element's length, the closest a neighboring shunt can be.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Shortening {
+    /// Omit values that don't fit
+    pub drop_values: bool,
+    /// Truncate labels that don't fit, ending them in `…`. Drawers that support it keep the full label as a tooltip
+    pub truncate: bool,
+}
+
+impl Shortening {
+    /// No shortening, labels and values may overlap their neighbors
+    pub const NONE: Shortening = Shortening { drop_values: false, truncate: false };
+    pub const ALL: Shortening = Shortening { drop_values: true, truncate: true };
+
+    /// Shortens the label and value of an element drawn with the given size, returning them as they should be drawn
+    pub fn apply(self, label: &str, value: Option<String>, size: Size, rotate: bool) -> (String, Option<String>) {
+        use crate::draw::symbols::text_width;
+        let available = if rotate { ELEMENT_SIZE.0 / 2 } else { size.0 };
+        let value = value.filter(|value| !self.drop_values || text_width(value) <= available);
+        if !self.truncate || text_width(label) <= available {
+            return (label.to_string(), value);
+        }
+        let mut truncated: String = label.chars().take(1).collect();
+        for c in label.chars().skip(1) {
+            if text_width(&truncated) + text_width(&format!("{c}…")) > available {
+                break;
+            }
+            truncated.push(c);
+        }
+        truncated.push('…');
+        (truncated, value)
+    }
+}
+
 pub trait Layout {
-    fn layout_size(&self) -> Size;
+    /// Size requested with the [`Rules::STANDARD`] rules
+    fn layout_size(&self) -> Size {
+        self.layout_size_with(Rules::STANDARD)
+    }
+
+    fn layout_size_with(&self, rules: Rules) -> Size;
+
+    /// The narrowest width this can be drawn with, without leads getting shorter than `rules.min_lead`
+    fn min_width(&self, rules: Rules) -> i32;
 }
 
 impl Layout for circuit::Element<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, _rules: Rules) -> Size {
         ELEMENT_SIZE
     }
+
+    fn min_width(&self, rules: Rules) -> i32 {
+        rules.body_width + 2 * rules.min_lead
+    }
 }
 
 impl Layout for circuit::SubCircuitGroup<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, rules: Rules) -> Size {
         use circuit::SubCircuitGroup::*;
         match self {
-            Single(circuit) => circuit.layout_size(),
+            Single(circuit) => circuit.layout_size_with(rules),
             Series(left, right) => {
-                let (left_size, right_size) = (left.layout_size(), right.layout_size());
+                let (left_size, right_size) = (left.layout_size_with(rules), right.layout_size_with(rules));
                 Size(left_size.0 + right_size.0, left_size.1.max(right_size.1))
             }
             Parallel(left, right) => {
-                let (left_size, right_size) = (left.layout_size(), right.layout_size());
-                Size(left_size.0.max(right_size.0), left_size.1 + right_size.1)
+                let (left_size, right_size) = (left.layout_size_with(rules), right.layout_size_with(rules));
+                let needed = left.min_width(rules).max(right.min_width(rules)) + 2 * rules.end_wire;
+                Size(left_size.0.max(right_size.0).max(needed), left_size.1 + right_size.1)
+            }
+        }
+    }
+
+    fn min_width(&self, rules: Rules) -> i32 {
+        use circuit::SubCircuitGroup::*;
+        match self {
+            Single(circuit) => circuit.min_width(rules),
+            // the width is shared in proportion to the requested widths, so the tightest side decides
+            Series(left, right) => {
+                let (left_width, right_width) = (left.layout_size_with(rules).0, right.layout_size_with(rules).0);
+                let total = left_width + right_width;
+                let scaled = |min: i32, width: i32| (min * total + width - 1) / width;
+                scaled(left.min_width(rules), left_width).max(scaled(right.min_width(rules), right_width))
             }
+            Parallel(left, right) => left.min_width(rules).max(right.min_width(rules)) + 2 * rules.end_wire,
         }
     }
 }
 
 impl Layout for circuit::SubCircuit<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, rules: Rules) -> Size {
         match self {
-            circuit::SubCircuit::Element(element) => element.layout_size(),
-            circuit::SubCircuit::Group(group) => group.layout_size(),
+            circuit::SubCircuit::Element(element, _) => element.layout_size_with(rules),
+            circuit::SubCircuit::Group(group) => group.layout_size_with(rules),
+        }
+    }
+
+    fn min_width(&self, rules: Rules) -> i32 {
+        match self {
+            circuit::SubCircuit::Element(element, _) => element.min_width(rules),
+            circuit::SubCircuit::Group(group) => group.min_width(rules),
         }
     }
 }
 
 impl Layout for circuit::TwoportLink<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, rules: Rules) -> Size {
         match self {
-            circuit::TwoportLink::Series(circuit) => circuit.layout_size(),
-            circuit::TwoportLink::Shunt(circuit) => circuit.layout_size().rotate(),
+            circuit::TwoportLink::Series(circuit) => circuit.layout_size_with(rules),
+            circuit::TwoportLink::Shunt(circuit) => circuit.layout_size_with(rules).rotate(),
+            circuit::TwoportLink::Transformer { .. } => TRANSFORMER_SIZE,
+            circuit::TwoportLink::Node(_) | circuit::TwoportLink::Annotation { .. } => Size(0, 0),
+        }
+    }
+
+    /// Shunt links are drawn in their natural width, rotated
+    fn min_width(&self, rules: Rules) -> i32 {
+        match self {
+            circuit::TwoportLink::Series(circuit) => circuit.min_width(rules),
+            circuit::TwoportLink::Shunt(circuit) => circuit.layout_size_with(rules).1,
+            circuit::TwoportLink::Transformer { .. } => TRANSFORMER_SIZE.0,
+            circuit::TwoportLink::Node(_) | circuit::TwoportLink::Annotation { .. } => 0,
         }
     }
 }
 
 impl Layout for circuit::Twoport<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, rules: Rules) -> Size {
         self.links.iter().fold(Size(0, 0), |size, link| {
-            let Size(w, h) = link.layout_size();
+            let Size(w, h) = link.layout_size_with(rules);
             Size(size.0 + w, size.1.max(h))
         })
     }
+
+    // links are placed in columns of their requested width
+    fn min_width(&self, rules: Rules) -> i32 {
+        self.layout_size_with(rules).0
+    }
 }
 
 impl Layout for circuit::Document<'_> {
-    fn layout_size(&self) -> Size {
+    fn layout_size_with(&self, rules: Rules) -> Size {
         match self {
-            circuit::Document::Circuit(circuit) => circuit.layout_size(),
-            circuit::Document::Twoport(twoport) => twoport.layout_size(),
+            circuit::Document::Circuit(circuit) => circuit.layout_size_with(rules),
+            circuit::Document::Twoport(twoport) => twoport.layout_size_with(rules),
+        }
+    }
+
+    fn min_width(&self, rules: Rules) -> i32 {
+        match self {
+            circuit::Document::Circuit(circuit) => circuit.min_width(rules),
+            circuit::Document::Twoport(twoport) => twoport.min_width(rules),
+        }
+    }
+}
+
+/// Vertical space between twoports that are stacked below each other
+pub const STACK_SPACING: i32 = 40;
+
+/// Vertical space taken by the title above a drawing
+pub const TITLE_HEIGHT: i32 = 60;
+
+/// Computes the column widths shared by twoports stacked below each other.
+///
+/// Each column is as wide as the widest link at that position.
+pub fn shared_columns(twoports: &[circuit::Twoport], rules: Rules) -> Vec<i32> {
+    let mut columns = Vec::new();
+    for twoport in twoports {
+        for (i, link) in twoport.links.iter().enumerate() {
+            let width = link.layout_size_with(rules).0;
+            match columns.get_mut(i) {
+                Some(column) => *column = width.max(*column),
+                None => columns.push(width),
+            }
         }
     }
+    columns
+}
+
+/// Size of the figure produced by stacking the given twoports below each other
+pub fn stacked_size(twoports: &[circuit::Twoport], rules: Rules) -> Size {
+    let width = shared_columns(twoports, rules).iter().sum();
+    let height = twoports.iter().map(|twoport| twoport.layout_size_with(rules).1).sum::<i32>()
+        + STACK_SPACING * (twoports.len() as i32 - 1).max(0);
+    Size(width, height)
 }
 
 #[cfg(test)]
@@ -95,27 +251,71 @@ mod tests {
     #[test]
     fn test_group_series() {
         assert_eq!(circuit::SubCircuitGroup::Series(
-            circuit::SubCircuit::Element(circuit::Element::R("1")),
-            circuit::SubCircuit::Element(circuit::Element::R("2")),
+            circuit::SubCircuit::Element(circuit::Element::R("1"), None),
+            circuit::SubCircuit::Element(circuit::Element::R("2"), None),
         ).layout_size(), Size(ELEMENT_SIZE.0 * 2, ELEMENT_SIZE.1));
     }
 
     #[test]
     fn test_group_parallel() {
         assert_eq!(circuit::SubCircuitGroup::Parallel(
-            circuit::SubCircuit::Element(circuit::Element::R("1")),
-            circuit::SubCircuit::Element(circuit::Element::R("2")),
+            circuit::SubCircuit::Element(circuit::Element::R("1"), None),
+            circuit::SubCircuit::Element(circuit::Element::R("2"), None),
         ).layout_size(), Size(ELEMENT_SIZE.0, ELEMENT_SIZE.1 * 2));
     }
 
     #[test]
     fn test_group_parallel_with_series_element() {
         assert_eq!(circuit::SubCircuitGroup::Parallel(
-            circuit::SubCircuit::Element(circuit::Element::R("1")),
+            circuit::SubCircuit::Element(circuit::Element::R("1"), None),
             circuit::SubCircuit::Group(Box::new(circuit::SubCircuitGroup::Series(
-                circuit::SubCircuit::Element(circuit::Element::R("2")),
-                circuit::SubCircuit::Element(circuit::Element::R("3")),
+                circuit::SubCircuit::Element(circuit::Element::R("2"), None),
+                circuit::SubCircuit::Element(circuit::Element::R("3"), None),
             )))
         ).layout_size(), Size(ELEMENT_SIZE.0 * 2, ELEMENT_SIZE.1 * 2));
     }
+
+    #[test]
+    fn test_nested_parallel_groups() {
+        type E = nom::error::VerboseError<&'static str>;
+        // two levels of end wires still leave room for the leads
+        let circuit = circuit::sub_circuit::<E>("((R1||R2)||R3)").unwrap().1;
+        assert_eq!(circuit.layout_size().0, ELEMENT_SIZE.0);
+        // four levels don't, so the group is widened
+        let circuit = circuit::sub_circuit::<E>("((((R1||R2)||R3)||R4)||R5)").unwrap().1;
+        assert_eq!(circuit.min_width(Rules::STANDARD), 100 + 4 * 40);
+        assert_eq!(circuit.layout_size().0, 260);
+        let rules = Rules { min_lead: 0, ..Rules::STANDARD };
+        assert_eq!(circuit.layout_size_with(rules).0, 80 + 4 * 40);
+    }
+
+    #[test]
+    fn test_shortening() {
+        let size = ELEMENT_SIZE;
+        let value = || Some(String::from("4.7kΩ"));
+        assert_eq!(Shortening::NONE.apply("Rverylongname", value(), Size(40, 60), false), (String::from("Rverylongname"), value()));
+        assert_eq!(Shortening::ALL.apply("R1", value(), size, false), (String::from("R1"), value()));
+        assert_eq!(Shortening::ALL.apply("R1", value(), Size(40, 60), false), (String::from("R1"), None));
+        assert_eq!(Shortening::ALL.apply("Rverylongname", None, Size(40, 60), false), (String::from("Rve…"), None));
+        assert_eq!(Shortening::ALL.apply("Rloadresistance", None, size, true), (String::from("Rloadresis…"), None));
+        assert_eq!(Shortening::ALL.apply("Rload", None, Size(4, 60), false), (String::from("R…"), None));
+    }
+
+    #[test]
+    fn test_transformer() {
+        type E = nom::error::VerboseError<&'static str>;
+        let twoport = circuit::twoport::<E>("|V1=T1|C1").unwrap().1;
+        assert_eq!(twoport.layout_size(), Size(2 * ELEMENT_SIZE.1 + TRANSFORMER_SIZE.0, ELEMENT_SIZE.0));
+    }
+
+    #[test]
+    fn test_shared_columns() {
+        type E = nom::error::VerboseError<&'static str>;
+        let twoports = [
+            circuit::twoport::<E>("|V1-R1|C1").unwrap().1,
+            circuit::twoport::<E>("|V1-(R1+R2)|C1-R3").unwrap().1,
+        ];
+        assert_eq!(shared_columns(&twoports, Rules::STANDARD), vec![ELEMENT_SIZE.1, ELEMENT_SIZE.0 * 2, ELEMENT_SIZE.1, ELEMENT_SIZE.0]);
+        assert_eq!(stacked_size(&twoports, Rules::STANDARD), Size(ELEMENT_SIZE.0 * 3 + ELEMENT_SIZE.1 * 2, ELEMENT_SIZE.0 * 2 + STACK_SPACING));
+    }
 }
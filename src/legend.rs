@@ -0,0 +1,129 @@
+//! Legends, explaining the designator prefixes used in a circuit.
+
+use crate::circuit::{Document, Element};
+
+/// English names of all element kinds, by designator prefix
+pub const ENGLISH: &[(&str, &str)] = &[
+    ("R", "resistor"),
+    ("C", "capacitor"),
+    ("L", "inductor"),
+    ("Z", "impedance"),
+    ("V", "voltage source"),
+    ("I", "current source"),
+    ("D", "diode"),
+    ("Dz", "zener diode"),
+    ("S", "switch"),
+    ("P", "potentiometer"),
+    ("LED", "light emitting diode"),
+    ("NTC", "thermistor"),
+    ("XTAL", "crystal"),
+    ("FUSE", "fuse"),
+    ("E", "voltage controlled voltage source"),
+    ("G", "voltage controlled current source"),
+    ("H", "current controlled voltage source"),
+    ("F", "current controlled current source"),
+];
+
+/// German names of all element kinds, by designator prefix
+pub const GERMAN: &[(&str, &str)] = &[
+    ("R", "Widerstand"),
+    ("C", "Kondensator"),
+    ("L", "Spule"),
+    ("Z", "Impedanz"),
+    ("V", "Spannungsquelle"),
+    ("I", "Stromquelle"),
+    ("D", "Diode"),
+    ("Dz", "Z-Diode"),
+    ("S", "Schalter"),
+    ("P", "Potentiometer"),
+    ("LED", "Leuchtdiode"),
+    ("NTC", "Heißleiter"),
+    ("XTAL", "Quarz"),
+    ("FUSE", "Sicherung"),
+    ("E", "spannungsgesteuerte Spannungsquelle"),
+    ("G", "spannungsgesteuerte Stromquelle"),
+    ("H", "stromgesteuerte Spannungsquelle"),
+    ("F", "stromgesteuerte Stromquelle"),
+];
+
+/// The names of the language, `en` for [`ENGLISH`] or `de` for [`GERMAN`]
+pub fn names(language: &str) -> Result<&'static [(&'static str, &'static str)], crate::error::UnknownName> {
+    match language {
+        "en" => Ok(ENGLISH),
+        "de" => Ok(GERMAN),
+        _ => Err(crate::error::UnknownName { option: "legend language", name: language.to_string(), expected: &["en", "de"] }),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegendEntry {
+    pub prefix: &'static str,
+    pub name: String,
+}
+
+impl std::fmt::Display for LegendEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} — {}", self.prefix, self.name)
+    }
+}
+
+/// List of the element kinds present in a document, with their (localized) names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Legend {
+    pub entries: Vec<LegendEntry>,
+}
+
+impl Legend {
+    /// Builds the legend for the given document, taking the names from `names` (see [`ENGLISH`] and [`GERMAN`]).
+    ///
+    /// Entries are ordered like in `names`. Kinds without a name are listed with their prefix as name.
+    pub fn new(document: &Document, names: &[(&str, &str)]) -> Self {
+        Self::from_elements(document.elements(), names)
+    }
+
+    /// Builds the legend for the given elements, e.g. those of multiple documents
+    pub fn from_elements<'a, 'b: 'a>(elements: impl IntoIterator<Item = &'a Element<'b>>, names: &[(&str, &str)]) -> Self {
+        let mut prefixes: Vec<&'static str> = vec![];
+        for prefix in elements.into_iter().filter_map(|element| element.prefix()) {
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        let position = |prefix: &str| names.iter().position(|(p, _)| *p == prefix).unwrap_or(names.len());
+        prefixes.sort_by_key(|prefix| position(prefix));
+        let entries = prefixes.into_iter().map(|prefix| LegendEntry {
+            prefix,
+            name: names.iter()
+                .find(|(p, _)| *p == prefix)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_else(|| prefix.to_string()),
+        }).collect();
+        Self { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_legend() {
+        let document = circuit::document("|V1-(C1+R1||R2)|O-R3").unwrap();
+        let legend = Legend::new(&document, GERMAN);
+        assert_eq!(
+            legend.entries.iter().map(|entry| entry.to_string()).collect::<Vec<_>>(),
+            vec!["R — Widerstand", "C — Kondensator", "V — Spannungsquelle"],
+        );
+    }
+
+    #[test]
+    fn test_legend_missing_name() {
+        let document = circuit::document("(R1+L1)").unwrap();
+        let legend = Legend::new(&document, &[("R", "resistance")]);
+        assert_eq!(legend.entries, vec![
+            LegendEntry { prefix: "R", name: "resistance".to_string() },
+            LegendEntry { prefix: "L", name: "L".to_string() },
+        ]);
+    }
+}
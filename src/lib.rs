@@ -1,4 +1,34 @@
 pub mod circuit;
+pub mod document;
+pub mod error;
 pub mod layout;
 pub mod draw;
+pub mod describe;
+pub mod format;
+pub mod legend;
+pub mod search;
+pub mod outline;
+pub mod validate;
+pub mod resolve;
+pub mod graph;
+pub mod netlist;
+pub mod bom;
+pub mod analysis;
+pub mod parasitics;
+pub mod simplify;
+pub mod smith;
+pub mod sweep;
+pub mod fit;
+pub mod eseries;
+pub mod dump;
+pub mod render;
+pub mod gallery;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
+pub mod builder;
+
+pub use error::ParseError;
+pub use render::{render_document, render_svg, RenderError, RenderOptions};
@@ -0,0 +1,547 @@
+//! SPICE netlist export, e.g. for simulating circuits with ngspice, and KiCad netlist export, e.g. for laying out
+//! the circuit on a board.
+
+use std::collections::HashMap;
+use crate::{
+    circuit::{Document, Element, Polarity, Spice, Value},
+    format::{self, Locale},
+    graph::{ElementRef, NodeId},
+};
+
+/// Node of ground, which is the bottom line of twoports
+const GROUND: &str = "0";
+
+/// Primary inductance of transformers, large enough for them to be ideal at usual frequencies
+const TRANSFORMER_INDUCTANCE: f64 = 1e6;
+
+/// KiCad library and symbol of the kinds of elements by prefix, those missing having no symbol
+const KICAD_SYMBOLS: &[(&str, &str, &str)] = &[
+    ("R", "Device", "R"),
+    ("C", "Device", "C"),
+    ("L", "Device", "L"),
+    ("Z", "Device", "R"),
+    ("V", "Simulation_SPICE", "VDC"),
+    ("I", "Simulation_SPICE", "IDC"),
+    ("D", "Device", "D"),
+    ("Dz", "Device", "D_Zener"),
+    ("S", "Switch", "SW_SPST"),
+    ("P", "Device", "R_Potentiometer"),
+    ("LED", "Device", "LED"),
+    ("NTC", "Device", "Thermistor_NTC"),
+    ("XTAL", "Device", "Crystal"),
+    ("FUSE", "Device", "Fuse"),
+    ("T", "Device", "Transformer_1P_1S"),
+];
+
+/// An element or transformer as connected in the netlist, for the KiCad export
+struct Component {
+    reference: String,
+    /// The prefix of the kind of element, `T` for transformers
+    prefix: &'static str,
+    value: Option<String>,
+    /// Nodes by pin, the pins being numbered from 1
+    pins: Vec<String>,
+}
+
+struct Netlist {
+    lines: Vec<String>,
+    params: Vec<String>,
+    models: Vec<String>,
+    /// Nodes of the elements by label, and whether they are voltage sources, for resolving controlling elements
+    nodes: HashMap<String, (String, String, bool)>,
+    /// Controlled sources and their gain, which are added once all controlling elements are known
+    controlled: Vec<(String, Spice, String, Option<f64>, String, String)>,
+    components: Vec<Component>,
+}
+
+impl Netlist {
+    /// Adds the line for an element, `a` being its left (or top) end and `wiper` the wiper of potentiometers
+    fn element(&mut self, element: &Element, value: Option<&Value>, a: &str, b: &str, wiper: &str) {
+        let label = element.label();
+        self.nodes.insert(label.clone(), (a.to_string(), b.to_string(), element.kind().prefix == "V"));
+        if element.id().is_some() {
+            self.components.push(Component {
+                reference: label.clone(),
+                prefix: element.kind().prefix,
+                value: value.map(|value| format::si(value.number, value.unit, &Locale::ASCII)),
+                pins: vec![a.to_string(), b.to_string()],
+            });
+        }
+        // the anode of diodes is their first node, unless they are marked to point to their first end
+        let (anode, cathode) = if element.polarity() == Polarity::Up { (b, a) } else { (a, b) };
+        match element.kind().spice {
+            Spice::Passive(prefix) => {
+                let value = self.value(&label, value);
+                self.lines.push(format!("{prefix}{label} {a} {b} {value}"));
+            }
+            // the positive terminal is on the left (or top), where the long plate is drawn, unless the source
+            // points the other way: sources pointing up drive current out of their top, which SPICE current
+            // sources do out of their second node
+            Spice::Source => {
+                let value = self.value(&label, value);
+                let (a, b) = match element {
+                    Element::V { polarity: Polarity::Down, .. } | Element::I { polarity: Polarity::Up, .. } => (b, a),
+                    _ => (a, b),
+                };
+                self.lines.push(format!("{label} {a} {b} DC {value}"));
+            }
+            // SPICE tells the kinds of elements apart by their first letter, so `LED1` becomes `DLED1`
+            Spice::Diode => {
+                self.model("D", "D");
+                let name = if label.starts_with('D') { label.clone() } else { format!("D{label}") };
+                self.lines.push(format!("{name} {anode} {cathode} D"));
+            }
+            // the value of a zener diode is its breakdown voltage
+            Spice::ZenerDiode => {
+                let model = format!("{label}_model");
+                match value {
+                    Some(value) => self.model(&model, &format!("D(BV={:e})", value.number)),
+                    None => self.model(&model, "D"),
+                }
+                self.lines.push(format!("{label} {anode} {cathode} {model}"));
+            }
+            // SPICE has no static switch, so a voltage controlled one is held open or closed by its own source
+            Spice::Switch { closed } => {
+                self.model("switch", "SW(VT=0.5 RON=1e-3 ROFF=1e12)");
+                let control = format!("{label}_control");
+                self.lines.push(format!("{label} {a} {b} {control} {GROUND} switch"));
+                self.lines.push(format!("V{control} {control} {GROUND} DC {}", if closed { 1 } else { 0 }));
+            }
+            Spice::VoltageControlled | Spice::CurrentControlled => {
+                let gain = value.map(|value| value.number);
+                let control = element.control().unwrap_or_default().to_string();
+                self.controlled.push((label, element.kind().spice, control, gain, a.to_string(), b.to_string()));
+            }
+            Spice::Potentiometer => {
+                let half = match value {
+                    Some(value) => format!("{:e}", value.number / 2.0),
+                    None => {
+                        self.params.push(format!(".param {label}=1"));
+                        format!("{{{label}/2}}")
+                    }
+                };
+                // the wiper is the middle pin
+                self.components.last_mut().unwrap().pins.insert(1, wiper.to_string());
+                self.lines.push(format!("R{label}_a {a} {wiper} {half}"));
+                self.lines.push(format!("R{label}_b {wiper} {b} {half}"));
+            }
+            // SPICE names voltage sources with a `V`, so `FUSE1` becomes `VFUSE1`
+            Spice::Short => self.lines.push(format!("V{label} {a} {b} DC 0")),
+            Spice::Omitted => {}
+        }
+    }
+
+    /// Adds the lines for the controlled sources, controlled by the voltage across (E, G) or the current
+    /// through (H, F) their controlling element
+    fn controlled_sources(&mut self) {
+        for (label, spice, control, gain, a, b) in std::mem::take(&mut self.controlled) {
+            let gain = |netlist: &mut Self| netlist.value(&label, gain.map(|number| Value { number, unit: "" }).as_ref());
+            match (spice, self.nodes.get(&control).cloned()) {
+                (Spice::VoltageControlled, Some((c, d, _))) => {
+                    let gain = gain(self);
+                    self.lines.push(format!("{label} {a} {b} {c} {d} {gain}"));
+                }
+                // SPICE measures currents through voltage sources only
+                (Spice::CurrentControlled, Some((_, _, true))) => {
+                    let gain = gain(self);
+                    self.lines.push(format!("{label} {a} {b} {control} {gain}"));
+                }
+                (Spice::CurrentControlled, Some(_)) => self.lines.push(format!("* {label} omitted: {control} is not a voltage source")),
+                _ => self.lines.push(format!("* {label} omitted: there is no element {control}")),
+            }
+        }
+    }
+
+    /// Adds an ideal transformer as coupled inductors, the secondary being on the `n:1` side
+    fn transformer(&mut self, label: &str, ratio: Option<&Value>, primary: &str, secondary: &str) {
+        let secondary_inductance = match ratio {
+            Some(ratio) => format!("{:e}", TRANSFORMER_INDUCTANCE / (ratio.number * ratio.number)),
+            None => {
+                self.params.push(format!(".param {label}=1"));
+                format!("{{{TRANSFORMER_INDUCTANCE:e}/({label}*{label})}}")
+            }
+        };
+        self.lines.push(format!("L{label}_primary {primary} {GROUND} {TRANSFORMER_INDUCTANCE:e}"));
+        self.lines.push(format!("L{label}_secondary {secondary} {GROUND} {secondary_inductance}"));
+        self.lines.push(format!("K{label} L{label}_primary L{label}_secondary 1"));
+        self.components.push(Component {
+            reference: label.to_string(),
+            prefix: "T",
+            value: ratio.map(|ratio| format!("{}:1", format::number(ratio.number, &Locale::ASCII))),
+            pins: vec![primary.to_string(), GROUND.to_string(), secondary.to_string(), GROUND.to_string()],
+        });
+    }
+
+    /// Returns the value as written in SPICE, declaring a parameter for elements without value
+    fn value(&mut self, label: &str, value: Option<&Value>) -> String {
+        match value {
+            Some(value) => format!("{:e}", value.number),
+            None => {
+                self.params.push(format!(".param {label}=1"));
+                format!("{{{label}}}")
+            }
+        }
+    }
+
+    fn model(&mut self, name: &str, definition: &str) {
+        let model = format!(".model {name} {definition}");
+        if !self.models.contains(&model) {
+            self.models.push(model);
+        }
+    }
+}
+
+/// The SPICE node for a node named in the circuit, which must not be taken for a numbered one
+fn node_name(name: &str) -> String {
+    if name.bytes().all(|byte| byte.is_ascii_digit()) {
+        format!("n{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Produces a SPICE deck for the document.
+///
+/// Circuits are connected between nodes 1 and 0 (ground). For twoports the bottom line is ground,
+/// the input is node 1 and the output is the node numbered last. Nodes named in the circuit, with
+/// `.vout` or by the wiper of `P1>vout`, keep their name (prefixed with `n` if it is a number).
+/// Elements without a value get a parameter of the same name (defaulting to 1), which can be
+/// adjusted with `.param`.
+///
+/// ```
+/// use circmark_parse::{circuit, netlist::to_spice};
+/// let document = circuit::document("|V1=5V-R1=1k|C1=100nF").unwrap();
+/// assert_eq!(to_spice(&document), "\
+/// * circmark netlist
+/// * input: 1 0, output: 2 0
+/// V1 1 0 DC 5e0
+/// R1 1 2 1e3
+/// C1 2 0 1e-7
+/// .end
+/// ");
+/// ```
+pub fn to_spice(document: &Document) -> String {
+    let (netlist, input, output) = connect(document);
+    let mut deck = String::from("* circmark netlist\n");
+    match output {
+        Some(output) => deck.push_str(&format!("* input: {input} {GROUND}, output: {output} {GROUND}\n")),
+        None => deck.push_str(&format!("* terminals: {input} {GROUND}\n")),
+    }
+    for line in netlist.params.iter().chain(&netlist.models).chain(&netlist.lines) {
+        deck.push_str(line);
+        deck.push('\n');
+    }
+    deck.push_str(".end\n");
+    deck
+}
+
+/// Produces a KiCad netlist (in the S-expression format of KiCad 6 and later) for the document, e.g. for
+/// importing the parts and their connections into a board.
+///
+/// The nets are the nodes of [`to_spice`], ground being named `GND`. Potentiometers have their wiper as the middle
+/// pin, and transformers their primary on pins 1 and 2. Open circuits are left out, as are the symbols of elements
+/// KiCad has none for, e.g. controlled sources.
+///
+/// ```
+/// use circmark_parse::{circuit, netlist::to_kicad};
+/// let netlist = to_kicad(&circuit::document("|V1=5V-R1=1k|C1").unwrap());
+/// assert!(netlist.contains("(comp (ref \"R1\")\n      (value \"1k\")\n      (libsource (lib \"Device\") (part \"R\")))"));
+/// assert!(netlist.contains("(net (code \"2\") (name \"1\")\n      (node (ref \"V1\") (pin \"1\"))\n      (node (ref \"R1\") (pin \"1\")))"));
+/// ```
+pub fn to_kicad(document: &Document) -> String {
+    let (netlist, _, _) = connect(document);
+    let mut result = String::from("(export (version \"E\")\n  (design\n    (tool \"circmark\"))\n  (components");
+    for component in &netlist.components {
+        result.push_str(&format!("\n    (comp (ref {})", quote(&component.reference)));
+        let symbol = KICAD_SYMBOLS.iter().find(|(prefix, _, _)| *prefix == component.prefix);
+        // KiCad shows the symbol's name for parts without value
+        let value = component.value.as_deref().or(symbol.map(|(_, _, name)| *name)).unwrap_or(component.prefix);
+        result.push_str(&format!("\n      (value {})", quote(value)));
+        if let Some((_, library, name)) = symbol {
+            result.push_str(&format!("\n      (libsource (lib {}) (part {}))", quote(library), quote(name)));
+        }
+        result.push(')');
+    }
+    result.push_str(")\n  (nets");
+    // ground first, the other nets in the order of their first pin
+    let mut nets = vec![GROUND];
+    for node in netlist.components.iter().flat_map(|component| &component.pins) {
+        if !nets.contains(&node.as_str()) {
+            nets.push(node);
+        }
+    }
+    for (code, net) in nets.iter().enumerate() {
+        let name = if *net == GROUND { "GND" } else { net };
+        result.push_str(&format!("\n    (net (code \"{}\") (name {})", code + 1, quote(name)));
+        for component in &netlist.components {
+            for (pin, _) in component.pins.iter().enumerate().filter(|(_, node)| node == net) {
+                result.push_str(&format!("\n      (node (ref {}) (pin \"{}\"))", quote(&component.reference), pin + 1));
+            }
+        }
+        result.push(')');
+    }
+    result.push_str("))\n");
+    result
+}
+
+/// A string in KiCad's S-expressions, in double quotes
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Connects the elements of the document along its [`crate::graph::CircuitGraph`], returning the netlist, the input and the output
+/// node (for twoports)
+fn connect(document: &Document) -> (Netlist, String, Option<String>) {
+    let graph = document.graph();
+    let mut netlist = Netlist {
+        lines: vec![],
+        params: vec![],
+        models: vec![],
+        nodes: HashMap::new(),
+        controlled: vec![],
+        components: vec![],
+    };
+    // named nodes keep their name, the others are numbered in the order of the graph
+    let mut numbered = 0;
+    let names: Vec<String> = graph.nodes.iter().enumerate().map(|(id, node)| match &node.name {
+        _ if NodeId(id) == NodeId::GROUND => GROUND.to_string(),
+        Some(name) => node_name(name),
+        None => {
+            numbered += 1;
+            numbered.to_string()
+        }
+    }).collect();
+    let name = |node: &NodeId| names[node.0].as_str();
+    // potentiometers and transformers are two edges following each other
+    let mut edges = graph.edges.iter();
+    while let Some((a, b, edge)) = edges.next() {
+        match edge {
+            ElementRef::Element { element: element @ Element::P { .. }, value, .. } => {
+                let (_, c, _) = edges.next().expect("potentiometers are two edges");
+                netlist.element(element, value.as_ref(), name(a), name(c), name(b));
+            }
+            ElementRef::Element { element, value, .. } => netlist.element(element, value.as_ref(), name(a), name(b), GROUND),
+            ElementRef::Winding { label, ratio, .. } => {
+                let (secondary, _, _) = edges.next().expect("transformers are two windings");
+                netlist.transformer(label, ratio.as_ref(), name(a), name(secondary));
+            }
+        }
+    }
+    netlist.controlled_sources();
+    (netlist, name(&graph.input).to_string(), graph.output.as_ref().map(|output| name(output).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_circuit() {
+        let document = circuit::document("(R1+(C1||L1=1m)+D1)").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* terminals: 1 0
+.param R1=1
+.param C1=1
+.model D D
+R1 1 2 {R1}
+C1 2 3 {C1}
+L1 2 3 1e-3
+D1 3 0 D
+.end
+");
+    }
+
+    #[test]
+    fn test_twoport() {
+        let document = circuit::document("|O-Zth=50|Dz5v1=5.1|O").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 2 0
+.model Dz5v1_model D(BV=5.1e0)
+RZth 1 2 5e1
+Dz5v1 2 0 Dz5v1_model
+.end
+");
+    }
+
+    #[test]
+    fn test_source_polarity() {
+        let document = circuit::document("|vV1=1|^I1=2|I2=3|O").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 1 0
+V1 0 1 DC 1e0
+I1 0 1 DC 2e0
+I2 1 0 DC 3e0
+.end
+");
+    }
+
+    #[test]
+    fn test_controlled_sources() {
+        let document = circuit::document("|V1=1-R1|Egain(R1)=10|F1(V1)|H1(R1)|G1(R9)").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 2 0
+.param R1=1
+.param F1=1
+V1 1 0 DC 1e0
+R1 1 2 {R1}
+Egain 2 0 1 2 1e1
+F1 2 0 V1 {F1}
+* H1 omitted: R1 is not a voltage source
+* G1 omitted: there is no element R9
+.end
+");
+    }
+
+    #[test]
+    fn test_potentiometers() {
+        let document = circuit::document("|V1=1-P1>out=10k|P2>out-P3").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 3 0
+.param P2=1
+.param P3=1
+V1 1 0 DC 1e0
+RP1_a 1 out 5e3
+RP1_b out 2 5e3
+RP2_a 2 out {P2/2}
+RP2_b out 0 {P2/2}
+RP3_a 2 4 {P3/2}
+RP3_b 4 3 {P3/2}
+.end
+");
+    }
+
+    #[test]
+    fn test_multi_letter_kinds() {
+        let document = circuit::document("|V1=5-FUSE1=1A-NTC1=10k|XTAL1=16MHz|LED1").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 3 0
+.model D D
+V1 1 0 DC 5e0
+VFUSE1 1 2 DC 0
+RNTC1 2 3 1e4
+DLED1 3 0 D
+.end
+");
+    }
+
+    #[test]
+    fn test_named_nodes() {
+        let document = circuit::document(".vin|V1=1-R1=1k|C1=1n.vout-R2=1k.2").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: vin 0, output: n2 0
+V1 vin 0 DC 1e0
+R1 vin vout 1e3
+C1 vout 0 1e-9
+R2 vout n2 1e3
+.end
+");
+    }
+
+    #[test]
+    fn test_transformers() {
+        let document = circuit::document("|V1=1=T1(n=10)|R1=1=T2").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 3 0
+.param T2=1
+V1 1 0 DC 1e0
+LT1_primary 1 0 1e6
+LT1_secondary 2 0 1e4
+KT1 LT1_primary LT1_secondary 1
+R1 2 0 1e0
+LT2_primary 2 0 1e6
+LT2_secondary 3 0 {1e6/(T2*T2)}
+KT2 LT2_primary LT2_secondary 1
+.end
+");
+    }
+
+    #[test]
+    fn test_kicad() {
+        let document = circuit::document(".in|V1=1-P1>out=10k|LED1-Egain(V1)=2=T1(n=10)|R1=1ohm|O").unwrap();
+        assert_eq!(to_kicad(&document), "\
+(export (version \"E\")
+  (design
+    (tool \"circmark\"))
+  (components
+    (comp (ref \"V1\")
+      (value \"1\")
+      (libsource (lib \"Simulation_SPICE\") (part \"VDC\")))
+    (comp (ref \"P1\")
+      (value \"10k\")
+      (libsource (lib \"Device\") (part \"R_Potentiometer\")))
+    (comp (ref \"LED1\")
+      (value \"LED\")
+      (libsource (lib \"Device\") (part \"LED\")))
+    (comp (ref \"Egain\")
+      (value \"2\"))
+    (comp (ref \"T1\")
+      (value \"10:1\")
+      (libsource (lib \"Device\") (part \"Transformer_1P_1S\")))
+    (comp (ref \"R1\")
+      (value \"1Ω\")
+      (libsource (lib \"Device\") (part \"R\"))))
+  (nets
+    (net (code \"1\") (name \"GND\")
+      (node (ref \"V1\") (pin \"2\"))
+      (node (ref \"LED1\") (pin \"2\"))
+      (node (ref \"T1\") (pin \"2\"))
+      (node (ref \"T1\") (pin \"4\"))
+      (node (ref \"R1\") (pin \"2\")))
+    (net (code \"2\") (name \"in\")
+      (node (ref \"V1\") (pin \"1\"))
+      (node (ref \"P1\") (pin \"1\")))
+    (net (code \"3\") (name \"out\")
+      (node (ref \"P1\") (pin \"2\")))
+    (net (code \"4\") (name \"1\")
+      (node (ref \"P1\") (pin \"3\"))
+      (node (ref \"LED1\") (pin \"1\"))
+      (node (ref \"Egain\") (pin \"1\")))
+    (net (code \"5\") (name \"2\")
+      (node (ref \"Egain\") (pin \"2\"))
+      (node (ref \"T1\") (pin \"1\")))
+    (net (code \"6\") (name \"3\")
+      (node (ref \"T1\") (pin \"3\"))
+      (node (ref \"R1\") (pin \"1\")))))
+");
+    }
+
+    #[test]
+    fn test_diode_polarity() {
+        let document = circuit::document("|V1=1-^D1|vLED1|^Dz1").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* input: 1 0, output: 2 0
+.model D D
+.model Dz1_model D
+V1 1 0 DC 1e0
+D1 2 1 D
+DLED1 2 0 D
+Dz1 0 2 Dz1_model
+.end
+");
+    }
+
+    #[test]
+    fn test_switches() {
+        let document = circuit::document("(S1+S2*)").unwrap();
+        assert_eq!(to_spice(&document), "\
+* circmark netlist
+* terminals: 1 0
+.model switch SW(VT=0.5 RON=1e-3 ROFF=1e12)
+S1 1 2 S1_control 0 switch
+VS1_control S1_control 0 DC 0
+S2 2 0 S2_control 0 switch
+VS2_control S2_control 0 DC 1
+.end
+");
+    }
+}
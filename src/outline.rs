@@ -0,0 +1,110 @@
+//! Outlines of documents, e.g. for tables of contents or the symbols of an editor.
+
+use std::ops::Range;
+use crate::circuit::{self, Document, SubCircuit, TwoportLink};
+
+/// What an item of an outline is
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutlineKind {
+    /// A whole circuit, which is not a twoport
+    Circuit,
+    Series,
+    Shunt,
+    Transformer,
+    Node,
+    Annotation,
+}
+
+impl OutlineKind {
+    /// The kind in words, e.g. `series`
+    pub fn name(self) -> &'static str {
+        match self {
+            OutlineKind::Circuit => "circuit",
+            OutlineKind::Series => "series",
+            OutlineKind::Shunt => "shunt",
+            OutlineKind::Transformer => "transformer",
+            OutlineKind::Node => "node",
+            OutlineKind::Annotation => "annotation",
+        }
+    }
+}
+
+/// One top-level part of a document: a link of a twoport, or the whole circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub kind: OutlineKind,
+    /// The item in canonical notation (e.g. `(R1+C1)`), the label of a transformer or the name of a node or annotation
+    pub caption: String,
+    /// Byte range in the source, not including surrounding whitespace and comments
+    pub span: Range<usize>,
+    /// Number of elements within the item
+    pub elements: usize,
+}
+
+impl Document<'_> {
+    /// The items of this document's outline, in order.
+    ///
+    /// The document doesn't know where it came from, so `source` must be the input it was parsed from. Returns
+    /// `None` if it isn't, i.e. if the source doesn't parse into as many links.
+    ///
+    /// ```
+    /// use circmark_parse::{circuit, outline::OutlineKind};
+    /// let source = "|V1 -(R1+R2) # divider\n|C1";
+    /// let outline = circuit::document(source).unwrap().outline(source).unwrap();
+    /// assert_eq!(outline[1].kind, OutlineKind::Series);
+    /// assert_eq!(outline[1].caption, "(R1+R2)");
+    /// assert_eq!(&source[outline[1].span.clone()], "-(R1+R2)");
+    /// assert_eq!(outline[1].elements, 2);
+    /// ```
+    pub fn outline(&self, source: &str) -> Option<Vec<OutlineItem>> {
+        let spans = circuit::spans(source).ok()?;
+        match self {
+            Document::Circuit(circuit) => match &spans[..] {
+                [span] => Some(vec![item(OutlineKind::Circuit, circuit, span.clone())]),
+                _ => None,
+            },
+            Document::Twoport(twoport) if twoport.links.len() == spans.len() => Some(twoport.links.iter().zip(spans).map(|(link, span)| match link {
+                TwoportLink::Series(circuit) => item(OutlineKind::Series, circuit, span),
+                TwoportLink::Shunt(circuit) => item(OutlineKind::Shunt, circuit, span),
+                TwoportLink::Transformer { .. } => OutlineItem { kind: OutlineKind::Transformer, caption: link.label().unwrap(), span, elements: 0 },
+                TwoportLink::Node(name) => OutlineItem { kind: OutlineKind::Node, caption: name.to_string(), span, elements: 0 },
+                TwoportLink::Annotation { name, .. } => OutlineItem { kind: OutlineKind::Annotation, caption: name.to_string(), span, elements: 0 },
+            }).collect()),
+            Document::Twoport(_) => None,
+        }
+    }
+}
+
+fn item(kind: OutlineKind, circuit: &SubCircuit, span: Range<usize>) -> OutlineItem {
+    OutlineItem { kind, caption: circuit.to_string(), span, elements: circuit.elements().len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_twoport_outline() {
+        let source = "/* input */ |V1=5V\n-R1 = 1k=T1(n=2) .vout\n|(C1||L1) # load\n";
+        let outline = circuit::document(source).unwrap().outline(source).unwrap();
+        let items: Vec<_> = outline.iter().map(|item| (item.kind, item.caption.as_str(), &source[item.span.clone()], item.elements)).collect();
+        assert_eq!(items, [
+            (OutlineKind::Shunt, "V1=5V", "|V1=5V", 1),
+            (OutlineKind::Series, "R1=1k", "-R1 = 1k", 1),
+            (OutlineKind::Transformer, "T1", "=T1(n=2)", 0),
+            (OutlineKind::Node, "vout", ".vout", 0),
+            (OutlineKind::Shunt, "(C1||L1)", "|(C1||L1)", 2),
+        ]);
+        // the document wasn't parsed from these
+        assert_eq!(circuit::document(source).unwrap().outline("|V1"), None);
+        assert_eq!(circuit::document(source).unwrap().outline("|V1-("), None);
+    }
+
+    #[test]
+    fn test_circuit_outline() {
+        let source = "  (R1+C1||L1) # filter";
+        assert_eq!(circuit::document(source).unwrap().outline(source), Some(vec![
+            OutlineItem { kind: OutlineKind::Circuit, caption: "(R1+C1||L1)".to_string(), span: 2..13, elements: 3 },
+        ]));
+    }
+}
@@ -0,0 +1,104 @@
+//! Adding the parasitics of real components, to compare a circuit with its ideal version.
+//!
+//! Each element matching an entry of the table is replaced by a group of the element and
+//! its parasitic, e.g. `C1=100n` becomes `(C1=100n+Resr1=100mΩ)`. The result can be drawn,
+//! analysed and exported like any other document.
+//!
+//! ```
+//! use circmark_parse::{circuit, parasitics};
+//! let document = circuit::document("|V1-R1|C1=100n").unwrap();
+//! let real = parasitics::expand(&document, parasitics::STANDARD);
+//! assert_eq!(real.borrow().to_string(), "|V1-R1|(C1=100n+Resr1=100mΩ)");
+//! ```
+
+use crate::circuit::{element_kinds, owned, Document};
+
+/// How a parasitic is connected to its element
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Connection {
+    Series,
+    Parallel,
+}
+
+/// Entry of the table of parasitics
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Parasitic {
+    /// Prefix of the elements having the parasitic, e.g. `C`
+    pub of: &'static str,
+    /// Prefix of the parasitic element, e.g. `R`
+    pub prefix: &'static str,
+    /// Prepended to the id of the element to form the parasitic's id, e.g. `esr` for `Resr1`
+    pub name: &'static str,
+    pub connection: Connection,
+    /// Number and unit of the parasitic's value, if it has one
+    pub value: Option<(f64, &'static str)>,
+}
+
+/// Typical parasitics: the equivalent series resistance of capacitors, and the winding resistance of inductors
+pub const STANDARD: &[Parasitic] = &[
+    Parasitic { of: "C", prefix: "R", name: "esr", connection: Connection::Series, value: Some((0.1, "Ω")) },
+    Parasitic { of: "L", prefix: "R", name: "dcr", connection: Connection::Series, value: Some((1.0, "Ω")) },
+];
+
+/// Returns the document with the parasitics of `table` added to its elements.
+///
+/// Elements having several entries get all of them, in the order of the table. Entries whose
+/// prefix names no kind of element are ignored.
+pub fn expand(document: &Document, table: &[Parasitic]) -> owned::Document {
+    match document.to_owned() {
+        owned::Document::Circuit(circuit) => owned::Document::Circuit(expand_circuit(circuit, table)),
+        owned::Document::Twoport(twoport) => owned::Document::Twoport(owned::Twoport {
+            links: twoport.links.into_iter().map(|link| match link {
+                owned::TwoportLink::Series(circuit) => owned::TwoportLink::Series(expand_circuit(circuit, table)),
+                owned::TwoportLink::Shunt(circuit) => owned::TwoportLink::Shunt(expand_circuit(circuit, table)),
+                link => link,
+            }).collect(),
+        }),
+    }
+}
+
+fn expand_circuit(circuit: owned::SubCircuit, table: &[Parasitic]) -> owned::SubCircuit {
+    match circuit {
+        owned::SubCircuit::Element(element, value) => {
+            let own_prefix = element.kind.prefix;
+            let id = element.id.clone();
+            let mut circuit = owned::SubCircuit::Element(element, value);
+            for parasitic in table.iter().filter(|parasitic| parasitic.of == own_prefix) {
+                let Some(kind) = element_kinds().find(|kind| kind.prefix == parasitic.prefix && kind.has_id && kind.marker.is_empty() && kind.suffix.is_empty()) else {
+                    continue;
+                };
+                let element = owned::Element { kind, id: format!("{}{id}", parasitic.name), reference: String::new() };
+                let value = parasitic.value.map(|(number, unit)| owned::Value { number, unit: unit.to_string() });
+                let parasitic_circuit = owned::SubCircuit::Element(element, value);
+                circuit = owned::SubCircuit::Group(Box::new(match parasitic.connection {
+                    Connection::Series => owned::SubCircuitGroup::Series(circuit, parasitic_circuit),
+                    Connection::Parallel => owned::SubCircuitGroup::Parallel(circuit, parasitic_circuit),
+                }));
+            }
+            circuit
+        }
+        owned::SubCircuit::Group(group) => owned::SubCircuit::Group(Box::new(match *group {
+            owned::SubCircuitGroup::Single(circuit) => owned::SubCircuitGroup::Single(expand_circuit(circuit, table)),
+            owned::SubCircuitGroup::Series(a, b) => owned::SubCircuitGroup::Series(expand_circuit(a, table), expand_circuit(b, table)),
+            owned::SubCircuitGroup::Parallel(a, b) => owned::SubCircuitGroup::Parallel(expand_circuit(a, table), expand_circuit(b, table)),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_expand() {
+        let document = circuit::document("|V1=T1-(L1=1m||C2)|Cout").unwrap();
+        assert_eq!(expand(&document, STANDARD).borrow().to_string(), "|V1=T1-((L1=1m+Rdcr1=1Ω)||(C2+Resr2=100mΩ))|(Cout+Resrout=100mΩ)");
+        let leakage = [
+            Parasitic { of: "C", prefix: "R", name: "leak", connection: Connection::Parallel, value: None },
+            Parasitic { of: "C", prefix: "L", name: "esl", connection: Connection::Series, value: Some((1e-9, "H")) },
+            Parasitic { of: "C", prefix: "Q", name: "x", connection: Connection::Series, value: None },
+        ];
+        assert_eq!(expand(&circuit::document("(R1+C1)").unwrap(), &leakage).borrow().to_string(), "(R1+C1||Rleak1+Lesl1=1nH)");
+    }
+}
@@ -0,0 +1,438 @@
+//! The whole pipeline from source to SVG in a single call, for web services and build tools.
+//!
+//! ```
+//! use circmark_parse::render::{render_svg, RenderOptions};
+//! let svg = render_svg("|V1-R1|C1", &RenderOptions::default()).unwrap();
+//! assert!(svg.starts_with("<svg"));
+//! ```
+
+use crate::{
+    analysis::{self, response::{log_frequencies, Response}},
+    circuit::{self, Document},
+    document::{self, Section, SectionError, SectionKind},
+    draw::{self, svg::{EmptyDrawing, Font, SvgDrawer, Theme}, symbols::{Geometry, SymbolStandard}, Draw, Drawer, DrawerCapabilities},
+    format,
+    layout::{self, Layout},
+    legend::Legend,
+    parasitics,
+    simplify,
+    smith::SmithChart,
+    sweep::{self, Axis, Matrix, Metric},
+};
+
+/// Points per decade of the frequency response plots
+const RESPONSE_POINTS_PER_DECADE: usize = 50;
+
+/// Smallest size in pixels that labels of thumbnails are drawn at, below which they couldn't be read
+const THUMBNAIL_LABEL_SIZE: f64 = 6.0;
+
+/// Smallest size in pixels that values of thumbnails are drawn at, as they are easily mistaken at small sizes
+const THUMBNAIL_VALUE_SIZE: f64 = 9.0;
+
+/// How [`render_svg`] draws the source, the defaults giving a plain drawing
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RenderOptions {
+    /// Draw mirrored, see [`draw::Context::mirror`]
+    pub mirror: bool,
+    /// Shorten labels and values that don't fit, see [`layout::Shortening::ALL`]
+    pub shorten: bool,
+    /// Colors, strokes and text, [`Theme::LIGHT`] by default. Its locale applies to the values of elements as well
+    pub theme: Theme,
+    /// Take color and text size from the embedding page, see [`SvgDrawer::inherit_page_style`]
+    pub inherit_style: bool,
+    /// Make elements focusable, announcing them if `Some(true)`, see [`SvgDrawer::keyboard_navigation`]
+    pub keyboard_navigation: Option<bool>,
+    pub font: Option<Font>,
+    /// Names of the kinds of elements for a legend, e.g. [`crate::legend::ENGLISH`]
+    pub legend: Option<&'static [(&'static str, &'static str)]>,
+    /// Only draw the sections of this variant (and those without one)
+    pub variant: Option<String>,
+    /// Add the parasitics of [`parasitics::STANDARD`]
+    pub parasitics: bool,
+    /// Annotate reactive elements with their impedance at this frequency, see [`draw::Context::impedance_at`]
+    pub impedance_at: Option<f64>,
+    /// Draw resistors as rectangles (IEC, the default) or zigzag lines (ANSI)
+    pub symbols: SymbolStandard,
+    /// Plot the frequency response of twoports between these frequencies, see [`SvgDrawer::response_plot`]
+    pub response: Option<(f64, f64)>,
+    /// Sweep one or two elements of twoports, drawing the metric as a heatmap, see [`sweep::sweep`]
+    pub sweep: Option<(Metric, Axis, Option<Axis>)>,
+    /// Scale the document's width and height, see [`SvgDrawer::scale`]
+    pub scale: Option<f64>,
+    /// The document's `<title>`, instead of the titles of the sections
+    pub title: Option<String>,
+    /// The document's `<desc>`, instead of descriptions of the titled sections
+    pub description: Option<String>,
+    /// Attributes of the document's root element, e.g. `preserveAspectRatio`, see [`SvgDrawer::attribute`]
+    pub attributes: Vec<(String, String)>,
+    /// Render a square thumbnail this many pixels wide instead, see [`render_thumbnail`]
+    pub thumbnail: Option<u32>,
+    /// Directory of font files that PNG output (with the `raster` feature) loads, overriding the embedded font
+    pub font_dir: Option<std::path::PathBuf>,
+}
+
+impl RenderOptions {
+    /// The context to draw in with these options
+    pub fn context(&self) -> draw::Context {
+        let mut ctx = draw::Context::default().locale(self.theme.locale);
+        if self.mirror {
+            ctx = ctx.mirror();
+        }
+        if self.shorten {
+            ctx = ctx.shortening(layout::Shortening::ALL);
+        }
+        if let Some(frequency) = self.impedance_at {
+            ctx = ctx.impedance_at(frequency);
+        }
+        ctx
+    }
+
+    /// The geometry of the symbols with these options
+    pub fn geometry(&self) -> Geometry {
+        Geometry { standard: self.symbols, ..Geometry::STANDARD }
+    }
+
+    fn svg_drawer(&self) -> SvgDrawer {
+        let mut drawer = SvgDrawer::new().theme(self.theme.clone()).geometry(self.geometry());
+        if self.inherit_style {
+            drawer = drawer.inherit_page_style();
+        }
+        if let Some(announce) = self.keyboard_navigation {
+            drawer = drawer.keyboard_navigation(announce);
+        }
+        if let Some(font) = &self.font {
+            drawer = drawer.font(font.clone());
+        }
+        if let Some(scale) = self.scale {
+            drawer = drawer.scale(scale);
+        }
+        if let Some(title) = &self.title {
+            drawer = drawer.document_title(title.clone());
+        }
+        if let Some(description) = &self.description {
+            drawer = drawer.description(description.clone());
+        }
+        for (name, value) in &self.attributes {
+            drawer = drawer.attribute(name, value);
+        }
+        drawer
+    }
+}
+
+/// Parses the source into its sections (see [`document::parse`]), keeping those of the selected variant
+/// and adding parasitics if requested
+pub fn sections(source: &str, options: &RenderOptions) -> Result<Vec<Section>, SectionError> {
+    let mut sections = document::parse(source.trim_end())?;
+    if let Some(variant) = &options.variant {
+        sections.retain(|section| section.in_variant(variant));
+    }
+    if options.parasitics {
+        for section in &mut sections {
+            section.document = parasitics::expand(&section.document.borrow(), parasitics::STANDARD);
+        }
+    }
+    Ok(sections)
+}
+
+/// Draws sections the way [`render_svg`] does: titled ones below each other with their titles,
+/// several twoports stacked with their links aligned, anything else below each other
+pub fn draw_sections<D: Drawer>(sections: &[Section], ctx: draw::Context, drawer: &mut D) {
+    let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+    if sections.iter().any(|section| section.title.is_some()) {
+        let titled: Vec<_> = sections.iter().map(|section| section.title.as_deref()).zip(&documents).collect();
+        draw::draw_titled(&titled, ctx, drawer);
+    } else if let [document] = &documents[..] {
+        document.draw(document.layout_size(), ctx, drawer);
+    } else if documents.iter().all(|document| matches!(document, Document::Twoport(_))) {
+        // multiple twoports are stacked for comparison
+        let twoports: Vec<_> = documents.into_iter().map(|document| match document {
+            Document::Twoport(twoport) => twoport,
+            Document::Circuit(_) => unreachable!(),
+        }).collect();
+        draw::draw_stacked(&twoports, ctx, drawer);
+    } else {
+        draw::draw_below(&documents, ctx, drawer);
+    }
+}
+
+/// The Smith charts of the `@smith` sections, leaving out those of twoports missing values
+pub fn smith_charts(sections: &[Section]) -> Vec<SmithChart> {
+    sections.iter().filter(|section| section.kind == SectionKind::Smith).filter_map(|section| {
+        let document = section.document.borrow();
+        let Document::Twoport(twoport) = &document else { unreachable!("smith sections are twoports") };
+        let frequency = section.frequency.expect("smith sections have a frequency");
+        SmithChart::new(twoport, &analysis::values(&document), frequency, section.z0.unwrap_or(50.0))
+    }).collect()
+}
+
+/// The frequency responses of the twoports of the sections over the range of [`RenderOptions::response`],
+/// leaving out those missing values
+pub fn responses(sections: &[Section], options: &RenderOptions) -> Vec<Response> {
+    let Some((start, stop)) = options.response else {
+        return vec![];
+    };
+    let freqs = log_frequencies(start, stop, RESPONSE_POINTS_PER_DECADE);
+    sections.iter().filter_map(|section| match &section.document.borrow() {
+        document @ Document::Twoport(twoport) => Response::sweep(twoport, &analysis::values(document), &freqs),
+        Document::Circuit(_) => None,
+    }).collect()
+}
+
+/// The sweeps of [`RenderOptions::sweep`] over the twoports of the sections
+pub fn sweeps(sections: &[Section], options: &RenderOptions) -> Vec<Matrix> {
+    let Some((metric, rows, columns)) = &options.sweep else {
+        return vec![];
+    };
+    sections.iter().filter_map(|section| match &section.document.borrow() {
+        document @ Document::Twoport(twoport) => Some(sweep::sweep(twoport, &analysis::values(document), *metric, rows.clone(), columns.clone())),
+        Document::Circuit(_) => None,
+    }).collect()
+}
+
+/// Error returned by [`render_svg`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// The source doesn't parse into sections
+    Section(SectionError),
+    /// There is nothing to draw, e.g. as no section is of the selected variant
+    Empty(EmptyDrawing),
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RenderError::Section(error) => write!(f, "{error}"),
+            RenderError::Empty(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<SectionError> for RenderError {
+    fn from(error: SectionError) -> Self {
+        RenderError::Section(error)
+    }
+}
+
+impl From<EmptyDrawing> for RenderError {
+    fn from(error: EmptyDrawing) -> Self {
+        RenderError::Empty(error)
+    }
+}
+
+/// Renders sections to an SVG document, with a legend and a `<desc>` of the titled sections where appropriate.
+/// The Smith charts of `@smith` sections, the requested frequency responses and sweeps are drawn below everything else.
+/// Fails if there is nothing to draw
+pub fn render_sections(sections: &[Section], options: &RenderOptions) -> Result<String, EmptyDrawing> {
+    if let Some(size) = options.thumbnail {
+        return render_thumbnail(sections, size, options);
+    }
+    let mut drawer = options.svg_drawer();
+    // titled sections are described in the SVG's <desc>
+    let descriptions: Vec<_> = sections.iter()
+        .filter_map(|section| Some(format!("{}: {}", section.title.as_ref()?, crate::describe::describe(&section.document.borrow()))))
+        .collect();
+    if !descriptions.is_empty() && options.description.is_none() {
+        drawer = drawer.description(descriptions.join("\n"));
+    }
+    draw_sections(sections, options.context(), &mut drawer);
+    for chart in smith_charts(sections) {
+        drawer.smith_chart(&chart);
+    }
+    for response in responses(sections, options) {
+        drawer.response_plot(&response);
+    }
+    for matrix in sweeps(sections, options) {
+        drawer.heatmap(&matrix);
+    }
+    if let Some(names) = options.legend {
+        let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+        let legend = Legend::from_elements(documents.iter().flat_map(|document| document.elements()), names);
+        if !legend.entries.is_empty() {
+            drawer.legend(&legend);
+        }
+    }
+    Ok(drawer.finalize()?.to_string())
+}
+
+/// Renders sections to a square SVG document `size` pixels wide and high, e.g. for the gallery of a circuit library.
+///
+/// The sections are drawn without their titles, centered and scaled to fit. Labels are shortened, values left out
+/// at sizes they'd be hard to read at, and so are labels at the smallest sizes, e.g. for favicons. Charts and
+/// legends are left out. Fails if there is nothing to draw
+///
+/// ```
+/// use circmark_parse::render::{render_thumbnail, sections, RenderOptions};
+/// let options = RenderOptions::default();
+/// let svg = render_thumbnail(&sections("|V1-R1=1k|C1=1u", &options).unwrap(), 64, &options).unwrap();
+/// assert!(svg.contains("height=\"64\"") && svg.contains("width=\"64\""));
+/// assert!(!svg.contains("1k"));
+/// ```
+pub fn render_thumbnail(sections: &[Section], size: u32, options: &RenderOptions) -> Result<String, EmptyDrawing> {
+    let sections: Vec<_> = sections.iter().map(|section| Section { title: None, ..section.clone() }).collect();
+    let ctx = options.context().shortening(layout::Shortening::ALL);
+    // the size of the drawing with all text tells the size text would be scaled to, at 16 units per line
+    // user attributes are set last, so that they replace the thumbnail's size
+    let mut drawer = RenderOptions { attributes: vec![], ..options.clone() }.svg_drawer();
+    draw_sections(&sections, ctx, &mut drawer);
+    let (width, height) = drawer.size();
+    drawer.finalize()?;
+    let text_size = 16.0 * size as f64 / width.max(height) as f64;
+    let capabilities = DrawerCapabilities {
+        text: text_size >= THUMBNAIL_LABEL_SIZE,
+        values: text_size >= THUMBNAIL_VALUE_SIZE,
+        ..DrawerCapabilities::ALL
+    };
+    let mut drawer = drawer.capabilities(capabilities);
+    draw_sections(&sections, ctx, &mut drawer);
+    let (width, height) = drawer.size();
+    let side = width.max(height);
+    let mut drawer = drawer
+        .attribute("viewBox", format!("{} {} {side} {side}", (width - side) as f64 / 2.0, (height - side) as f64 / 2.0))
+        .attribute("width", size.to_string())
+        .attribute("height", size.to_string());
+    for (name, value) in &options.attributes {
+        drawer = drawer.attribute(name, value);
+    }
+    Ok(drawer.finalize()?.to_string())
+}
+
+/// Parses the source and renders it to an SVG document
+pub fn render_svg(source: &str, options: &RenderOptions) -> Result<String, RenderError> {
+    Ok(render_sections(&sections(source, options)?, options)?)
+}
+
+/// Renders how a document reduces to equivalent elements (see [`simplify::reduction_steps`]): the document
+/// followed by the document after each step, titled with the element the step added, e.g. `Req1 = R2 || R3 = 500Ω`
+pub fn render_steps(document: &circuit::Document, options: &RenderOptions) -> String {
+    let steps = simplify::reduction_steps(document, &analysis::Values::new());
+    let titles: Vec<String> = steps.iter().map(|(_, equivalent)| match equivalent.value {
+        Some(value) => format!("{} = {} = {}", equivalent.label, equivalent.expr, format::si(value, equivalent.unit(), &options.theme.locale)),
+        None => format!("{} = {}", equivalent.label, equivalent.expr),
+    }).collect();
+    let documents: Vec<_> = steps.iter().map(|(document, _)| document.borrow()).collect();
+    let mut titled = vec![(None, document)];
+    titled.extend(titles.iter().map(|title| Some(title.as_str())).zip(&documents));
+    let mut drawer = options.svg_drawer();
+    draw::draw_titled(&titled, options.context(), &mut drawer);
+    drawer.finalize().expect("documents have elements").to_string()
+}
+
+/// Renders a parsed document to an SVG document. The options selecting sections don't apply
+pub fn render_document(document: &circuit::Document, options: &RenderOptions) -> String {
+    let kind = match document {
+        Document::Twoport(_) => document::SectionKind::Twoport,
+        Document::Circuit(_) => document::SectionKind::Circuit,
+    };
+    let document = if options.parasitics { parasitics::expand(document, parasitics::STANDARD) } else { document.to_owned() };
+    let section = Section { kind, name: None, title: None, variant: None, frequency: None, z0: None, document, span: 0..0, content: 0..0 };
+    render_sections(&[section], options).expect("documents have elements")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legend;
+
+    #[test]
+    fn test_render_svg() {
+        let options = RenderOptions { legend: Some(legend::ENGLISH), variant: Some("real".to_string()), ..RenderOptions::default() };
+        let svg = render_svg("@twoport a\n|V1-R1\n@twoport[variant=ideal] extends a |C1\n@twoport[variant=real] extends a |(C1+R2)\n", &options).unwrap();
+        assert!(svg.contains("id=\"legend\""));
+        assert_eq!(svg.matches("inkscape:label=\"C1\"").count(), 2);
+        assert!(svg.contains("inkscape:label=\"R2\""));
+        assert!(matches!(render_svg("|V1-(R1", &options), Err(RenderError::Section(SectionError::Parse(_)))));
+        assert_eq!(render_svg("@twoport[variant=ideal]\n|V1-R1", &options), Err(RenderError::Empty(EmptyDrawing)));
+    }
+
+    #[test]
+    fn test_render_metadata() {
+        let options = RenderOptions {
+            title: Some("Filter".to_string()),
+            description: Some("A low pass".to_string()),
+            attributes: vec![
+                ("preserveAspectRatio".to_string(), "none".to_string()),
+                ("data-note".to_string(), "\"R&D\"".to_string()),
+                ("on click".to_string(), "alert()".to_string()),
+            ],
+            ..RenderOptions::default()
+        };
+        let svg = render_svg("@twoport \"Low pass\"\n|V1-R1|C1\n", &options).unwrap();
+        assert!(svg.contains("<title>\nFilter\n</title>"));
+        assert!(svg.contains("<desc>\nA low pass\n</desc>"));
+        assert!(svg.contains("preserveAspectRatio=\"none\""));
+        assert!(svg.contains("data-note=\"&quot;R&amp;D&quot;\""));
+        assert!(!svg.contains("alert"));
+    }
+
+    #[test]
+    fn test_render_thumbnail() {
+        let thumbnail = |size| {
+            let options = RenderOptions { thumbnail: Some(size), ..RenderOptions::default() };
+            render_svg("@twoport \"Low pass\"\n|V1-R1=1k|C1=1u\n", &options).unwrap()
+        };
+        // the drawing is 389 by 290 units, centered in a square
+        let svg = thumbnail(400);
+        assert!(svg.contains("viewBox=\"0 -49.5 389 389\""));
+        assert!(svg.contains(">\n1k\n</text>") && !svg.contains("Low pass"));
+        let svg = thumbnail(150);
+        assert!(svg.contains(">\nR1\n</text>") && !svg.contains(">\n1k\n</text>"));
+        assert!(!thumbnail(64).contains("<text"));
+        let options = RenderOptions { thumbnail: Some(64), attributes: vec![("viewBox".to_string(), "0 0 10 10".to_string())], ..RenderOptions::default() };
+        assert!(render_svg("|V1-R1", &options).unwrap().contains("viewBox=\"0 0 10 10\""));
+    }
+
+    #[test]
+    fn test_render_document() {
+        let document = circuit::document("|V1-L1=1m|C1").unwrap();
+        let options = RenderOptions { parasitics: true, impedance_at: Some(1e3), ..RenderOptions::default() };
+        let svg = render_document(&document, &options);
+        assert!(svg.contains("inkscape:label=\"Rdcr1\""));
+        assert!(svg.contains("j6.28Ω @ 1kHz"));
+    }
+
+    #[test]
+    fn test_render_steps() {
+        let document = circuit::document("(R1=1k+R2=1k||R3=1k)").unwrap();
+        let svg = render_steps(&document, &RenderOptions::default());
+        assert!(svg.contains("Req1 = R2 || R3 = 500Ω"));
+        assert!(svg.contains("Req2 = R1 + Req1 = 1.5kΩ"));
+        assert!(svg.contains("inkscape:label=\"Req2\""));
+    }
+
+    #[test]
+    fn test_render_sweep() {
+        let options = RenderOptions { sweep: Some((Metric::Cutoff, Axis::logarithmic("R1", 1e3, 10e3, 2), None)), ..RenderOptions::default() };
+        let svg = render_svg("|V1-R1|C1=1u", &options).unwrap();
+        assert!(svg.contains("id=\"heatmap\""));
+        assert!(svg.contains("\n159Hz\n"));
+        assert!(svg.contains("\n15.9Hz\n"));
+    }
+
+    #[test]
+    fn test_render_symbols() {
+        let options = RenderOptions { symbols: SymbolStandard::Ansi, ..RenderOptions::default() };
+        let svg = render_svg("|V1-R1|C1", &options).unwrap();
+        assert!(svg.contains("stroke-linejoin=\"round\""));
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn test_render_smith() {
+        let svg = render_svg("@smith[f=100MHz,z0=50]\n|V1-L1=100n|C1=10p|Rload=100\n", &RenderOptions::default()).unwrap();
+        assert!(svg.contains("id=\"smith-chart\""));
+        assert!(svg.contains("Z0 = 50Ω, f = 100MHz"));
+        let svg = render_svg("@smith[f=100MHz]\n|V1-L1|C1=10p|Rload=100\n", &RenderOptions::default()).unwrap();
+        assert!(!svg.contains("id=\"smith-chart\""));
+    }
+
+    #[test]
+    fn test_render_response() {
+        let options = RenderOptions { response: Some((10.0, 100e3)), ..RenderOptions::default() };
+        let svg = render_svg("|V1-R1=1k|C1=1u", &options).unwrap();
+        assert!(svg.contains("id=\"response-plot\""));
+        assert!(svg.contains("\n100kHz\n"));
+        assert!(!render_svg("|V1-R1|C1", &options).unwrap().contains("id=\"response-plot\""));
+    }
+}
@@ -0,0 +1,125 @@
+//! Documents building on earlier ones, e.g. the steps of a tutorial.
+//!
+//! A twoport starting with `@twoport extends NAME` continues the twoport remembered as `NAME`:
+//! `@twoport extends fig1 -R3` is `fig1` followed by a series resistor.
+//!
+//! Extending only appends links. The links of the extended twoport are kept as they are, values included, so a step
+//! changing them (e.g. `R1=2k` instead of `R1=1k`) writes the twoport out again, or puts both values in variants of
+//! a section (see [`crate::document`]).
+
+use std::collections::HashMap;
+use crate::{
+    circuit::{self, Document},
+    document::{self, Header},
+};
+
+/// Error returned when a document can't be resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// No document of this name has been added
+    Unknown(String),
+    /// The named document, or the links extending it, are not a twoport
+    NotATwoport(String),
+    /// The links extending the document don't parse
+    Parse(crate::ParseError),
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResolveError::Unknown(name) => write!(f, "no document named {name:?} to extend"),
+            ResolveError::NotATwoport(name) => write!(f, "only twoports can be extended, {name:?} is not one"),
+            ResolveError::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Remembers named documents, to resolve the documents extending them.
+///
+/// ```
+/// use circmark_parse::{circuit, resolve::Resolver};
+/// let mut resolver = Resolver::new();
+/// let fig1 = resolver.resolve("|V1-R1").unwrap();
+/// resolver.add("fig1", fig1);
+/// let fig2 = resolver.resolve("@twoport extends fig1 |C1").unwrap();
+/// assert_eq!(circuit::document(&fig2).unwrap(), circuit::document("|V1-R1|C1").unwrap());
+/// ```
+#[derive(Debug, Default)]
+pub struct Resolver {
+    /// Resolved sources by name
+    documents: HashMap<String, String>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the resolved source of a document under `name`, replacing an earlier one of the same name
+    pub fn add(&mut self, name: &str, resolved: String) {
+        self.documents.insert(name.to_string(), resolved);
+    }
+
+    /// Whether a document has been added under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.documents.contains_key(name)
+    }
+
+    /// Returns the source of the concrete document, with the one it extends written out.
+    ///
+    /// The source may start with a section header like those of [`crate::document`], e.g. `@twoport "Step 2" extends
+    /// fig1`. Sources without a header extending another twoport are returned as they are.
+    pub fn resolve(&self, source: &str) -> Result<String, ResolveError> {
+        let text = source.trim_start();
+        match document::header(text, 1) {
+            Ok(Header { base: Some(base), content_start, .. }) => self.extend(base, &text[content_start..]),
+            _ => Ok(source.to_string()),
+        }
+    }
+
+    /// Returns the source of the twoport named `name` continued by `links`, which may be empty
+    pub fn extend(&self, name: &str, links: &str) -> Result<String, ResolveError> {
+        let base = self.documents.get(name).ok_or_else(|| ResolveError::Unknown(name.to_string()))?;
+        if !matches!(circuit::document(base), Ok(Document::Twoport(_))) {
+            return Err(ResolveError::NotATwoport(name.to_string()));
+        }
+        if links.trim().is_empty() {
+            return Ok(base.clone());
+        }
+        match circuit::document(links).map_err(ResolveError::Parse)? {
+            Document::Twoport(_) => Ok(format!("{base}\n{links}")),
+            Document::Circuit(_) => Err(ResolveError::NotATwoport(links.trim().to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extends() {
+        let mut resolver = Resolver::new();
+        resolver.add("fig1", resolver.resolve("|V1-R1=1k # source").unwrap());
+        resolver.add("fig2", resolver.resolve("@twoport extends fig1\n|C1").unwrap());
+        let fig3 = resolver.resolve("@twoport extends fig2 -R3 .vout").unwrap();
+        assert_eq!(circuit::document(&fig3).unwrap(), circuit::document("|V1-R1=1k|C1-R3.vout").unwrap());
+        assert_eq!(resolver.resolve("@twoport extends fig1").unwrap(), "|V1-R1=1k # source");
+        assert_eq!(resolver.resolve("@twoport  extends\tfig1\t|C1").unwrap(), "|V1-R1=1k # source\n\t|C1");
+        assert_eq!(resolver.resolve("@twoport[variant=ideal] \"Step 2\" extends fig1 |C1").unwrap(), "|V1-R1=1k # source\n |C1");
+        assert_eq!(resolver.resolve("(R1+R2)").unwrap(), "(R1+R2)");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut resolver = Resolver::new();
+        resolver.add("fig1", "|V1-R1".to_string());
+        resolver.add("divider", "(R1+R2)".to_string());
+        assert_eq!(resolver.resolve("@twoport extends fig2 -R2"), Err(ResolveError::Unknown("fig2".to_string())));
+        assert_eq!(resolver.resolve("@twoport extends divider -R3"), Err(ResolveError::NotATwoport("divider".to_string())));
+        assert_eq!(resolver.resolve("@twoport extends fig1 R3"), Err(ResolveError::NotATwoport("R3".to_string())));
+        assert!(matches!(resolver.resolve("@twoport extends fig1 -(R3"), Err(ResolveError::Parse(_))));
+    }
+}
@@ -0,0 +1,366 @@
+//! Searching for elements within a document, and addressing nodes by their path.
+//!
+//! Paths are written as steps separated by `/`, for example `links[1]/series[0]/parallel[1]`
+//! means: the top side of the parallel group which is on the left side of a series group
+//! in the second link of a twoport. The empty path addresses the document's root.
+//!
+//! Sources with several sections (see [`crate::document`]) are addressed as a whole by paths starting with
+//! the section, e.g. `sections[2]/links[1]`, which [`find`], [`get`] and [`get_mut`] resolve.
+
+use crate::circuit::{Document, Element, SubCircuit, SubCircuitGroup};
+
+/// One step from a node of the document to one of its children
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The n-th section of a source, only ever the first step
+    Section(usize),
+    /// The n-th link of a twoport
+    Link(usize),
+    /// The left (0) or right (1) side of a series group
+    Series(usize),
+    /// The top (0) or bottom (1) side of a parallel group
+    Parallel(usize),
+}
+
+/// Location of a node within a document, as the steps leading there from the root
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NodePath(pub Vec<Step>);
+
+impl NodePath {
+    fn join(&self, step: Step) -> Self {
+        let mut steps = self.0.clone();
+        steps.push(step);
+        Self(steps)
+    }
+
+    /// The section the path starts with, and the path within it, or `None` if it doesn't start with one
+    ///
+    /// ```
+    /// use circmark_parse::search::NodePath;
+    /// let path: NodePath = "sections[2]/links[1]".parse().unwrap();
+    /// assert_eq!(path.section(), Some((2, "links[1]".parse().unwrap())));
+    /// ```
+    pub fn section(&self) -> Option<(usize, NodePath)> {
+        match self.0.split_first()? {
+            (Step::Section(i), steps) => Some((*i, NodePath(steps.to_vec()))),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Step::Section(i) => write!(f, "sections[{i}]"),
+            Step::Link(i) => write!(f, "links[{i}]"),
+            Step::Series(i) => write!(f, "series[{i}]"),
+            Step::Parallel(i) => write!(f, "parallel[{i}]"),
+        }
+    }
+}
+
+impl std::fmt::Display for NodePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing an invalid path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPath(pub String);
+
+impl std::fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid path step {:?}, expected sections[n] (first), links[n], series[0|1] or parallel[0|1]", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPath {}
+
+impl std::str::FromStr for Step {
+    type Err = InvalidPath;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidPath(s.to_string());
+        let (name, index) = s.strip_suffix(']').and_then(|s| s.split_once('[')).ok_or_else(invalid)?;
+        let index: usize = index.parse().map_err(|_| invalid())?;
+        match name {
+            "sections" => Ok(Step::Section(index)),
+            "links" => Ok(Step::Link(index)),
+            "series" if index < 2 => Ok(Step::Series(index)),
+            "parallel" if index < 2 => Ok(Step::Parallel(index)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl std::str::FromStr for NodePath {
+    type Err = InvalidPath;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(NodePath::default());
+        }
+        let steps: Vec<Step> = s.split('/').map(str::parse).collect::<Result<_, _>>()?;
+        match steps.iter().skip(1).position(|step| matches!(step, Step::Section(_))) {
+            Some(i) => Err(InvalidPath(s.split('/').nth(i + 1).unwrap_or_default().to_string())),
+            None => Ok(NodePath(steps)),
+        }
+    }
+}
+
+/// An element found by a search, along with its location
+#[derive(Debug, PartialEq)]
+pub struct Found<'d, 'a> {
+    pub path: NodePath,
+    pub element: &'d Element<'a>,
+}
+
+impl<'a> Document<'a> {
+    /// Finds all elements satisfying the predicate, in document order
+    pub fn find<F: Fn(&Element) -> bool>(&self, predicate: F) -> Vec<Found<'_, 'a>> {
+        let mut found = vec![];
+        match self {
+            Document::Circuit(circuit) => find_in(circuit, NodePath::default(), &predicate, &mut found),
+            Document::Twoport(twoport) => {
+                for (i, link) in twoport.links.iter().enumerate() {
+                    if let Some(circuit) = link.circuit() {
+                        find_in(circuit, NodePath(vec![Step::Link(i)]), &predicate, &mut found);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Returns the sub-circuit at the given path.
+    ///
+    /// Returns `None` if there is no such node, or if the path leads to the twoport itself.
+    pub fn get(&self, path: &NodePath) -> Option<&SubCircuit<'a>> {
+        let (root, steps) = match (self, path.0.split_first()) {
+            (Document::Circuit(circuit), _) => (circuit, &path.0[..]),
+            (Document::Twoport(twoport), Some((Step::Link(i), steps))) => {
+                (twoport.links.get(*i)?.circuit()?, steps)
+            }
+            (Document::Twoport(_), _) => return None,
+        };
+        steps.iter().try_fold(root, |circuit, step| child(circuit, *step))
+    }
+
+    /// Returns the sub-circuit at the given path for modification, e.g. to replace it.
+    ///
+    /// ```
+    /// use circmark_parse::circuit::{document, Element, SubCircuit};
+    /// let mut doc = document("|V1-(R1+R2)").unwrap();
+    /// *doc.get_mut(&"links[1]/series[1]".parse().unwrap()).unwrap() = SubCircuit::Element(Element::C("1"), None);
+    /// assert_eq!(doc, document("|V1-(R1+C1)").unwrap());
+    /// ```
+    pub fn get_mut(&mut self, path: &NodePath) -> Option<&mut SubCircuit<'a>> {
+        let (root, steps) = match (self, path.0.split_first()) {
+            (Document::Circuit(circuit), _) => (circuit, &path.0[..]),
+            (Document::Twoport(twoport), Some((Step::Link(i), steps))) => {
+                (twoport.links.get_mut(*i)?.circuit_mut()?, steps)
+            }
+            (Document::Twoport(_), _) => return None,
+        };
+        steps.iter().try_fold(root, |circuit, step| child_mut(circuit, *step))
+    }
+
+    /// Finds all elements whose label matches the glob pattern, e.g. `R*`
+    pub fn find_label(&self, pattern: &str) -> Vec<Found<'_, 'a>> {
+        self.find(|element| glob(pattern, &element.label()))
+    }
+}
+
+/// Finds all elements of the documents (e.g. the sections of a source) satisfying the predicate, in order, their
+/// paths starting with `sections[n]`
+pub fn find<'d, 'a, F: Fn(&Element) -> bool>(documents: &'d [Document<'a>], predicate: F) -> Vec<Found<'d, 'a>> {
+    let mut found = vec![];
+    for (i, document) in documents.iter().enumerate() {
+        found.extend(document.find(&predicate).into_iter().map(|Found { path, element }| Found {
+            path: NodePath([Step::Section(i)].into_iter().chain(path.0).collect()),
+            element,
+        }));
+    }
+    found
+}
+
+/// Returns the sub-circuit of the documents at a path starting with `sections[n]`, see [`Document::get`]
+///
+/// ```
+/// use circmark_parse::{circuit::{Element, SubCircuit}, document, search};
+/// let sections = document::parse("@circuit\n(R1+C1)\n@twoport\n|V1-(R2||C2)").unwrap();
+/// let documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+/// let path = "sections[1]/links[1]/parallel[1]".parse().unwrap();
+/// assert_eq!(search::get(&documents, &path), Some(&SubCircuit::Element(Element::C("2"), None)));
+/// ```
+pub fn get<'d, 'a>(documents: &'d [Document<'a>], path: &NodePath) -> Option<&'d SubCircuit<'a>> {
+    let (i, path) = path.section()?;
+    documents.get(i)?.get(&path)
+}
+
+/// Returns the sub-circuit of the documents at a path starting with `sections[n]` for modification, see
+/// [`Document::get_mut`]
+pub fn get_mut<'d, 'a>(documents: &'d mut [Document<'a>], path: &NodePath) -> Option<&'d mut SubCircuit<'a>> {
+    let (i, path) = path.section()?;
+    documents.get_mut(i)?.get_mut(&path)
+}
+
+fn child<'d, 'a>(circuit: &'d SubCircuit<'a>, step: Step) -> Option<&'d SubCircuit<'a>> {
+    let SubCircuit::Group(group) = circuit else {
+        return None;
+    };
+    match (group.as_ref(), step) {
+        (SubCircuitGroup::Single(circuit), _) => child(circuit, step),
+        (SubCircuitGroup::Series(a, _), Step::Series(0)) | (SubCircuitGroup::Parallel(a, _), Step::Parallel(0)) => Some(a),
+        (SubCircuitGroup::Series(_, b), Step::Series(1)) | (SubCircuitGroup::Parallel(_, b), Step::Parallel(1)) => Some(b),
+        _ => None,
+    }
+}
+
+fn child_mut<'d, 'a>(circuit: &'d mut SubCircuit<'a>, step: Step) -> Option<&'d mut SubCircuit<'a>> {
+    let SubCircuit::Group(group) = circuit else {
+        return None;
+    };
+    match (group.as_mut(), step) {
+        (SubCircuitGroup::Single(circuit), _) => child_mut(circuit, step),
+        (SubCircuitGroup::Series(a, _), Step::Series(0)) | (SubCircuitGroup::Parallel(a, _), Step::Parallel(0)) => Some(a),
+        (SubCircuitGroup::Series(_, b), Step::Series(1)) | (SubCircuitGroup::Parallel(_, b), Step::Parallel(1)) => Some(b),
+        _ => None,
+    }
+}
+
+fn find_in<'d, 'a, F: Fn(&Element) -> bool>(circuit: &'d SubCircuit<'a>, path: NodePath, predicate: &F, found: &mut Vec<Found<'d, 'a>>) {
+    match circuit {
+        SubCircuit::Element(element, _) => {
+            if predicate(element) {
+                found.push(Found { path, element });
+            }
+        }
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => find_in(circuit, path, predicate, found),
+            SubCircuitGroup::Series(a, b) => {
+                find_in(a, path.join(Step::Series(0)), predicate, found);
+                find_in(b, path.join(Step::Series(1)), predicate, found);
+            }
+            SubCircuitGroup::Parallel(a, b) => {
+                find_in(a, path.join(Step::Parallel(0)), predicate, found);
+                find_in(b, path.join(Step::Parallel(1)), predicate, found);
+            }
+        },
+    }
+}
+
+/// Matches `text` against a glob pattern, where `*` matches any sequence of characters and `?` any single character
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // positions to resume from, for the last `*` seen
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("R*", "R1"));
+        assert!(glob("R*", "R"));
+        assert!(glob("*", ""));
+        assert!(glob("R?", "R1"));
+        assert!(!glob("R?", "R12"));
+        assert!(glob("*pu*", "Rpullup"));
+        assert!(glob("C*1", "C2201"));
+        assert!(!glob("C*1", "C12"));
+        assert!(!glob("R*", "C1"));
+    }
+
+    #[test]
+    fn test_find_label() {
+        let document = circuit::document("|V1-(R1+C1||Rpu)|R2").unwrap();
+        assert_eq!(document.find_label("R*"), vec![
+            Found { path: NodePath(vec![Step::Link(1), Step::Series(0)]), element: &Element::R("1") },
+            Found { path: NodePath(vec![Step::Link(1), Step::Series(1), Step::Parallel(1)]), element: &Element::R("pu") },
+            Found { path: NodePath(vec![Step::Link(2)]), element: &Element::R("2") },
+        ]);
+    }
+
+    #[test]
+    fn test_path_syntax() {
+        let path = NodePath(vec![Step::Link(12), Step::Series(1), Step::Parallel(0)]);
+        assert_eq!(path.to_string(), "links[12]/series[1]/parallel[0]");
+        assert_eq!("links[12]/series[1]/parallel[0]".parse(), Ok(path));
+        assert_eq!("".parse(), Ok(NodePath::default()));
+        assert_eq!("series[2]".parse::<NodePath>(), Err(InvalidPath("series[2]".to_string())));
+        assert_eq!("links[1]/".parse::<NodePath>(), Err(InvalidPath("".to_string())));
+        assert_eq!("links[x]".parse::<NodePath>(), Err(InvalidPath("links[x]".to_string())));
+        let path = NodePath(vec![Step::Section(0), Step::Link(2), Step::Parallel(1)]);
+        assert_eq!(path.to_string(), "sections[0]/links[2]/parallel[1]");
+        assert_eq!("sections[0]/links[2]/parallel[1]".parse(), Ok(path));
+        assert_eq!("links[1]/sections[0]".parse::<NodePath>(), Err(InvalidPath("sections[0]".to_string())));
+    }
+
+    #[test]
+    fn test_sections() {
+        let sections = crate::document::parse("@circuit\n(R1+C1)\n@twoport\n|V1-(R2||C2)").unwrap();
+        let mut documents: Vec<_> = sections.iter().map(|section| section.document.borrow()).collect();
+        let found = find(&documents, |element| matches!(element, Element::C(_)));
+        assert_eq!(found.iter().map(|found| found.path.to_string()).collect::<Vec<_>>(), ["sections[0]/series[1]", "sections[1]/links[1]/parallel[1]"]);
+        for found in find(&documents, |_| true) {
+            assert_eq!(get(&documents, &found.path), Some(&SubCircuit::Element(found.element.clone(), None)));
+        }
+        assert_eq!(get(&documents, &"sections[2]/links[0]".parse().unwrap()), None);
+        assert_eq!(get(&documents, &"links[1]".parse().unwrap()), None);
+        *get_mut(&mut documents, &"sections[0]/series[0]".parse().unwrap()).unwrap() = SubCircuit::Element(Element::L("1"), None);
+        assert_eq!(documents[0], circuit::document("(L1+C1)").unwrap());
+    }
+
+    #[test]
+    fn test_get() {
+        let document = circuit::document("|V1-(R1+C1||Rpu)|R2").unwrap();
+        for found in document.find(|_| true) {
+            assert_eq!(document.get(&found.path), Some(&SubCircuit::Element(found.element.clone(), None)));
+        }
+        assert_eq!(document.get(&"links[3]".parse().unwrap()), None);
+        assert_eq!(document.get(&"links[0]/series[0]".parse().unwrap()), None);
+        assert_eq!(document.get(&NodePath::default()), None);
+    }
+
+    #[test]
+    fn test_find() {
+        let document = circuit::document("(C1||L1+C3)").unwrap();
+        let found = document.find(|element| matches!(element, Element::C(_)));
+        assert_eq!(found.iter().map(|found| found.element.label()).collect::<Vec<_>>(), vec!["C1", "C3"]);
+        assert_eq!(found[1].path, NodePath(vec![Step::Series(1)]));
+    }
+}
@@ -0,0 +1,369 @@
+//! Reducing series and parallel groups of resistors, capacitors or inductors to a single equivalent element,
+//! e.g. for showing how a network of resistors collapses to one.
+//!
+//! Within a chain of elements in series (or in parallel), all resistors are combined into one equivalent
+//! resistor, placed where the first of them was, and likewise all capacitors and all inductors. Groups reduced
+//! to one element take part in the chain they are in, so `(R1+R2||R3)` becomes a single `Req1`. Equivalent
+//! elements are numbered in the order they appear, e.g. `Req1`, `Req2` and `Ceq1`.
+//!
+//! For showing the reduction one step at a time, [`SubCircuit::reduction_steps`] combines the elements of one
+//! chain per step, starting with the innermost groups, so that `(R1+R2||R3)` first becomes `(R1+Req1)` and then `Req2`.
+//!
+//! ```
+//! use circmark_parse::{analysis::Values, circuit};
+//! let circuit = circuit::sub_circuit::<nom::error::VerboseError<&str>>("(R1=1k+R2=2k||R3=2k+C1)").unwrap().1;
+//! let simplified = circuit.simplify(&Values::new());
+//! assert_eq!(simplified.circuit.borrow().to_string(), "(Req1=2kΩ+C1)");
+//! assert_eq!(simplified.equivalents[0].expr.to_string(), "R1 + (R2 || R3)");
+//! assert_eq!(simplified.equivalents[0].value, Some(2000.0));
+//! ```
+
+use crate::{
+    analysis::{self, Complex64, Expr, Values},
+    circuit::{owned, Document, Element, ElementKind, Impedance, SubCircuit, SubCircuitGroup},
+    format,
+};
+
+/// An element standing for a group of elements of the same kind
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equivalent {
+    /// The label of the element in the simplified circuit, e.g. `Req1`
+    pub label: String,
+    /// The value of the element in terms of the values of the elements it replaces, e.g. `R1 + R2`
+    pub expr: Expr,
+    /// The value of the element, `None` if a value it depends on is not known (or it was simplified symbolically)
+    pub value: Option<f64>,
+    /// The unit of the value, given by the kind of the elements
+    unit: &'static str,
+}
+
+impl Equivalent {
+    /// The unit of the value: `Ω`, `F` or `H`
+    pub fn unit(&self) -> &'static str {
+        self.unit
+    }
+}
+
+/// The result of simplifying a sub-circuit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simplified {
+    pub circuit: owned::SubCircuit,
+    /// The equivalent elements of the circuit, in the order they appear
+    pub equivalents: Vec<Equivalent>,
+}
+
+/// One step of a reduction, see [`SubCircuit::reduction_steps`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// The circuit after this step
+    pub circuit: owned::SubCircuit,
+    /// The element this step added, its expression being in terms of the elements of the step before
+    pub equivalent: Equivalent,
+}
+
+impl SubCircuit<'_> {
+    /// Reduces the groups of resistors, capacitors and inductors, computing the values of the equivalent elements.
+    ///
+    /// Values are looked up in `values`, falling back to the ones given in the circuit. Equivalent elements whose
+    /// value is known have it in the simplified circuit, with its unit.
+    pub fn simplify(&self, values: &Values) -> Simplified {
+        simplify(self, Some(&known_values(&Document::Circuit(self.clone()), values)))
+    }
+
+    /// Reduces the groups of resistors, capacitors and inductors like [`SubCircuit::simplify`], without computing values
+    pub fn simplify_symbolic(&self) -> Simplified {
+        simplify(self, None)
+    }
+
+    /// Reduces the circuit one step at a time, returning the circuit after each step, e.g. for drawing how it collapses.
+    ///
+    /// Each step combines the resistors, capacitors or inductors of one chain of elements in series (or in
+    /// parallel), groups within a chain being reduced before it. Values are computed like [`SubCircuit::simplify`] does.
+    ///
+    /// ```
+    /// use circmark_parse::{analysis::Values, circuit};
+    /// let circuit = circuit::sub_circuit::<nom::error::VerboseError<&str>>("(R1+R2||R3)").unwrap().1;
+    /// let steps = circuit.reduction_steps(&Values::new());
+    /// assert_eq!(steps.iter().map(|step| step.circuit.borrow().to_string()).collect::<Vec<_>>(), ["(R1+Req1)", "Req2"]);
+    /// assert_eq!(steps[1].equivalent.expr.to_string(), "R1 + Req1");
+    /// ```
+    pub fn reduction_steps(&self, values: &Values) -> Vec<Step> {
+        let mut known = known_values(&Document::Circuit(self.clone()), values);
+        let mut equivalents = vec![];
+        let circuits = steps(self.to_owned(), &mut known, &mut equivalents);
+        circuits.into_iter().zip(equivalents).map(|(circuit, equivalent)| Step { circuit, equivalent }).collect()
+    }
+}
+
+/// Reduces the sub-circuits of the document one step at a time like [`SubCircuit::reduction_steps`], returning the
+/// document after each step and the element it added. Twoports are reduced one link after another.
+pub fn reduction_steps(document: &Document, values: &Values) -> Vec<(owned::Document, Equivalent)> {
+    let mut known = known_values(document, values);
+    let mut equivalents = vec![];
+    let mut documents = vec![];
+    let mut current = document.to_owned();
+    match &mut current {
+        owned::Document::Circuit(circuit) => {
+            documents.extend(steps(circuit.clone(), &mut known, &mut equivalents).into_iter().map(owned::Document::Circuit));
+        }
+        owned::Document::Twoport(twoport) => for i in 0..twoport.links.len() {
+            let (owned::TwoportLink::Series(circuit) | owned::TwoportLink::Shunt(circuit)) = &twoport.links[i] else {
+                continue;
+            };
+            for reduced in steps(circuit.clone(), &mut known, &mut equivalents) {
+                if let owned::TwoportLink::Series(circuit) | owned::TwoportLink::Shunt(circuit) = &mut twoport.links[i] {
+                    *circuit = reduced;
+                }
+                documents.push(owned::Document::Twoport(twoport.clone()));
+            }
+        },
+    }
+    documents.into_iter().zip(equivalents).collect()
+}
+
+/// The values of `values`, falling back to the ones given in the document
+fn known_values(document: &Document, values: &Values) -> Values {
+    let mut known = analysis::values(document);
+    known.extend(values.iter().map(|(label, value)| (label.clone(), *value)));
+    known
+}
+
+fn simplify(circuit: &SubCircuit, values: Option<&Values>) -> Simplified {
+    let mut equivalents = vec![];
+    let circuit = build(reduce(circuit), values, &mut equivalents);
+    Simplified { circuit, equivalents }
+}
+
+/// A sub-circuit while simplifying it
+enum Reduced {
+    /// Element kept as it is (unless it is combined with another one)
+    Element(owned::SubCircuit),
+    /// Elements of one kind combined into one, with the expression of its value
+    Equivalent(&'static ElementKind, Expr),
+    Series(Vec<Reduced>),
+    Parallel(Vec<Reduced>),
+}
+
+impl Reduced {
+    /// The kind and value of a resistor, capacitor or inductor (or an equivalent one), `None` for other elements and groups
+    fn reducible(&self) -> Option<(&'static ElementKind, Expr)> {
+        match self {
+            Reduced::Element(owned::SubCircuit::Element(element, _)) => {
+                reducible(&element.borrow()).map(|kind| (kind, Expr::Sym(element.borrow().label())))
+            }
+            Reduced::Equivalent(kind, expr) => Some((kind, expr.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of resistors, capacitors and inductors, `None` for other elements
+fn reducible(element: &Element) -> Option<&'static ElementKind> {
+    matches!(element, Element::R(_) | Element::C(_) | Element::L(_)).then(|| element.kind())
+}
+
+fn reduce(circuit: &SubCircuit) -> Reduced {
+    let series = match circuit {
+        SubCircuit::Element(..) => return Reduced::Element(circuit.to_owned()),
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => return reduce(circuit),
+            SubCircuitGroup::Series(..) => true,
+            SubCircuitGroup::Parallel(..) => false,
+        },
+    };
+    let mut operands = vec![];
+    chain(circuit, series, &mut operands);
+    let mut reduced: Vec<Reduced> = vec![];
+    for operand in operands.into_iter().map(reduce) {
+        let first = operand.reducible().and_then(|(kind, expr)| {
+            let index = reduced.iter().position(|other| other.reducible().is_some_and(|(other_kind, _)| other_kind.prefix == kind.prefix))?;
+            Some((index, kind, expr))
+        });
+        match first {
+            Some((index, kind, expr)) => {
+                let (_, other) = reduced[index].reducible().unwrap();
+                reduced[index] = Reduced::Equivalent(kind, combine(kind, series, other, expr));
+            }
+            None => reduced.push(operand),
+        }
+    }
+    match reduced.len() {
+        1 => reduced.pop().unwrap(),
+        _ if series => Reduced::Series(reduced),
+        _ => Reduced::Parallel(reduced),
+    }
+}
+
+/// Collects the operands of a chain of groups of the same kind, e.g. `R1`, `C1` and `R2` for `(R1+C1+R2)`
+fn chain<'c, 'a>(circuit: &'c SubCircuit<'a>, series: bool, operands: &mut Vec<&'c SubCircuit<'a>>) {
+    if let SubCircuit::Group(group) = circuit {
+        match (group.as_ref(), series) {
+            (SubCircuitGroup::Single(inner), _) => return chain(inner, series, operands),
+            (SubCircuitGroup::Series(a, b), true) | (SubCircuitGroup::Parallel(a, b), false) => {
+                chain(a, series, operands);
+                chain(b, series, operands);
+                return;
+            }
+            _ => {}
+        }
+    }
+    operands.push(circuit);
+}
+
+/// The value of two elements of a kind combined in series or in parallel
+fn combine(kind: &ElementKind, series: bool, a: Expr, b: Expr) -> Expr {
+    let capacitive = kind.impedance == Impedance::Capacitive;
+    // resistances and inductances add up in series, capacitances in parallel
+    if series != capacitive {
+        a.plus(b)
+    } else if capacitive {
+        a.reciprocal().plus(b.reciprocal()).reciprocal()
+    } else {
+        a.parallel(b)
+    }
+}
+
+/// The circuits after each step of reducing the circuit, adding the elements of the steps to `equivalents` and their values to `known`
+fn steps(mut circuit: owned::SubCircuit, known: &mut Values, equivalents: &mut Vec<Equivalent>) -> Vec<owned::SubCircuit> {
+    let mut circuits = vec![];
+    loop {
+        let Some(next) = step(&circuit.borrow(), &mut |kind, expr| equivalent(kind, expr, Some(known), equivalents)) else {
+            return circuits;
+        };
+        let added = equivalents.last().expect("each step adds an equivalent element");
+        if let Some(value) = added.value {
+            known.insert(added.label.clone(), value);
+        }
+        circuits.push(next.clone());
+        circuit = next;
+    }
+}
+
+/// Combines the elements of one kind in the first innermost chain having several, `None` if there is none
+fn step(circuit: &SubCircuit, equivalent: &mut dyn FnMut(&'static ElementKind, Expr) -> owned::SubCircuit) -> Option<owned::SubCircuit> {
+    let series = match circuit {
+        SubCircuit::Element(..) => return None,
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => return step(circuit, equivalent),
+            SubCircuitGroup::Series(..) => true,
+            SubCircuitGroup::Parallel(..) => false,
+        },
+    };
+    let mut operands = vec![];
+    chain(circuit, series, &mut operands);
+    let mut circuits: Vec<owned::SubCircuit> = operands.iter().map(|operand| SubCircuit::to_owned(operand)).collect();
+    for (i, operand) in operands.iter().enumerate() {
+        if let Some(reduced) = step(operand, equivalent) {
+            circuits[i] = reduced;
+            return Some(join(circuits, series));
+        }
+    }
+    let kinds: Vec<_> = operands.iter().map(|operand| match operand {
+        SubCircuit::Element(element, _) => reducible(element).map(|kind| (kind, element.label())),
+        SubCircuit::Group(_) => None,
+    }).collect();
+    let same = |i: usize, prefix: &str| kinds[i].as_ref().is_some_and(|(kind, _)| kind.prefix == prefix);
+    let (first, kind) = (0..kinds.len())
+        .filter_map(|i| Some((i, kinds[i].as_ref()?.0)))
+        .find(|(_, kind)| (0..kinds.len()).filter(|j| same(*j, kind.prefix)).count() > 1)?;
+    let expr = (0..kinds.len()).filter(|i| same(*i, kind.prefix))
+        .map(|i| Expr::Sym(kinds[i].as_ref().unwrap().1.clone()))
+        .reduce(|a, b| combine(kind, series, a, b))
+        .unwrap();
+    let combined = equivalent(kind, expr);
+    let circuits = circuits.into_iter().enumerate()
+        .filter_map(|(i, circuit)| if i == first { Some(combined.clone()) } else { (!same(i, kind.prefix)).then_some(circuit) })
+        .collect();
+    Some(join(circuits, series))
+}
+
+/// Adds an equivalent element of the kind, numbered after those of its kind added before
+fn equivalent(kind: &'static ElementKind, expr: Expr, values: Option<&Values>, equivalents: &mut Vec<Equivalent>) -> owned::SubCircuit {
+    let number = equivalents.iter().filter(|equivalent| equivalent.label.starts_with(kind.prefix)).count() + 1;
+    let element = owned::Element { kind, id: format!("eq{number}"), reference: String::new() };
+    // rounded, so that e.g. two 1mH inductors in parallel make 500uH rather than 499.9999999999999uH
+    let value = values.and_then(|values| expr.eval(Complex64::new(0.0, 0.0), values)).map(|value| format::significant(value.re, 12));
+    let unit = match kind.impedance {
+        Impedance::Capacitive => "F",
+        Impedance::Inductive => "H",
+        _ => "Ω",
+    };
+    equivalents.push(Equivalent { label: element.borrow().label(), expr, value, unit });
+    owned::SubCircuit::Element(element, value.map(|number| owned::Value { number, unit: unit.to_string() }))
+}
+
+fn build(reduced: Reduced, values: Option<&Values>, equivalents: &mut Vec<Equivalent>) -> owned::SubCircuit {
+    let (operands, series) = match reduced {
+        Reduced::Element(circuit) => return circuit,
+        Reduced::Equivalent(kind, expr) => return equivalent(kind, expr, values, equivalents),
+        Reduced::Series(operands) => (operands, true),
+        Reduced::Parallel(operands) => (operands, false),
+    };
+    // built from left to right, so that equivalent elements are numbered in order
+    let circuits = operands.into_iter().map(|operand| build(operand, values, equivalents)).collect();
+    join(circuits, series)
+}
+
+/// Joins circuits in series or in parallel, nested to the right like the parser does
+fn join(mut circuits: Vec<owned::SubCircuit>, series: bool) -> owned::SubCircuit {
+    let mut circuit = circuits.pop().unwrap();
+    while let Some(left) = circuits.pop() {
+        circuit = owned::SubCircuit::Group(Box::new(match series {
+            true => owned::SubCircuitGroup::Series(left, circuit),
+            false => owned::SubCircuitGroup::Parallel(left, circuit),
+        }));
+    }
+    circuit
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit;
+    use super::*;
+
+    fn parse(input: &str) -> SubCircuit<'_> {
+        circuit::sub_circuit::<nom::error::VerboseError<&str>>(input).unwrap().1
+    }
+
+    #[test]
+    fn test_simplify() {
+        let simplified = parse("(R1=100+C1=1u+R2=200||R3=200+(L1=1m||L2=1m))").simplify(&Values::from([("R1".to_string(), 200.0)]));
+        assert_eq!(simplified.circuit.borrow().to_string(), "(Req1=300Ω+C1=1u+Leq1=500uH)");
+        assert_eq!(simplified.equivalents.iter().map(|equivalent| equivalent.value).collect::<Vec<_>>(), [Some(300.0), Some(0.5e-3)]);
+        let simplified = parse("(C1=1u+C2=1u||R1)").simplify(&Values::new());
+        assert_eq!(simplified.circuit.borrow().to_string(), "(C1=1u+C2=1u||R1)");
+        assert!(simplified.equivalents.is_empty());
+        let simplified = parse("(R1=1k+C1)").simplify(&Values::new());
+        assert_eq!(simplified.circuit.borrow().to_string(), "(R1=1k+C1)");
+    }
+
+    #[test]
+    fn test_symbolic() {
+        let simplified = parse("((C1+C2||C3)||R1||R2+V1+C4)").simplify_symbolic();
+        assert_eq!(simplified.circuit.borrow().to_string(), "(Ceq1||Req1+V1+C4)");
+        assert_eq!(simplified.equivalents, [
+            Equivalent { label: "Ceq1".to_string(), expr: Expr::Sym("C1".to_string()).reciprocal().plus(Expr::Sym("C2".to_string()).plus(Expr::Sym("C3".to_string())).reciprocal()).reciprocal(), value: None, unit: "F" },
+            Equivalent { label: "Req1".to_string(), expr: Expr::Sym("R1".to_string()).parallel(Expr::Sym("R2".to_string())), value: None, unit: "Ω" },
+        ]);
+        assert_eq!(simplified.equivalents[0].expr.to_string(), "1/(1/C1 + 1/(C2 + C3))");
+        assert_eq!(simplified.equivalents[0].unit(), "F");
+    }
+
+    #[test]
+    fn test_steps() {
+        let steps = parse("((C1=1u+C2=2u||C3=2u)||R1=1k||R2=1k+V1+C4)").reduction_steps(&Values::new());
+        let circuits: Vec<_> = steps.iter().map(|step| step.circuit.borrow().to_string()).collect();
+        assert_eq!(circuits, [
+            "((C1=1u+Ceq1=4uF)||R1=1k||R2=1k+V1+C4)",
+            "(Ceq2=800nF||R1=1k||R2=1k+V1+C4)",
+            "(Ceq2=800nF||Req1=500Ω+V1+C4)",
+        ]);
+        assert_eq!(steps.iter().map(|step| step.equivalent.value).collect::<Vec<_>>(), [Some(4e-6), Some(0.8e-6), Some(500.0)]);
+
+        let document = crate::circuit::document("|V1-(R1=1k+R2=1k)|(L1+L2)-R3").unwrap();
+        let steps = reduction_steps(&document, &Values::new());
+        let documents: Vec<_> = steps.iter().map(|(document, _)| document.borrow().to_string()).collect();
+        assert_eq!(documents, ["|V1-Req1=2kΩ|(L1+L2)-R3", "|V1-Req1=2kΩ|Leq1-R3"]);
+        assert_eq!(steps[0].1.value, Some(2e3));
+    }
+}
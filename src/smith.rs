@@ -0,0 +1,121 @@
+//! Impedances of matching networks, for plotting them on a Smith chart (see [`crate::draw::svg::SvgDrawer::smith_chart`]).
+//!
+//! The load of a twoport is at its output: walking the links from there towards the input, each one transforms
+//! the impedance seen so far, up to the input impedance the source sees. Shunt links before the first series
+//! link are parallel to the source, like in [`crate::circuit::Twoport::transfer_function`], and left out.
+//!
+//! ```
+//! use circmark_parse::{analysis, circuit, smith::SmithChart};
+//! let twoport = circuit::twoport::<nom::error::VerboseError<&str>>("|V1-L1=100n|C1=10p|Rload=100").unwrap().1;
+//! let chart = SmithChart::new(&twoport, &analysis::values(&circuit::Document::Twoport(twoport.clone())), 100e6, 50.0).unwrap();
+//! assert_eq!(chart.trajectory.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>(), ["Rload", "C1", "L1"]);
+//! assert!((chart.trajectory[0].1.re - 100.0).abs() < 1e-9);
+//! ```
+
+use crate::{
+    analysis::{response::log_frequencies, Complex64, Values},
+    circuit::{SubCircuit, Twoport, TwoportLink},
+};
+
+/// Points per decade of the frequency sweep
+const SWEEP_POINTS_PER_DECADE: usize = 20;
+
+/// Impedances seen after links, labelled with the links' elements
+type Trajectory = Vec<(String, Complex64)>;
+
+/// What a Smith chart shows of a twoport
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmithChart {
+    /// Reference impedance the chart is normalized to, e.g. 50Ω
+    pub z0: f64,
+    /// Frequency of the trajectory, in Hz
+    pub frequency: f64,
+    /// Impedance seen after each link, from the load towards the input, labelled with the link's elements
+    pub trajectory: Vec<(String, Complex64)>,
+    /// Input impedance from a tenth to ten times the frequency
+    pub sweep: Vec<(f64, Complex64)>,
+}
+
+impl SmithChart {
+    /// Computes the chart at `frequency` (in Hz), `None` if an element has no value or the input is open
+    pub fn new(twoport: &Twoport, values: &Values, frequency: f64, z0: f64) -> Option<Self> {
+        let (trajectory, _) = trajectory_at(twoport, values, frequency)?;
+        trajectory.last()?;
+        let sweep = log_frequencies(frequency / 10.0, frequency * 10.0, SWEEP_POINTS_PER_DECADE).into_iter()
+            .filter_map(|f| Some((f, input_impedance(twoport, values, f)?)))
+            .collect();
+        Some(SmithChart { z0, frequency, trajectory, sweep })
+    }
+
+    /// The reflection coefficient of an impedance, which is its position on the chart
+    pub fn reflection(&self, z: Complex64) -> Complex64 {
+        (z - self.z0) / (z + self.z0)
+    }
+}
+
+/// The input impedance of the twoport at `frequency` (in Hz), `None` if an element lacks a value or the input is open
+pub fn input_impedance(twoport: &Twoport, values: &Values, frequency: f64) -> Option<Complex64> {
+    trajectory_at(twoport, values, frequency)?.1.filter(|z| z.is_finite())
+}
+
+/// The impedances seen after each link at `frequency` and the input impedance, `None` if an element lacks a value
+fn trajectory_at(twoport: &Twoport, values: &Values, frequency: f64) -> Option<(Trajectory, Option<Complex64>)> {
+    let s = Complex64::new(0.0, 2.0 * std::f64::consts::PI * frequency);
+    let impedance = |circuit: &SubCircuit| match circuit.impedance() {
+        Some(impedance) => impedance.eval(s, values).map(Some),
+        None => Some(None),
+    };
+    let first_series = twoport.links.iter().position(|link| matches!(link, TwoportLink::Series(_))).unwrap_or(twoport.links.len());
+    // `None` while the output is open
+    let mut z: Option<Complex64> = None;
+    let mut points = vec![];
+    for link in twoport.links[first_series..].iter().rev() {
+        let label = match link {
+            TwoportLink::Series(circuit) | TwoportLink::Shunt(circuit) => {
+                circuit.elements().iter().map(|element| element.label()).collect::<Vec<_>>().join(", ")
+            }
+            TwoportLink::Transformer { .. } => link.label().unwrap(),
+            TwoportLink::Node(_) | TwoportLink::Annotation { .. } => continue,
+        };
+        z = match link {
+            TwoportLink::Series(circuit) => match impedance(circuit)? {
+                Some(series) => z.map(|z| z + series),
+                None => None,
+            },
+            TwoportLink::Shunt(circuit) => match (z, impedance(circuit)?) {
+                (Some(z), Some(shunt)) => Some(z * shunt / (z + shunt)),
+                (z, shunt) => z.or(shunt),
+            },
+            TwoportLink::Transformer { .. } => {
+                let n = *values.get(&label)?;
+                z.map(|z| z * n * n)
+            }
+            TwoportLink::Node(_) | TwoportLink::Annotation { .. } => unreachable!(),
+        };
+        if let Some(z) = z.filter(|z| z.is_finite()) {
+            points.push((label, z));
+        }
+    }
+    Some((points, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis, circuit};
+
+    type E = nom::error::VerboseError<&'static str>;
+
+    #[test]
+    fn test_trajectory() {
+        let twoport = circuit::twoport::<E>("|V1-R1=25=T1(n=2)|Rload=50").unwrap().1;
+        let values = analysis::values(&circuit::Document::Twoport(twoport.clone()));
+        let chart = SmithChart::new(&twoport, &values, 1e6, 50.0).unwrap();
+        let trajectory: Vec<_> = chart.trajectory.iter().map(|(label, z)| (label.as_str(), z.re)).collect();
+        assert_eq!(trajectory, [("Rload", 50.0), ("T1", 200.0), ("R1", 225.0)]);
+        assert_eq!(chart.reflection(Complex64::new(50.0, 0.0)), Complex64::new(0.0, 0.0));
+        assert_eq!(chart.sweep.len(), 41);
+        assert_eq!(input_impedance(&circuit::twoport::<E>("|V1-O|R1=50").unwrap().1, &values, 1e6), None);
+        assert_eq!(SmithChart::new(&circuit::twoport::<E>("|V1-R1|C1").unwrap().1, &values, 1e6, 50.0), None);
+    }
+}
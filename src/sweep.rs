@@ -0,0 +1,216 @@
+//! Design space exploration: the values of one or two elements of a twoport are swept over ranges, and a metric
+//! is evaluated for each combination. The results form a table, written as CSV by [`Matrix::to_csv`] or drawn
+//! as a heatmap by [`crate::draw::svg::SvgDrawer::heatmap`].
+//!
+//! ```
+//! use circmark_parse::{analysis, circuit, sweep::{sweep, Axis, Metric}};
+//! let document = circuit::document("|V1-R1|C1=1u").unwrap();
+//! let circuit::Document::Twoport(twoport) = &document else { unreachable!() };
+//! let matrix = sweep(twoport, &analysis::values(&document), Metric::Cutoff, Axis::logarithmic("R1", 1e3, 10e3, 2), None);
+//! // 1/(2πRC)
+//! assert!((matrix.cells[0][0].unwrap() - 159.15).abs() < 0.01);
+//! assert!((matrix.cells[1][0].unwrap() - 15.915).abs() < 0.001);
+//! ```
+
+use crate::{
+    analysis::{ac_sweep, response::log_frequencies, Values},
+    circuit::Twoport,
+    format,
+    smith,
+};
+
+/// Frequencies in Hz over which the cutoff and ripple are looked for
+const SEARCH_RANGE: (f64, f64) = (1e-3, 1e12);
+
+/// Points per decade of the search, ripple in between them is missed
+const SEARCH_POINTS_PER_DECADE: usize = 20;
+
+/// What is evaluated for each combination of values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// The lowest frequency at which the gain is 3dB below its peak, in Hz
+    Cutoff,
+    /// Difference in dB between the highest peak of the gain and the lowest of its other peaks and dips
+    /// in the passband around the highest peak (where the gain is within 3dB of it), 0 if there are none
+    Ripple,
+    /// Magnitude of the input impedance at the frequency (in Hz), in Ω
+    InputImpedance(f64),
+}
+
+impl Metric {
+    /// Name of the metric, as a caption
+    pub fn name(&self) -> String {
+        match self {
+            Metric::Cutoff => "Cutoff frequency".to_string(),
+            Metric::Ripple => "Passband ripple".to_string(),
+            Metric::InputImpedance(frequency) => format!("Input impedance at {}", format::si(*frequency, "Hz", &format::Locale::default())),
+        }
+    }
+
+    /// Unit of the metric's values
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::Cutoff => "Hz",
+            Metric::Ripple => "dB",
+            Metric::InputImpedance(_) => "Ω",
+        }
+    }
+
+    /// Name of the metric's column in CSV tables
+    pub fn column(&self) -> &'static str {
+        match self {
+            Metric::Cutoff => "cutoff_hz",
+            Metric::Ripple => "ripple_db",
+            Metric::InputImpedance(_) => "zin_ohm",
+        }
+    }
+
+    /// Evaluates the metric, `None` if an element lacks a value or the metric doesn't exist for the twoport,
+    /// e.g. the cutoff of an all pass
+    pub fn evaluate(&self, twoport: &Twoport, values: &Values) -> Option<f64> {
+        let magnitude = |frequency: f64| Some(ac_sweep(twoport, values, &[frequency])?[0].norm());
+        match self {
+            Metric::Cutoff => {
+                let freqs = log_frequencies(SEARCH_RANGE.0, SEARCH_RANGE.1, SEARCH_POINTS_PER_DECADE);
+                let gains = ac_sweep(twoport, values, &freqs)?;
+                let threshold = gains.iter().map(|gain| gain.norm()).fold(0.0, f64::max) / 2f64.sqrt();
+                let above = |gain: f64| gain >= threshold;
+                let i = (1..freqs.len()).find(|&i| above(gains[i - 1].norm()) != above(gains[i].norm()))?;
+                // the crossing lies between the two frequencies
+                let (mut low, mut high) = (freqs[i - 1], freqs[i]);
+                let rising = !above(gains[i - 1].norm());
+                for _ in 0..40 {
+                    let middle = (low * high).sqrt();
+                    if above(magnitude(middle)?) != rising {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                Some((low * high).sqrt())
+            }
+            Metric::Ripple => {
+                let freqs = log_frequencies(SEARCH_RANGE.0, SEARCH_RANGE.1, SEARCH_POINTS_PER_DECADE);
+                let gains: Vec<_> = ac_sweep(twoport, values, &freqs)?.iter().map(|gain| 20.0 * gain.norm().log10()).collect();
+                let highest = (0..gains.len()).max_by(|&a, &b| gains[a].total_cmp(&gains[b]))?;
+                let in_passband = |i: usize| gains[i] >= gains[highest] - 3.0;
+                let first = (0..highest).rev().take_while(|&i| in_passband(i)).last().unwrap_or(highest);
+                let last = (highest + 1..gains.len()).take_while(|&i| in_passband(i)).last().unwrap_or(highest);
+                // the ends of the sweep count as extremes when the passband reaches them, e.g. at DC
+                let lowest = (first..=last)
+                    .filter(|&i| i == 0 || i == gains.len() - 1 || (gains[i] - gains[i - 1]) * (gains[i + 1] - gains[i]) <= 0.0)
+                    .map(|i| gains[i])
+                    .fold(gains[highest], f64::min);
+                Some(gains[highest] - lowest)
+            }
+            Metric::InputImpedance(frequency) => Some(smith::input_impedance(twoport, values, *frequency)?.norm()),
+        }
+    }
+}
+
+/// Values a named element takes in a sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct Axis {
+    /// Label of the element, e.g. `R1`
+    pub label: String,
+    pub values: Vec<f64>,
+}
+
+impl Axis {
+    /// `steps` logarithmically spaced values from `start` to `stop`, both included
+    pub fn logarithmic(label: &str, start: f64, stop: f64, steps: usize) -> Axis {
+        let values = match steps {
+            0 => vec![],
+            1 => vec![start],
+            // rounded, so decades come out as e.g. 1e-8 rather than 9.999999999999999e-9
+            _ => (0..steps).map(|i| format::significant(start * (stop / start).powf(i as f64 / (steps - 1) as f64), 12)).collect(),
+        };
+        Axis { label: label.to_string(), values }
+    }
+}
+
+/// The metric for each combination of values, by row and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub metric: Metric,
+    pub rows: Axis,
+    /// The second swept element, a single column if there is none
+    pub columns: Option<Axis>,
+    /// The metric of each row and column, `None` where it doesn't exist
+    pub cells: Vec<Vec<Option<f64>>>,
+}
+
+/// Evaluates the metric for each value of the rows' element, and each value of the columns' element if given,
+/// keeping the values of all other elements
+pub fn sweep(twoport: &Twoport, values: &Values, metric: Metric, rows: Axis, columns: Option<Axis>) -> Matrix {
+    let cells = rows.values.iter().map(|row| {
+        let mut values = values.clone();
+        values.insert(rows.label.clone(), *row);
+        match &columns {
+            Some(columns) => columns.values.iter().map(|column| {
+                values.insert(columns.label.clone(), *column);
+                metric.evaluate(twoport, &values)
+            }).collect(),
+            None => vec![metric.evaluate(twoport, &values)],
+        }
+    }).collect();
+    Matrix { metric, rows, columns, cells }
+}
+
+impl Matrix {
+    /// The table as CSV: a header line with the values of the columns' element (or the metric's name),
+    /// then a line for each value of the rows' element. Missing cells are empty
+    pub fn to_csv(&self) -> String {
+        let mut csv = match &self.columns {
+            Some(columns) => {
+                let header: Vec<_> = columns.values.iter().map(|value| value.to_string()).collect();
+                format!("{}\\{},{}\n", self.rows.label, columns.label, header.join(","))
+            }
+            None => format!("{},{}\n", self.rows.label, self.metric.column()),
+        };
+        for (value, cells) in self.rows.values.iter().zip(&self.cells) {
+            let cells: Vec<_> = cells.iter().map(|cell| cell.map(|cell| cell.to_string()).unwrap_or_default()).collect();
+            csv.push_str(&format!("{value},{}\n", cells.join(",")));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis, circuit};
+
+    #[test]
+    fn test_metrics() {
+        let document = circuit::document("|V1-R1=1k|C1=1u").unwrap();
+        let circuit::Document::Twoport(lowpass) = &document else { unreachable!() };
+        let values = analysis::values(&document);
+        assert!((Metric::Cutoff.evaluate(lowpass, &values).unwrap() - 159.155).abs() < 1e-3);
+        assert!(Metric::Ripple.evaluate(lowpass, &values).unwrap() < 1e-6);
+        assert!((Metric::InputImpedance(1e6).evaluate(lowpass, &values).unwrap() - 1e3).abs() < 1e-3);
+        assert_eq!(Metric::Cutoff.evaluate(lowpass, &Values::new()), None);
+        let document = circuit::document("|V1-C1=1u|R1=1k").unwrap();
+        let circuit::Document::Twoport(highpass) = &document else { unreachable!() };
+        assert!((Metric::Cutoff.evaluate(highpass, &analysis::values(&document)).unwrap() - 159.155).abs() < 1e-3);
+        // an underdamped LC low pass peaks by about 2.4dB before rolling off
+        let document = circuit::document("|V1-R1=1-L1=1m|(C1=1u||R2=38)").unwrap();
+        let circuit::Document::Twoport(resonant) = &document else { unreachable!() };
+        let ripple = Metric::Ripple.evaluate(resonant, &analysis::values(&document)).unwrap();
+        assert!(ripple > 2.0 && ripple < 3.0);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let twoport = circuit::twoport::<nom::error::VerboseError<&str>>("|V1-R1|C1").unwrap().1;
+        let matrix = sweep(&twoport, &Values::new(), Metric::Cutoff, Axis::logarithmic("R1", 1e3, 100e3, 3), Some(Axis::logarithmic("C1", 1e-9, 1e-6, 2)));
+        assert_eq!(matrix.cells.len(), 3);
+        assert_eq!(matrix.cells[0].len(), 2);
+        assert!((matrix.cells[2][1].unwrap() - 1.59155).abs() < 1e-4);
+        let csv = matrix.to_csv();
+        assert!(csv.starts_with("R1\\C1,0.000000001,0.000001\n1000,"));
+        assert_eq!(csv.lines().count(), 4);
+        let matrix = sweep(&twoport, &Values::new(), Metric::Cutoff, Axis::logarithmic("R1", 1e3, 1e3, 1), None);
+        assert_eq!(matrix.to_csv(), "R1,cutoff_hz\n1000,\n");
+    }
+}
@@ -0,0 +1,199 @@
+//! Checks of sources beyond the grammar, with diagnostics located in the source for editors to underline.
+//!
+//! Errors are sources that don't parse (explaining common mistakes, like `|` within a group, better than
+//! the parser does) and labels naming more than one element. Warnings are circuits that parse but are
+//! likely not what was meant, e.g. a branch of a parallel group that is cut off by an open circuit.
+//!
+//! ```
+//! use circmark_parse::validate::{validate, Severity};
+//! let source = "|V1-R1|((R2+O)||C1)-R1";
+//! let diagnostics = validate(source);
+//! assert_eq!(diagnostics.len(), 2);
+//! assert_eq!((diagnostics[0].severity, &source[diagnostics[0].span.clone()]), (Severity::Warning, "O"));
+//! assert_eq!((diagnostics[1].severity, &source[diagnostics[1].span.clone()]), (Severity::Error, "R1"));
+//! ```
+
+use std::ops::Range;
+use crate::{
+    circuit::{self, Document, Element, Spice, SubCircuit, SubCircuitGroup, TwoportLink},
+    document::{self, SectionError},
+    error::one_of,
+    ParseError,
+};
+
+/// How bad a [`Diagnostic`] is
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The source is valid, but likely not what was meant
+    Warning,
+    /// The source is invalid
+    Error,
+}
+
+/// A problem found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Byte range in the source the problem is about
+    pub span: Range<usize>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// Checks a source, which may consist of sections (see [`crate::document`]), returning the problems in the
+/// order of the source. Sources that don't parse only get the diagnostic of where they stop parsing.
+pub fn validate(source: &str) -> Vec<Diagnostic> {
+    let sections = match document::parse(source) {
+        Ok(sections) => sections,
+        Err(SectionError::Parse(error)) => return vec![parse_error(source, &error)],
+        Err(error) => {
+            let line = match &error {
+                SectionError::UnknownKind { line, .. } | SectionError::InvalidHeader { line } | SectionError::UnknownAttribute { line, .. }
+                    | SectionError::OutsideSection { line } | SectionError::WrongKind { line, .. } | SectionError::Resolve { line, .. } => *line,
+                SectionError::Parse(_) => unreachable!(),
+            };
+            let start: usize = source.split_inclusive('\n').take(line - 1).map(str::len).sum();
+            let end = start + source[start..].find('\n').unwrap_or(source.len() - start);
+            return vec![Diagnostic { severity: Severity::Error, message: error.to_string(), span: start..end }];
+        }
+    };
+    let mut diagnostics = vec![];
+    for section in &sections {
+        let content = &source[section.content.clone()];
+        let Ok(document) = circuit::document(content) else {
+            // an extending section without content of its own
+            continue;
+        };
+        let offset = section.content.start;
+        let spans = circuit::element_spans(content).expect("the content parses");
+        let links = circuit::spans(content).expect("the content parses");
+        let shift = |span: &Range<usize>| span.start + offset..span.end + offset;
+        // the input is checked in the resolved twoport, as a section may extend one driven by a source
+        let resolved = section.document.borrow();
+        if let (Document::Twoport(twoport), Document::Twoport(own)) = (&resolved, &document) {
+            let first = twoport.links.iter().find(|link| link.circuit().is_some());
+            let has_source = twoport.elements().iter().any(|element| matches!(element.kind().spice, Spice::Source));
+            if matches!(first, Some(TwoportLink::Series(_))) && !has_source && twoport.links.len() == own.links.len() {
+                let index = own.links.iter().position(|link| link.circuit().is_some()).unwrap();
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    message: "the twoport starts with a series link and has no source, so its input is left open".to_string(),
+                    span: shift(&links[index]),
+                });
+            }
+        }
+        let mut index = 0;
+        let mut open = |circuit: &SubCircuit| open_branches(circuit, false, false, &mut index, &mut |index| diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message: "the open circuit cuts off its branch of the parallel group, which carries no current".to_string(),
+            span: shift(&spans[index]),
+        }));
+        match &document {
+            Document::Circuit(circuit) => open(circuit),
+            Document::Twoport(twoport) => twoport.links.iter().filter_map(TwoportLink::circuit).for_each(open),
+        }
+        // labels naming a second element, in the order of the source
+        let mut labels: Vec<(String, Range<usize>)> = document.elements().iter().zip(&spans)
+            .filter(|(element, _)| element.id().is_some())
+            .map(|(element, span)| (element.label(), span.clone()))
+            .collect();
+        if let Document::Twoport(twoport) = &document {
+            labels.extend(twoport.links.iter().zip(&links).filter_map(|(link, span)| Some((link.label()?, span.clone()))));
+            labels.sort_by_key(|(_, span)| span.start);
+        }
+        for (i, (label, span)) in labels.iter().enumerate() {
+            if labels[..i].iter().any(|(other, _)| other == label) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    message: format!("`{label}` already names another element"),
+                    span: shift(span),
+                });
+            }
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+/// Reports the open circuits in series within a branch of a parallel group, calling `report` with their index among the elements.
+///
+/// An open circuit directly in parallel is merely redundant, while one in series cuts off its whole branch.
+fn open_branches(circuit: &SubCircuit, in_parallel: bool, in_series: bool, index: &mut usize, report: &mut impl FnMut(usize)) {
+    match circuit {
+        SubCircuit::Element(element, _) => {
+            if in_series && *element == Element::Open {
+                report(*index);
+            }
+            *index += 1;
+        }
+        SubCircuit::Group(group) => match group.as_ref() {
+            SubCircuitGroup::Single(circuit) => open_branches(circuit, in_parallel, in_series, index, report),
+            SubCircuitGroup::Series(a, b) => {
+                open_branches(a, in_parallel, in_parallel, index, report);
+                open_branches(b, in_parallel, in_parallel, index, report);
+            }
+            SubCircuitGroup::Parallel(a, b) => {
+                open_branches(a, true, false, index, report);
+                open_branches(b, true, false, index, report);
+            }
+        },
+    }
+}
+
+/// Explains where the source stops parsing, recognizing common mistakes
+fn parse_error(source: &str, error: &ParseError) -> Diagnostic {
+    let offset = error.offset;
+    let before = source[..offset].trim_end();
+    let (message, span) = match error.found {
+        Some('|') if error.expected.contains(&"`||`") => {
+            ("a shunt link can't be within a group, elements in parallel are joined with `||`".to_string(), offset..offset + 1)
+        }
+        Some(')') if before.ends_with('(') => ("the group is empty".to_string(), before.len() - 1..offset + 1),
+        Some(c) => (format!("expected {}, found `{c}`", one_of(error.expected)), offset..offset + c.len_utf8()),
+        None => (format!("expected {}, found end of input", one_of(error.expected)), offset..offset),
+    };
+    Diagnostic { severity: Severity::Error, message, span }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The severity, message and spanned text of the diagnostics
+    fn check(source: &str) -> Vec<(Severity, String, &str)> {
+        validate(source).into_iter().map(|diagnostic| (diagnostic.severity, diagnostic.message, &source[diagnostic.span])).collect()
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(check("|V1-(R1|C1)"), [(Severity::Error, "a shunt link can't be within a group, elements in parallel are joined with `||`".to_string(), "|")]);
+        assert_eq!(check("|V1-( )|C1"), [(Severity::Error, "the group is empty".to_string(), "( )")]);
+        assert_eq!(check("|V1-R1="), [(Severity::Error, "expected number, found end of input".to_string(), "")]);
+        assert_eq!(check("@twoport\n|V1\n@network\n-R1"), [(Severity::Error, "unknown kind of section @network at line 3, expected @twoport, @circuit or @smith".to_string(), "@network")]);
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(check("|V1-R1=T1(n=2)|R2=T1"), [(Severity::Error, "`T1` already names another element".to_string(), "=T1")]);
+        assert_eq!(check("(R1+O+O||R2)"), []);
+        assert!(check("@twoport a |V1-R1\n@twoport extends a |C1").is_empty());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let input = "the twoport starts with a series link and has no source, so its input is left open".to_string();
+        assert_eq!(check("{i:i1}-R1|C1"), [(Severity::Warning, input, "-R1")]);
+        assert!(check("@twoport a |V1\n@twoport extends a -R1|C1").is_empty());
+        let open = "the open circuit cuts off its branch of the parallel group, which carries no current".to_string();
+        assert_eq!(check("|V1-((R1+(O||C1)+L1)||C2)|(O||R2)"), []);
+        assert_eq!(check("(((R1+(L1+O))||C2)+O)"), [(Severity::Warning, open, "O")]);
+    }
+}
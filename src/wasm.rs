@@ -0,0 +1,92 @@
+//! JavaScript bindings, for rendering in browsers without a server (built with `cargo rustc --lib --target
+//! wasm32-unknown-unknown --features wasm --crate-type cdylib` and `wasm-bindgen`, see the README).
+//!
+//! ```js
+//! import init, { parse, render_svg } from "circmark-parse";
+//! await init();
+//! const tree = parse("|V1-R1|C1");
+//! const svg = render_svg("|V1-R1|C1", JSON.stringify({ theme: "dark", locale: "de" }));
+//! ```
+
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use crate::{
+    circuit, dump,
+    draw::{svg::Theme, symbols::SymbolStandard},
+    format::Locale,
+    legend,
+    render::{self, RenderOptions},
+};
+
+/// Parses a circuit or twoport into its syntax tree, as the object [`dump::to_json`] describes
+#[wasm_bindgen]
+pub fn parse(input: &str) -> Result<JsValue, JsError> {
+    let document = circuit::document(input).map_err(|error| JsError::new(&error.to_string()))?;
+    js_sys::JSON::parse(&dump::to_json(&document)).map_err(|_| JsError::new("invalid syntax tree"))
+}
+
+/// Renders the source (which may have sections) to an SVG document, with the options given as JSON, see [`options`]
+#[wasm_bindgen]
+pub fn render_svg(input: &str, options: &str) -> Result<String, JsError> {
+    let options = self::options(options).map_err(|error| JsError::new(&error))?;
+    render::render_svg(input, &options).map_err(|error| JsError::new(&error.to_string()))
+}
+
+/// Reads render options from a JSON object, named like the options of the command line, e.g.
+/// `{"theme": "dark", "locale": "de", "mirror": true}`. An empty string gives the defaults
+///
+/// The options are `mirror`, `shorten`, `inheritStyle`, `parasitics` (booleans), `theme` (`light` or `dark`),
+/// `symbols` (`iec` or `ansi`), `locale` (`en`, `de`, `si` or `ascii`), `legend` (`en` or `de`), `variant`,
+/// `title`, `description` (strings), `scale` (a number) and `thumbnail` (a size in pixels).
+pub fn options(json: &str) -> Result<RenderOptions, String> {
+    let mut options = RenderOptions::default();
+    if json.trim().is_empty() {
+        return Ok(options);
+    }
+    let json: Value = serde_json::from_str(json).map_err(|error| format!("invalid options: {error}"))?;
+    let Value::Object(object) = json else {
+        return Err("invalid options: expected an object".to_string());
+    };
+    let mut locale = None;
+    for (key, value) in &object {
+        let flag = || value.as_bool().ok_or_else(|| format!("option {key} must be true or false"));
+        let text = || value.as_str().ok_or_else(|| format!("option {key} must be a string"));
+        match key.as_str() {
+            "mirror" => options.mirror = flag()?,
+            "shorten" => options.shorten = flag()?,
+            "inheritStyle" => options.inherit_style = flag()?,
+            "parasitics" => options.parasitics = flag()?,
+            "theme" => options.theme = text()?.parse::<Theme>().map_err(|error| error.to_string())?,
+            "symbols" => options.symbols = text()?.parse::<SymbolStandard>().map_err(|error| error.to_string())?,
+            // applied after all options, so that the theme doesn't reset it
+            "locale" => locale = Some(text()?.parse::<Locale>().map_err(|error| error.to_string())?),
+            "legend" => options.legend = Some(legend::names(text()?).map_err(|error| error.to_string())?),
+            "variant" => options.variant = Some(text()?.to_string()),
+            "title" => options.title = Some(text()?.to_string()),
+            "description" => options.description = Some(text()?.to_string()),
+            "scale" => options.scale = Some(value.as_f64().filter(|scale| *scale > 0.0).ok_or("option scale must be a positive number")?),
+            "thumbnail" => options.thumbnail = Some(value.as_u64().and_then(|size| u32::try_from(size).ok()).filter(|size| *size > 0).ok_or("option thumbnail must be a positive number of pixels")?),
+            _ => return Err(format!("unknown option {key}")),
+        }
+    }
+    if let Some(locale) = locale {
+        options.theme.locale = locale;
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let options = options(r#"{"locale": "de", "theme": "dark", "mirror": true, "scale": 2}"#).unwrap();
+        assert_eq!(options.theme, Theme { locale: Locale::DE, ..Theme::DARK });
+        assert!(options.mirror);
+        assert_eq!(options.scale, Some(2.0));
+        assert_eq!(self::options("").unwrap(), RenderOptions::default());
+        assert_eq!(self::options(r#"{"theme": "blue"}"#).unwrap_err(), "unknown theme \"blue\", expected light or dark");
+        assert_eq!(self::options(r#"{"mirror": 1}"#).unwrap_err(), "option mirror must be true or false");
+    }
+}
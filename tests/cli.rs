@@ -0,0 +1,28 @@
+use std::{io::Write, process::{Command, Stdio}};
+
+/// Runs `circmark` with the arguments, writing the input to its stdin, and returns its status, stdout and stderr
+fn circmark(args: &[&str], input: &str) -> (bool, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_circmark"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("circmark runs");
+    child.stdin.take().expect("stdin is piped").write_all(input.as_bytes()).expect("write stdin");
+    let output = child.wait_with_output().expect("circmark exits");
+    (output.status.success(), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn test_org_babel() {
+    let (success, stdout, stderr) = circmark(&["render", "--stdin", "--stdout", "--format", "svg"], "|V1-R1|C1\n");
+    assert!(success);
+    assert!(stdout.starts_with("<svg"));
+    assert_eq!(stderr, "");
+
+    let (success, stdout, stderr) = circmark(&["render", "--stdin", "--stdout", "--format", "svg"], "|V1-(R1\n");
+    assert!(!success);
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("circmark: can't parse stdin:"));
+}